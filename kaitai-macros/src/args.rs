@@ -0,0 +1,249 @@
+use proc_macro2::Ident;
+use syn::{Lit, LitStr, Meta, NestedMeta, Visibility};
+
+/// Where the ksy of the `kaitai_source` macro is read from.
+#[derive(Debug)]
+pub enum Source {
+    /// The path of the ksy file, relative to the current file.
+    Path(LitStr),
+    /// The ksy itself (`inline = "..."`).
+    Inline(LitStr),
+}
+
+/// The arguments of the `kaitai_source` macro, e.g.
+/// `#[kaitai_source("foo.ksy", root = "MyRoot", visibility = "pub(crate)", to_bytes)]`.
+#[derive(Debug)]
+pub struct Args {
+    /// The ksy file or the inline ksy.
+    pub source: Source,
+    /// The name of the root type, overriding the name of the struct the macro is applied to.
+    pub root: Option<Ident>,
+    /// The visibility of the root type, `pub` by default.
+    pub visibility: Option<Visibility>,
+    /// Whether the root type has a `to_bytes` method writing it back to bytes.
+    pub to_bytes: bool,
+    /// Whether the root type has a `LAYOUT` constant listing the offset and size of its fields.
+    pub layout: bool,
+    /// The module the generated types are placed in, if any (`module` or `module = "name"`). It
+    /// is named after the root type in snake case if no name is given.
+    pub module: Option<Option<Ident>>,
+    /// Whether the types other than the root type made of a single field are generated as
+    /// newtypes, e.g. `struct Length(pub u32)`.
+    pub newtypes: bool,
+    /// Whether the generated types derive `Hash`, so that they can be used as keys of a
+    /// `HashMap`.
+    pub hash: bool,
+    /// Whether the byte arrays and strings of the generated types borrow from the slice they are
+    /// parsed from, e.g. `&'a [u8]`, rather than being copied.
+    pub borrowed: bool,
+}
+
+impl TryFrom<syn::AttributeArgs> for Args {
+    type Error = syn::Error;
+
+    fn try_from(args: syn::AttributeArgs) -> Result<Self, Self::Error> {
+        let mut args = args.into_iter();
+        let source = match args.next() {
+            Some(NestedMeta::Lit(Lit::Str(path))) => Source::Path(path),
+            Some(NestedMeta::Meta(Meta::NameValue(name_value)))
+                if name_value.path.is_ident("inline") =>
+            {
+                match name_value.lit {
+                    Lit::Str(ksy) => Source::Inline(ksy),
+                    lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                }
+            }
+            Some(arg) => {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "expected the path of the ksy file or `inline = \"...\"`",
+                ))
+            }
+            None => {
+                return Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    "expected the path of the ksy file or `inline = \"...\"`",
+                ))
+            }
+        };
+
+        let mut root = None;
+        let mut visibility = None;
+        let mut to_bytes = false;
+        let mut layout = false;
+        let mut module = None;
+        let mut newtypes = false;
+        let mut hash = false;
+        let mut borrowed = false;
+        for arg in args {
+            let name_value = match arg {
+                NestedMeta::Meta(Meta::NameValue(name_value)) => name_value,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("to_bytes") => {
+                    to_bytes = true;
+                    continue;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("layout") => {
+                    layout = true;
+                    continue;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("newtypes") => {
+                    newtypes = true;
+                    continue;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("hash") => {
+                    hash = true;
+                    continue;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("borrowed") => {
+                    borrowed = true;
+                    continue;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("module") => {
+                    module = Some(None);
+                    continue;
+                }
+                arg => {
+                    return Err(syn::Error::new_spanned(
+                        arg,
+                        "expected an argument like `root = \"MyRoot\"` or `to_bytes`",
+                    ))
+                }
+            };
+            let value = match &name_value.lit {
+                Lit::Str(value) => value,
+                lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+            };
+
+            if name_value.path.is_ident("root") {
+                root = Some(value.parse()?);
+            } else if name_value.path.is_ident("visibility") {
+                visibility = Some(value.parse()?);
+            } else if name_value.path.is_ident("module") {
+                module = Some(Some(value.parse()?));
+            } else {
+                return Err(syn::Error::new_spanned(
+                    name_value.path,
+                    "unknown argument, expected `root`, `visibility`, `to_bytes`, `layout`, `module`, `newtypes`, `hash` or `borrowed`",
+                ));
+            }
+        }
+
+        Ok(Self {
+            source,
+            root,
+            visibility,
+            to_bytes,
+            layout,
+            module,
+            newtypes,
+            hash,
+            borrowed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+    use syn::{parse::Parser, punctuated::Punctuated, Token};
+
+    fn args(tokens: proc_macro2::TokenStream) -> syn::Result<Args> {
+        let args = Punctuated::<NestedMeta, Token![,]>::parse_terminated.parse2(tokens)?;
+        Args::try_from(args.into_iter().collect::<syn::AttributeArgs>())
+    }
+
+    #[test]
+    fn path_only() {
+        let args = args(quote! { "foo.ksy" }).unwrap();
+        assert!(matches!(args.source, Source::Path(path) if path.value() == "foo.ksy"));
+        assert!(args.root.is_none());
+        assert!(args.visibility.is_none());
+        assert!(!args.to_bytes);
+        assert!(!args.layout);
+        assert!(args.module.is_none());
+        assert!(!args.newtypes);
+        assert!(!args.hash);
+        assert!(!args.borrowed);
+    }
+
+    #[test]
+    fn inline() {
+        let args = args(quote! { inline = "meta:\n  id: foo\n", to_bytes }).unwrap();
+        assert!(matches!(args.source, Source::Inline(ksy) if ksy.value() == "meta:\n  id: foo\n"));
+        assert!(args.to_bytes);
+    }
+
+    #[test]
+    fn root_and_visibility() {
+        let args = args(quote! { "foo.ksy", root = "MyRoot", visibility = "pub(crate)" }).unwrap();
+        assert_eq!(args.root.unwrap(), "MyRoot");
+        let visibility = args.visibility.unwrap();
+        assert_eq!(quote! { #visibility }.to_string(), "pub (crate)");
+    }
+
+    #[test]
+    fn to_bytes() {
+        assert!(args(quote! { "foo.ksy", to_bytes }).unwrap().to_bytes);
+        assert!(
+            args(quote! { "foo.ksy", to_bytes, root = "MyRoot" })
+                .unwrap()
+                .to_bytes
+        );
+    }
+
+    #[test]
+    fn layout() {
+        let args = args(quote! { "foo.ksy", layout, to_bytes }).unwrap();
+        assert!(args.layout && args.to_bytes);
+    }
+
+    #[test]
+    fn newtypes() {
+        assert!(args(quote! { "foo.ksy", newtypes }).unwrap().newtypes);
+    }
+
+    #[test]
+    fn hash() {
+        assert!(args(quote! { "foo.ksy", hash }).unwrap().hash);
+    }
+
+    #[test]
+    fn borrowed() {
+        assert!(args(quote! { "foo.ksy", borrowed }).unwrap().borrowed);
+    }
+
+    #[test]
+    fn module() {
+        assert_eq!(
+            args(quote! { "foo.ksy", module }).unwrap().module,
+            Some(None)
+        );
+        let module = args(quote! { "foo.ksy", module = "formats" })
+            .unwrap()
+            .module;
+        assert_eq!(module.unwrap().unwrap(), "formats");
+    }
+
+    #[test]
+    fn invalid() {
+        for tokens in [
+            quote! {},
+            quote! { root = "MyRoot" },
+            quote! { inline = 1 },
+            quote! { "foo.ksy", inline = "meta:" },
+            quote! { "foo.ksy", root = "not an ident" },
+            quote! { "foo.ksy", root = MyRoot },
+            quote! { "foo.ksy", name = "MyRoot" },
+            quote! { "foo.ksy", visibility = "public" },
+            quote! { "foo.ksy", from_bytes },
+            quote! { "foo.ksy", module = "not a module" },
+        ] {
+            assert!(
+                args(tokens.clone()).is_err(),
+                "{} should be invalid",
+                tokens
+            );
+        }
+    }
+}
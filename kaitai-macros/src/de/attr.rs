@@ -1,6 +1,8 @@
-use crate::de::{data::IntegerValue, doc::Doc};
-
-use std::collections::HashMap;
+use crate::de::{
+    data::{Expression, IntegerValue},
+    doc::Doc,
+    en::Enum,
+};
 
 use serde::{de, Deserialize, Deserializer};
 
@@ -8,6 +10,9 @@ use serde::{de, Deserialize, Deserializer};
 #[serde(rename_all = "kebab-case", default)]
 pub struct Attr {
     pub id: Option<String>,
+    /// The name of the attribute in the original format, when it isn't a valid identifier.
+    #[serde(rename = "-orig-id")]
+    pub orig_id: Option<String>,
     #[serde(flatten)]
     pub doc: Doc,
     #[serde(deserialize_with = "deserialize_contents")]
@@ -16,14 +21,26 @@ pub struct Attr {
     pub ty: Option<AttrType>,
     pub repeat: Option<Repeat>,
     pub repeat_expr: Option<IntegerValue>,
-    pub repeat_until: Option<String>,
+    pub repeat_until: Option<Expression>,
     #[serde(rename = "if")]
     pub if_expr: Option<String>,
+    /// Whether an attribute with an `if` is stored with the default value of its type when the
+    /// condition is false, rather than in an `Option`. This key is specific to this crate.
+    #[serde(rename = "-rust-if-default")]
+    pub if_default: bool,
+    /// Whether the attribute is a mixed-endian GUID of 16 bytes. This key is specific to this
+    /// crate.
+    #[serde(rename = "-rust-guid")]
+    pub guid: bool,
+    /// How the attribute is read as a boolean from a byte, if it is one. This key is specific to
+    /// this crate.
+    #[serde(rename = "-rust-bool")]
+    pub bool_mode: Option<BoolMode>,
     pub size: Option<IntegerValue>,
     pub size_eos: bool,
     pub process: Option<String>,
     #[serde(rename = "enum")]
-    pub en: Option<String>,
+    pub en: Option<AttrEnum>,
     pub encoding: Option<String>,
     pub pad_right: Option<u64>,
     pub terminator: Option<u64>,
@@ -33,12 +50,14 @@ pub struct Attr {
     pub pos: Option<IntegerValue>,
     pub io: Option<String>,
     pub value: Option<String>,
+    pub valid: Option<Valid>,
 }
 
 impl Default for Attr {
     fn default() -> Self {
         Self {
             id: None,
+            orig_id: None,
             doc: Doc::default(),
             contents: None,
             ty: None,
@@ -46,6 +65,9 @@ impl Default for Attr {
             repeat_expr: None,
             repeat_until: None,
             if_expr: None,
+            if_default: false,
+            guid: false,
+            bool_mode: None,
             size: None,
             size_eos: false,
             process: None,
@@ -59,6 +81,7 @@ impl Default for Attr {
             pos: None,
             io: None,
             value: None,
+            valid: None,
         }
     }
 }
@@ -106,17 +129,69 @@ where
     Ok(Some(deserializer.deserialize_any(ContentsVisitor)?))
 }
 
+/// The enum of an attribute: the id of an enum of the file, or the members of an enum defined on
+/// the attribute itself, which some dialects of Kaitai Struct allow.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AttrEnum {
+    Named(String),
+    Inline(Enum),
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(untagged)]
 pub enum AttrType {
     TypeRef(String),
     #[serde(rename_all = "kebab-case")]
     Switch {
-        switch_on: String,
-        cases: HashMap<String, String>,
+        switch_on: Expression,
+        #[serde(deserialize_with = "deserialize_cases")]
+        cases: Vec<(Expression, String)>,
     },
 }
 
+/// Deserializes the `cases` of a switch, keeping them in the order they are written in.
+pub(crate) fn deserialize_cases<'de, D, T>(
+    deserializer: D,
+) -> Result<Vec<(Expression, T)>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct CasesVisitor<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> de::Visitor<'de> for CasesVisitor<T> {
+        type Value = Vec<(Expression, T)>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            formatter.write_str("map of cases")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: de::MapAccess<'de>,
+        {
+            let mut vec = Vec::with_capacity(map.size_hint().unwrap_or(0));
+            while let Some(case) = map.next_entry()? {
+                vec.push(case);
+            }
+            Ok(vec)
+        }
+    }
+
+    deserializer.deserialize_map(CasesVisitor(std::marker::PhantomData))
+}
+
+/// How a byte is read as a boolean (`-rust-bool`).
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoolMode {
+    /// 0 is `false` and any other value `true`.
+    Nonzero,
+    /// 0 is `false`, 1 is `true` and any other value an error.
+    Strict,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Repeat {
@@ -124,3 +199,22 @@ pub enum Repeat {
     Expr,
     Until,
 }
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Valid {
+    Eq(Expression),
+    Checks(ValidChecks),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ValidChecks {
+    pub eq: Option<Expression>,
+    #[serde(alias = "min-eq")]
+    pub min: Option<Expression>,
+    #[serde(alias = "max-eq")]
+    pub max: Option<Expression>,
+    pub any_of: Option<Vec<Expression>>,
+    pub expr: Option<Expression>,
+}
@@ -1,4 +1,4 @@
-use serde::{de, Deserializer};
+use serde::{de, Deserialize, Deserializer};
 
 #[derive(Clone, Debug, serde::Deserialize)]
 #[serde(untagged)]
@@ -10,14 +10,79 @@ pub enum IntegerValue {
 impl quote::ToTokens for IntegerValue {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         tokens.extend(match self {
-            IntegerValue::Variable(id) => {
-                proc_macro2::Ident::new(id, proc_macro2::Span::call_site()).into_token_stream()
-            }
+            IntegerValue::Variable(id) => crate::util::ident(id).into_token_stream(),
             IntegerValue::Literal(value) => quote::quote! { #value },
         });
     }
 }
 
+/// The source of an expression. Scalars that aren't strings are converted to their source form so
+/// that e.g. `valid: 5` and `valid: "5"` are equivalent.
+#[derive(Clone, Debug)]
+pub struct Expression(pub String);
+
+impl<'de> Deserialize<'de> for Expression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ExpressionVisitor;
+
+        impl<'de> de::Visitor<'de> for ExpressionVisitor {
+            type Value = Expression;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("expression")
+            }
+
+            fn visit_bool<E>(self, value: bool) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Expression(value.to_string()))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Expression(value.to_string()))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Expression(value.to_string()))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                // Debug formatting keeps the decimal point of whole numbers.
+                Ok(Expression(format!("{:?}", value)))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Expression(value.to_owned()))
+            }
+
+            fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Expression(value))
+            }
+        }
+
+        deserializer.deserialize_any(ExpressionVisitor)
+    }
+}
+
 pub fn deserialize_string_or_seq<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
 where
     D: Deserializer<'de>,
@@ -8,7 +8,71 @@ use serde::{
 };
 
 #[derive(Clone, Debug, serde::Deserialize)]
-pub struct Enum(pub HashMap<u64, EnumValue>);
+pub struct Enum(#[serde(deserialize_with = "deserialize_values")] pub HashMap<u64, EnumValue>);
+
+/// Deserializes the values of an enum. Values above [`i64::MAX`] can only be written as strings
+/// in YAML, e.g. `0xffff_ffff_ffff_ffff`, so strings are parsed as integer literals too.
+fn deserialize_values<'de, D>(deserializer: D) -> Result<HashMap<u64, EnumValue>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(PartialEq, Eq, Hash)]
+    struct Value(u64);
+
+    impl<'de> Deserialize<'de> for Value {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct ValueVisitor;
+
+            impl<'de> de::Visitor<'de> for ValueVisitor {
+                type Value = Value;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    formatter.write_str("a non-negative integer")
+                }
+
+                fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+                where
+                    E: de::Error,
+                {
+                    Ok(Value(value))
+                }
+
+                fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+                where
+                    E: de::Error,
+                {
+                    u64::try_from(value)
+                        .map(Value)
+                        .map_err(|_| E::custom(format!("negative enum value {}", value)))
+                }
+
+                fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+                where
+                    E: de::Error,
+                {
+                    let digits = value.replace('_', "");
+                    let (digits, radix) = match digits.get(..2) {
+                        Some("0x" | "0X") => (&digits[2..], 16),
+                        Some("0b" | "0B") => (&digits[2..], 2),
+                        Some("0o" | "0O") => (&digits[2..], 8),
+                        _ => (&digits[..], 10),
+                    };
+                    u64::from_str_radix(digits, radix)
+                        .map(Value)
+                        .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+                }
+            }
+
+            deserializer.deserialize_any(ValueVisitor)
+        }
+    }
+
+    let values = HashMap::<Value, EnumValue>::deserialize(deserializer)?;
+    Ok(values.into_iter().map(|(k, v)| (k.0, v)).collect())
+}
 
 #[derive(Clone, Debug)]
 pub struct EnumValue {
@@ -1,4 +1,7 @@
-use crate::de::data::deserialize_string_or_seq;
+use crate::de::{
+    attr::deserialize_cases,
+    data::{deserialize_string_or_seq, Expression},
+};
 
 use serde::Deserialize;
 
@@ -8,13 +11,15 @@ pub struct Meta {
     pub id: Option<String>,
     #[serde(flatten)]
     pub doc: MetaDoc,
-    pub ks_version: Option<String>,
+    pub ks_version: Option<Expression>,
     pub ks_debug: bool,
     pub ks_opaque_types: bool,
     pub imports: Vec<String>,
     pub encoding: Option<String>,
     #[serde(rename = "endian")]
-    pub endianness: Option<Endianness>,
+    pub endianness: Option<EndianSpec>,
+    #[serde(rename = "bit-endian")]
+    pub bit_endianness: Option<Endianness>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -29,6 +34,19 @@ pub struct MetaDoc {
     pub license: String,
 }
 
+/// The `endian` of a `meta`, either fixed or calculated from a value when the type is parsed.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EndianSpec {
+    Fixed(Endianness),
+    #[serde(rename_all = "kebab-case")]
+    Switch {
+        switch_on: Expression,
+        #[serde(deserialize_with = "deserialize_cases")]
+        cases: Vec<(Expression, Endianness)>,
+    },
+}
+
 #[derive(Copy, Clone, Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Endianness {
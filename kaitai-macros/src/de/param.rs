@@ -3,11 +3,11 @@ use crate::de::doc::Doc;
 #[derive(Clone, Debug, Default, serde::Deserialize)]
 #[serde(rename_all = "kebab-case", default)]
 pub struct Param {
-    id: String,
+    pub id: String,
     #[serde(rename = "type")]
-    ty: String,
+    pub ty: String,
     #[serde(flatten)]
-    doc: Doc,
+    pub doc: Doc,
     #[serde(rename = "enum")]
-    en: Option<String>,
+    pub en: Option<String>,
 }
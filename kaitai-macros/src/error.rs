@@ -12,4 +12,61 @@ pub enum Error {
     },
     #[error("{0} not found")]
     RequiredAttrNotFound(String),
+    #[error("invalid expression `{expr}`: {reason}")]
+    InvalidExpression { expr: String, reason: String },
+    #[error("enum {0} not found")]
+    UnknownEnum(String),
+    #[error("{member} is not a member of enum {en}")]
+    UnknownEnumMember { en: String, member: String },
+    #[error("the inline enum of {field} clashes with the enum {en}")]
+    InlineEnumClash { field: String, en: String },
+    #[error("unsupported io `{0}`, only `_io` and `_parent._io` are supported")]
+    UnsupportedIo(String),
+    #[error("GUID field {0} must be 16 bytes, without a type or process")]
+    InvalidGuid(String),
+    #[error("boolean field {0} must be a single byte, without a size, enum or process")]
+    InvalidBool(String),
+    #[error("{0} is read without an endianness, which is set with meta/endian")]
+    MissingEndianness(String),
+    #[error("bit-sized integer {0} can't have a size or process")]
+    SizedBits(String),
+    #[error("unknown type {segment} in the type path {path}")]
+    UnknownTypePath { path: String, segment: String },
+    #[error("can't read the imported file {import}: {reason}")]
+    InvalidImport { import: String, reason: String },
+    #[error("param {param} has the unsupported type `{ty}`")]
+    UnsupportedParamType { param: String, ty: String },
+    #[error("the root type can't have params, it is parsed without arguments")]
+    RootParams,
+    #[error("_root.{0} must be a field of the root type that is read once, into a type a param can have")]
+    UnsupportedRootValue(String),
+    #[error("type {ty} takes {expected} arguments, but {actual} are passed")]
+    WrongArgumentCount {
+        ty: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("invalid switch case `{0}`")]
+    InvalidCase(String),
+    #[error("str field '{0}' requires size, size-eos, or terminator")]
+    UnderspecifiedStr(String),
+    #[error("invalid terminator {0}, terminators are bytes")]
+    InvalidTerminator(u64),
+    #[error("invalid pad-right {0}, padding is a byte")]
+    InvalidPad(u64),
+    #[error("unknown process `{0}`, only `xor` is supported")]
+    UnknownProcess(String),
+    #[error("{field} can't be converted to bytes: {reason}")]
+    NotSerializable { field: String, reason: &'static str },
+    #[error("{field} doesn't have a fixed layout: {reason}")]
+    NotFixedLayout { field: String, reason: &'static str },
+    #[error("{field} can't be borrowed from the input: {reason}")]
+    NotBorrowable { field: String, reason: &'static str },
+    #[error("invalid ks-version `{0}`")]
+    InvalidKsVersion(String),
+    #[error("the format requires Kaitai Struct {required}, but only {supported} is supported")]
+    UnsupportedKsVersion {
+        required: String,
+        supported: &'static str,
+    },
 }
@@ -1,33 +1,100 @@
 use crate::{
     de,
-    hir::{doc::Doc, meta::Endianness},
-    util::sc_to_ucc,
+    error::Error,
+    hir::{
+        doc::Doc,
+        expr::{self, BinaryOp, Expr, ExprType, IoProperty},
+        meta::{self, Endian, Endianness},
+        param::{self, RootValue},
+    },
+    util::{ident, sc_to_ucc, type_ident},
 };
 
-use proc_macro2::{Ident, Span, TokenStream};
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::{Ident, Literal, Span, TokenStream};
 use quote::{quote, ToTokens};
 
 pub use crate::de::data::IntegerValue;
 
+/// Whether the errors of the attributes get the path to them, see the `error-path` feature of the
+/// runtime crate.
+const ERROR_PATH: bool = cfg!(feature = "error-path");
+
+/// The type that attributes are defined in.
+#[derive(Clone, Debug)]
+pub struct Context<'a> {
+    pub meta_doc: Option<de::meta::MetaDoc>,
+    /// The identifier of the type.
+    pub ty: &'a Ident,
+    /// The enums visible from the type, mapping their KS ids to the KS ids of their members.
+    pub enums: &'a HashMap<String, Vec<String>>,
+    /// The default encoding of strings (KS: `meta/encoding`).
+    pub encoding: Option<&'a str>,
+    /// The order of the bits of bit-sized integers (KS: `meta/bit-endian`).
+    pub bit_endianness: Endianness,
+    /// The types of the parameters of the user types, by KS id.
+    pub params: &'a HashMap<String, Vec<ExprType>>,
+    /// The root values of the user types, by KS id, see
+    /// [`root_values`](crate::hir::param::root_values).
+    pub root_values: &'a HashMap<String, Vec<RootValue>>,
+    /// Whether the type is the root type, whose fields are passed as root values.
+    pub in_root: bool,
+    /// The endianness of the type, passed to the types it reads that inherit it.
+    pub endianness: Endian,
+    /// The KS ids of the user types inheriting a calculated endianness, see
+    /// [`runtime_endian`](crate::hir::param::runtime_endian).
+    pub runtime_endian: &'a HashSet<String>,
+    /// Whether the types derive `Hash`, see [`InheritedMeta::hash`](crate::hir::ty::InheritedMeta::hash).
+    pub hash: bool,
+    /// Whether the types borrow from the slice they are parsed from, see
+    /// [`InheritedMeta::borrowed`](crate::hir::ty::InheritedMeta::borrowed).
+    pub borrowed: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct Attributes(Vec<Attribute>);
 
-impl TryFrom<(Option<de::meta::MetaDoc>, Vec<de::attr::Attr>)> for Attributes {
-    type Error = ();
+impl TryFrom<(&Context<'_>, Vec<de::attr::Attr>)> for Attributes {
+    type Error = Error;
 
     fn try_from(
-        (meta_doc, attrs): (Option<de::meta::MetaDoc>, Vec<de::attr::Attr>),
+        (context, attrs): (&Context<'_>, Vec<de::attr::Attr>),
     ) -> Result<Self, Self::Error> {
         Ok(Self(
             attrs
                 .into_iter()
-                .map(|a| (meta_doc.clone(), a).try_into())
+                .map(|a| (context, a).try_into())
                 .collect::<Result<Vec<_>, _>>()?,
         ))
     }
 }
 
 impl Attributes {
+    /// Returns the definitions of the types generated for the attributes, e.g. the enum holding
+    /// the possible types of a switch.
+    pub fn type_definitions(&self) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0
+            .iter()
+            .filter_map(|a| a.switch().map(Switch::definition))
+    }
+
+    /// Returns the identifier of the enum of each switch, with the types of its variants.
+    pub fn switch_types(&self) -> impl Iterator<Item = (&Ident, Vec<TokenStream>)> + '_ {
+        self.0.iter().filter_map(Attribute::switch).map(|switch| {
+            let types = switch.cases.iter().map(|(_, _, ty)| ty.ty()).collect();
+            (&switch.ident, types)
+        })
+    }
+
+    /// Marks the enums of the switches that hold floats, given the identifiers of the types and
+    /// enums that hold floats, see [`Type::mark_floats`](crate::hir::ty::Type::mark_floats).
+    pub fn mark_float_switches(&mut self, floats: &HashSet<String>) {
+        for switch in self.0.iter_mut().filter_map(Attribute::switch_mut) {
+            switch.floats = floats.contains(&switch.ident.to_string());
+        }
+    }
+
     pub fn field_definitions(&self) -> impl Iterator<Item = TokenStream> + '_ {
         self.0
             .iter()
@@ -35,26 +102,239 @@ impl Attributes {
             .map(|a| a.field_definition())
     }
 
-    pub fn variable_assignments(
-        &self,
-        endianness: Endianness,
-    ) -> impl Iterator<Item = TokenStream> + '_ {
+    /// Returns the assignments of the attributes, each preceded by the statements `before`
+    /// returns for its index. With `record_spans`, the byte range of each stored attribute is also
+    /// recorded in a `_spans` variable, see [`spans_definition`].
+    ///
+    /// [`spans_definition`]: Attributes::spans_definition
+    pub fn variable_assignments<'a>(
+        &'a self,
+        endianness: Endian,
+        record_spans: bool,
+        before: impl Fn(usize) -> TokenStream + 'a,
+    ) -> impl Iterator<Item = TokenStream> + 'a {
+        // Bit-sized integers share the bits left over by the previous ones, which are discarded
+        // by the first attribute that isn't one.
+        let bits = self.0.iter().any(Attribute::is_bits).then(|| {
+            quote! { let mut _bits = ::kaitai::BitReader::new(); }
+        });
+        let after_bits = std::iter::once(false).chain(self.0.iter().map(Attribute::is_bits));
+        let assignments =
+            self.0
+                .iter()
+                .zip(after_bits)
+                .enumerate()
+                .map(move |(index, (a, after_bits))| {
+                    let before = before(index);
+                    let assignment = a.variable_assignment(endianness);
+                    let assignment = if after_bits && !a.is_bits() {
+                        quote! {
+                            _bits.align_to_byte();
+                            #assignment
+                        }
+                    } else {
+                        assignment
+                    };
+                    if record_spans && a.is_stored() {
+                        let id = &a.id;
+                        quote! {
+                            #before
+                            let _start = buf.pos()?;
+                            #assignment
+                            _spans.#id = (_start, buf.pos()?);
+                        }
+                    } else {
+                        quote! {
+                            #before
+                            #assignment
+                        }
+                    }
+                });
+        bits.into_iter().chain(assignments)
+    }
+
+    /// Returns the definition of the struct `spans_id`, holding the byte range each stored
+    /// attribute of the type `ty` was read from.
+    pub fn spans_definition(&self, ty: &Ident, spans_id: &Ident) -> TokenStream {
+        let doc = format!(
+            " The byte ranges the fields of [`{}`] were read from, as `(start, end)` positions in \
+             the stream.",
+            ty
+        );
+        let fields = self.0.iter().filter(|a| a.is_stored()).map(|a| {
+            let id = &a.id;
+            let doc = format!(" The range of [`{0}`]({1}::{0}).", id, ty);
+            quote! {
+                #[doc = #doc]
+                pub #id: (u64, u64)
+            }
+        });
+
+        quote! {
+            #[doc = #doc]
+            #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub struct #spans_id {
+                #(#fields),*
+            }
+        }
+    }
+
+    /// Returns the calls adding the stored attributes to a `DebugStruct`, in the order of their
+    /// definitions. Byte arrays are rendered in hexadecimal.
+    pub fn debug_fields(&self) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().filter(|a| a.is_stored()).map(|a| {
+            let id = &a.id;
+            let name = id.to_string().trim_start_matches("r#").to_owned();
+            let value = if a.is_bytes() {
+                quote! { &::kaitai::HexBytes(&self.#id) }
+            } else {
+                quote! { &self.#id }
+            };
+            let raw = a.raw_id().map(|raw_id| {
+                let raw_name = raw_id.to_string().trim_start_matches("r#").to_owned();
+                quote! { .field(#raw_name, &self.#raw_id) }
+            });
+            quote! { .field(#name, #value) #raw }
+        })
+    }
+
+    /// Returns the field of the type as a newtype (macro argument `newtypes`) if the attribute is
+    /// the only one and is stored in a single field: its id, its doc comment, its type and the
+    /// value rendered by `Debug` for `self.0`.
+    pub fn newtype_field(&self) -> Option<(&Ident, &Doc, TokenStream, TokenStream)> {
+        let a = match &self.0[..] {
+            [a] if a.raw_id().is_none() => a,
+            _ => return None,
+        };
+        let value = if a.is_bytes() {
+            quote! { &::kaitai::HexBytes(&self.0) }
+        } else {
+            quote! { &self.0 }
+        };
+        Some((&a.id, &a.doc, a.field_ty()?, value))
+    }
+
+    /// Returns the KS id of each attribute, with the names its condition refers to.
+    pub fn conditions(&self) -> Vec<(&str, Vec<&str>)> {
         self.0
             .iter()
-            .map(move |a| a.variable_assignment(endianness))
+            .map(|a| {
+                let names = a.condition.iter().flat_map(Expr::names).collect();
+                (a.ks_id.as_str(), names)
+            })
+            .collect()
+    }
+
+    /// Returns the types of the stored attributes, by KS id, for the expressions referring to
+    /// them.
+    pub fn expr_types(&self) -> HashMap<String, ExprType> {
+        self.0
+            .iter()
+            .filter_map(|a| Some((a.ks_id.clone(), a.expr_type()?)))
+            .collect()
     }
 
-    pub fn field_assignments(&self) -> impl Iterator<Item = &Ident> {
-        self.0.iter().filter(|a| a.is_stored()).map(|a| &a.id)
+    pub fn field_assignments(&self) -> impl Iterator<Item = Ident> + '_ {
+        self.0
+            .iter()
+            .filter(|a| a.is_stored())
+            .flat_map(|a| std::iter::once(a.id.clone()).chain(a.raw_id()))
+    }
+
+    /// Returns the accessor methods of the attributes, e.g. the `_raw` accessors of enum fields.
+    pub fn methods(&self) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().filter_map(|a| a.raw_accessor())
+    }
+
+    /// Returns the number of bytes the attributes take up, and the statements appending them to
+    /// `_bytes` in the order they are read in.
+    ///
+    /// Only attributes that always take up the same number of bytes can be written, see
+    /// [`Attribute::write`].
+    pub fn write(&self, endianness: Endian) -> Result<(u64, Vec<TokenStream>), Error> {
+        let mut size = 0;
+        let mut writes = Vec::new();
+        for attr in &self.0 {
+            let (attr_size, write) = attr.write(endianness)?;
+            size += attr_size;
+            writes.push(write);
+        }
+        Ok((size, writes))
+    }
+
+    /// Returns the `FieldInfo` of each stored attribute, with its offset if the attributes before
+    /// it have a fixed size, see [`Attribute::fixed_size`].
+    pub fn schema(&self) -> Vec<TokenStream> {
+        let mut offset = Some(0u64);
+        let mut fields = Vec::new();
+        for attr in &self.0 {
+            if let Some(ty) = attr.field_ty() {
+                let name = &attr.ks_id;
+                let ty = ty.to_string().replace(' ', "");
+                let offset = match offset {
+                    Some(offset) => quote! { ::std::option::Option::Some(#offset) },
+                    None => quote! { ::std::option::Option::None },
+                };
+                fields.push(quote! {
+                    ::kaitai::FieldInfo { name: #name, ty: #ty, offset: #offset }
+                });
+            }
+            offset = offset
+                .zip(attr.fixed_size().ok())
+                .map(|(offset, size)| offset + size);
+        }
+        fields
+    }
+
+    /// Returns the KS id, the offset and the number of bytes of each attribute, or an error if
+    /// they can vary, see [`Attribute::fixed_size`].
+    pub fn layout(&self) -> Result<Vec<(&str, u64, u64)>, Error> {
+        let mut offset = 0;
+        let mut layout = Vec::new();
+        for attr in &self.0 {
+            let size = attr.fixed_size().map_err(|reason| Error::NotFixedLayout {
+                field: attr.ks_id.clone(),
+                reason,
+            })?;
+            layout.push((attr.ks_id.as_str(), offset, size));
+            offset += size;
+        }
+        Ok(layout)
+    }
+
+    /// Returns an error if an attribute can't be parsed in the borrowed mode (macro argument
+    /// `borrowed`): switches, and types parsed from processed bytes, which aren't part of the
+    /// slice, aren't supported.
+    pub fn check_borrowed(&self) -> Result<(), Error> {
+        for attr in &self.0 {
+            let reason = match &attr.logic {
+                _ if attr.switch().is_some() => "switches aren't supported",
+                Logic::Process { logic: Some(_), .. } => "processed types aren't supported",
+                _ => continue,
+            };
+            return Err(Error::NotBorrowable {
+                field: attr.ks_id.clone(),
+                reason,
+            });
+        }
+        Ok(())
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Attribute {
     id: Ident,
+    /// The id of the attribute in the KS file, which can differ from `id` if it is a keyword.
+    ks_id: String,
     doc: Doc,
     repeat: Option<Repeat>,
+    /// The condition the attribute is read under (KS: `if`).
+    condition: Option<Expr>,
+    /// Whether the attribute is stored with the default value of its type when the condition is
+    /// false, rather than in an [`Option`].
+    if_default: bool,
     logic: Logic,
+    valid: Option<Valid>,
 }
 
 impl Attribute {
@@ -62,12 +342,296 @@ impl Attribute {
         match &self.logic {
             Logic::FixedContents(_) => false,
             Logic::Type(_) => true,
-            Logic::Switch { .. } => true,
+            Logic::Switch(_) => true,
             Logic::Size(_) => true,
-            Logic::Process(_) => true,
+            Logic::Slice(_) => true,
+            Logic::Str(_) => true,
+            Logic::Substream { .. } => true,
+            Logic::Process { .. } => true,
+            Logic::Guid => true,
+            Logic::Bool { .. } => true,
+            Logic::Bits { .. } => true,
+        }
+    }
+
+    /// Returns the switch of the attribute, if its type is one. The switch of a sized or processed
+    /// attribute is parsed from its bytes.
+    fn switch(&self) -> Option<&Switch> {
+        let logic = match &self.logic {
+            Logic::Substream { logic, .. }
+            | Logic::Process {
+                logic: Some(logic), ..
+            } => &**logic,
+            logic => logic,
+        };
+        match logic {
+            Logic::Switch(switch) => Some(switch),
+            _ => None,
+        }
+    }
+
+    fn switch_mut(&mut self) -> Option<&mut Switch> {
+        let logic = match &mut self.logic {
+            Logic::Substream { logic, .. }
+            | Logic::Process {
+                logic: Some(logic), ..
+            } => &mut **logic,
+            logic => logic,
+        };
+        match logic {
+            Logic::Switch(switch) => Some(switch),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the attribute is a bit-sized integer, read with the `_bits` reader.
+    pub fn is_bits(&self) -> bool {
+        matches!(self.logic, Logic::Bits { .. })
+    }
+
+    pub fn id(&self) -> &Ident {
+        &self.id
+    }
+
+    /// Returns the type of the attribute in expressions, or [`None`] if it isn't stored.
+    pub fn expr_type(&self) -> Option<ExprType> {
+        let ty = self.logic.expr_type()?;
+        let ty = match self.repeat {
+            Some(_) => ExprType::Array(Box::new(ty), self.fixed_count()),
+            None => ty,
+        };
+        Some(if self.is_optional() {
+            let ty = ty.ty();
+            ExprType::Other(quote! { ::std::option::Option<#ty> })
+        } else {
+            ty
+        })
+    }
+
+    /// Returns the number of elements of the attribute if it is repeated a literal number of
+    /// times, in which case the elements are stored in an array rather than a `Vec`.
+    fn fixed_count(&self) -> Option<u64> {
+        match self.repeat {
+            Some(Repeat::Expr(Expr::Int(count))) => Some(count),
+            _ => None,
+        }
+    }
+
+    /// Returns the type storing the elements of type `ty` of the repeated attribute.
+    fn repeated_ty(&self, ty: TokenStream) -> TokenStream {
+        match self.fixed_count() {
+            Some(count) => {
+                let count = Literal::u64_unsuffixed(count);
+                quote! { [#ty; #count] }
+            }
+            None => quote! { ::std::vec::Vec<#ty> },
+        }
+    }
+
+    /// Returns whether the attribute is stored in an [`Option`], i.e. whether it has a condition
+    /// and isn't stored with a default value when the condition is false.
+    fn is_optional(&self) -> bool {
+        self.condition.is_some() && !self.if_default
+    }
+
+    /// Returns `ty` wrapped in an [`Option`] if the attribute is optional.
+    fn optional(&self, ty: TokenStream) -> TokenStream {
+        if self.is_optional() {
+            quote! { ::std::option::Option<#ty> }
+        } else {
+            ty
+        }
+    }
+
+    /// Returns the number of bytes the attribute always takes up, or the reason it can vary: only
+    /// integers, floats, enums, fixed contents and byte arrays of a literal size, without
+    /// `repeat` or `if`, have a fixed size.
+    fn fixed_size(&self) -> Result<u64, &'static str> {
+        if self.repeat.is_some() {
+            return Err("repeated fields aren't supported");
+        }
+        if self.condition.is_some() {
+            return Err("conditional fields aren't supported");
+        }
+        match &self.logic {
+            Logic::FixedContents(contents) => Ok(contents.len() as u64),
+            Logic::Type(Type::BuiltIn { ty, .. }) => Ok(ty.size()),
+            Logic::Size(Size::Fixed(IntegerValue::Literal(size)))
+            | Logic::Slice(Size::Fixed(IntegerValue::Literal(size))) => Ok(*size),
+            Logic::Guid => Ok(16),
+            Logic::Bool { .. } => Ok(1),
+            Logic::Size(_) | Logic::Slice(_) => {
+                Err("only byte arrays of a literal size are supported")
+            }
+            Logic::Type(Type::UserDefined { .. }) => Err("user types aren't supported"),
+            Logic::Switch(_) => Err("switches aren't supported"),
+            Logic::Str(_) => Err("strings aren't supported"),
+            Logic::Substream { .. } => Err("substreams aren't supported"),
+            Logic::Process { .. } => Err("processed fields aren't supported"),
+            Logic::Bits { .. } => Err("bit-sized integers aren't supported"),
         }
     }
 
+    /// Returns the number of bytes the attribute takes up, and the statement appending the bytes
+    /// it was read from to `_bytes`, or an error if they can't be determined from its value
+    /// alone.
+    fn write(&self, endianness: Endian) -> Result<(u64, TokenStream), Error> {
+        let size = self.fixed_size().map_err(|reason| Error::NotSerializable {
+            field: self.ks_id.clone(),
+            reason,
+        })?;
+
+        let id = &self.id;
+        let write = match &self.logic {
+            Logic::FixedContents(contents) => {
+                let bytes = Literal::byte_string(contents);
+                quote! { _bytes.extend_from_slice(#bytes); }
+            }
+            Logic::Type(Type::BuiltIn { ty, en }) => {
+                let to_bytes = match endianness {
+                    Endian::Fixed(Endianness::Be) => quote! { to_be_bytes },
+                    Endian::Fixed(Endianness::Le) => quote! { to_le_bytes },
+                    Endian::Runtime | Endian::Unspecified if ty.size() == 1 => {
+                        quote! { to_le_bytes }
+                    }
+                    Endian::Unspecified => {
+                        unreachable!("{} read without an endianness", self.ks_id)
+                    }
+                    Endian::Runtime => {
+                        return Err(Error::NotSerializable {
+                            field: self.ks_id.clone(),
+                            reason: "calculated endianness isn't supported",
+                        })
+                    }
+                };
+                let value = match en {
+                    // The raw value is kept unless the field was changed, so that values that
+                    // aren't members of the enum in lenient parses are written back unchanged.
+                    Some(en) => {
+                        let raw_id = self.raw_id().unwrap();
+                        let wide_ty = ty.wide_ty();
+                        quote! {
+                            if <#en as ::std::convert::TryFrom<#wide_ty>>::try_from(#wide_ty::from(self.#raw_id))
+                                .ok()
+                                .or_else(#en::__fallback)
+                                == ::std::option::Option::Some(self.#id)
+                            {
+                                self.#raw_id
+                            } else {
+                                self.#id as u64 as #ty
+                            }
+                        }
+                    }
+                    None => quote! { self.#id },
+                };
+                quote! { _bytes.extend_from_slice(&(#value).#to_bytes()); }
+            }
+            Logic::Size(_) | Logic::Slice(_) => {
+                let field = &self.ks_id;
+                quote! {
+                    if self.#id.len() as u64 != #size {
+                        return Err(::kaitai::error::Error::SizeMismatch {
+                            field: #field.to_owned(),
+                            expected: #size,
+                            actual: self.#id.len() as u64,
+                        });
+                    }
+                    _bytes.extend_from_slice(&self.#id);
+                }
+            }
+            Logic::Guid => quote! { _bytes.extend_from_slice(&self.#id.0); },
+            Logic::Bool { .. } => quote! { _bytes.push(self.#id as u8); },
+            _ => unreachable!("{} doesn't have a fixed size", self.ks_id),
+        };
+        Ok((size, write))
+    }
+
+    /// Returns whether the attribute is stored as a single byte array.
+    fn is_bytes(&self) -> bool {
+        self.repeat.is_none()
+            && !self.is_optional()
+            && matches!(
+                self.logic,
+                Logic::Size(_) | Logic::Slice(_) | Logic::Process { logic: None, .. }
+            )
+    }
+
+    /// Returns the type of the raw value and the enum of the attribute if it is an enum field,
+    /// read from a built-in integer type or from bits.
+    fn enum_type(&self) -> Option<(TokenStream, &Ident)> {
+        match &self.logic {
+            Logic::Type(Type::BuiltIn { ty, en: Some(en) }) => Some((ty.to_token_stream(), en)),
+            Logic::Bits {
+                width,
+                en: Some(en),
+                ..
+            } => Some((
+                Ident::new(bits_ty(*width), Span::call_site()).into_token_stream(),
+                en,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Returns the identifier of the private field storing the raw value of an enum field.
+    fn raw_id(&self) -> Option<Ident> {
+        self.enum_type()
+            .map(|_| ident(&format!("{}_raw", self.ks_id)))
+    }
+
+    /// Returns the type of the raw value of an enum field.
+    fn raw_ty(&self) -> Option<TokenStream> {
+        self.enum_type().map(|(ty, _)| {
+            self.optional(match self.repeat {
+                Some(_) => self.repeated_ty(ty),
+                None => ty,
+            })
+        })
+    }
+
+    /// Returns the method returning the raw value of an enum field, as it was read from the
+    /// stream.
+    fn raw_accessor(&self) -> Option<TokenStream> {
+        let raw_id = self.raw_id()?;
+        let doc = format!(
+            " The raw value of [`{0}`](Self::{0}), as it was read from the stream.",
+            self.id
+        );
+        Some(match self.repeat {
+            Some(_) if self.is_optional() => {
+                let (ty, _) = self.enum_type()?;
+                let raw = match self.fixed_count() {
+                    Some(_) => quote! { self.#raw_id.as_ref().map(|raw| &raw[..]) },
+                    None => quote! { self.#raw_id.as_deref() },
+                };
+                quote! {
+                    #[doc = #doc]
+                    pub fn #raw_id(&self) -> ::std::option::Option<&[#ty]> {
+                        #raw
+                    }
+                }
+            }
+            Some(_) => {
+                let (ty, _) = self.enum_type()?;
+                quote! {
+                    #[doc = #doc]
+                    pub fn #raw_id(&self) -> &[#ty] {
+                        &self.#raw_id
+                    }
+                }
+            }
+            None => {
+                let raw_ty = self.raw_ty()?;
+                quote! {
+                    #[doc = #doc]
+                    pub fn #raw_id(&self) -> #raw_ty {
+                        self.#raw_id
+                    }
+                }
+            }
+        })
+    }
+
     /// Returns a [`TokenStream`] containing the definition of the struct field
     /// containing the `Attribute`.
     ///
@@ -112,7 +676,28 @@ impl Attribute {
     /// pub example_attr: ExampleEnum
     /// ```
     ///
-    /// Note that the name of the enum is converted into upper camel case.
+    /// Note that the name of the enum is converted into upper camel case. The raw value is
+    /// stored in a private field as well, which is returned by the generated `example_attr_raw`
+    /// method.
+    ///
+    /// ## Switch
+    ///
+    /// ```yaml
+    /// id: body
+    /// type:
+    ///   switch-on: kind
+    ///   cases:
+    ///     'animal::cat': cat
+    ///     _: other
+    /// ## where the attribute is defined in the example_type type.
+    /// ```
+    /// results in
+    /// ```ignore
+    /// pub body: ExampleTypeBody
+    /// ```
+    ///
+    /// where `ExampleTypeBody` is a generated enum with a variant for each type, i.e. `Cat(Cat)`
+    /// and `Other(Other)`.
     ///
     /// ## Fixed Contents
     ///
@@ -120,23 +705,30 @@ impl Attribute {
     /// Hence, this method return an empty [`TokenStream`] if the attribute has fixed
     /// contents.
     pub fn field_definition(&self) -> TokenStream {
-        let mut ty = match &self.logic {
-            Logic::FixedContents(_) => return TokenStream::new(),
-            Logic::Type(ty) => ty.ty(),
-            Logic::Switch { .. } => todo!(),
-            Logic::Size(_) => quote! { ::std::vec::Vec<u8> },
-            Logic::Process(_) => todo!(),
+        let ty = match self.field_ty() {
+            Some(ty) => ty,
+            None => return TokenStream::new(),
         };
-        if self.repeat.is_some() {
-            ty = quote! { ::std::vec::Vec<#ty> };
-        }
 
         let doc = &self.doc;
         let id = &self.id;
+        let raw = self.raw_id().zip(self.raw_ty()).map(|(raw_id, raw_ty)| {
+            quote! { , #raw_id: #raw_ty }
+        });
         quote! {
             #doc
             pub #id: #ty
+            #raw
+        }
+    }
+
+    /// Returns the type of the field of the attribute, or [`None`] if it isn't stored.
+    fn field_ty(&self) -> Option<TokenStream> {
+        let mut ty = self.logic.ty()?;
+        if self.repeat.is_some() {
+            ty = self.repeated_ty(ty);
         }
+        Some(self.optional(ty))
     }
 
     /// Returns a [`TokenStream`] representing the assignment of the variable
@@ -192,126 +784,779 @@ impl Attribute {
     /// name: id
     /// contents: glTF
     /// ```
-    /// results is
-    /// ```ignore
-    /// buf.ensure_fixed_contents("glTF".as_bytes())?;
-    /// ```
+    /// results in a call of `buf.ensure_fixed_contents(&[103, 108, 84, 70])`, with the id of the
+    /// attribute added to the error if the check fails.
     ///
-    pub fn variable_assignment(&self, endianness: Endianness) -> TokenStream {
-        let mut expr = match &self.logic {
-            Logic::FixedContents(c) => {
-                let contents = c.iter().map(|i| quote! { #i });
-                return quote! { buf.ensure_fixed_contents(&[#(#contents),*])?; };
+    pub fn variable_assignment(&self, endianness: Endian) -> TokenStream {
+        let assignment = self.unconditional_assignment(endianness);
+        let condition = match &self.condition {
+            Some(condition) => condition,
+            None => return assignment,
+        };
+        if !self.is_stored() {
+            return quote! {
+                if #condition {
+                    #assignment
+                }
+            };
+        }
+
+        // All the variables are assigned in both branches, as a tuple if there is a raw value.
+        let vars = std::iter::once(self.id.clone())
+            .chain(self.raw_id())
+            .collect::<Vec<_>>();
+        let (values, defaults): (Vec<_>, Vec<_>) = vars
+            .iter()
+            .map(|var| {
+                if self.if_default {
+                    (
+                        quote! { #var },
+                        quote! { ::std::default::Default::default() },
+                    )
+                } else {
+                    (
+                        quote! { ::std::option::Option::Some(#var) },
+                        quote! { ::std::option::Option::None },
+                    )
+                }
+            })
+            .unzip();
+        let tuple = |items: &[TokenStream]| match items {
+            [item] => item.clone(),
+            items => quote! { (#(#items),*) },
+        };
+        let vars = tuple(
+            &vars
+                .iter()
+                .map(ToTokens::to_token_stream)
+                .collect::<Vec<_>>(),
+        );
+        let values = tuple(&values);
+        let defaults = tuple(&defaults);
+        quote! {
+            let #vars = if #condition {
+                #assignment
+                #values
+            } else {
+                #defaults
+            };
+        }
+    }
+
+    /// Returns the assignment of the variable containing the `Attribute`, ignoring its condition.
+    fn unconditional_assignment(&self, endianness: Endian) -> TokenStream {
+        if let Some((_, en)) = self.enum_type() {
+            return self.enum_assignment(en, endianness);
+        }
+
+        let id = &self.id;
+        if let (Logic::Type(Type::BuiltIn { ty, en: None }), None) = (&self.logic, &self.valid) {
+            if let Some(expr) = self.bulk_read(ty, endianness) {
+                let expr = self.in_path(expr);
+                return quote! { let #id = #expr; };
+            }
+        }
+
+        let mut expr = self.logic.expr(&self.ks_id, endianness, Io::Same);
+        if !self.is_stored() {
+            let expr = self.in_path(expr);
+            return quote! { #expr; };
+        }
+        expr = self.checked(expr);
+        expr = self.repeated(expr);
+        expr = self.in_path(expr);
+
+        quote! { let #id = #expr; }
+    }
+
+    /// Returns the expression reading all the elements of an array of the multi-byte built-in
+    /// type `ty` at once, if its count is a `repeat-expr` expression, rather than one by one.
+    fn bulk_read(&self, ty: &BuiltInType, endianness: Endian) -> Option<TokenStream> {
+        let count = match &self.repeat {
+            Some(Repeat::Expr(count)) if self.fixed_count().is_none() && ty.size() > 1 => {
+                count.operand()
             }
-            Logic::Type(ty) => ty.expr(endianness),
-            Logic::Switch { .. } => todo!(),
-            Logic::Size(size) => match size {
-                Size::Fixed(count) => quote! { buf.read_bytes(#count)? },
-                Size::Eos => quote! { buf.read_bytes_full()? },
+            _ => return None,
+        };
+        Some(ty.with_endianness(endianness, |suffix| {
+            let read = Ident::new(
+                &format!("read_{}{}_array", ty.ks_type(), suffix),
+                Span::call_site(),
+            );
+            quote! { buf.#read(#count as usize)? }
+        }))
+    }
+
+    /// Returns the assignments of both the raw value and the enum value of an enum field.
+    fn enum_assignment(&self, en: &Ident, endianness: Endian) -> TokenStream {
+        let id = &self.id;
+        let raw_id = self.raw_id();
+        let (raw_expr, wide_ty) = match &self.logic {
+            Logic::Type(Type::BuiltIn { ty, .. }) => match self.bulk_read(ty, endianness) {
+                Some(bulk) => (bulk, ty.wide_ty()),
+                None => (self.repeated(ty.expr(endianness)), ty.wide_ty()),
             },
-            Logic::Process(_) => todo!(),
+            // Bits are unsigned, and stored in the smallest integer holding them.
+            Logic::Bits {
+                width,
+                bit_endianness,
+                ..
+            } => {
+                let read = read_bits(*width, *bit_endianness);
+                let ty = Ident::new(bits_ty(*width), Span::call_site());
+                (self.repeated(quote! { (#read as #ty) }), quote! { u64 })
+            }
+            _ => unreachable!("{} isn't an enum field", self.ks_id),
         };
+        // In lenient parses unknown values get a fallback variant, the raw value is still stored.
+        let value = self.checked(quote! {
+            match <#en as ::std::convert::TryFrom<#wide_ty>>::try_from(#wide_ty::from(_raw)).ok() {
+                ::std::option::Option::Some(_value) => _value,
+                ::std::option::Option::None => {
+                    _ctx.recoverable(::kaitai::error::Error::NoEnumMatch)?;
+                    #en::__fallback().ok_or(::kaitai::error::Error::NoEnumMatch)?
+                }
+            }
+        });
 
-        if let Some(repeat) = &self.repeat {
-            expr = match repeat {
+        let expr = match self.repeat {
+            Some(_) if self.fixed_count().is_some() => {
+                let count = Literal::u64_unsuffixed(self.fixed_count().unwrap());
+                quote! {
+                    ::kaitai::__private::try_from_fn::<_, #count>(|_index| {
+                        let _raw = #raw_id[_index];
+                        Ok(#value)
+                    })?
+                }
+            }
+            Some(_) => quote! {
+                {
+                    let mut result = ::std::vec::Vec::with_capacity(#raw_id.len());
+                    for &_raw in &#raw_id {
+                        result.push(#value);
+                    }
+                    result
+                }
+            },
+            None => quote! {
+                {
+                    let _raw = #raw_id;
+                    #value
+                }
+            },
+        };
+
+        let raw_expr = self.in_path(raw_expr);
+        let expr = self.in_path(expr);
+        quote! {
+            let #raw_id = #raw_expr;
+            let #id = #expr;
+        }
+    }
+
+    /// Wraps `expr`, the expression reading the attribute, so that the id of the attribute is
+    /// added to the path of its errors with the `error-path` feature.
+    fn in_path(&self, expr: TokenStream) -> TokenStream {
+        if !ERROR_PATH {
+            return expr;
+        }
+        let ks_id = &self.ks_id;
+        quote! {
+            ::kaitai::__private::in_field(#ks_id, || {
+                let _value = #expr;
+                ::std::result::Result::Ok(_value)
+            })?
+        }
+    }
+
+    /// Wraps `expr` in the checks of the `valid` key, if there is one.
+    fn checked(&self, expr: TokenStream) -> TokenStream {
+        match &self.valid {
+            Some(valid) => {
+                let checks = valid.checks(&self.ks_id);
+                quote! {
+                    {
+                        let _value = #expr;
+                        #checks
+                        _value
+                    }
+                }
+            }
+            None => expr,
+        }
+    }
+
+    /// Wraps `expr`, the expression reading a single element, in the loop of the `repeat` key, if
+    /// there is one.
+    fn repeated(&self, expr: TokenStream) -> TokenStream {
+        // The index of the element is added to the path of its errors.
+        let element = |index: TokenStream| {
+            if ERROR_PATH {
+                quote! {
+                    ::kaitai::__private::in_element(#index, || {
+                        let _element = #expr;
+                        ::std::result::Result::Ok(_element)
+                    })?
+                }
+            } else {
+                expr.clone()
+            }
+        };
+        match &self.repeat {
+            Some(repeat) => match repeat {
                 Repeat::Eos => {
+                    let element = element(quote! { result.len() });
                     quote! {
                         {
                             let mut result = Vec::new();
                             while !buf.is_eof()? {
-                                result.push(#expr);
+                                result.push(#element);
                             }
                             result
                         }
                     }
                 }
-                Repeat::Expr(_) => todo!(),
-                Repeat::Until(_) => todo!(),
-            }
+                // Literal counts are read into an array.
+                Repeat::Expr(Expr::Int(count)) => {
+                    let count = Literal::u64_unsuffixed(*count);
+                    let expr = element(quote! { _index });
+                    quote! {
+                        ::kaitai::__private::try_from_fn::<_, #count>(|_index| {
+                            let element = #expr;
+                            Ok(element)
+                        })?
+                    }
+                }
+                Repeat::Expr(count) => {
+                    let count = count.operand();
+                    // The vector is allocated once if the size of the elements is known. The
+                    // capacity is limited to the number of elements left in the stream, so that
+                    // a corrupted count can't cause a huge allocation.
+                    let capacity = match self.logic.fixed_size() {
+                        Some(size) => quote! {
+                            _count.min((buf.size()?.saturating_sub(buf.pos()?) / #size) as usize)
+                        },
+                        None => quote! { 0 },
+                    };
+                    let element = element(quote! { _index });
+                    quote! {
+                        {
+                            let _count = #count as usize;
+                            let mut result = Vec::with_capacity(#capacity);
+                            for _index in 0.._count {
+                                result.push(#element);
+                            }
+                            result
+                        }
+                    }
+                }
+                Repeat::Until(until) => {
+                    let element = element(quote! { _index });
+                    quote! {
+                        {
+                            let mut result = Vec::new();
+                            let mut _index: usize = 0;
+                            loop {
+                                let _value = #element;
+                                let done = #until;
+                                result.push(_value);
+                                if done {
+                                    break;
+                                }
+                                _index += 1;
+                            }
+                            result
+                        }
+                    }
+                }
+            },
+            None => expr,
         }
-
-        let id = &self.id;
-        quote! { let #id = #expr; }
     }
 }
 
-impl TryFrom<(Option<de::meta::MetaDoc>, de::attr::Attr)> for Attribute {
-    type Error = ();
+impl TryFrom<(&Context<'_>, de::attr::Attr)> for Attribute {
+    type Error = Error;
 
-    fn try_from(
-        (meta_doc, attr): (Option<de::meta::MetaDoc>, de::attr::Attr),
-    ) -> Result<Self, Self::Error> {
-        let id = Ident::new(&attr.id.unwrap(), Span::call_site());
-        let doc = (meta_doc, attr.doc).into();
+    fn try_from((context, attr): (&Context<'_>, de::attr::Attr)) -> Result<Self, Self::Error> {
+        let ks_id = attr.id.unwrap();
+        let id = ident(&ks_id);
+        let mut attr_doc = attr.doc;
+        if let Some(orig_id) = attr.orig_id {
+            if !attr_doc.doc.is_empty() {
+                attr_doc.doc.push_str("\n\n");
+            }
+            attr_doc.doc += &format!("Named `{}` in the original format.", orig_id);
+        }
+        let doc = (context.meta_doc.clone(), attr_doc).into();
         let repeat = match attr.repeat {
             Some(repeat) => Some(match repeat {
                 de::attr::Repeat::Eos => Repeat::Eos,
-                de::attr::Repeat::Expr => Repeat::Expr(attr.repeat_expr.unwrap()),
-                de::attr::Repeat::Until => Repeat::Until(attr.repeat_until.unwrap()),
+                de::attr::Repeat::Expr => Repeat::Expr(
+                    match attr
+                        .repeat_expr
+                        .ok_or_else(|| Error::RequiredAttrNotFound("repeat-expr".to_owned()))?
+                    {
+                        IntegerValue::Literal(count) => Expr::Int(count),
+                        IntegerValue::Variable(count) => count.parse()?,
+                    },
+                ),
+                de::attr::Repeat::Until => Repeat::Until(
+                    attr.repeat_until
+                        .ok_or_else(|| Error::RequiredAttrNotFound("repeat-until".to_owned()))?
+                        .0
+                        .parse()?,
+                ),
             }),
             None => None,
         };
-        let logic = {
-            if let Some(contents) = attr.contents {
-                Logic::FixedContents(contents)
-            } else if let Some(size) = attr.size {
-                Logic::Size(Size::Fixed(size))
-            } else if attr.size_eos {
-                Logic::Size(Size::Eos)
-            } else {
-                match attr.ty.unwrap() {
-                    de::attr::AttrType::TypeRef(type_ref) => {
-                        Logic::Type(Type::from((type_ref, attr.en)))
-                    }
-                    de::attr::AttrType::Switch {
-                        switch_on: on,
-                        cases,
-                    } => Logic::Switch {
-                        on,
-                        cases: cases.into_iter().map(|(_k, _v)| todo!()).collect(),
-                    },
+        let size = match attr.size {
+            Some(size) => Some(Size::Fixed(size)),
+            None if attr.size_eos => Some(Size::Eos),
+            None => None,
+        };
+        let logic = if let Some(contents) = attr.contents {
+            Logic::FixedContents(contents)
+        } else if attr.guid {
+            let sized = matches!(size, None | Some(Size::Fixed(IntegerValue::Literal(16))));
+            if !sized || attr.ty.is_some() || attr.process.is_some() {
+                return Err(Error::InvalidGuid(ks_id));
+            }
+            Logic::Guid
+        } else if let Some(mode) = attr.bool_mode {
+            let byte = match &attr.ty {
+                Some(de::attr::AttrType::TypeRef(ty)) => ty == "u1",
+                Some(de::attr::AttrType::Switch { .. }) => false,
+                None => true,
+            };
+            if !byte || size.is_some() || attr.en.is_some() || attr.process.is_some() {
+                return Err(Error::InvalidBool(ks_id));
+            }
+            Logic::Bool {
+                strict: matches!(mode, de::attr::BoolMode::Strict),
+            }
+        } else if let Some(zero_terminated) = match &attr.ty {
+            Some(de::attr::AttrType::TypeRef(type_ref)) if type_ref == "str" => Some(false),
+            Some(de::attr::AttrType::TypeRef(type_ref)) if type_ref == "strz" => Some(true),
+            _ => None,
+        } {
+            let terminator = match attr.terminator {
+                Some(byte) => Some(byte),
+                None if zero_terminated => Some(0),
+                None => None,
+            };
+            let terminator = terminator
+                .map(|byte| {
+                    Ok(Terminator {
+                        byte: u8::try_from(byte).map_err(|_| Error::InvalidTerminator(byte))?,
+                        include: attr.include,
+                        consume: attr.consume,
+                    })
+                })
+                .transpose()?;
+            // `strz` always has a terminator, but `str` doesn't say where the string ends on its own.
+            if size.is_none() && terminator.is_none() {
+                return Err(Error::UnderspecifiedStr(ks_id));
+            }
+            // Only the default encoding is resolved from the byte order mark of the file.
+            let (encoding, bom) = match (attr.encoding, context.encoding) {
+                (Some(encoding), _) => (encoding, false),
+                (None, Some(encoding)) => (encoding.to_owned(), meta::is_generic_utf(encoding)),
+                (None, None) => return Err(Error::RequiredAttrNotFound("encoding".to_owned())),
+            };
+            let pad_right = attr
+                .pad_right
+                .map(|byte| u8::try_from(byte).map_err(|_| Error::InvalidPad(byte)))
+                .transpose()?;
+            Logic::Str(Str {
+                size,
+                terminator,
+                pad_right,
+                encoding,
+                bom,
+                borrowed: context.borrowed,
+            })
+        } else {
+            let ty = match attr.ty {
+                Some(de::attr::AttrType::TypeRef(type_ref)) => {
+                    let en = attr.en.map(|en| match en {
+                        de::attr::AttrEnum::Named(en) => en,
+                        // Types name the inline enums of their attributes before converting them.
+                        de::attr::AttrEnum::Inline(_) => unreachable!("inline enum of {}", ks_id),
+                    });
+                    Some(match bits_width(&type_ref) {
+                        Some(width) => Logic::Bits {
+                            width,
+                            bit_endianness: context.bit_endianness,
+                            en: en.map(|id| type_ident(&id)),
+                        },
+                        None => Logic::Type(Type::new(context, &type_ref, en)?),
+                    })
                 }
+                Some(de::attr::AttrType::Switch { switch_on, cases }) => Some(Logic::Switch(
+                    (context, ks_id.as_str(), switch_on, cases).try_into()?,
+                )),
+                None => None,
+            };
+            // A switch with a size is parsed from its bytes, so the bytes can be kept when no case
+            // matches.
+            let ty = match (&size, ty) {
+                (Some(_), Some(Logic::Switch(mut switch))) => {
+                    switch.raw_fallback = !switch.has_default();
+                    Some(Logic::Switch(switch))
+                }
+                (_, ty) => ty,
+            };
+            // Bits aren't read from whole bytes, so they can't be read from a substream.
+            if matches!(ty, Some(Logic::Bits { .. })) && (size.is_some() || attr.process.is_some())
+            {
+                return Err(Error::SizedBits(ks_id));
+            }
+            match (size, ty) {
+                (size, ty) if attr.process.is_some() => Logic::Process {
+                    size: size.ok_or_else(|| Error::RequiredAttrNotFound("size".to_owned()))?,
+                    process: attr.process.as_deref().unwrap().try_into()?,
+                    logic: ty.map(Box::new),
+                },
+                (Some(size), Some(logic)) => Logic::Substream {
+                    size,
+                    logic: Box::new(logic),
+                    borrowed: context.borrowed,
+                },
+                (Some(size), None) if context.borrowed => Logic::Slice(size),
+                (Some(size), None) => Logic::Size(size),
+                (None, Some(logic)) => logic,
+                (None, None) => return Err(Error::RequiredAttrNotFound("type".to_owned())),
             }
         };
 
+        let valid = attr.valid.map(Valid::try_from).transpose()?;
+        if let Some(valid) = &valid {
+            valid.check_enums(context.enums)?;
+        }
+        let condition = attr.if_expr.as_deref().map(str::parse).transpose()?;
+        let repeat_expr = match &repeat {
+            Some(Repeat::Expr(expr) | Repeat::Until(expr)) => Some(expr),
+            _ => None,
+        };
+        for expr in condition.iter().chain(repeat_expr) {
+            check_enum_members(expr, context.enums)?;
+        }
+
         Ok(Self {
             id,
+            ks_id,
             doc,
             repeat,
             logic,
+            condition,
+            if_default: attr.if_default,
+            valid,
         })
     }
 }
 
-#[derive(Clone, Debug)]
-pub enum Logic {
-    FixedContents(Vec<u8>),
-    Type(Type),
-    Switch {
-        on: String,
-        cases: Vec<(Pattern, Type)>,
-    },
-    // TODO: if logic
-    Size(Size),
-    // TODO: probably don't use string
-    Process(String),
-}
-
-// TODO: pad-right
+#[derive(Clone, Debug)]
+pub enum Logic {
+    FixedContents(Vec<u8>),
+    Type(Type),
+    Switch(Switch),
+    Size(Size),
+    /// Bytes of the given size borrowed from the slice the type is parsed from (macro argument
+    /// `borrowed`).
+    Slice(Size),
+    /// A string (KS: `type: str` or `strz`).
+    Str(Str),
+    /// A type parsed from a substream of the given size.
+    Substream {
+        size: Size,
+        logic: Box<Logic>,
+        /// Whether the substream is a `SliceStream` over bytes borrowed from the slice the type is
+        /// parsed from (macro argument `borrowed`).
+        borrowed: bool,
+    },
+    /// Bytes of the given size that are processed before being stored or parsed (KS: `process`).
+    Process {
+        size: Size,
+        process: Process,
+        /// The type parsed from the processed bytes, if any.
+        logic: Option<Box<Logic>>,
+    },
+    /// A mixed-endian GUID of 16 bytes (`-rust-guid: true`).
+    Guid,
+    /// A boolean read from a byte (`-rust-bool`), which must be 0 or 1 if `strict`.
+    Bool {
+        strict: bool,
+    },
+    /// An unsigned integer of `width` bits (KS: `bN`), a `bool` if it is a single bit.
+    Bits {
+        width: u32,
+        bit_endianness: Endianness,
+        en: Option<Ident>,
+    },
+}
+
+/// Returns the traits derived by the generated types and the enums of switches, also `Hash` with
+/// `hash`. Floats are neither `Eq`, `Ord` nor `Hash`, so the types that hold them only derive
+/// `PartialEq` and `PartialOrd`.
+pub fn derives(floats: bool, hash: bool) -> TokenStream {
+    match (floats, hash) {
+        (true, _) => quote! { Clone, PartialEq, PartialOrd },
+        (false, false) => quote! { Clone, PartialEq, Eq, PartialOrd, Ord },
+        (false, true) => quote! { Clone, PartialEq, Eq, PartialOrd, Ord, Hash },
+    }
+}
+
+/// Returns the number of bits of the bit-sized integer type `type_ref` (KS: `b1` to `b64`).
+fn bits_width(type_ref: &str) -> Option<u32> {
+    let width = type_ref.strip_prefix('b')?.parse().ok()?;
+    (1..=64).contains(&width).then_some(width)
+}
+
+/// Returns the expression reading `width` bits with the `_bits` reader, as a `u64`.
+fn read_bits(width: u32, bit_endianness: Endianness) -> TokenStream {
+    let read = Ident::new(
+        &format!("read_bits_int_{}", <&str>::from(bit_endianness)),
+        Span::call_site(),
+    );
+    quote! { _bits.#read(buf, #width)? }
+}
+
+/// Returns the smallest unsigned integer type holding `width` bits.
+fn bits_ty(width: u32) -> &'static str {
+    match width {
+        0..=8 => "u8",
+        9..=16 => "u16",
+        17..=32 => "u32",
+        _ => "u64",
+    }
+}
+
+impl Logic {
+    /// Returns the number of bytes the value takes up in the stream, if it is always the same.
+    fn fixed_size(&self) -> Option<u64> {
+        match self {
+            Logic::Type(Type::BuiltIn { ty, .. }) => Some(ty.size()),
+            Logic::Guid => Some(16),
+            Logic::Bool { .. } => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Returns the type of the value in expressions, or [`None`] if the value isn't stored.
+    fn expr_type(&self) -> Option<ExprType> {
+        Some(match self {
+            Logic::FixedContents(_) => return None,
+            Logic::Type(Type::BuiltIn { ty, en: None }) => match ty {
+                BuiltInType::F32 => ExprType::Float("f32"),
+                BuiltInType::F64 => ExprType::Float("f64"),
+                _ => ExprType::Int(ty.name()),
+            },
+            Logic::Size(_) | Logic::Slice(_) => ExprType::Bytes,
+            Logic::Str(_) => ExprType::Str,
+            Logic::Substream { logic, .. } => return logic.expr_type(),
+            Logic::Process {
+                logic: Some(logic), ..
+            } => return logic.expr_type(),
+            Logic::Process { logic: None, .. } => ExprType::Bytes,
+            Logic::Bool { .. } => ExprType::Bool,
+            Logic::Bits {
+                width: 1, en: None, ..
+            } => ExprType::Bool,
+            Logic::Bits {
+                width, en: None, ..
+            } => ExprType::Int(bits_ty(*width)),
+            _ => ExprType::Other(self.ty()?),
+        })
+    }
+
+    /// Returns the type of the value, or [`None`] if the value isn't stored.
+    fn ty(&self) -> Option<TokenStream> {
+        Some(match self {
+            Logic::FixedContents(_) => return None,
+            Logic::Type(ty) => ty.ty(),
+            Logic::Switch(switch) => switch.ident.to_token_stream(),
+            Logic::Size(_) => quote! { ::std::vec::Vec<u8> },
+            Logic::Slice(_) => quote! { &'a [u8] },
+            Logic::Str(Str {
+                borrowed: false, ..
+            }) => quote! { ::std::string::String },
+            Logic::Str(Str { borrowed: true, .. }) => quote! { ::std::borrow::Cow<'a, str> },
+            Logic::Substream { logic, .. } => return logic.ty(),
+            Logic::Process {
+                logic: Some(logic), ..
+            } => return logic.ty(),
+            Logic::Process { logic: None, .. } => quote! { ::std::vec::Vec<u8> },
+            Logic::Guid => quote! { ::kaitai::Guid },
+            Logic::Bool { .. } => quote! { bool },
+            Logic::Bits { en: Some(en), .. } => en.to_token_stream(),
+            Logic::Bits { width: 1, .. } => quote! { bool },
+            Logic::Bits { width, .. } => {
+                Ident::new(bits_ty(*width), Span::call_site()).into_token_stream()
+            }
+        })
+    }
+
+    /// Returns the expression reading the value of the attribute with id `id` from `io`.
+    fn expr(&self, id: &str, endianness: Endian, io: Io) -> TokenStream {
+        match self {
+            Logic::FixedContents(c) => {
+                let contents = c.iter().map(|i| quote! { #i });
+                // The error is given the id of the attribute, which the stream doesn't know.
+                quote! {
+                    match buf.ensure_fixed_contents(&[#(#contents),*]) {
+                        Err(::kaitai::error::Error::UnexpectedContents { actual, expected, .. }) => {
+                            return Err(::kaitai::error::Error::UnexpectedContents {
+                                field: Some(#id.to_owned()),
+                                actual,
+                                expected,
+                            });
+                        }
+                        result => result?,
+                    }
+                }
+            }
+            Logic::Type(ty) => ty.expr(endianness, io),
+            Logic::Switch(switch) => switch.expr(id, endianness, io),
+            Logic::Size(size) => size.read_bytes(),
+            Logic::Slice(size) => size.read_slice(),
+            Logic::Guid => quote! { ::kaitai::Guid::read(buf)? },
+            Logic::Bool { strict: false } => quote! { buf.read_bool()? },
+            Logic::Bool { strict: true } => quote! { buf.read_bool_strict()? },
+            Logic::Bits {
+                width,
+                bit_endianness,
+                en,
+            } => {
+                let value = read_bits(*width, *bit_endianness);
+                match en {
+                    Some(en) => {
+                        quote! { #en::n(#value).ok_or(::kaitai::error::Error::NoEnumMatch)? }
+                    }
+                    None if *width == 1 => quote! { (#value != 0) },
+                    None => {
+                        let ty = Ident::new(bits_ty(*width), Span::call_site());
+                        quote! { (#value as #ty) }
+                    }
+                }
+            }
+            Logic::Str(s) => s.expr(),
+            Logic::Substream {
+                size,
+                logic,
+                borrowed,
+            } => {
+                // The bytes of a borrowed substream are read up front, so the stream of the type
+                // is already past them.
+                let (substream, finish) = match (size, borrowed) {
+                    (size, true) => {
+                        let bytes = size.read_slice();
+                        (quote! { ::kaitai::SliceStream::new(#bytes) }, None)
+                    }
+                    (Size::Fixed(count), false) => (
+                        quote! { ::kaitai::SubStream::new(buf, #count as u64)? },
+                        Some(quote! { _io.finish()?; }),
+                    ),
+                    (Size::Eos, false) => (
+                        quote! { ::kaitai::SubStream::to_end(buf)? },
+                        Some(quote! { _io.finish()?; }),
+                    ),
+                };
+                let expr = logic.expr(id, endianness, Io::Substream);
+                quote! {
+                    {
+                        // Built-in types are read with methods of the trait.
+                        #[allow(unused_imports)]
+                        use ::kaitai::__private::KaitaiStream as _;
+
+                        let mut _io = #substream;
+                        let _value = {
+                            let buf = &mut _io;
+                            #expr
+                        };
+                        #finish
+                        _value
+                    }
+                }
+            }
+            Logic::Process {
+                size,
+                process,
+                logic,
+            } => {
+                let bytes = size.read_bytes();
+                let processed = process.expr(quote! { &_raw });
+                match logic {
+                    None => quote! {
+                        {
+                            let _raw = #bytes;
+                            #processed
+                        }
+                    },
+                    // The type is parsed from the processed bytes, so its positions are relative to
+                    // them.
+                    Some(logic) => {
+                        let expr = logic.expr(id, endianness, Io::Processed);
+                        quote! {
+                            {
+                                #[allow(unused_imports)]
+                                use ::kaitai::__private::KaitaiStream as _;
+
+                                let _raw = #bytes;
+                                let _processed = #processed;
+                                let mut _io = ::kaitai::SliceStream::new(&_processed);
+                                let _parent_io = &mut *buf;
+                                let buf = &mut _io;
+                                #expr
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// TODO: pad-right of byte arrays
 // TODO: pos
 // TODO: io
 // TODO: value
 
+/// The stream a value is read from, relative to the stream of the type it is a field of. User
+/// types are given the stream of that type as their parent stream (KS: `_parent._io`).
+#[derive(Clone, Copy, Debug)]
+enum Io {
+    /// The stream of the type, `buf`.
+    Same,
+    /// A substream of the stream of the type, `buf` being the `SubStream`.
+    Substream,
+    /// The processed bytes of the field, `buf` being their stream and `_parent_io` the stream of
+    /// the type.
+    Processed,
+}
+
 #[derive(Clone, Debug)]
 pub enum Type {
-    UserDefined(Ident),
-    BuiltIn { ty: BuiltInType, en: Option<Ident> },
+    /// A user type, with the arguments of its parameters.
+    UserDefined {
+        id: Ident,
+        args: Vec<TokenStream>,
+        /// Whether the type borrows from the slice it is parsed from (macro argument `borrowed`).
+        borrowed: bool,
+    },
+    BuiltIn {
+        ty: BuiltInType,
+        en: Option<Ident>,
+    },
 }
 
 impl Type {
     fn ty(&self) -> TokenStream {
         match self {
-            Type::UserDefined(id) => id.into_token_stream(),
+            Type::UserDefined {
+                id, borrowed: true, ..
+            } => quote! { #id<'a> },
+            Type::UserDefined { id, .. } => id.into_token_stream(),
             Type::BuiltIn { ty, en } => {
                 if let Some(enum_id) = en {
                     enum_id.into_token_stream()
@@ -322,14 +1567,29 @@ impl Type {
         }
     }
 
-    fn expr(&self, endianness: Endianness) -> TokenStream {
+    fn expr(&self, endianness: Endian, io: Io) -> TokenStream {
         match self {
-            Type::UserDefined(id) => quote! { <#id as ::kaitai::KaitaiStruct>::new(buf)? },
+            // Borrowed types are only parsed from the `SliceStream` of the type reading them.
+            Type::UserDefined {
+                id, borrowed: true, ..
+            } => quote! { _ctx.nested(|| #id::new_borrowed(buf, _ctx))? },
+            Type::UserDefined { id, args, .. } => {
+                let streams = match io {
+                    Io::Same => quote! { &mut ::kaitai::__private::SameStream(buf) },
+                    Io::Substream => quote! { buf },
+                    Io::Processed => {
+                        quote! { &mut ::kaitai::__private::SplitStreams(buf, _parent_io) }
+                    }
+                };
+                let parse = if args.is_empty() {
+                    quote! { <#id as ::kaitai::KaitaiStruct>::new_in(#streams, _ctx) }
+                } else {
+                    quote! { #id::new_with_params(#streams, _ctx, #(#args),*) }
+                };
+                quote! { _ctx.nested(|| #parse)? }
+            }
             Type::BuiltIn { ty, en } => {
-                let read_call =
-                    format!("buf.read_{}{}()?", ty.ks_type(), ty.endianness(endianness))
-                        .parse()
-                        .unwrap();
+                let read_call = ty.expr(endianness);
                 if let Some(enum_ident) = en {
                     quote! { #enum_ident::n(#read_call).ok_or(::kaitai::error::Error::NoEnumMatch)? }
                 } else {
@@ -340,17 +1600,39 @@ impl Type {
     }
 }
 
-// TODO: cow?
-impl From<(String, Option<String>)> for Type {
-    fn from((type_ref, en): (String, Option<String>)) -> Self {
-        if let Ok(built_in) = BuiltInType::try_from(type_ref.as_ref()) {
-            Type::BuiltIn {
-                ty: built_in,
-                en: en.map(|id| Ident::new(&sc_to_ucc(&id), Span::call_site())),
+impl Type {
+    /// Returns the type `type_ref` refers to, with the enum `en` if it is a built-in type, e.g.
+    /// `u2`, or with the arguments of its parameters if it is a user type, e.g. `entry(len, 2)`.
+    fn new(context: &Context<'_>, type_ref: &str, en: Option<String>) -> Result<Self, Error> {
+        if let Ok(built_in) = BuiltInType::try_from(type_ref) {
+            // Single bytes are the same in both endiannesses.
+            if matches!(context.endianness, Endian::Unspecified) && built_in.size() > 1 {
+                return Err(Error::MissingEndianness(type_ref.to_owned()));
             }
+            return Ok(Type::BuiltIn {
+                ty: built_in,
+                en: en.map(|id| type_ident(&id)),
+            });
+        }
+        let (id, args) = if type_ref.contains('(') {
+            expr::parse_type_ref(type_ref)?
         } else {
-            Type::UserDefined(Ident::new(&sc_to_ucc(&type_ref), Span::call_site()))
+            (type_ref.to_owned(), Vec::new())
+        };
+        // The root type isn't nested in any type, and has no parameters.
+        let params = context.params.get(&id).map_or(&[][..], Vec::as_slice);
+        let mut args = param::arguments(&id, params, &args)?;
+        if let Some(values) = context.root_values.get(&id) {
+            args.extend(param::root_arguments(values, context.in_root));
+        }
+        if context.runtime_endian.contains(&id) {
+            args.push(context.endianness.value());
         }
+        Ok(Type::UserDefined {
+            args,
+            id: type_ident(&id),
+            borrowed: context.borrowed,
+        })
     }
 }
 
@@ -389,6 +1671,50 @@ impl TryFrom<&str> for BuiltInType {
 }
 
 impl BuiltInType {
+    /// Returns the expression reading the type from `buf`.
+    fn expr(&self, endianness: Endian) -> TokenStream {
+        self.with_endianness(endianness, |suffix| {
+            format!("buf.read_{}{}()?", self.ks_type(), suffix)
+                .parse()
+                .unwrap()
+        })
+    }
+
+    /// Returns the read `read` makes for the suffix of the endianness of the type, e.g. `le` for
+    /// `read_u2le`, or a `match` between both reads on the `_endian` variable if the endianness is
+    /// calculated.
+    fn with_endianness(
+        &self,
+        endianness: Endian,
+        read: impl Fn(&'static str) -> TokenStream,
+    ) -> TokenStream {
+        match endianness {
+            Endian::Fixed(endianness) => read(self.endianness(endianness)),
+            Endian::Runtime | Endian::Unspecified if self.size() == 1 => read(""),
+            Endian::Unspecified => unreachable!("{} read without an endianness", self.ks_type()),
+            Endian::Runtime => {
+                let le = read("le");
+                let be = read("be");
+                quote! {
+                    match _endian {
+                        ::kaitai::Endian::Le => #le,
+                        ::kaitai::Endian::Be => #be,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the size of the type in bytes.
+    fn size(&self) -> u64 {
+        match self {
+            BuiltInType::U8 | BuiltInType::I8 => 1,
+            BuiltInType::U16 | BuiltInType::I16 => 2,
+            BuiltInType::U32 | BuiltInType::I32 | BuiltInType::F32 => 4,
+            BuiltInType::U64 | BuiltInType::I64 | BuiltInType::F64 => 8,
+        }
+    }
+
     fn ks_type(&self) -> &'static str {
         match self {
             BuiltInType::U8 => "u1",
@@ -417,6 +1743,42 @@ impl BuiltInType {
     }
 }
 
+impl BuiltInType {
+    /// Returns whether the type is a signed integer.
+    fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            BuiltInType::I8 | BuiltInType::I16 | BuiltInType::I32 | BuiltInType::I64
+        )
+    }
+
+    /// Returns the 64-bit integer type of the same signedness, which enums are converted from so
+    /// that unsigned values above `i64::MAX` still have a variant.
+    fn wide_ty(&self) -> TokenStream {
+        if self.is_signed() {
+            quote! { i64 }
+        } else {
+            quote! { u64 }
+        }
+    }
+
+    /// Returns the name of the Rust type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuiltInType::U8 => "u8",
+            BuiltInType::U16 => "u16",
+            BuiltInType::U32 => "u32",
+            BuiltInType::U64 => "u64",
+            BuiltInType::I8 => "i8",
+            BuiltInType::I16 => "i16",
+            BuiltInType::I32 => "i32",
+            BuiltInType::I64 => "i64",
+            BuiltInType::F32 => "f32",
+            BuiltInType::F64 => "f64",
+        }
+    }
+}
+
 impl ToTokens for BuiltInType {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         tokens.extend(match self {
@@ -434,13 +1796,368 @@ impl ToTokens for BuiltInType {
     }
 }
 
-// TODO: Encoding field on String type
-// TODO: terminator for String or Byte array
+/// A string, read with a size or up to a terminator, and decoded with the `Decoding` of the
+/// `ParseContext` of the parse.
+#[derive(Clone, Debug)]
+pub struct Str {
+    size: Option<Size>,
+    terminator: Option<Terminator>,
+    /// The byte the string is padded with after its end inside its size (KS: `pad-right`).
+    pad_right: Option<u8>,
+    /// The label of the encoding, e.g. `UTF-8`.
+    encoding: String,
+    /// Whether the encoding is the generic default `UTF-16` or `UTF`, which the byte order mark
+    /// of the file resolves, see `ParseContext::bom_encoding`.
+    bom: bool,
+    /// Whether the string is borrowed from the slice the type is parsed from, unless it has to be
+    /// transcoded (macro argument `borrowed`).
+    borrowed: bool,
+}
+
+/// The byte a string ends with (KS: `terminator`, `include` and `consume`).
+#[derive(Clone, Debug)]
+pub struct Terminator {
+    byte: u8,
+    include: bool,
+    consume: bool,
+}
+
+impl Terminator {
+    /// Returns the `TerminatorFlags` of the terminator, with the constructor of the defaults of
+    /// `strz` when neither `include` nor `consume` is changed.
+    fn flags(&self) -> TokenStream {
+        let Terminator {
+            include, consume, ..
+        } = self;
+        match (include, consume) {
+            (false, true) => quote! { ::kaitai::TerminatorFlags::strz() },
+            _ => quote! {
+                ::kaitai::TerminatorFlags {
+                    include: #include,
+                    consume: #consume,
+                }
+            },
+        }
+    }
+}
+
+impl Str {
+    /// Returns the expression reading and decoding the string.
+    fn expr(&self) -> TokenStream {
+        let bytes = match (&self.size, &self.terminator) {
+            // Inside a size the padding is stripped and the terminator only truncates the string,
+            // the whole size is consumed.
+            (Some(size), terminator) if self.borrowed => {
+                let mut bytes = size.read_slice();
+                if let Some(pad) = self.pad_right {
+                    bytes = quote! { ::kaitai::slice_strip_right(#bytes, #pad) };
+                }
+                match terminator {
+                    Some(Terminator { byte, include, .. }) => {
+                        quote! { ::kaitai::slice_terminate(#bytes, #byte, #include) }
+                    }
+                    None => bytes,
+                }
+            }
+            (Some(size), terminator) => {
+                let mut bytes = size.read_bytes();
+                if let Some(pad) = self.pad_right {
+                    bytes = quote! { ::kaitai::bytes_strip_right(#bytes, #pad) };
+                }
+                match terminator {
+                    Some(Terminator { byte, include, .. }) => {
+                        quote! { ::kaitai::bytes_terminate(#bytes, #byte, #include) }
+                    }
+                    None => bytes,
+                }
+            }
+            (None, Some(terminator)) => {
+                let byte = terminator.byte;
+                let flags = terminator.flags();
+                match self.borrowed {
+                    true => quote! { buf.read_slice_term(#byte, #flags)? },
+                    false => quote! { buf.read_bytes_term(#byte as char, #flags)? },
+                }
+            }
+            (None, None) => unreachable!("strings without a size or terminator are rejected"),
+        };
+        let encoding = &self.encoding;
+        let encoding = match self.bom {
+            true => quote! { _ctx.resolve_encoding(#encoding) },
+            false => quote! { #encoding },
+        };
+        match self.borrowed {
+            true => quote! { ::kaitai::slice_to_str(#bytes, #encoding, _ctx.decoding())? },
+            false => quote! { ::kaitai::bytes_to_str(&#bytes, #encoding, _ctx.decoding())? },
+        }
+    }
+}
+
+/// How the bytes of an attribute are processed before being stored or parsed (KS: `process`).
+#[derive(Clone, Debug)]
+pub enum Process {
+    /// XOR with a key (KS: `xor(key)`), which is either a byte or a byte array. Byte array keys
+    /// are usually attributes read before the processed one, and are repeated as many times as
+    /// needed.
+    Xor(Expr),
+    /// Decompression of a zlib stream (KS: `zlib`), limited by the allocation limit of the parse.
+    Zlib,
+}
+
+impl TryFrom<&str> for Process {
+    type Error = Error;
+
+    fn try_from(process: &str) -> Result<Self, Self::Error> {
+        if process.trim() == "zlib" {
+            return Ok(Process::Zlib);
+        }
+        let key = process
+            .trim()
+            .strip_prefix("xor(")
+            .and_then(|args| args.strip_suffix(')'))
+            .ok_or_else(|| Error::UnknownProcess(process.to_owned()))?;
+        match key.parse()? {
+            Expr::Int(key) if key > u8::MAX.into() => Err(Error::InvalidExpression {
+                expr: process.to_owned(),
+                reason: "integer xor keys must be bytes".to_owned(),
+            }),
+            key => Ok(Process::Xor(key)),
+        }
+    }
+}
+
+impl Process {
+    /// Returns the expression processing the bytes `bytes`.
+    fn expr(&self, bytes: TokenStream) -> TokenStream {
+        match self {
+            Process::Xor(key) => {
+                // Whether the key is a byte or a byte array is only known by the compiler.
+                let key = match key {
+                    Expr::Int(key) => Literal::u8_suffixed(*key as u8).into_token_stream(),
+                    key => quote! { (#key) },
+                };
+                quote! {
+                    {
+                        use ::kaitai::__private::XorKey as _;
+                        #key.xor_bytes(#bytes)
+                    }
+                }
+            }
+            Process::Zlib => quote! {
+                ::kaitai::__private::process_zlib_limited(#bytes, _ctx.allocation_limit())?
+            },
+        }
+    }
+}
+
+/// A type that depends on the value of an expression (KS: `switch-on`).
+///
+/// The value is stored in a generated enum with a variant for each of the possible types.
+#[derive(Clone, Debug)]
+pub struct Switch {
+    on: Expr,
+    /// The identifier of the generated enum.
+    ident: Ident,
+    /// The cases in the order they are defined in, with the variant and type they result in.
+    cases: Vec<(Pattern, Ident, Type)>,
+    /// Whether values matching no case are stored as the bytes of the switch in an `Unknown`
+    /// variant rather than being an error, which is the case when the switch has a size and no
+    /// default case.
+    raw_fallback: bool,
+    /// Whether the enum derives `Hash`, see [`Context::hash`].
+    hash: bool,
+    /// Whether one of the types holds floats, so that the enum derives neither `Eq`, `Ord` nor
+    /// `Hash`.
+    floats: bool,
+}
+
+impl
+    TryFrom<(
+        &Context<'_>,
+        &str,
+        de::data::Expression,
+        Vec<(de::data::Expression, String)>,
+    )> for Switch
+{
+    type Error = Error;
+
+    fn try_from(
+        (context, id, on, cases): (
+            &Context<'_>,
+            &str,
+            de::data::Expression,
+            Vec<(de::data::Expression, String)>,
+        ),
+    ) -> Result<Self, Self::Error> {
+        let ident = Ident::new(
+            &format!("{}{}", context.ty, sc_to_ucc(id)),
+            Span::call_site(),
+        );
+        let cases = cases
+            .into_iter()
+            .map(|(key, type_ref)| {
+                let pattern = (context.enums, key).try_into()?;
+                let ty = Type::new(context, &type_ref, None)?;
+                let variant = match &ty {
+                    Type::UserDefined { id, .. } => id.clone(),
+                    Type::BuiltIn { .. } => type_ident(&type_ref),
+                };
+                Ok((pattern, variant, ty))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let on = on.0.parse()?;
+        check_enum_members(&on, context.enums)?;
+
+        Ok(Self {
+            on,
+            ident,
+            cases,
+            raw_fallback: false,
+            hash: context.hash,
+            floats: false,
+        })
+    }
+}
+
+impl Switch {
+    /// Returns whether the switch has a default (`_`) case.
+    fn has_default(&self) -> bool {
+        self.cases
+            .iter()
+            .any(|(p, ..)| matches!(p, Pattern::Default))
+    }
+
+    /// Returns the definition of the enum holding the possible types.
+    fn definition(&self) -> TokenStream {
+        let ident = &self.ident;
+        let mut variants = Vec::<(&Ident, &Type)>::new();
+        for (_, variant, ty) in &self.cases {
+            if !variants.iter().any(|(v, _)| *v == variant) {
+                variants.push((variant, ty));
+            }
+        }
+        let mut variant_defs = variants
+            .iter()
+            .map(|(variant, ty)| {
+                let ty = ty.ty();
+                quote! { #variant(#ty) }
+            })
+            .collect::<Vec<_>>();
+        if self.raw_fallback {
+            variant_defs.push(quote! {
+                /// The bytes of a value that matches no case.
+                Unknown(::std::vec::Vec<u8>)
+            });
+        }
+
+        let derives = derives(self.floats, self.hash);
+        quote! {
+            #[derive(Debug, #derives)]
+            pub enum #ident {
+                #(#variant_defs),*
+            }
+        }
+    }
+
+    /// Returns the expression reading the type matching the value of the switch. If no case
+    /// matches and there is no default case the expression returns an error.
+    fn expr(&self, id: &str, endianness: Endian, io: Io) -> TokenStream {
+        let on = self.on.operand();
+        let ident = &self.ident;
+        let arms = self.cases.iter().map(|(pattern, variant, ty)| {
+            let expr = ty.expr(endianness, io);
+            quote! { #pattern => #ident::#variant(#expr), }
+        });
+        let default = if self.has_default() {
+            TokenStream::new()
+        } else if self.raw_fallback {
+            // The stream is the substream of the size of the switch.
+            quote! { _ => #ident::Unknown(buf.read_bytes_full()?), }
+        } else {
+            quote! {
+                _on => return Err(::kaitai::error::Error::NoSwitchMatch {
+                    field: #id.to_owned(),
+                    value: format!("{:?}", _on),
+                }),
+            }
+        };
+
+        quote! {
+            match &#on {
+                #(#arms)*
+                #default
+            }
+        }
+    }
+}
 
+/// The key of a switch case.
 #[derive(Clone, Debug)]
 pub enum Pattern {
-    Enum(String),
+    Enum {
+        en: Ident,
+        member: Ident,
+    },
     Int(u64),
+    Bool(bool),
+    /// The `_` case, which matches any value.
+    Default,
+}
+
+impl TryFrom<(&HashMap<String, Vec<String>>, de::data::Expression)> for Pattern {
+    type Error = Error;
+
+    fn try_from(
+        (enums, key): (&HashMap<String, Vec<String>>, de::data::Expression),
+    ) -> Result<Self, Self::Error> {
+        Ok(match key.0.parse()? {
+            Expr::Name(name) if name == "_" => Pattern::Default,
+            Expr::Int(value) => Pattern::Int(value),
+            Expr::Bool(value) => Pattern::Bool(value),
+            Expr::EnumMember { en, member } => {
+                let members = enums
+                    .get(&en)
+                    .ok_or_else(|| Error::UnknownEnum(en.clone()))?;
+                if !members.contains(&member) {
+                    return Err(Error::UnknownEnumMember { en, member });
+                }
+                Pattern::Enum {
+                    en: type_ident(&en),
+                    member: type_ident(&member),
+                }
+            }
+            _ => return Err(Error::InvalidCase(key.0)),
+        })
+    }
+}
+
+/// Checks that the enum members `expr` refers to, e.g. `file_type::png` in
+/// `file_type == file_type::png`, exist, given the KS ids of the members of the enums in scope.
+pub fn check_enum_members(expr: &Expr, enums: &HashMap<String, Vec<String>>) -> Result<(), Error> {
+    for (en, member) in expr.enum_members() {
+        let members = enums
+            .get(en)
+            .ok_or_else(|| Error::UnknownEnum(en.to_owned()))?;
+        if !members.iter().any(|m| m == member) {
+            return Err(Error::UnknownEnumMember {
+                en: en.to_owned(),
+                member: member.to_owned(),
+            });
+        }
+    }
+    Ok(())
+}
+
+impl ToTokens for Pattern {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            Pattern::Enum { en, member } => quote! { #en::#member },
+            Pattern::Int(value) => Literal::u64_unsuffixed(*value).into_token_stream(),
+            Pattern::Bool(value) => quote! { #value },
+            Pattern::Default => quote! { _ },
+        })
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -449,11 +2166,193 @@ pub enum Size {
     Eos,
 }
 
+impl Size {
+    /// Returns the expression reading the bytes of the size, whose length is checked against the
+    /// allocation limit of the parse.
+    fn read_bytes(&self) -> TokenStream {
+        match self {
+            Size::Fixed(count) => quote! { _ctx.read_bytes(buf, #count as u64)? },
+            Size::Eos => quote! { buf.read_bytes_full()? },
+        }
+    }
+
+    /// Returns the expression reading the bytes of the size borrowed from a `SliceStream`, which
+    /// doesn't allocate them.
+    fn read_slice(&self) -> TokenStream {
+        match self {
+            // A count that doesn't fit in a `usize` is past the end of any slice.
+            Size::Fixed(count) => quote! {
+                buf.read_slice(::std::convert::TryFrom::try_from(#count as u64).unwrap_or(usize::MAX))?
+            },
+            Size::Eos => quote! { buf.read_slice_full() },
+        }
+    }
+}
+
+/// The checks of a `valid` key.
+///
+/// Both bounds are inclusive, Kaitai Struct has no exclusive bounds. The `min-eq` and `max-eq`
+/// keys are accepted as aliases of `min` and `max`. The `expr` check can refer to the value as `_`.
+#[derive(Clone, Debug, Default)]
+pub struct Valid {
+    eq: Option<Expr>,
+    min: Option<Expr>,
+    max: Option<Expr>,
+    /// The values the value has to be one of (KS: `any-of`), e.g. members of its enum.
+    any_of: Option<Vec<Expr>>,
+    /// The boolean expression the value has to satisfy (KS: `expr`), with its source for the
+    /// error message.
+    expr: Option<(String, Expr)>,
+}
+
+impl TryFrom<de::attr::Valid> for Valid {
+    type Error = Error;
+
+    fn try_from(valid: de::attr::Valid) -> Result<Self, Self::Error> {
+        let parse = |e: Option<de::data::Expression>| e.map(|e| e.0.parse()).transpose();
+        Ok(match valid {
+            de::attr::Valid::Eq(eq) => Self {
+                eq: Some(eq.0.parse()?),
+                ..Default::default()
+            },
+            de::attr::Valid::Checks(checks) => Self {
+                eq: parse(checks.eq)?,
+                min: parse(checks.min)?,
+                max: parse(checks.max)?,
+                any_of: checks
+                    .any_of
+                    .map(|values| values.into_iter().map(|e| e.0.parse()).collect())
+                    .transpose()?,
+                expr: checks
+                    .expr
+                    .map(|e| e.0.parse().map(|expr| (e.0, expr)))
+                    .transpose()?,
+            },
+        })
+    }
+}
+
+impl Valid {
+    /// Checks that the enum members the values refer to exist, given the KS ids of the members of
+    /// the enums in scope.
+    fn check_enums(&self, enums: &HashMap<String, Vec<String>>) -> Result<(), Error> {
+        let mut values = self
+            .eq
+            .iter()
+            .chain(&self.min)
+            .chain(&self.max)
+            .chain(self.any_of.iter().flatten());
+        values.try_for_each(|value| check_enum_members(value, enums))
+    }
+
+    /// Returns a [`TokenStream`] checking that `_value`, the value of the attribute with id `id`,
+    /// is valid. Failed checks are recoverable errors, which are collected in lenient parses.
+    fn checks(&self, id: &str) -> TokenStream {
+        let mut checks = TokenStream::new();
+
+        let remaining = Expr::Binary {
+            op: BinaryOp::Sub,
+            lhs: Box::new(Expr::Io(IoProperty::Size)),
+            rhs: Box::new(Expr::Io(IoProperty::Pos)),
+        };
+        if self.eq.as_ref() == Some(&remaining) {
+            // A length of the rest of the stream, e.g. the total length of a container, whose
+            // error gives both lengths.
+            checks.extend(quote! {
+                let _remaining = ::kaitai::KaitaiStream::remaining(buf)?;
+                if ::std::convert::TryFrom::try_from(_value) != Ok(_remaining) {
+                    _ctx.recoverable(::kaitai::error::Error::ValidationRemainingSize {
+                        field: #id.to_owned(),
+                        declared: format!("{:?}", _value),
+                        remaining: _remaining,
+                    })?;
+                }
+            });
+        } else if let Some(eq) = &self.eq {
+            checks.extend(quote! {
+                let _expected = #eq;
+                if _value != _expected {
+                    _ctx.recoverable(::kaitai::error::Error::ValidationNotEqual {
+                        field: #id.to_owned(),
+                        expected: format!("{:?}", _expected),
+                        actual: format!("{:?}", _value),
+                    })?;
+                }
+            });
+        }
+        if let Some(min) = &self.min {
+            checks.extend(quote! {
+                let _min = #min;
+                if _value < _min {
+                    _ctx.recoverable(::kaitai::error::Error::ValidationLessThan {
+                        field: #id.to_owned(),
+                        min: format!("{:?}", _min),
+                        actual: format!("{:?}", _value),
+                    })?;
+                }
+            });
+        }
+        if let Some(max) = &self.max {
+            checks.extend(quote! {
+                let _max = #max;
+                if _value > _max {
+                    _ctx.recoverable(::kaitai::error::Error::ValidationGreaterThan {
+                        field: #id.to_owned(),
+                        max: format!("{:?}", _max),
+                        actual: format!("{:?}", _value),
+                    })?;
+                }
+            });
+        }
+        if let Some(any_of) = &self.any_of {
+            let allowed = any_of
+                .iter()
+                .map(|value| value.to_token_stream().to_string().replace(' ', ""))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let operands = any_of.iter().map(Expr::operand);
+            let matches = if any_of.is_empty() {
+                quote! { false }
+            } else {
+                quote! { #(_value == #operands)||* }
+            };
+            checks.extend(quote! {
+                if !(#matches) {
+                    _ctx.recoverable(::kaitai::error::Error::ValidationNotAnyOf {
+                        field: #id.to_owned(),
+                        allowed: #allowed.to_owned(),
+                        actual: format!("{:?}", _value),
+                    })?;
+                }
+            });
+        }
+        if let Some((source, expr)) = &self.expr {
+            checks.extend(quote! {
+                if !(#expr) {
+                    _ctx.recoverable(::kaitai::error::Error::ValidationExprFailed {
+                        field: #id.to_owned(),
+                        expr: #source.to_owned(),
+                        actual: format!("{:?}", _value),
+                    })?;
+                }
+            });
+        }
+
+        checks
+    }
+}
+
+/// How an attribute is repeated (KS: `repeat`).
+///
+/// Expressions inside the repeat can refer to the zero-based index of the current element as
+/// `_index`. The `repeat-until` condition can refer to the element that was just read as `_`.
 #[derive(Clone, Debug)]
 pub enum Repeat {
     Eos,
-    Expr(IntegerValue),
-    Until(String),
+    /// Repeated the number of times the expression evaluates to (KS: `repeat-expr`), e.g.
+    /// `count` or `kind.to_i`.
+    Expr(Expr),
+    Until(Expr),
 }
 
 #[cfg(test)]
@@ -472,7 +2371,11 @@ mod tests {
         ];
         let logics = vec![
             Logic::FixedContents(vec![0, 1]),
-            Logic::Type(Type::UserDefined(Ident::new("MyType", Span::call_site()))),
+            Logic::Type(Type::UserDefined {
+                id: Ident::new("MyType", Span::call_site()),
+                args: Vec::new(),
+                borrowed: false,
+            }),
             Logic::Type(Type::BuiltIn {
                 ty: BuiltInType::U16,
                 en: None,
@@ -496,7 +2399,8 @@ mod tests {
             },
             quote! {
                 #[doc = ""]
-                pub my: ::std::vec::Vec<MyEnum>
+                pub my: ::std::vec::Vec<MyEnum>,
+                my_raw: ::std::vec::Vec<u16>
             },
             quote! {
                 #[doc = ""]
@@ -504,7 +2408,7 @@ mod tests {
                 pub vibe: ::std::vec::Vec<::std::vec::Vec<u8> >
             },
         ];
-        vec!["bitch", "dont", "kill", "my", "vibe"]
+        ["bitch", "dont", "kill", "my", "vibe"]
             .iter()
             .map(|id| Ident::new(id, Span::call_site()))
             .zip(docs)
@@ -512,14 +2416,326 @@ mod tests {
             .zip(logics)
             .map(|(((id, doc), repeat), logic)| {
                 Attribute {
+                    ks_id: id.to_string(),
                     id,
                     doc,
                     repeat,
+                    condition: None,
+                    if_default: false,
                     logic,
+                    valid: None,
                 }
                 .field_definition()
             })
             .zip(expected)
             .for_each(|(def, expected)| assert_eq!(def.to_string(), expected.to_string()));
     }
+
+    fn attribute(yaml: &str) -> Result<Attribute, Error> {
+        let enums = HashMap::from([(
+            "animal".to_owned(),
+            vec!["cat".to_owned(), "dog".to_owned()],
+        )]);
+        let context = Context {
+            meta_doc: None,
+            ty: &Ident::new("Zoo", Span::call_site()),
+            enums: &enums,
+            encoding: None,
+            bit_endianness: Endianness::Be,
+            params: &HashMap::new(),
+            root_values: &HashMap::new(),
+            in_root: true,
+            endianness: Endian::Fixed(Endianness::Le),
+            runtime_endian: &HashSet::new(),
+            hash: false,
+            borrowed: false,
+        };
+        (
+            &context,
+            serde_yaml::from_str::<de::attr::Attr>(yaml).unwrap(),
+        )
+            .try_into()
+    }
+
+    #[test]
+    fn switch_enum_cases() {
+        let attr = attribute(
+            r#"
+id: body
+type:
+  switch-on: kind
+  cases:
+    'animal::cat': cat_body
+    'animal::dog': u1
+    _: cat_body
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            attr.field_definition().to_string(),
+            quote! {
+                #[doc = ""]
+                pub body: ZooBody
+            }
+            .to_string()
+        );
+        assert_eq!(
+            Attributes(vec![attr])
+                .type_definitions()
+                .next()
+                .unwrap()
+                .to_string(),
+            quote! {
+                #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+                pub enum ZooBody {
+                    CatBody(CatBody),
+                    U1(u8)
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn valid_any_of_members() {
+        let yaml = |members: &str| {
+            format!(
+                "id: kind\ntype: u1\nenum: animal\nvalid:\n  any-of: [{}]\n",
+                members
+            )
+        };
+        assert!(attribute(&yaml("animal::cat, animal::dog")).is_ok());
+        assert_eq!(
+            attribute(&yaml("animal::cat, animal::fish")).unwrap_err(),
+            Error::UnknownEnumMember {
+                en: "animal".to_owned(),
+                member: "fish".to_owned(),
+            }
+        );
+        assert_eq!(
+            attribute(&yaml("plant::tree")).unwrap_err(),
+            Error::UnknownEnum("plant".to_owned())
+        );
+    }
+
+    #[test]
+    fn expression_members() {
+        let yaml = |expr: &str| format!("id: size\ntype: u1\nif: {}\n", expr);
+        assert!(attribute(&yaml("kind == animal::cat or kind != animal::dog")).is_ok());
+        assert_eq!(
+            attribute(&yaml("kind == animal::fish")).unwrap_err(),
+            Error::UnknownEnumMember {
+                en: "animal".to_owned(),
+                member: "fish".to_owned(),
+            }
+        );
+        assert_eq!(
+            attribute(
+                "id: body\ntype:\n  switch-on: kind == plant::tree\n  cases:\n    true: u1\n"
+            )
+            .unwrap_err(),
+            Error::UnknownEnum("plant".to_owned())
+        );
+    }
+
+    #[test]
+    fn switch_invalid_cases() {
+        let yaml = |case: &str| {
+            format!(
+                "id: body\ntype:\n  switch-on: kind\n  cases:\n    '{}': cat_body\n",
+                case
+            )
+        };
+        assert_eq!(
+            attribute(&yaml("animal::fish")).unwrap_err(),
+            Error::UnknownEnumMember {
+                en: "animal".to_owned(),
+                member: "fish".to_owned(),
+            }
+        );
+        assert_eq!(
+            attribute(&yaml("plant::tree")).unwrap_err(),
+            Error::UnknownEnum("plant".to_owned())
+        );
+        assert_eq!(
+            attribute(&yaml("kind + 1")).unwrap_err(),
+            Error::InvalidCase("kind + 1".to_owned())
+        );
+    }
+
+    #[test]
+    fn string_attributes() {
+        assert_eq!(
+            attribute("id: name\ntype: strz\n").unwrap_err(),
+            Error::RequiredAttrNotFound("encoding".to_owned())
+        );
+        let err = attribute("id: name\ntype: str\nencoding: UTF-8\n").unwrap_err();
+        assert_eq!(err, Error::UnderspecifiedStr("name".to_owned()));
+        assert_eq!(
+            err.to_string(),
+            "str field 'name' requires size, size-eos, or terminator"
+        );
+        assert!(attribute("id: name\ntype: str\nencoding: UTF-8\nsize-eos: true\n").is_ok());
+        assert!(attribute("id: name\ntype: str\nencoding: UTF-8\nterminator: 10\n").is_ok());
+        assert_eq!(
+            attribute("id: name\ntype: str\nencoding: UTF-8\nterminator: 256\n").unwrap_err(),
+            Error::InvalidTerminator(256)
+        );
+        assert!(attribute("id: name\ntype: strz\nencoding: UTF-8\n").is_ok());
+        assert_eq!(
+            attribute("id: name\ntype: str\nencoding: UTF-8\nsize: 4\npad-right: 256\n")
+                .unwrap_err(),
+            Error::InvalidPad(256)
+        );
+    }
+
+    #[test]
+    fn terminator_flags() {
+        let terminator = |include, consume| Terminator {
+            byte: 0,
+            include,
+            consume,
+        };
+        assert_eq!(
+            terminator(false, true).flags().to_string(),
+            quote! { ::kaitai::TerminatorFlags::strz() }.to_string()
+        );
+        assert_eq!(
+            terminator(true, false).flags().to_string(),
+            quote! { ::kaitai::TerminatorFlags { include: true, consume: false, } }.to_string()
+        );
+    }
+
+    #[test]
+    fn orig_id_doc() {
+        let attr = attribute("id: file_name\n-orig-id: FileName\ntype: u1\n").unwrap();
+        assert_eq!(
+            attr.field_definition().to_string(),
+            quote! {
+                #[doc = "Named `FileName` in the original format."]
+                pub file_name: u8
+            }
+            .to_string()
+        );
+        let attr = attribute("id: len\n-orig-id: Len\ndoc: The length.\ntype: u1\n").unwrap();
+        assert_eq!(
+            attr.field_definition().to_string(),
+            quote! {
+                #[doc = "The length.\n\nNamed `Len` in the original format."]
+                pub len: u8
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn bits_attributes() {
+        let attr = attribute("id: flag\ntype: b1\n").unwrap();
+        assert!(attr.is_bits());
+        assert_eq!(attr.logic.ty().unwrap().to_string(), "bool");
+        let attr = attribute("id: channel\ntype: b12\n").unwrap();
+        assert_eq!(attr.logic.ty().unwrap().to_string(), "u16");
+        assert!(!attribute("id: len\ntype: u1\n").unwrap().is_bits());
+        assert_eq!(
+            attribute("id: channel\ntype: b12\nsize: 2\n").unwrap_err(),
+            Error::SizedBits("channel".to_owned())
+        );
+    }
+
+    #[test]
+    fn guid_attributes() {
+        assert!(attribute("id: guid\n-rust-guid: true\n").is_ok());
+        assert!(attribute("id: guid\nsize: 16\n-rust-guid: true\n").is_ok());
+        assert_eq!(
+            attribute("id: guid\nsize: 8\n-rust-guid: true\n").unwrap_err(),
+            Error::InvalidGuid("guid".to_owned())
+        );
+        assert_eq!(
+            attribute("id: guid\ntype: u4\n-rust-guid: true\n").unwrap_err(),
+            Error::InvalidGuid("guid".to_owned())
+        );
+    }
+
+    #[test]
+    fn bool_attributes() {
+        assert!(attribute("id: flag\n-rust-bool: nonzero\n").is_ok());
+        assert!(attribute("id: flag\ntype: u1\n-rust-bool: strict\n").is_ok());
+        assert_eq!(
+            attribute("id: flag\ntype: u2\n-rust-bool: strict\n").unwrap_err(),
+            Error::InvalidBool("flag".to_owned())
+        );
+        assert_eq!(
+            attribute("id: flag\nsize: 1\n-rust-bool: nonzero\n").unwrap_err(),
+            Error::InvalidBool("flag".to_owned())
+        );
+    }
+
+    #[test]
+    fn write_attributes() {
+        let (size, _) = attribute("id: magic\ncontents: [1, 2]\n")
+            .unwrap()
+            .write(Endian::Fixed(Endianness::Le))
+            .unwrap();
+        assert_eq!(size, 2);
+        let (size, _) = attribute("id: kind\ntype: u4\nenum: animal\n")
+            .unwrap()
+            .write(Endian::Fixed(Endianness::Be))
+            .unwrap();
+        assert_eq!(size, 4);
+
+        for (yaml, reason) in [
+            (
+                "id: data\nsize: len\n",
+                "only byte arrays of a literal size are supported",
+            ),
+            (
+                "id: data\nsize-eos: true\n",
+                "only byte arrays of a literal size are supported",
+            ),
+            (
+                "id: values\ntype: u1\nrepeat: eos\n",
+                "repeated fields aren't supported",
+            ),
+            (
+                "id: value\ntype: u1\nif: version > 1\n",
+                "conditional fields aren't supported",
+            ),
+            (
+                "id: name\ntype: strz\nencoding: UTF-8\n",
+                "strings aren't supported",
+            ),
+            ("id: child\ntype: child\n", "user types aren't supported"),
+        ] {
+            let err = attribute(yaml)
+                .unwrap()
+                .write(Endian::Fixed(Endianness::Le))
+                .unwrap_err();
+            assert!(
+                matches!(&err, Error::NotSerializable { reason: r, .. } if *r == reason),
+                "{}: {}",
+                yaml,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn process_attributes() {
+        assert!(attribute("id: blob\nsize: 4\nprocess: xor(key)\n").is_ok());
+        assert!(attribute("id: blob\nsize-eos: true\nprocess: xor(0xff)\n").is_ok());
+        assert_eq!(
+            attribute("id: blob\nprocess: xor(key)\n").unwrap_err(),
+            Error::RequiredAttrNotFound("size".to_owned())
+        );
+        assert!(attribute("id: blob\nsize: 4\nprocess: zlib\n").is_ok());
+        assert_eq!(
+            attribute("id: blob\nsize: 4\nprocess: lz4\n").unwrap_err(),
+            Error::UnknownProcess("lz4".to_owned())
+        );
+        assert!(matches!(
+            attribute("id: blob\nsize: 4\nprocess: xor(256)\n").unwrap_err(),
+            Error::InvalidExpression { .. }
+        ));
+    }
 }
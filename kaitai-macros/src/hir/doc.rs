@@ -90,3 +90,46 @@ where
 {
     iter.into_iter().map(|i| format!("- {}\n", i)).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use quote::ToTokens;
+    use syn::parse::Parser;
+
+    /// Returns the text of the doc comment of `doc`.
+    fn rendered(doc: &Doc) -> String {
+        let attrs = syn::Attribute::parse_outer
+            .parse2(doc.to_token_stream())
+            .unwrap();
+        match attrs[0].parse_meta().unwrap() {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(doc),
+                ..
+            }) => doc.value(),
+            meta => panic!("not a doc comment: {:?}", meta),
+        }
+    }
+
+    #[test]
+    fn doc_refs() {
+        let doc = |yaml: &str| Doc::from((None, serde_yaml::from_str::<doc::Doc>(yaml).unwrap()));
+
+        let single = rendered(&doc("doc-ref: https://example.com/spec"));
+        assert_eq!(single, "### References\n- https://example.com/spec\n");
+
+        // Each reference of a list gets a line of its own.
+        let list = rendered(&doc(
+            "doc-ref:\n  - https://example.com/spec\n  - RFC 1952 https://example.com/rfc\n",
+        ));
+        assert_eq!(
+            list.lines().collect::<Vec<_>>(),
+            [
+                "### References",
+                "- https://example.com/spec",
+                "- RFC 1952 https://example.com/rfc",
+            ]
+        );
+    }
+}
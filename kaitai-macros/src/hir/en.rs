@@ -1,6 +1,6 @@
-use crate::{de, hir::doc::Doc, util::sc_to_ucc};
+use crate::{de, hir::doc::Doc, util::type_ident};
 
-use proc_macro2::{Ident, Span, TokenStream};
+use proc_macro2::{Ident, TokenStream};
 use quote::{quote, ToTokens};
 
 #[derive(Clone, Debug)]
@@ -11,17 +11,20 @@ pub struct Enumeration {
 
 impl From<(&str, de::en::Enum)> for Enumeration {
     fn from((id, en): (&str, de::en::Enum)) -> Self {
-        Self {
-            ident: Ident::new(&sc_to_ucc(&id), Span::call_site()),
-            variants: en
-                .0
-                .into_iter()
+        let mut variants: Vec<_> =
+            en.0.into_iter()
                 .map(|(value, de::en::EnumValue { id, doc })| Variant {
                     doc: (None, doc).into(),
-                    ident: Ident::new(&sc_to_ucc(&id), Span::call_site()),
+                    ident: type_ident(&id),
                     value,
                 })
-                .collect(),
+                .collect();
+        // The values are stored in a map, sorting them keeps the generated code deterministic.
+        variants.sort_by_key(|v| v.value);
+
+        Self {
+            ident: type_ident(id),
+            variants,
         }
     }
 }
@@ -31,9 +34,14 @@ impl ToTokens for Enumeration {
         let ident = &self.ident;
         let variant_defs = self.variants.iter().map(|v| v.def());
         let variant_match_arms = self.variants.iter().map(|v| v.match_arm());
+        // The variants are sorted, so the first one has the smallest value.
+        let fallback = match self.variants.first() {
+            Some(Variant { ident, .. }) => quote! { ::std::option::Option::Some(Self::#ident) },
+            None => quote! { ::std::option::Option::None },
+        };
 
         tokens.extend(quote! {
-            #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
             // TODO: Is this repr ok?
             #[repr(u64)]
             pub enum #ident {
@@ -50,6 +58,43 @@ impl ToTokens for Enumeration {
                         _ => None,
                     }
                 }
+
+                /// Returns the variant that unknown values get in lenient parses.
+                #[doc(hidden)]
+                pub fn __fallback() -> Option<Self> {
+                    #fallback
+                }
+            }
+
+            // The value of a variant in expressions (KS: `to_i`).
+            #[automatically_derived]
+            impl ::kaitai::__private::KsEnum for #ident {
+                fn ks_value(self) -> u64 {
+                    self as u64
+                }
+            }
+
+            /// The conversion of the values of unsigned fields, up to [`u64::MAX`].
+            #[automatically_derived]
+            impl ::std::convert::TryFrom<u64> for #ident {
+                type Error = ::kaitai::error::Error;
+
+                fn try_from(value: u64) -> ::kaitai::error::Result<Self> {
+                    Self::n(value).ok_or(::kaitai::error::Error::NoEnumMatch)
+                }
+            }
+
+            /// The conversion of the values of signed fields. Negative values have no variant.
+            #[automatically_derived]
+            impl ::std::convert::TryFrom<i64> for #ident {
+                type Error = ::kaitai::error::Error;
+
+                fn try_from(value: i64) -> ::kaitai::error::Result<Self> {
+                    u64::try_from(value)
+                        .ok()
+                        .and_then(Self::n)
+                        .ok_or(::kaitai::error::Error::NoEnumMatch)
+                }
             }
         })
     }
@@ -76,3 +121,53 @@ impl Variant {
         quote! { #value => ::std::option::Option::Some(Self::#ident) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variant_docs() {
+        let en = serde_yaml::from_str::<de::en::Enum>(
+            r#"
+1: plain
+2:
+  id: documented
+  doc: A documented variant.
+  doc-ref: https://example.com
+"#,
+        )
+        .unwrap();
+        let en = Enumeration::from(("example", en));
+
+        let defs = en.variants.iter().map(|v| v.def().to_string());
+        assert_eq!(
+            defs.collect::<Vec<_>>(),
+            [
+                quote! {
+                    #[doc = ""]
+                    Plain = 1u64
+                },
+                quote! {
+                    #[doc = "A documented variant.\n### References\n- https://example.com\n"]
+                    Documented = 2u64
+                }
+            ]
+            .map(|ts| ts.to_string())
+        );
+    }
+
+    #[test]
+    fn large_values() {
+        let en = serde_yaml::from_str::<de::en::Enum>(
+            "0: empty\n0x8000_0000_0000_0000: high\n'18446744073709551615': full\n",
+        )
+        .unwrap();
+        let en = Enumeration::from(("marker", en));
+        let values = en.variants.iter().map(|v| v.value).collect::<Vec<_>>();
+        assert_eq!(values, [0, 1 << 63, u64::MAX]);
+
+        assert!(serde_yaml::from_str::<de::en::Enum>("-1: negative\n").is_err());
+        assert!(serde_yaml::from_str::<de::en::Enum>("0x1_0000_0000_0000_0000: big\n").is_err());
+    }
+}
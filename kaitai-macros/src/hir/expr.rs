@@ -0,0 +1,1633 @@
+use crate::{
+    error::Error,
+    util::{ident, type_ident},
+};
+
+use std::{collections::HashMap, str::FromStr};
+
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::{quote, ToTokens};
+
+/// An expression in the Kaitai Struct expression language.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Int(u64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Name(String),
+    EnumMember {
+        en: String,
+        member: String,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// A call of a built-in method, e.g. `name.length`.
+    Call {
+        receiver: Box<Expr>,
+        method: Method,
+    },
+    /// A field of a user type, e.g. `_.kind` in the `repeat-until` of an array of user types.
+    Field {
+        receiver: Box<Expr>,
+        field: String,
+    },
+    /// A property of the stream of the type, e.g. `_io.size`.
+    Io(IoProperty),
+    /// A field of the root type, e.g. `_root.version`, which is passed to the types referring to
+    /// it like a parameter.
+    Root(String),
+    /// A conditional expression, e.g. `version > 1 ? len : 0`.
+    Ternary {
+        condition: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+    /// The conversion of a number to the numeric type `ty`, which isn't part of the expression
+    /// language but is inserted by [`Expr::unified`].
+    Cast {
+        expr: Box<Expr>,
+        ty: &'static str,
+    },
+}
+
+/// A built-in method of the expression language.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Method {
+    /// The number of characters of a string or elements of an array (KS: `length` and `size`).
+    Length,
+    Reverse,
+    First,
+    Last,
+    Min,
+    Max,
+    Substring {
+        from: Box<Expr>,
+        to: Box<Expr>,
+    },
+    /// The decoding of a byte array (KS: `to_s`).
+    ToS {
+        encoding: Box<Expr>,
+    },
+    /// The parsing of a string as an integer (KS: `to_i`), in radix 10 by default.
+    ToI {
+        radix: Option<Box<Expr>>,
+    },
+    /// The element of an array at an index (KS: `array[index]`), which is an error if the index
+    /// is negative or past the end of the array.
+    At {
+        index: Box<Expr>,
+    },
+}
+
+impl Method {
+    /// The names of the methods, which can't be the names of fields.
+    const NAMES: &'static [&'static str] = &[
+        "length",
+        "size",
+        "reverse",
+        "first",
+        "last",
+        "min",
+        "max",
+        "substring",
+        "to_s",
+        "to_i",
+    ];
+
+    /// Returns the method called `name` with the arguments `args`.
+    fn new(name: &str, args: Vec<Expr>) -> Result<Self, String> {
+        let mut args = args.into_iter().map(Box::new);
+        let count = args.len();
+        let method = match (name, count) {
+            ("length" | "size", 0) => Method::Length,
+            ("reverse", 0) => Method::Reverse,
+            ("first", 0) => Method::First,
+            ("last", 0) => Method::Last,
+            ("min", 0) => Method::Min,
+            ("max", 0) => Method::Max,
+            ("substring", 2) => Method::Substring {
+                from: args.next().unwrap(),
+                to: args.next().unwrap(),
+            },
+            ("to_s", 1) => Method::ToS {
+                encoding: args.next().unwrap(),
+            },
+            ("to_i", 0 | 1) => Method::ToI { radix: args.next() },
+            _ if Self::NAMES.contains(&name) => {
+                return Err(format!("wrong number of arguments for {}: {}", name, count))
+            }
+            _ => return Err(format!("unknown method {}", name)),
+        };
+        Ok(method)
+    }
+}
+
+/// A property of the stream of the type (KS: `_io`), read when the expression is evaluated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IoProperty {
+    Size,
+    Pos,
+    Eof,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    /// The bitwise not of an integer (KS: `~`).
+    BitNot,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    /// The concatenation of strings (KS: `+` on strings), which `+` is turned into by
+    /// [`Expr::unified`] when its operands are strings.
+    Concat,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+// Binding powers of the operators. Operators with a higher binding power are evaluated first.
+const TERNARY_BP: u8 = 5;
+const NOT_BP: u8 = 30;
+const NEG_BP: u8 = 110;
+
+impl BinaryOp {
+    /// Returns whether the operator compares its operands.
+    fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge
+        )
+    }
+
+    fn binding_power(self) -> u8 {
+        match self {
+            BinaryOp::Or => 10,
+            BinaryOp::And => 20,
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge => 40,
+            BinaryOp::BitOr => 50,
+            BinaryOp::BitXor => 60,
+            BinaryOp::BitAnd => 70,
+            BinaryOp::Shl | BinaryOp::Shr => 80,
+            BinaryOp::Add | BinaryOp::Concat | BinaryOp::Sub => 90,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 100,
+        }
+    }
+}
+
+impl ToTokens for BinaryOp {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            BinaryOp::Add | BinaryOp::Concat => quote! { + },
+            BinaryOp::Sub => quote! { - },
+            BinaryOp::Mul => quote! { * },
+            BinaryOp::Div => quote! { / },
+            BinaryOp::Rem => quote! { % },
+            BinaryOp::Eq => quote! { == },
+            BinaryOp::Ne => quote! { != },
+            BinaryOp::Lt => quote! { < },
+            BinaryOp::Le => quote! { <= },
+            BinaryOp::Gt => quote! { > },
+            BinaryOp::Ge => quote! { >= },
+            BinaryOp::And => quote! { && },
+            BinaryOp::Or => quote! { || },
+            BinaryOp::BitAnd => quote! { & },
+            BinaryOp::BitOr => quote! { | },
+            BinaryOp::BitXor => quote! { ^ },
+            BinaryOp::Shl => quote! { << },
+            BinaryOp::Shr => quote! { >> },
+        })
+    }
+}
+
+impl FromStr for Expr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| Error::InvalidExpression {
+            expr: s.to_owned(),
+            reason,
+        };
+
+        let mut parser = Parser {
+            tokens: tokenize(s).map_err(invalid)?,
+            pos: 0,
+        };
+        let expr = parser.parse_expr(0).map_err(invalid)?;
+        match parser.next() {
+            None => Ok(expr),
+            Some(token) => Err(invalid(format!("unexpected {:?}", token))),
+        }
+    }
+}
+
+/// Parses a reference to a user type with the arguments of its parameters, e.g. `entry(len, 2)`,
+/// into the KS id of the type and the arguments.
+pub fn parse_type_ref(s: &str) -> Result<(String, Vec<Expr>), Error> {
+    let invalid = |reason: String| Error::InvalidExpression {
+        expr: s.to_owned(),
+        reason,
+    };
+
+    let mut parser = Parser {
+        tokens: tokenize(s).map_err(invalid)?,
+        pos: 0,
+    };
+    let id = parser.ident().map_err(invalid)?;
+    let args = parser.parse_args().map_err(invalid)?;
+    match parser.next() {
+        None => Ok((id, args)),
+        Some(token) => Err(invalid(format!("unexpected {:?}", token))),
+    }
+}
+
+impl ToTokens for Expr {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            Expr::Int(value) => Literal::u64_unsuffixed(*value).into_token_stream(),
+            Expr::Float(value) => Literal::f64_unsuffixed(*value).into_token_stream(),
+            Expr::Bool(value) => quote! { #value },
+            Expr::Str(value) => quote! { #value },
+            // The current value, e.g. the element read last in `repeat-until`.
+            Expr::Name(name) if name == "_" => quote! { _value },
+            Expr::Name(name) => ident(name).into_token_stream(),
+            Expr::EnumMember { en, member } => {
+                let en = type_ident(en);
+                let member = type_ident(member);
+                quote! { #en::#member }
+            }
+            Expr::Unary { op, expr } => {
+                // Negative literals can't overflow, and stay literals of the type they are used as.
+                let literal = matches!(**expr, Expr::Int(_) | Expr::Float(_));
+                let expr = expr.operand();
+                match op {
+                    UnaryOp::Neg if literal => quote! { -#expr },
+                    UnaryOp::Neg => quote! { ::kaitai::__private::KsNum::ks_neg(#expr)? },
+                    UnaryOp::Not | UnaryOp::BitNot => quote! { !#expr },
+                }
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                // Integer arithmetic and shifts are checked, so that they fail rather than wrap or
+                // panic.
+                let method = match op {
+                    BinaryOp::Add => Some(quote! { KsNum::ks_add }),
+                    BinaryOp::Sub => Some(quote! { KsNum::ks_sub }),
+                    BinaryOp::Mul => Some(quote! { KsNum::ks_mul }),
+                    BinaryOp::Div => Some(quote! { KsNum::ks_div }),
+                    BinaryOp::Rem => Some(quote! { KsNum::ks_rem }),
+                    BinaryOp::Shl => Some(quote! { KsShift::ks_shl }),
+                    BinaryOp::Shr => Some(quote! { KsShift::ks_shr }),
+                    _ => None,
+                };
+                match method {
+                    Some(method) => quote! {
+                        ::kaitai::__private::#method(#lhs, #rhs)?
+                    },
+                    None if *op == BinaryOp::Concat => quote! {
+                        ::std::format!("{}{}", #lhs, #rhs)
+                    },
+                    // A `String` can't be ordered against a literal `&str`, so the other operand
+                    // is compared as a `str`.
+                    None if op.is_comparison() && matches!(**rhs, Expr::Str(_)) => {
+                        let lhs = lhs.operand();
+                        quote! { ::std::convert::AsRef::<str>::as_ref(&#lhs) #op #rhs }
+                    }
+                    None if op.is_comparison() && matches!(**lhs, Expr::Str(_)) => {
+                        let rhs = rhs.operand();
+                        quote! { #lhs #op ::std::convert::AsRef::<str>::as_ref(&#rhs) }
+                    }
+                    None => {
+                        let lhs = lhs.operand();
+                        let rhs = rhs.operand();
+                        quote! { #lhs #op #rhs }
+                    }
+                }
+            }
+            Expr::Call { receiver, method } => {
+                let receiver = match **receiver {
+                    Expr::Unary { .. } => quote! { (#receiver) },
+                    _ => receiver.operand(),
+                };
+                let call = match method {
+                    Method::Length => quote! { #receiver.ks_length() },
+                    Method::Reverse => quote! { #receiver.ks_reverse() },
+                    Method::First => quote! { #receiver.ks_first()? },
+                    Method::Last => quote! { #receiver.ks_last()? },
+                    Method::Min => quote! { #receiver.ks_min()? },
+                    Method::Max => quote! { #receiver.ks_max()? },
+                    Method::Substring { from, to } => {
+                        let from = from.cast(quote! { i64 });
+                        let to = to.cast(quote! { i64 });
+                        quote! { #receiver.ks_substring(#from, #to) }
+                    }
+                    Method::ToS { encoding } => {
+                        let encoding = encoding.operand();
+                        quote! { #receiver.ks_to_s(&#encoding, _ctx.decoding())? }
+                    }
+                    Method::ToI { radix } => {
+                        let radix = match radix {
+                            Some(radix) => radix.cast(quote! { u32 }),
+                            None => quote! { 10u32 },
+                        };
+                        quote! { #receiver.ks_to_i(#radix)? }
+                    }
+                    Method::At { index } => {
+                        let index = index.cast(quote! { i64 });
+                        quote! { #receiver.ks_at(#index)? }
+                    }
+                };
+                // The methods are implemented by traits for the types they are called on.
+                quote! {
+                    {
+                        #[allow(unused_imports)]
+                        use ::kaitai::__private::{KsBytes as _, KsEnum as _, KsSeq as _, KsStr as _};
+                        #call
+                    }
+                }
+            }
+            Expr::Field { receiver, field } => {
+                let receiver = match **receiver {
+                    Expr::Unary { .. } => quote! { (#receiver) },
+                    _ => receiver.operand(),
+                };
+                let field = ident(field);
+                quote! { #receiver.#field }
+            }
+            Expr::Root(field) => ident(&format!("_root_{}", field)).into_token_stream(),
+            Expr::Io(property) => match property {
+                IoProperty::Size => quote! { ::kaitai::KaitaiStream::size(buf)? },
+                IoProperty::Pos => quote! { ::kaitai::KaitaiStream::pos(buf)? },
+                IoProperty::Eof => quote! { ::kaitai::KaitaiStream::is_eof(buf)? },
+            },
+            Expr::Ternary {
+                condition,
+                then,
+                otherwise,
+            } => quote! {
+                if #condition { #then } else { #otherwise }
+            },
+            Expr::Cast { expr, ty } => {
+                let expr = expr.operand();
+                let ty = Ident::new(ty, Span::call_site());
+                quote! { #expr as #ty }
+            }
+        })
+    }
+}
+
+impl Expr {
+    /// Returns the tokens of the expression when it is used as an operand, i.e. wrapped in
+    /// parentheses if it is an operation itself.
+    pub fn operand(&self) -> TokenStream {
+        match self {
+            // Checked arithmetic, shifts and concatenation are calls already.
+            Expr::Binary {
+                op:
+                    BinaryOp::Add
+                    | BinaryOp::Concat
+                    | BinaryOp::Sub
+                    | BinaryOp::Mul
+                    | BinaryOp::Div
+                    | BinaryOp::Rem
+                    | BinaryOp::Shl
+                    | BinaryOp::Shr,
+                ..
+            } => self.to_token_stream(),
+            Expr::Binary { .. } | Expr::Ternary { .. } | Expr::Cast { .. } => quote! { (#self) },
+            _ => self.to_token_stream(),
+        }
+    }
+
+    /// Returns the tokens of the expression converted to the integer type `ty`. Integer literals
+    /// get the type as a suffix instead of being cast.
+    pub fn cast(&self, ty: TokenStream) -> TokenStream {
+        match self {
+            Expr::Int(value) => format!("{}{}", value, ty).parse().unwrap(),
+            _ => {
+                let expr = self.operand();
+                quote! { #expr as #ty }
+            }
+        }
+    }
+}
+
+/// The type of an expression, inferred from the types of the names it refers to.
+#[derive(Clone, Debug)]
+pub enum ExprType {
+    /// An integer literal, whose type is the type of the other operand when there is one.
+    IntLiteral(u64),
+    /// A built-in integer type, e.g. `u16`.
+    Int(&'static str),
+    /// A built-in float type, e.g. `f64`.
+    Float(&'static str),
+    Bool,
+    Str,
+    Bytes,
+    /// An array of elements of the given type, with a fixed length if it is stored as a Rust
+    /// array rather than a `Vec`.
+    Array(Box<ExprType>, Option<u64>),
+    /// A generated type, e.g. a user type or an enum.
+    Other(TokenStream),
+}
+
+impl ExprType {
+    /// Returns whether values of the type are [`Copy`].
+    pub fn is_copy(&self) -> bool {
+        matches!(
+            self,
+            ExprType::IntLiteral(_) | ExprType::Int(_) | ExprType::Float(_) | ExprType::Bool
+        )
+    }
+
+    /// Returns the Rust type. Integer literals on their own are `i32`, or the smallest of `i64`
+    /// and `u64` that fits their value.
+    pub fn ty(&self) -> TokenStream {
+        match self {
+            ExprType::IntLiteral(value) if *value <= i32::MAX as u64 => quote! { i32 },
+            ExprType::IntLiteral(value) if *value <= i64::MAX as u64 => quote! { i64 },
+            ExprType::IntLiteral(_) => quote! { u64 },
+            ExprType::Int(ty) | ExprType::Float(ty) => {
+                Ident::new(ty, Span::call_site()).into_token_stream()
+            }
+            ExprType::Bool => quote! { bool },
+            ExprType::Str => quote! { ::std::string::String },
+            ExprType::Bytes => quote! { ::std::vec::Vec<u8> },
+            ExprType::Array(ty, None) => {
+                let ty = ty.ty();
+                quote! { ::std::vec::Vec<#ty> }
+            }
+            ExprType::Array(ty, Some(len)) => {
+                let ty = ty.ty();
+                let len = Literal::u64_unsuffixed(*len);
+                quote! { [#ty; #len] }
+            }
+            ExprType::Other(ty) => ty.clone(),
+        }
+    }
+}
+
+impl Expr {
+    /// Returns the type of the expression, given the types of the names in scope.
+    pub fn ty(&self, names: &HashMap<String, ExprType>) -> Result<ExprType, String> {
+        Ok(match self {
+            Expr::Int(value) => ExprType::IntLiteral(*value),
+            Expr::Float(_) => ExprType::Float("f64"),
+            Expr::Bool(_) => ExprType::Bool,
+            Expr::Str(_) => ExprType::Str,
+            Expr::Name(name) => names
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("unknown name {}", name))?,
+            Expr::EnumMember { en, .. } => ExprType::Other(type_ident(en).into_token_stream()),
+            Expr::Unary {
+                op: UnaryOp::Not, ..
+            } => ExprType::Bool,
+            Expr::Unary {
+                op: UnaryOp::Neg | UnaryOp::BitNot,
+                expr,
+            } => expr.ty(names)?,
+            Expr::Binary { op, lhs, rhs } => match op {
+                BinaryOp::Eq
+                | BinaryOp::Ne
+                | BinaryOp::Lt
+                | BinaryOp::Le
+                | BinaryOp::Gt
+                | BinaryOp::Ge
+                | BinaryOp::And
+                | BinaryOp::Or => ExprType::Bool,
+                BinaryOp::Concat => ExprType::Str,
+                // A shift has the type of the shifted value.
+                BinaryOp::Shl | BinaryOp::Shr => lhs.ty(names)?,
+                // Both operands have the same type in Rust, unless one is an integer literal.
+                _ => match (lhs.ty(names)?, rhs.ty(names)?) {
+                    (ExprType::IntLiteral(lhs), ExprType::IntLiteral(rhs)) => {
+                        ExprType::IntLiteral(lhs.max(rhs))
+                    }
+                    (ExprType::IntLiteral(_), ty) | (ty, _) => ty,
+                },
+            },
+            Expr::Call { receiver, method } => match method {
+                Method::Length => ExprType::Int("usize"),
+                // Arrays of a fixed length are reversed into a `Vec` too.
+                Method::Reverse => match receiver.ty(names)? {
+                    ExprType::Array(ty, _) => ExprType::Array(ty, None),
+                    ty => ty,
+                },
+                Method::First | Method::Last | Method::Min | Method::Max => {
+                    match receiver.ty(names)? {
+                        ExprType::Array(ty, _) => *ty,
+                        ExprType::Bytes => ExprType::Int("u8"),
+                        _ => return Err(format!("{:?} can only be called on arrays", method)),
+                    }
+                }
+                Method::Substring { .. } | Method::ToS { .. } => ExprType::Str,
+                Method::ToI { .. } => ExprType::Int("i64"),
+                Method::At { .. } => match receiver.ty(names)? {
+                    ExprType::Array(ty, _) => *ty,
+                    ExprType::Bytes => ExprType::Int("u8"),
+                    ty => return Err(format!("only arrays can be indexed, not {:?}", ty)),
+                },
+            },
+            // The types of user types aren't known from their fields, so their fields can be
+            // compared, as in `_.kind == 0`, but not stored.
+            Expr::Field { field, .. } => {
+                return Err(format!(
+                    "the type of the field {} of a user type isn't known",
+                    field
+                ))
+            }
+            Expr::Root(field) => names
+                .get(&format!("_root.{}", field))
+                .cloned()
+                .ok_or_else(|| format!("unknown name _root.{}", field))?,
+            Expr::Io(IoProperty::Eof) => ExprType::Bool,
+            Expr::Io(_) => ExprType::Int("u64"),
+            Expr::Ternary {
+                condition,
+                then,
+                otherwise,
+            } => {
+                match condition.ty(names)? {
+                    ExprType::Bool => {}
+                    ty => return Err(format!("the condition of `?:` is a {:?}, not a bool", ty)),
+                }
+                common_type(then.ty(names)?, otherwise.ty(names)?)?
+            }
+            Expr::Cast { ty, .. } if ty.starts_with('f') => ExprType::Float(ty),
+            Expr::Cast { ty, .. } => ExprType::Int(ty),
+        })
+    }
+
+    /// Returns the expression with the branches of its conditional expressions converted to the
+    /// type of the whole conditional expression, e.g. `a ? 1 : len` with a `u8` field `len` and a
+    /// `u16` field `a` has its first branch converted to `u16`.
+    ///
+    /// Rust requires both branches of an `if` to have the same type, unlike Kaitai Struct.
+    pub fn unified(&self, names: &HashMap<String, ExprType>) -> Result<Expr, String> {
+        let unified = |expr: &Expr| expr.unified(names).map(Box::new);
+        Ok(match self {
+            Expr::Ternary {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let ty = self.ty(names)?;
+                let branch = |expr: &Expr| {
+                    let expr = expr.unified(names)?;
+                    let cast = match (&ty, expr.ty(names)?) {
+                        (ExprType::Int(ty) | ExprType::Float(ty), ExprType::Int(branch_ty))
+                        | (ExprType::Int(ty) | ExprType::Float(ty), ExprType::Float(branch_ty))
+                            if *ty != branch_ty =>
+                        {
+                            Some(*ty)
+                        }
+                        // Integer literals can't be floats.
+                        (ExprType::Float(ty), ExprType::IntLiteral(_)) => Some(*ty),
+                        _ => None,
+                    };
+                    Ok::<_, String>(Box::new(match cast {
+                        Some(ty) => Expr::Cast {
+                            expr: Box::new(expr),
+                            ty,
+                        },
+                        None => expr,
+                    }))
+                };
+                Expr::Ternary {
+                    condition: unified(condition)?,
+                    then: branch(then)?,
+                    otherwise: branch(otherwise)?,
+                }
+            }
+            Expr::Unary { op, expr } => Expr::Unary {
+                op: *op,
+                expr: unified(expr)?,
+            },
+            Expr::Binary { op, lhs, rhs } => {
+                // `+` concatenates strings, and adds anything else.
+                let op = match op {
+                    BinaryOp::Add
+                        if matches!(
+                            (lhs.ty(names), rhs.ty(names)),
+                            (Ok(ExprType::Str), Ok(ExprType::Str))
+                        ) =>
+                    {
+                        BinaryOp::Concat
+                    }
+                    op => *op,
+                };
+                Expr::Binary {
+                    op,
+                    lhs: unified(lhs)?,
+                    rhs: unified(rhs)?,
+                }
+            }
+            Expr::Call { receiver, method } => Expr::Call {
+                receiver: unified(receiver)?,
+                method: method.clone(),
+            },
+            Expr::Field { receiver, field } => Expr::Field {
+                receiver: unified(receiver)?,
+                field: field.clone(),
+            },
+            Expr::Cast { expr, ty } => Expr::Cast {
+                expr: unified(expr)?,
+                ty,
+            },
+            expr => expr.clone(),
+        })
+    }
+
+    /// Returns the names the expression refers to.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Expr::Int(_) | Expr::Float(_) | Expr::Bool(_) | Expr::Str(_) => Vec::new(),
+            Expr::EnumMember { .. } | Expr::Io(_) | Expr::Root(_) => Vec::new(),
+            Expr::Name(name) => vec![name.as_str()],
+            Expr::Unary { expr, .. } | Expr::Field { receiver: expr, .. } => expr.names(),
+            Expr::Binary { lhs, rhs, .. } => {
+                let mut names = lhs.names();
+                names.extend(rhs.names());
+                names
+            }
+            Expr::Call { receiver, method } => {
+                let mut names = receiver.names();
+                let args = match method {
+                    Method::Substring { from, to } => vec![from, to],
+                    Method::ToS { encoding } => vec![encoding],
+                    Method::ToI { radix: Some(radix) } => vec![radix],
+                    Method::At { index } => vec![index],
+                    _ => Vec::new(),
+                };
+                names.extend(args.into_iter().flat_map(|arg| arg.names()));
+                names
+            }
+            Expr::Ternary {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let mut names = condition.names();
+                names.extend(then.names());
+                names.extend(otherwise.names());
+                names
+            }
+            Expr::Cast { expr, .. } => expr.names(),
+        }
+    }
+
+    /// Returns the enum members the expression refers to, as the KS ids of their enum and of the
+    /// member.
+    pub fn enum_members(&self) -> Vec<(&str, &str)> {
+        match self {
+            Expr::EnumMember { en, member } => vec![(en.as_str(), member.as_str())],
+            Expr::Int(_) | Expr::Float(_) | Expr::Bool(_) | Expr::Str(_) => Vec::new(),
+            Expr::Name(_) | Expr::Io(_) | Expr::Root(_) => Vec::new(),
+            Expr::Unary { expr, .. }
+            | Expr::Cast { expr, .. }
+            | Expr::Field { receiver: expr, .. } => expr.enum_members(),
+            Expr::Binary { lhs, rhs, .. } => {
+                let mut members = lhs.enum_members();
+                members.extend(rhs.enum_members());
+                members
+            }
+            Expr::Call { receiver, method } => {
+                let mut members = receiver.enum_members();
+                let args = match method {
+                    Method::Substring { from, to } => vec![from, to],
+                    Method::ToS { encoding } => vec![encoding],
+                    Method::ToI { radix: Some(radix) } => vec![radix],
+                    Method::At { index } => vec![index],
+                    _ => Vec::new(),
+                };
+                members.extend(args.into_iter().flat_map(|arg| arg.enum_members()));
+                members
+            }
+            Expr::Ternary {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let mut members = condition.enum_members();
+                members.extend(then.enum_members());
+                members.extend(otherwise.enum_members());
+                members
+            }
+        }
+    }
+}
+
+/// Returns the type both branches of a conditional expression are converted to.
+///
+/// Integers are converted to the smallest integer type holding the values of both, e.g. `u8`
+/// and `i8` to `i16`, and to a float if the other branch is a float.
+fn common_type(lhs: ExprType, rhs: ExprType) -> Result<ExprType, String> {
+    // The signedness and the number of bits of an integer type.
+    let int = |ty: &str| {
+        let bits = match &ty[1..] {
+            "size" => 64,
+            bits => bits.parse::<u32>().unwrap(),
+        };
+        (ty.starts_with('i'), bits)
+    };
+    let int_ty = |signed, bits| match (signed, bits) {
+        (false, 8) => "u8",
+        (false, 16) => "u16",
+        (false, 32) => "u32",
+        (false, _) => "u64",
+        (true, 8) => "i8",
+        (true, 16) => "i16",
+        (true, 32) => "i32",
+        (true, _) => "i64",
+    };
+
+    Ok(match (lhs, rhs) {
+        (ExprType::IntLiteral(lhs), ExprType::IntLiteral(rhs)) => {
+            ExprType::IntLiteral(lhs.max(rhs))
+        }
+        (ExprType::IntLiteral(_), ty @ (ExprType::Int(_) | ExprType::Float(_)))
+        | (ty @ (ExprType::Int(_) | ExprType::Float(_)), ExprType::IntLiteral(_)) => ty,
+        (ExprType::Int(lhs), ExprType::Int(rhs)) if lhs == rhs => ExprType::Int(lhs),
+        (ExprType::Int(lhs), ExprType::Int(rhs)) => {
+            let ((lhs_signed, lhs_bits), (rhs_signed, rhs_bits)) = (int(lhs), int(rhs));
+            if lhs_signed == rhs_signed {
+                ExprType::Int(int_ty(lhs_signed, lhs_bits.max(rhs_bits)))
+            } else {
+                let (signed_bits, unsigned_bits) = if lhs_signed {
+                    (lhs_bits, rhs_bits)
+                } else {
+                    (rhs_bits, lhs_bits)
+                };
+                if signed_bits > unsigned_bits {
+                    ExprType::Int(int_ty(true, signed_bits))
+                } else if unsigned_bits < 64 {
+                    ExprType::Int(int_ty(true, unsigned_bits * 2))
+                } else {
+                    return Err(format!(
+                        "the branches of `?:` are {} and {}, which no integer type holds",
+                        lhs, rhs
+                    ));
+                }
+            }
+        }
+        (ExprType::Float("f32"), ExprType::Float("f32")) => ExprType::Float("f32"),
+        (ExprType::Float(_), ExprType::Float(_)) => ExprType::Float("f64"),
+        (ExprType::Float(ty), ExprType::Int(_)) | (ExprType::Int(_), ExprType::Float(ty)) => {
+            ExprType::Float(ty)
+        }
+        (ExprType::Bool, ExprType::Bool) => ExprType::Bool,
+        (ExprType::Str, ExprType::Str) => ExprType::Str,
+        (ExprType::Bytes, ExprType::Bytes) => ExprType::Bytes,
+        (lhs, rhs) if lhs.ty().to_string() == rhs.ty().to_string() => lhs,
+        (lhs, rhs) => {
+            return Err(format!(
+                "the branches of `?:` have incompatible types {} and {}",
+                lhs.ty(),
+                rhs.ty()
+            ))
+        }
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Int(u64),
+    Float(f64),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+// Longer punctuation has to come first so that e.g. `<=` isn't read as `<` followed by `=`.
+const PUNCTUATION: &[&str] = &[
+    "::", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "+", "-", "*", "/", "%", "&", "|", "^",
+    "~", "(", ")", "[", "]", ".", ",", "?", ":",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = s;
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_digit() {
+            let (token, len) = number(rest)?;
+            tokens.push(token);
+            rest = &rest[len..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_owned()));
+            rest = &rest[len..];
+        } else if c == '"' || c == '\'' {
+            let (token, len) = string(rest)?;
+            tokens.push(token);
+            rest = &rest[len..];
+        } else if let Some(punct) = PUNCTUATION.iter().find(|p| rest.starts_with(**p)) {
+            tokens.push(Token::Punct(punct));
+            rest = &rest[punct.len()..];
+        } else {
+            return Err(format!("unexpected character {:?}", c));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a number from the start of `s`, returning the token and the number of bytes read.
+fn number(s: &str) -> Result<(Token, usize), String> {
+    let radix = match s.get(..2) {
+        Some("0x") | Some("0X") => 16,
+        Some("0b") | Some("0B") => 2,
+        Some("0o") | Some("0O") => 8,
+        _ => 10,
+    };
+    let start = if radix == 10 { 0 } else { 2 };
+    let digits_len = |s: &str| {
+        s.find(|c: char| !(c.is_digit(radix) || c == '_'))
+            .unwrap_or(s.len())
+    };
+
+    let mut len = start + digits_len(&s[start..]);
+    let mut is_float = false;
+    if radix == 10 {
+        // A dot is only part of the number if it is followed by a digit, otherwise it is a method
+        // call on an integer.
+        let after = &s[len..];
+        if after.starts_with('.') && after[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            is_float = true;
+            len += 1 + digits_len(&after[1..]);
+        }
+        let after = &s[len..];
+        if after.starts_with(['e', 'E']) {
+            let exponent = after[1..].strip_prefix(['+', '-']).unwrap_or(&after[1..]);
+            let exponent_len = digits_len(exponent);
+            if exponent_len > 0 {
+                is_float = true;
+                len += after.len() - exponent.len() + exponent_len;
+            }
+        }
+    }
+
+    let text: String = s[start..len].chars().filter(|c| *c != '_').collect();
+    let token = if is_float {
+        Token::Float(
+            text.parse()
+                .map_err(|_| format!("invalid float {}", &s[..len]))?,
+        )
+    } else {
+        Token::Int(
+            u64::from_str_radix(&text, radix)
+                .map_err(|_| format!("invalid integer {}", &s[..len]))?,
+        )
+    };
+    Ok((token, len))
+}
+
+/// Reads a string literal from the start of `s`, returning the token and the number of bytes read.
+///
+/// Like in Kaitai Struct, escape sequences are only interpreted in double quoted strings.
+fn string(s: &str) -> Result<(Token, usize), String> {
+    let quote = s.chars().next().unwrap();
+    let mut value = String::new();
+    let mut chars = s.char_indices().skip(1);
+
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return Ok((Token::Str(value), i + 1));
+        }
+        if c == '\\' && quote == '"' {
+            value.push(match chars.next() {
+                Some((_, 'n')) => '\n',
+                Some((_, 'r')) => '\r',
+                Some((_, 't')) => '\t',
+                Some((_, '0')) => '\0',
+                Some((_, c @ ('\\' | '"' | '\''))) => c,
+                Some((_, c)) => return Err(format!("unknown escape sequence \\{}", c)),
+                None => break,
+            });
+        } else {
+            value.push(c);
+        }
+    }
+
+    Err("unterminated string".to_owned())
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Punct(p)) if p == punct => Ok(()),
+            Some(token) => Err(format!("expected `{}`, found {:?}", punct, token)),
+            None => Err(format!("expected `{}`, found end of expression", punct)),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Ident(ident)) => Ok(ident),
+            Some(token) => Err(format!("expected identifier, found {:?}", token)),
+            None => Err("expected identifier, found end of expression".to_owned()),
+        }
+    }
+
+    /// Parses an expression containing only operators with a binding power of at least `min_bp`.
+    fn parse_expr(&mut self, min_bp: u8) -> Result<Expr, String> {
+        let mut lhs = self.parse_prefix()?;
+        // Method calls and indexing bind tighter than any operator.
+        loop {
+            lhs = match self.peek() {
+                Some(Token::Punct(".")) => {
+                    self.next();
+                    match lhs {
+                        Expr::Name(name) if name == "_io" => {
+                            Expr::Io(match self.ident()?.as_str() {
+                                "size" => IoProperty::Size,
+                                "pos" => IoProperty::Pos,
+                                "eof" => IoProperty::Eof,
+                                property => {
+                                    return Err(format!("unknown property _io.{}", property))
+                                }
+                            })
+                        }
+                        Expr::Name(name) if name == "_root" => Expr::Root(self.ident()?),
+                        lhs => self.parse_call(lhs)?,
+                    }
+                }
+                Some(Token::Punct("[")) => {
+                    self.next();
+                    let index = self.parse_expr(0)?;
+                    self.expect("]")?;
+                    Expr::Call {
+                        receiver: Box::new(lhs),
+                        method: Method::At {
+                            index: Box::new(index),
+                        },
+                    }
+                }
+                _ => break,
+            };
+        }
+
+        while let Some(op) = self.peek().and_then(binary_op) {
+            let bp = op.binding_power();
+            if bp < min_bp {
+                break;
+            }
+            self.next();
+            // All binary operators are left associative.
+            let rhs = self.parse_expr(bp + 1)?;
+            lhs = Expr::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+
+        // The conditional operator has the lowest precedence and is right associative.
+        if TERNARY_BP >= min_bp && self.peek() == Some(&Token::Punct("?")) {
+            self.next();
+            let then = self.parse_expr(0)?;
+            self.expect(":")?;
+            let otherwise = self.parse_expr(TERNARY_BP)?;
+            lhs = Expr::Ternary {
+                condition: Box::new(lhs),
+                then: Box::new(then),
+                otherwise: Box::new(otherwise),
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parses the method called on `receiver`, after the dot.
+    fn parse_call(&mut self, receiver: Expr) -> Result<Expr, String> {
+        let name = self.ident()?;
+        // Names that aren't methods are fields of user types, without parentheses.
+        if !Method::NAMES.contains(&name.as_str()) && self.peek() != Some(&Token::Punct("(")) {
+            return Ok(Expr::Field {
+                receiver: Box::new(receiver),
+                field: name,
+            });
+        }
+        let args = self.parse_args()?;
+
+        Ok(Expr::Call {
+            receiver: Box::new(receiver),
+            method: Method::new(&name, args)?,
+        })
+    }
+
+    /// Parses the parenthesized, comma-separated arguments of a call, if there are parentheses.
+    fn parse_args(&mut self) -> Result<Vec<Expr>, String> {
+        let mut args = Vec::new();
+        if self.peek() == Some(&Token::Punct("(")) {
+            self.next();
+            if self.peek() != Some(&Token::Punct(")")) {
+                loop {
+                    args.push(self.parse_expr(0)?);
+                    if self.peek() != Some(&Token::Punct(",")) {
+                        break;
+                    }
+                    self.next();
+                }
+            }
+            self.expect(")")?;
+        }
+        Ok(args)
+    }
+
+    fn parse_prefix(&mut self) -> Result<Expr, String> {
+        Ok(match self.next() {
+            Some(Token::Int(value)) => Expr::Int(value),
+            Some(Token::Float(value)) => Expr::Float(value),
+            Some(Token::Str(value)) => Expr::Str(value),
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Expr::Bool(true),
+                "false" => Expr::Bool(false),
+                "not" => Expr::Unary {
+                    op: UnaryOp::Not,
+                    expr: Box::new(self.parse_expr(NOT_BP)?),
+                },
+                _ => {
+                    if self.peek() == Some(&Token::Punct("::")) {
+                        self.next();
+                        Expr::EnumMember {
+                            en: ident,
+                            member: self.ident()?,
+                        }
+                    } else {
+                        Expr::Name(ident)
+                    }
+                }
+            },
+            Some(Token::Punct("-")) => Expr::Unary {
+                op: UnaryOp::Neg,
+                expr: Box::new(self.parse_expr(NEG_BP)?),
+            },
+            Some(Token::Punct("~")) => Expr::Unary {
+                op: UnaryOp::BitNot,
+                expr: Box::new(self.parse_expr(NEG_BP)?),
+            },
+            Some(Token::Punct("(")) => {
+                let expr = self.parse_expr(0)?;
+                self.expect(")")?;
+                expr
+            }
+            Some(token) => return Err(format!("unexpected {:?}", token)),
+            None => return Err("unexpected end of expression".to_owned()),
+        })
+    }
+}
+
+fn binary_op(token: &Token) -> Option<BinaryOp> {
+    Some(match token {
+        Token::Punct("+") => BinaryOp::Add,
+        Token::Punct("-") => BinaryOp::Sub,
+        Token::Punct("*") => BinaryOp::Mul,
+        Token::Punct("/") => BinaryOp::Div,
+        Token::Punct("%") => BinaryOp::Rem,
+        Token::Punct("==") => BinaryOp::Eq,
+        Token::Punct("!=") => BinaryOp::Ne,
+        Token::Punct("<") => BinaryOp::Lt,
+        Token::Punct("<=") => BinaryOp::Le,
+        Token::Punct(">") => BinaryOp::Gt,
+        Token::Punct(">=") => BinaryOp::Ge,
+        Token::Punct("&") => BinaryOp::BitAnd,
+        Token::Punct("|") => BinaryOp::BitOr,
+        Token::Punct("^") => BinaryOp::BitXor,
+        Token::Punct("<<") => BinaryOp::Shl,
+        Token::Punct(">>") => BinaryOp::Shr,
+        Token::Ident(ident) if ident == "and" => BinaryOp::And,
+        Token::Ident(ident) if ident == "or" => BinaryOp::Or,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> Box<Expr> {
+        Box::new(Expr::Name(name.to_owned()))
+    }
+
+    #[test]
+    fn literals() {
+        let input = [
+            "42",
+            "0x2a",
+            "0b10_1010",
+            "0o52",
+            "1_000",
+            "1.5",
+            "2e3",
+            "'a\\n'",
+            "\"a\\n\"",
+        ];
+        let expected = [
+            Expr::Int(42),
+            Expr::Int(42),
+            Expr::Int(42),
+            Expr::Int(42),
+            Expr::Int(1000),
+            Expr::Float(1.5),
+            Expr::Float(2000.0),
+            Expr::Str("a\\n".to_owned()),
+            Expr::Str("a\n".to_owned()),
+        ];
+
+        for (input, expected) in input.iter().zip(expected) {
+            assert_eq!(input.parse::<Expr>().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            "a + b * c == d or not e and f".parse::<Expr>().unwrap(),
+            Expr::Binary {
+                op: BinaryOp::Or,
+                lhs: Box::new(Expr::Binary {
+                    op: BinaryOp::Eq,
+                    lhs: Box::new(Expr::Binary {
+                        op: BinaryOp::Add,
+                        lhs: name("a"),
+                        rhs: Box::new(Expr::Binary {
+                            op: BinaryOp::Mul,
+                            lhs: name("b"),
+                            rhs: name("c"),
+                        }),
+                    }),
+                    rhs: name("d"),
+                }),
+                rhs: Box::new(Expr::Binary {
+                    op: BinaryOp::And,
+                    lhs: Box::new(Expr::Unary {
+                        op: UnaryOp::Not,
+                        expr: name("e"),
+                    }),
+                    rhs: name("f"),
+                }),
+            }
+        );
+        assert_eq!(
+            "a - b - c".parse::<Expr>().unwrap(),
+            Expr::Binary {
+                op: BinaryOp::Sub,
+                lhs: Box::new(Expr::Binary {
+                    op: BinaryOp::Sub,
+                    lhs: name("a"),
+                    rhs: name("b"),
+                }),
+                rhs: name("c"),
+            }
+        );
+    }
+
+    #[test]
+    fn bitwise_precedence() {
+        assert_eq!(
+            "a | b & c".parse::<Expr>().unwrap(),
+            Expr::Binary {
+                op: BinaryOp::BitOr,
+                lhs: name("a"),
+                rhs: Box::new(Expr::Binary {
+                    op: BinaryOp::BitAnd,
+                    lhs: name("b"),
+                    rhs: name("c"),
+                }),
+            }
+        );
+        assert_eq!(
+            "a ^ b << 1 + c == ~d".parse::<Expr>().unwrap(),
+            Expr::Binary {
+                op: BinaryOp::Eq,
+                lhs: Box::new(Expr::Binary {
+                    op: BinaryOp::BitXor,
+                    lhs: name("a"),
+                    rhs: Box::new(Expr::Binary {
+                        op: BinaryOp::Shl,
+                        lhs: name("b"),
+                        rhs: Box::new(Expr::Binary {
+                            op: BinaryOp::Add,
+                            lhs: Box::new(Expr::Int(1)),
+                            rhs: name("c"),
+                        }),
+                    }),
+                }),
+                rhs: Box::new(Expr::Unary {
+                    op: UnaryOp::BitNot,
+                    expr: name("d"),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        for input in ["", "a +", "(a", "a b", "a $ b", "'a", "colors::"] {
+            assert!(
+                input.parse::<Expr>().is_err(),
+                "{} should be invalid",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let input = [
+            "-4",
+            "(a + b) * -c",
+            "a / (b - 1) % 2",
+            "not (a == ip_protocol::tcp)",
+            "a & 0xf0 | b ^ c",
+            "~(a + 1)",
+            "a << 2 >> b",
+        ];
+        let expected = [
+            quote! { -4 },
+            quote! {
+                ::kaitai::__private::KsNum::ks_mul(
+                    ::kaitai::__private::KsNum::ks_add(a, b)?,
+                    ::kaitai::__private::KsNum::ks_neg(c)?
+                )?
+            },
+            quote! {
+                ::kaitai::__private::KsNum::ks_rem(
+                    ::kaitai::__private::KsNum::ks_div(a, ::kaitai::__private::KsNum::ks_sub(b, 1)?)?,
+                    2
+                )?
+            },
+            quote! { !(a == IpProtocol::Tcp) },
+            quote! { (a & 240) | (b ^ c) },
+            quote! { !::kaitai::__private::KsNum::ks_add(a, 1)? },
+            quote! {
+                ::kaitai::__private::KsShift::ks_shr(::kaitai::__private::KsShift::ks_shl(a, 2)?, b)?
+            },
+        ];
+
+        for (input, expected) in input.iter().zip(expected) {
+            let expr = input.parse::<Expr>().unwrap();
+            assert_eq!(expr.to_token_stream().to_string(), expected.to_string());
+        }
+    }
+
+    #[test]
+    fn methods() {
+        let call = |receiver: Box<Expr>, method| Expr::Call { receiver, method };
+        let input = [
+            "name.length",
+            "items.size",
+            "name.reverse",
+            "name.substring(1, a + 1)",
+            "bytes.to_s(\"UTF-8\")",
+            "name.to_i",
+            "name.to_i(16)",
+            "-name.to_i.reverse",
+            "values[i + 1]",
+            "a[0].length",
+            "_.kind == 0",
+            "header.entries.size",
+        ];
+        let expected = [
+            call(name("name"), Method::Length),
+            call(name("items"), Method::Length),
+            call(name("name"), Method::Reverse),
+            call(
+                name("name"),
+                Method::Substring {
+                    from: Box::new(Expr::Int(1)),
+                    to: Box::new(Expr::Binary {
+                        op: BinaryOp::Add,
+                        lhs: name("a"),
+                        rhs: Box::new(Expr::Int(1)),
+                    }),
+                },
+            ),
+            call(
+                name("bytes"),
+                Method::ToS {
+                    encoding: Box::new(Expr::Str("UTF-8".to_owned())),
+                },
+            ),
+            call(name("name"), Method::ToI { radix: None }),
+            call(
+                name("name"),
+                Method::ToI {
+                    radix: Some(Box::new(Expr::Int(16))),
+                },
+            ),
+            Expr::Unary {
+                op: UnaryOp::Neg,
+                expr: Box::new(call(
+                    Box::new(call(name("name"), Method::ToI { radix: None })),
+                    Method::Reverse,
+                )),
+            },
+            call(
+                name("values"),
+                Method::At {
+                    index: Box::new(Expr::Binary {
+                        op: BinaryOp::Add,
+                        lhs: name("i"),
+                        rhs: Box::new(Expr::Int(1)),
+                    }),
+                },
+            ),
+            call(
+                Box::new(call(
+                    name("a"),
+                    Method::At {
+                        index: Box::new(Expr::Int(0)),
+                    },
+                )),
+                Method::Length,
+            ),
+            Expr::Binary {
+                op: BinaryOp::Eq,
+                lhs: Box::new(Expr::Field {
+                    receiver: name("_"),
+                    field: "kind".to_owned(),
+                }),
+                rhs: Box::new(Expr::Int(0)),
+            },
+            call(
+                Box::new(Expr::Field {
+                    receiver: name("header"),
+                    field: "entries".to_owned(),
+                }),
+                Method::Length,
+            ),
+        ];
+
+        for (input, expected) in input.iter().zip(expected) {
+            assert_eq!(input.parse::<Expr>().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn invalid_methods() {
+        for input in [
+            "a.sum(1)",
+            "a.first(1)",
+            "a.length(1)",
+            "a.substring(1)",
+            "a.to_i(2, 3)",
+            "a.",
+            "a.to_s(",
+            "a[",
+            "a[]",
+            "a[1",
+        ] {
+            assert!(
+                input.parse::<Expr>().is_err(),
+                "{} should be invalid",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn method_tokens() {
+        let imports = quote! {
+            #[allow(unused_imports)]
+            use ::kaitai::__private::{KsBytes as _, KsEnum as _, KsSeq as _, KsStr as _};
+        };
+        let input = [
+            "name.length",
+            "name.substring(1, n)",
+            "bytes.to_s('ASCII')",
+            "name.to_i",
+            "items.max",
+            "items[i]",
+            "_.type == 0",
+        ];
+        let expected = [
+            quote! { { #imports name.ks_length() } },
+            quote! { { #imports name.ks_substring(1i64, n as i64) } },
+            quote! { { #imports bytes.ks_to_s(&"ASCII", _ctx.decoding())? } },
+            quote! { { #imports name.ks_to_i(10u32)? } },
+            quote! { { #imports items.ks_max()? } },
+            quote! { { #imports items.ks_at(i as i64)? } },
+            quote! { _value.r#type == 0 },
+        ];
+
+        for (input, expected) in input.iter().zip(expected) {
+            let expr = input.parse::<Expr>().unwrap();
+            assert_eq!(expr.to_token_stream().to_string(), expected.to_string());
+        }
+    }
+
+    #[test]
+    fn io_properties() {
+        assert_eq!(
+            "_io.size - _io.pos".parse::<Expr>().unwrap(),
+            Expr::Binary {
+                op: BinaryOp::Sub,
+                lhs: Box::new(Expr::Io(IoProperty::Size)),
+                rhs: Box::new(Expr::Io(IoProperty::Pos)),
+            }
+        );
+        let eof = "not _io.eof".parse::<Expr>().unwrap();
+        assert_eq!(
+            eof.to_token_stream().to_string(),
+            quote! { !::kaitai::KaitaiStream::is_eof(buf)? }.to_string()
+        );
+        assert!(matches!(eof.ty(&HashMap::new()), Ok(ExprType::Bool)));
+        assert!("_io.name".parse::<Expr>().is_err());
+    }
+
+    #[test]
+    fn expr_types() {
+        let names = HashMap::from([
+            ("len".to_owned(), ExprType::Int("u16")),
+            ("name".to_owned(), ExprType::Str),
+            (
+                "values".to_owned(),
+                ExprType::Array(Box::new(ExprType::Int("u32")), None),
+            ),
+        ]);
+        let ty = |expr: &str| {
+            expr.parse::<Expr>()
+                .unwrap()
+                .ty(&names)
+                .map(|ty| ty.ty().to_string())
+        };
+
+        assert_eq!(ty("1 + len * 2").unwrap(), "u16");
+        assert_eq!(ty("1 + 2").unwrap(), "i32");
+        assert_eq!(ty("0x1_0000_0000").unwrap(), "i64");
+        assert_eq!(ty("len > 2 and not false").unwrap(), "bool");
+        assert_eq!(ty("values.max").unwrap(), "u32");
+        assert_eq!(ty("values[len - 1]").unwrap(), "u32");
+        assert_eq!(ty("name.length").unwrap(), "usize");
+        assert_eq!(ty("name.to_i").unwrap(), "i64");
+        assert!(ty("unknown + 1").is_err());
+        assert!(ty("len.first").is_err());
+        assert!(ty("name[0]").is_err());
+        assert_eq!(ty("header.len == 2").unwrap(), "bool");
+        assert!(ty("header.len").is_err());
+    }
+
+    #[test]
+    fn string_operators() {
+        let names = HashMap::from([
+            ("name".to_owned(), ExprType::Str),
+            ("ext".to_owned(), ExprType::Str),
+            ("len".to_owned(), ExprType::Int("u16")),
+        ]);
+        let unified = |expr: &str| {
+            let expr = expr.parse::<Expr>().unwrap().unified(&names).unwrap();
+            (
+                expr.ty(&names).unwrap().ty().to_string(),
+                expr.to_token_stream().to_string(),
+            )
+        };
+
+        assert_eq!(
+            unified("name + \".\" + ext"),
+            (
+                quote! { ::std::string::String }.to_string(),
+                quote! { ::std::format!("{}{}", ::std::format!("{}{}", name, "."), ext) }
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            unified("len + 1").1,
+            quote! { ::kaitai::__private::KsNum::ks_add(len, 1)? }.to_string()
+        );
+        assert_eq!(
+            unified("name < \"m\"").1,
+            quote! { ::std::convert::AsRef::<str>::as_ref(&name) < "m" }.to_string()
+        );
+        assert_eq!(
+            unified("\"a.\" + ext == name").1,
+            quote! { ::std::format!("{}{}", "a.", ext) == name }.to_string()
+        );
+    }
+
+    #[test]
+    fn ternary() {
+        assert_eq!(
+            "a ? b : c ? 1 : 2 + 3".parse::<Expr>().unwrap(),
+            Expr::Ternary {
+                condition: name("a"),
+                then: name("b"),
+                otherwise: Box::new(Expr::Ternary {
+                    condition: name("c"),
+                    then: Box::new(Expr::Int(1)),
+                    otherwise: Box::new(Expr::Binary {
+                        op: BinaryOp::Add,
+                        lhs: Box::new(Expr::Int(2)),
+                        rhs: Box::new(Expr::Int(3)),
+                    }),
+                }),
+            }
+        );
+        assert_eq!(
+            "a or b ? 1 : 2"
+                .parse::<Expr>()
+                .unwrap()
+                .to_token_stream()
+                .to_string(),
+            quote! { if a || b { 1 } else { 2 } }.to_string()
+        );
+        assert!("a ? b".parse::<Expr>().is_err());
+    }
+
+    #[test]
+    fn ternary_types() {
+        let names = HashMap::from([
+            ("a".to_owned(), ExprType::Bool),
+            ("small".to_owned(), ExprType::Int("u8")),
+            ("signed".to_owned(), ExprType::Int("i8")),
+            ("wide".to_owned(), ExprType::Int("u64")),
+            ("ratio".to_owned(), ExprType::Float("f32")),
+            ("name".to_owned(), ExprType::Str),
+        ]);
+        let unified = |expr: &str| {
+            let expr = expr.parse::<Expr>().unwrap().unified(&names)?;
+            Ok::<_, String>((
+                expr.ty(&names)?.ty().to_string(),
+                expr.to_token_stream().to_string(),
+            ))
+        };
+
+        assert_eq!(
+            unified("a ? small : 300").unwrap(),
+            (
+                "u8".to_owned(),
+                quote! { if a { small } else { 300 } }.to_string()
+            )
+        );
+        assert_eq!(
+            unified("a ? small : signed").unwrap(),
+            (
+                "i16".to_owned(),
+                quote! { if a { small as i16 } else { signed as i16 } }.to_string()
+            )
+        );
+        assert_eq!(
+            unified("a ? ratio : 1").unwrap(),
+            (
+                "f32".to_owned(),
+                quote! { if a { ratio } else { 1 as f32 } }.to_string()
+            )
+        );
+        assert_eq!(unified("a ? a : not a").unwrap().0, "bool");
+        assert_eq!(unified("a ? small * 2 : wide").unwrap().0, "u64");
+
+        assert!(unified("a ? wide : signed").is_err());
+        assert!(unified("a ? name : small").is_err());
+        assert!(unified("small ? 1 : 2").is_err());
+    }
+}
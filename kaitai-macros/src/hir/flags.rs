@@ -0,0 +1,178 @@
+use crate::{
+    de,
+    hir::{doc::Doc, meta::Endianness},
+    util::ident,
+};
+
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::quote;
+
+/// The fields of a type made only of single-bit fields (KS: consecutive `b1` fields and nothing
+/// else), e.g. a byte of named flags.
+///
+/// Rather than a `bool` field for each flag, the generated struct stores the bits that were read
+/// and has an accessor for each flag, as well as a `bits` accessor for the raw bits.
+#[derive(Clone, Debug)]
+pub struct Flags {
+    flags: Vec<(Ident, Doc)>,
+    /// The order the flags are read in (KS: `meta/bit-endian`): from the most significant bit of
+    /// each byte if big endian, and from the least significant bit otherwise.
+    bit_endianness: Endianness,
+}
+
+impl Flags {
+    /// Returns the flags of `seq`, or [`None`] if it isn't only made of `b1` fields without any
+    /// other keys. The number of flags is limited to 64, so that the bits fit in a [`u64`].
+    pub fn from_seq(seq: &[de::attr::Attr], bit_endianness: Endianness) -> Option<Self> {
+        if seq.is_empty() || seq.len() > 64 {
+            return None;
+        }
+
+        seq.iter()
+            .map(|attr| {
+                let is_flag = matches!(&attr.ty, Some(de::attr::AttrType::TypeRef(ty)) if ty == "b1")
+                    && attr.contents.is_none()
+                    && attr.repeat.is_none()
+                    && attr.if_expr.is_none()
+                    && attr.size.is_none()
+                    && !attr.size_eos
+                    && attr.process.is_none()
+                    && attr.en.is_none()
+                    && attr.pos.is_none()
+                    && attr.io.is_none()
+                    && attr.value.is_none()
+                    && attr.valid.is_none();
+                let id = attr.id.as_deref()?;
+                // A flag named `bits` would clash with the accessor of the raw bits.
+                if !is_flag || id == "bits" {
+                    return None;
+                }
+                Some((ident(id), (None, attr.doc.clone()).into()))
+            })
+            .collect::<Option<_>>()
+            .map(|flags| Self {
+                flags,
+                bit_endianness,
+            })
+    }
+
+    /// Returns the number of bits that are read, i.e. the number of flags rounded up to whole
+    /// bytes.
+    fn bit_count(&self) -> u32 {
+        (self.flags.len() as u32).div_ceil(8) * 8
+    }
+
+    /// Returns the smallest integer type holding the bits that are read.
+    fn int_ty(&self) -> TokenStream {
+        match self.bit_count() {
+            8 => quote! { u8 },
+            16 => quote! { u16 },
+            24 | 32 => quote! { u32 },
+            _ => quote! { u64 },
+        }
+    }
+
+    /// Returns the definition of the type `id` holding the flags, and its implementation of
+    /// `KaitaiStruct`.
+    pub fn definition(&self, id: &Ident, visibility: &TokenStream, doc: &Doc) -> TokenStream {
+        let int_ty = self.int_ty();
+        let bit_count = self.bit_count();
+        let (read, first_flag) = match self.bit_endianness {
+            Endianness::Be => (quote! { read_bits_int_be }, "most"),
+            Endianness::Le => (quote! { read_bits_int_le }, "least"),
+        };
+        let accessors = self.flags.iter().enumerate().map(|(i, (flag, doc))| {
+            let bit = match self.bit_endianness {
+                Endianness::Be => bit_count - 1 - i as u32,
+                Endianness::Le => i as u32,
+            };
+            let mask = Literal::u64_unsuffixed(1 << bit);
+            quote! {
+                #doc
+                pub fn #flag(&self) -> bool {
+                    self.bits & #mask != 0
+                }
+            }
+        });
+        let bits_doc = format!(
+            "Returns the bits the flags were read from, the first flag being the {} significant bit.",
+            first_flag
+        );
+        let bits = match bit_count {
+            64 => quote! { _bits },
+            _ => quote! { _bits as #int_ty },
+        };
+
+        quote! {
+            #doc
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            #visibility struct #id {
+                bits: #int_ty,
+            }
+
+            impl #id {
+                #(#accessors)*
+
+                #[doc = #bits_doc]
+                pub fn bits(&self) -> #int_ty {
+                    self.bits
+                }
+            }
+
+            #[automatically_derived]
+            impl ::kaitai::KaitaiStruct for #id {
+                fn new<S: ::kaitai::__private::KaitaiStream + ?Sized>(buf: &mut S, _ctx: &::kaitai::ParseContext) -> ::kaitai::error::Result<Self> {
+                    let _bits = ::kaitai::BitReader::new().#read(buf, #bit_count)?;
+                    Ok(Self { bits: #bits })
+                }
+                fn read<S: ::kaitai::__private::KaitaiStream + ?Sized>(&mut self, buf: &mut S, _ctx: &::kaitai::ParseContext) -> ::kaitai::error::Result<()> {
+                    *self = Self::new(buf, _ctx)?;
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seq(yaml: &str) -> Vec<de::attr::Attr> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn flags_detection() {
+        let flags = Flags::from_seq(
+            &seq("- {id: a, type: b1}\n- {id: b, type: b1}\n"),
+            Endianness::Be,
+        )
+        .unwrap();
+        assert_eq!(flags.bit_count(), 8);
+        assert_eq!(flags.int_ty().to_string(), "u8");
+
+        for yaml in [
+            "[]",
+            "- {id: a, type: b1}\n- {id: b, type: u1}\n",
+            "- {id: a, type: b1, repeat: eos}\n",
+            "- {id: bits, type: b1}\n",
+        ] {
+            assert!(
+                Flags::from_seq(&seq(yaml), Endianness::Be).is_none(),
+                "{}",
+                yaml
+            );
+        }
+    }
+
+    #[test]
+    fn bit_count() {
+        let yaml = (0..9)
+            .map(|i| format!("- {{id: f{}, type: b1}}\n", i))
+            .collect::<String>();
+        let flags = Flags::from_seq(&seq(&yaml), Endianness::Be).unwrap();
+        assert_eq!(flags.bit_count(), 16);
+        assert_eq!(flags.int_ty().to_string(), "u16");
+    }
+}
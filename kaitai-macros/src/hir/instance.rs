@@ -0,0 +1,511 @@
+use crate::{
+    de,
+    error::Error,
+    hir::{
+        attr::{check_enum_members, Attribute, Context},
+        doc::Doc,
+        expr::{Expr, ExprType},
+        meta::Endian,
+    },
+    util::ident,
+};
+
+use std::collections::HashMap;
+
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+
+/// The instances of a type (KS: `instances`), in an order in which each instance comes after the
+/// instances it refers to.
+///
+/// Unlike in the other Kaitai Struct runtimes, instances are computed when the type is parsed,
+/// right after its `seq`, and stored in private fields returned by accessor methods. The value
+/// instances the conditions of the `seq` refer to are computed right before the first attribute
+/// referring to them instead, see [`Instances::schedule`].
+#[derive(Clone, Debug, Default)]
+pub struct Instances(Vec<Instance>);
+
+#[derive(Clone, Debug)]
+struct Instance {
+    id: Ident,
+    /// The id of the instance in the KS file, which can differ from `id` if it is a keyword.
+    ks_id: String,
+    doc: Doc,
+    /// The condition the instance exists under (KS: `if`), which makes its value an [`Option`].
+    condition: Option<Expr>,
+    kind: InstanceKind,
+    /// The type of the value, without the [`Option`] of a condition.
+    ty: ExprType,
+    /// The index of the first `seq` attribute whose condition refers to the instance, if any.
+    seq_index: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum InstanceKind {
+    /// A value computed from an expression (KS: `value`).
+    Value(Expr),
+    /// An attribute read at a position of the stream (KS: `pos`), which is restored afterwards.
+    Pos {
+        pos: Expr,
+        attr: Box<Attribute>,
+        /// Whether the attribute is read from the stream of the parent type rather than the one
+        /// of the type (KS: `io: _parent._io`).
+        parent_io: bool,
+    },
+}
+
+impl
+    TryFrom<(
+        &Context<'_>,
+        HashMap<String, ExprType>,
+        HashMap<String, de::attr::Attr>,
+    )> for Instances
+{
+    type Error = Error;
+
+    /// Converts the instances of a type, given the types of its `seq` attributes.
+    fn try_from(
+        (context, mut names, instances): (
+            &Context<'_>,
+            HashMap<String, ExprType>,
+            HashMap<String, de::attr::Attr>,
+        ),
+    ) -> Result<Self, Self::Error> {
+        let parse = |ks_id: &str, expr: &str| {
+            let expr = expr.parse::<Expr>().map_err(|e| match e {
+                Error::InvalidExpression { expr, reason } => Error::InvalidExpression {
+                    expr,
+                    reason: format!("{} (in instance {})", reason, ks_id),
+                },
+                e => e,
+            })?;
+            check_enum_members(&expr, context.enums)?;
+            Ok(expr)
+        };
+
+        // The exprs of each instance, sorted by id so that the order doesn't depend on the map.
+        let mut pending = instances
+            .into_iter()
+            .map(|(ks_id, attr)| {
+                let condition = attr
+                    .if_expr
+                    .as_deref()
+                    .map(|expr| parse(&ks_id, expr))
+                    .transpose()?;
+                let kind = match (&attr.value, &attr.pos) {
+                    (Some(value), _) => InstanceKind::Value(parse(&ks_id, value)?),
+                    (None, Some(pos)) => {
+                        let pos = match pos {
+                            de::data::IntegerValue::Literal(pos) => Expr::Int(*pos),
+                            de::data::IntegerValue::Variable(pos) => parse(&ks_id, pos)?,
+                        };
+                        let parent_io = match attr.io.as_deref().map(str::trim) {
+                            None | Some("_io") => false,
+                            Some("_parent._io") => true,
+                            Some(io) => return Err(Error::UnsupportedIo(io.to_owned())),
+                        };
+                        let attr = de::attr::Attr {
+                            id: Some(ks_id.clone()),
+                            if_expr: None,
+                            pos: None,
+                            io: None,
+                            ..attr.clone()
+                        };
+                        InstanceKind::Pos {
+                            pos,
+                            attr: Box::new((context, attr).try_into()?),
+                            parent_io,
+                        }
+                    }
+                    (None, None) => return Err(Error::RequiredAttrNotFound("value".to_owned())),
+                };
+                let doc = (context.meta_doc.clone(), attr.doc).into();
+                Ok((ks_id, doc, condition, kind))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        pending.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let ids = pending
+            .iter()
+            .map(|(ks_id, ..)| ks_id.clone())
+            .collect::<Vec<_>>();
+        let mut sorted = Vec::new();
+        while !pending.is_empty() {
+            // The instances whose referenced instances all have a type already.
+            let ready = pending.iter().position(|(_, _, condition, kind)| {
+                let mut refs = condition.iter().flat_map(Expr::names).collect::<Vec<_>>();
+                match kind {
+                    InstanceKind::Value(value) => refs.extend(value.names()),
+                    InstanceKind::Pos { pos, .. } => refs.extend(pos.names()),
+                }
+                refs.iter()
+                    .all(|name| names.contains_key(*name) || !ids.iter().any(|id| id == name))
+            });
+            let (ks_id, doc, condition, kind) = match ready {
+                Some(i) => pending.remove(i),
+                None => {
+                    let cycle = pending
+                        .iter()
+                        .map(|(ks_id, ..)| ks_id.as_str())
+                        .collect::<Vec<_>>();
+                    return Err(Error::InvalidExpression {
+                        expr: cycle.join(", "),
+                        reason: "instances refer to each other".to_owned(),
+                    });
+                }
+            };
+
+            let invalid = |reason: String| Error::InvalidExpression {
+                expr: ks_id.clone(),
+                reason,
+            };
+            let condition = condition
+                .map(|condition| {
+                    condition.ty(&names)?;
+                    condition.unified(&names)
+                })
+                .transpose()
+                .map_err(invalid)?;
+            let kind = match kind {
+                InstanceKind::Value(value) => {
+                    InstanceKind::Value(value.unified(&names).map_err(invalid)?)
+                }
+                kind => kind,
+            };
+            let ty = match &kind {
+                InstanceKind::Value(value) => value.ty(&names).map_err(invalid)?,
+                InstanceKind::Pos { pos, attr, .. } => {
+                    pos.ty(&names).map_err(invalid)?;
+                    attr.expr_type()
+                        .ok_or_else(|| invalid("instances must be stored".to_owned()))?
+                }
+            };
+            names.insert(ks_id.clone(), ty.clone());
+            sorted.push(Instance {
+                id: ident(&ks_id),
+                ks_id,
+                doc,
+                condition,
+                kind,
+                ty,
+                seq_index: None,
+            });
+        }
+
+        Ok(Self(sorted))
+    }
+}
+
+impl Instance {
+    /// Returns the type of the field storing the instance.
+    fn field_ty(&self) -> TokenStream {
+        let ty = self.ty.ty();
+        match self.condition {
+            Some(_) => quote! { ::std::option::Option<#ty> },
+            None => ty,
+        }
+    }
+
+    /// Returns the assignment of the variable holding the instance.
+    fn variable_assignment(&self, endianness: Endian) -> TokenStream {
+        let id = &self.id;
+        let value = match &self.kind {
+            InstanceKind::Value(Expr::Name(name)) => {
+                // The attribute is still stored in its own field.
+                let name = ident(name);
+                quote! { ::std::clone::Clone::clone(&#name) }
+            }
+            InstanceKind::Value(value) => quote! { #value },
+            InstanceKind::Pos {
+                pos,
+                attr,
+                parent_io,
+            } => {
+                let pos = pos.cast(quote! { u64 });
+                let assignment = attr.variable_assignment(endianness);
+                let attr_id = attr.id();
+                let parent_buf = parent_io.then(|| {
+                    quote! { let buf = ::kaitai::__private::ParentStreams::parent(_streams); }
+                });
+                // The bits are read from the first byte at the position.
+                let bits = attr.is_bits().then(|| {
+                    quote! { let mut _bits = ::kaitai::BitReader::new(); }
+                });
+                quote! {
+                    {
+                        #parent_buf
+                        #bits
+                        let _pos = buf.pos()?;
+                        ::std::io::Seek::seek(buf, ::std::io::SeekFrom::Start(#pos))?;
+                        #assignment
+                        ::std::io::Seek::seek(buf, ::std::io::SeekFrom::Start(_pos))?;
+                        #attr_id
+                    }
+                }
+            }
+        };
+        let value = match &self.condition {
+            Some(condition) => quote! {
+                if #condition {
+                    ::std::option::Option::Some(#value)
+                } else {
+                    ::std::option::Option::None
+                }
+            },
+            None => value,
+        };
+        let ty = self.field_ty();
+        if !self.parent_io() {
+            return quote! { let #id: #ty = #value; };
+        }
+        // The stream of the type is borrowed again once the parent stream isn't used anymore.
+        quote! {
+            let #id: #ty = #value;
+            #[allow(unused_variables)]
+            let buf = ::kaitai::__private::ParentStreams::io(_streams);
+        }
+    }
+
+    /// Returns whether the instance is read from the stream of the parent type.
+    fn parent_io(&self) -> bool {
+        matches!(
+            self.kind,
+            InstanceKind::Pos {
+                parent_io: true,
+                ..
+            }
+        )
+    }
+
+    /// Returns the method returning the instance, by value if it is [`Copy`].
+    fn accessor(&self) -> TokenStream {
+        let id = &self.id;
+        let doc = &self.doc;
+        let ty = self.ty.ty();
+        let (ty, value) = match (&self.condition, self.ty.is_copy()) {
+            (None, true) => (ty, quote! { self.#id }),
+            (None, false) => (quote! { &#ty }, quote! { &self.#id }),
+            (Some(_), true) => (quote! { ::std::option::Option<#ty> }, quote! { self.#id }),
+            (Some(_), false) => (
+                quote! { ::std::option::Option<&#ty> },
+                quote! { self.#id.as_ref() },
+            ),
+        };
+        quote! {
+            #doc
+            pub fn #id(&self) -> #ty {
+                #value
+            }
+        }
+    }
+}
+
+impl Instances {
+    /// Returns the definitions of the private fields storing the instances.
+    pub fn field_definitions(&self) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().map(|i| {
+            let id = &i.id;
+            let ty = i.field_ty();
+            quote! { #id: #ty }
+        })
+    }
+
+    /// Computes the value instances the conditions of the `seq` refer to, and those they refer to
+    /// in turn, before the first attribute referring to them, given the KS id of each attribute
+    /// and the names its condition refers to.
+    ///
+    /// Returns an error if one of them is a positional instance, or refers to an attribute that
+    /// isn't read yet.
+    pub fn schedule(&mut self, seq: &[(&str, Vec<&str>)]) -> Result<(), Error> {
+        for (index, (attr_id, names)) in seq.iter().enumerate() {
+            let mut pending = names
+                .iter()
+                .map(|&name| name.to_owned())
+                .collect::<Vec<_>>();
+            while let Some(name) = pending.pop() {
+                let instance = match self.0.iter_mut().find(|i| i.ks_id == name) {
+                    Some(instance) if instance.seq_index.is_none() => instance,
+                    _ => continue,
+                };
+                let invalid = |reason: String| Error::InvalidExpression {
+                    expr: instance.ks_id.clone(),
+                    reason: format!("{} (in the condition of {})", reason, attr_id),
+                };
+                let value = match &instance.kind {
+                    InstanceKind::Value(value) => value,
+                    InstanceKind::Pos { .. } => {
+                        return Err(invalid(
+                            "positional instances can't be computed during the seq".to_owned(),
+                        ))
+                    }
+                };
+                let refs = instance
+                    .condition
+                    .iter()
+                    .flat_map(Expr::names)
+                    .chain(value.names())
+                    .collect::<Vec<_>>();
+                if let Some(field) = refs
+                    .iter()
+                    .find(|name| seq[index..].iter().any(|(id, _)| id == *name))
+                {
+                    return Err(invalid(format!("{} isn't read yet", field)));
+                }
+                pending.extend(refs.into_iter().map(str::to_owned));
+                instance.seq_index = Some(index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the assignments of the instances computed before the `seq` attribute at `index`,
+    /// see [`schedule`](Instances::schedule).
+    pub fn assignments_before(&self, index: usize, endianness: Endian) -> TokenStream {
+        self.0
+            .iter()
+            .filter(|i| i.seq_index == Some(index))
+            .map(|i| i.variable_assignment(endianness))
+            .collect()
+    }
+
+    /// Returns the assignments of the other instances, which come after the assignments of the
+    /// `seq`.
+    pub fn variable_assignments(
+        &self,
+        endianness: Endian,
+    ) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0
+            .iter()
+            .filter(|i| i.seq_index.is_none())
+            .map(move |i| i.variable_assignment(endianness))
+    }
+
+    pub fn field_assignments(&self) -> impl Iterator<Item = &Ident> + '_ {
+        self.0.iter().map(|i| &i.id)
+    }
+
+    /// Returns the accessor methods of the instances.
+    pub fn methods(&self) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().map(Instance::accessor)
+    }
+
+    /// Returns the KS id of the first instance read at a position of the stream, if any.
+    pub fn positional(&self) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|i| matches!(i.kind, InstanceKind::Pos { .. }))
+            .map(|i| i.ks_id.as_str())
+    }
+
+    /// Returns whether some instances are read from the stream of the parent type, in which case
+    /// the type is parsed with both streams.
+    pub fn parent_io(&self) -> bool {
+        self.0.iter().any(Instance::parent_io)
+    }
+
+    /// Returns the calls adding the instances to a `DebugStruct`.
+    pub fn debug_fields(&self) -> impl Iterator<Item = TokenStream> + '_ {
+        self.0.iter().map(|i| {
+            let id = &i.id;
+            let name = &i.ks_id;
+            quote! { .field(#name, &self.#id) }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hir::meta::Endianness;
+
+    use std::collections::HashSet;
+
+    fn instances(yaml: &str) -> Result<Instances, Error> {
+        let enums = HashMap::new();
+        let context = Context {
+            meta_doc: None,
+            ty: &ident("Header"),
+            enums: &enums,
+            encoding: None,
+            bit_endianness: Endianness::Be,
+            params: &HashMap::new(),
+            root_values: &HashMap::new(),
+            in_root: true,
+            endianness: Endian::Fixed(Endianness::Le),
+            runtime_endian: &HashSet::new(),
+            hash: false,
+            borrowed: false,
+        };
+        let names = HashMap::from([("version".to_owned(), ExprType::Int("u8"))]);
+        (&context, names, serde_yaml::from_str(yaml).unwrap()).try_into()
+    }
+
+    #[test]
+    fn dependency_order() {
+        let instances = instances(
+            "b: {value: a + 1}\na: {value: version * 2}\nc: {value: b > 3, if: a != 0}\n",
+        )
+        .unwrap();
+        let order = instances
+            .0
+            .iter()
+            .map(|i| i.ks_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["a", "b", "c"]);
+        assert_eq!(instances.0[1].field_ty().to_string(), "u8");
+        assert_eq!(
+            instances.0[2].field_ty().to_string(),
+            quote! { ::std::option::Option<bool> }.to_string()
+        );
+    }
+
+    #[test]
+    fn seq_conditions() {
+        let mut early =
+            instances("a: {value: version * 2}\nb: {value: a > 3}\nc: {value: version + 1}\n")
+                .unwrap();
+        early
+            .schedule(&[("version", Vec::new()), ("len", vec!["b"])])
+            .unwrap();
+        let seq_indices = early.0.iter().map(|i| i.seq_index).collect::<Vec<_>>();
+        assert_eq!(seq_indices, [Some(1), Some(1), None]);
+        assert!(early
+            .assignments_before(0, Endian::Fixed(Endianness::Le))
+            .is_empty());
+        assert_eq!(
+            early
+                .variable_assignments(Endian::Fixed(Endianness::Le))
+                .count(),
+            1
+        );
+
+        let mut late = instances("a: {value: version * 2}\n").unwrap();
+        assert!(matches!(
+            late.schedule(&[("len", vec!["a"]), ("version", Vec::new())]),
+            Err(Error::InvalidExpression { expr, .. }) if expr == "a"
+        ));
+        let mut positional = instances("a: {pos: 0, type: u1}\n").unwrap();
+        assert!(positional.schedule(&[("len", vec!["a"])]).is_err());
+    }
+
+    #[test]
+    fn invalid_instances() {
+        assert!(matches!(
+            instances("a: {value: b}\nb: {value: a}\n").unwrap_err(),
+            Error::InvalidExpression { .. }
+        ));
+        assert!(matches!(
+            instances("a: {value: unknown}\n").unwrap_err(),
+            Error::InvalidExpression { .. }
+        ));
+        assert_eq!(
+            instances("a: {type: u1}\n").unwrap_err(),
+            Error::RequiredAttrNotFound("value".to_owned())
+        );
+        assert_eq!(
+            instances("a: {pos: 0, type: u1, io: _root._io}\n").unwrap_err(),
+            Error::UnsupportedIo("_root._io".to_owned())
+        );
+    }
+}
@@ -1,5 +1,25 @@
 pub use crate::de::meta::Endianness;
 
+use crate::{
+    de::data::Expression,
+    error::Error,
+    hir::{
+        attr::{check_enum_members, Pattern},
+        expr::Expr,
+    },
+};
+
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+
+/// The version of Kaitai Struct whose features the macro implements. Formats requiring a newer
+/// version (KS: `meta/ks-version`) are rejected.
+///
+/// This has to be kept in sync with `kaitai::KS_VERSION`.
+pub const KS_VERSION: &str = "0.9";
+
 impl From<Endianness> for &'static str {
     fn from(e: Endianness) -> Self {
         match e {
@@ -8,3 +28,156 @@ impl From<Endianness> for &'static str {
         }
     }
 }
+
+/// The endianness of the multi-byte numbers of a type.
+#[derive(Copy, Clone, Debug)]
+pub enum Endian {
+    Fixed(Endianness),
+    /// Calculated when the type is parsed (KS: `meta/endian/switch-on`), and held by the `_endian`
+    /// variable of the generated code.
+    Runtime,
+    /// Set neither by the type nor its parents, which is only allowed for types without
+    /// multi-byte numbers.
+    Unspecified,
+}
+
+impl Endian {
+    /// Returns the `kaitai::Endian` value of the endianness.
+    pub fn value(self) -> TokenStream {
+        match self {
+            Endian::Fixed(Endianness::Le) => quote! { ::kaitai::Endian::Le },
+            Endian::Fixed(Endianness::Be) => quote! { ::kaitai::Endian::Be },
+            Endian::Runtime => quote! { _endian },
+            Endian::Unspecified => unreachable!("read without an endianness"),
+        }
+    }
+}
+
+/// The calculated endianness of a type (KS: `meta/endian` with `switch-on` and `cases`).
+#[derive(Clone, Debug)]
+pub struct EndianSwitch {
+    /// The name of the type, for the error of values matching no case.
+    ty: String,
+    on: Expr,
+    cases: Vec<(Pattern, Endianness)>,
+}
+
+impl
+    TryFrom<(
+        &str,
+        &HashMap<String, Vec<String>>,
+        Expression,
+        Vec<(Expression, Endianness)>,
+    )> for EndianSwitch
+{
+    type Error = Error;
+
+    fn try_from(
+        (ty, enums, on, cases): (
+            &str,
+            &HashMap<String, Vec<String>>,
+            Expression,
+            Vec<(Expression, Endianness)>,
+        ),
+    ) -> Result<Self, Self::Error> {
+        let on = on.0.parse()?;
+        check_enum_members(&on, enums)?;
+        let cases = cases
+            .into_iter()
+            .map(|(key, endianness)| Ok(((enums, key).try_into()?, endianness)))
+            .collect::<Result<_, Error>>()?;
+        Ok(Self {
+            ty: ty.to_owned(),
+            on,
+            cases,
+        })
+    }
+}
+
+impl ToTokens for EndianSwitch {
+    /// Declares the `_endian` variable, or returns an error if no case matches and there is no
+    /// default case.
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let on = self.on.operand();
+        let ty = &self.ty;
+        let arms = self.cases.iter().map(|(pattern, endianness)| {
+            let value = Endian::Fixed(*endianness).value();
+            quote! { #pattern => #value, }
+        });
+        let default = match self
+            .cases
+            .iter()
+            .any(|(p, _)| matches!(p, Pattern::Default))
+        {
+            true => TokenStream::new(),
+            false => quote! {
+                _on => return Err(::kaitai::error::Error::UndecidedEndianness {
+                    ty: #ty.to_owned(),
+                    value: format!("{:?}", _on),
+                }),
+            },
+        };
+        tokens.extend(quote! {
+            let _endian = match &#on {
+                #(#arms)*
+                #default
+            };
+        });
+    }
+}
+
+/// Returns whether `encoding` is the generic `UTF-16` or `UTF`, whose byte order and width are
+/// given by the byte order mark of the file when it is the default encoding (KS: `meta/encoding`).
+pub fn is_generic_utf(encoding: &str) -> bool {
+    encoding.eq_ignore_ascii_case("UTF-16") || encoding.eq_ignore_ascii_case("UTF")
+}
+
+/// Returns an error if `required` is a newer version of Kaitai Struct than [`KS_VERSION`].
+pub fn check_ks_version(required: &str) -> Result<(), Error> {
+    let parse = |version: &str| {
+        let mut parts = version
+            .split('.')
+            .map(|part| part.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| Error::InvalidKsVersion(version.to_owned()))?;
+        // 0.9 and 0.9.0 are the same version.
+        while parts.last() == Some(&0) {
+            parts.pop();
+        }
+        Ok(parts)
+    };
+
+    if parse(required)? > parse(KS_VERSION)? {
+        Err(Error::UnsupportedKsVersion {
+            required: required.to_owned(),
+            supported: KS_VERSION,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ks_version() {
+        assert!(check_ks_version("0.8").is_ok());
+        assert!(check_ks_version("0.9").is_ok());
+        assert!(check_ks_version("0.9.0").is_ok());
+
+        assert_eq!(
+            check_ks_version("0.10"),
+            Err(Error::UnsupportedKsVersion {
+                required: "0.10".to_owned(),
+                supported: KS_VERSION,
+            })
+        );
+        assert!(check_ks_version("1").is_err());
+        assert_eq!(
+            check_ks_version("latest"),
+            Err(Error::InvalidKsVersion("latest".to_owned()))
+        );
+    }
+}
@@ -1,6 +1,9 @@
 pub mod attr;
 pub mod doc;
 pub mod en;
+pub mod expr;
+pub mod flags;
+pub mod instance;
 pub mod meta;
 pub mod param;
 pub mod ty;
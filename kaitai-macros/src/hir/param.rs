@@ -1,2 +1,586 @@
+use crate::{
+    de,
+    error::Error,
+    hir::{
+        attr::BuiltInType,
+        doc::Doc,
+        expr::{Expr, ExprType},
+        flags::Flags,
+        meta::Endianness,
+    },
+    util::{ident, type_ident},
+};
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+
+/// A parameter of a type (KS: `params`), whose value is passed by the attribute of the parent type
+/// that reads it, e.g. `type: entry(len_entry)`.
+///
+/// Parameters are stored in the struct like attributes, and are in scope in the expressions of the
+/// type.
+///
+/// The fields of the root type a type refers to (e.g. `_root.version`) are passed to it as hidden
+/// parameters too, which aren't stored, and so is the calculated endianness of the type it is
+/// nested in if it inherits it.
 #[derive(Clone, Debug)]
-pub struct Parameter;
+pub struct Parameter {
+    id: Ident,
+    ks_id: String,
+    doc: Doc,
+    ty: ExprType,
+    source: Source,
+}
+
+/// Where the value of a parameter comes from.
+#[derive(Clone, Debug)]
+enum Source {
+    /// A parameter of the type (KS: `params`).
+    Declared,
+    /// The field of the root type with this id.
+    Root(String),
+    /// The calculated endianness of the type reading the type.
+    Endian,
+}
+
+/// A field of the root type that a type refers to as `_root.<field>`, with its type.
+pub type RootValue = (String, ExprType);
+
+/// Returns the type of the parameter `param`: integers, floats, booleans, strings, byte arrays
+/// and enums are supported.
+fn param_type(param: &de::param::Param) -> Result<ExprType, Error> {
+    let unsupported = || Error::UnsupportedParamType {
+        param: param.id.clone(),
+        ty: param.ty.clone(),
+    };
+    Ok(match (param.ty.as_str(), &param.en) {
+        ("bool", None) => ExprType::Bool,
+        ("str", None) => ExprType::Str,
+        ("bytes", None) => ExprType::Bytes,
+        (ty, en) => match (
+            BuiltInType::try_from(ty)
+                .map_err(|()| unsupported())?
+                .name(),
+            en,
+        ) {
+            (name, None) if name.starts_with('f') => ExprType::Float(name),
+            (name, None) => ExprType::Int(name),
+            // Enums are only read from integers.
+            (name, Some(en)) if !name.starts_with('f') => {
+                let en = type_ident(en);
+                ExprType::Other(quote! { #en })
+            }
+            _ => return Err(unsupported()),
+        },
+    })
+}
+
+/// Returns the types of the parameters of the types nested in `root` and in the files it imports,
+/// `imports`, by KS id, which the attributes reading them check their arguments against. The root
+/// type is parsed without arguments, so it can't have parameters.
+pub fn param_types(
+    root: &de::ty::Type,
+    imports: &[de::ty::Type],
+) -> Result<HashMap<String, Vec<ExprType>>, Error> {
+    fn collect(
+        ty: &de::ty::Type,
+        params: &mut HashMap<String, Vec<ExprType>>,
+    ) -> Result<(), Error> {
+        for (id, nested) in &ty.types {
+            let types = nested
+                .params
+                .iter()
+                .map(param_type)
+                .collect::<Result<_, _>>()?;
+            params.insert(id.clone(), types);
+            collect(nested, params)?;
+        }
+        Ok(())
+    }
+
+    if !root.params.is_empty() {
+        return Err(Error::RootParams);
+    }
+    let mut params = HashMap::new();
+    for ty in imports.iter().chain([root]) {
+        collect(ty, &mut params)?;
+    }
+    Ok(params)
+}
+
+/// Returns the KS ids of the types nested in `root` and in the files it imports, `imports`, that
+/// inherit the calculated endianness (KS: `meta/endian/switch-on`) of the type they are nested in,
+/// which the types reading them pass as the hidden parameter `_endian`. Types made of bit flags
+/// don't read multi-byte numbers, and don't get it.
+pub fn runtime_endian(root: &de::ty::Type, imports: &[de::ty::Type]) -> HashSet<String> {
+    fn is_calculated(ty: &de::ty::Type) -> Option<bool> {
+        match ty.meta.as_ref()?.endianness.as_ref()? {
+            de::meta::EndianSpec::Fixed(_) => Some(false),
+            de::meta::EndianSpec::Switch { .. } => Some(true),
+        }
+    }
+
+    fn collect(ty: &de::ty::Type, calculated: bool, types: &mut HashSet<String>) {
+        for (id, nested) in &ty.types {
+            let inherits = is_calculated(nested).is_none() && calculated;
+            let flags = nested.instances.is_empty()
+                && Flags::from_seq(&nested.seq, Endianness::Be).is_some();
+            if inherits && !flags {
+                types.insert(id.clone());
+            }
+            collect(nested, is_calculated(nested).unwrap_or(calculated), types);
+        }
+    }
+
+    let mut types = HashSet::new();
+    for ty in imports.iter().chain([root]) {
+        collect(ty, is_calculated(ty).unwrap_or(false), &mut types);
+    }
+    types
+}
+
+/// Returns the type of the field of the root type `attr` if it can be a root value, i.e. if it
+/// is read once, into a type that a parameter could have.
+fn root_value_type(attr: &de::attr::Attr) -> Result<ExprType, Error> {
+    let field = attr.id.clone().unwrap_or_default();
+    let unsupported = || Error::UnsupportedRootValue(field.clone());
+    if attr.if_expr.is_some() || attr.repeat.is_some() {
+        return Err(unsupported());
+    }
+    let en = match &attr.en {
+        Some(de::attr::AttrEnum::Named(en)) => Some(en.clone()),
+        Some(de::attr::AttrEnum::Inline(_)) => return Err(unsupported()),
+        None => None,
+    };
+    let ty = match &attr.ty {
+        Some(de::attr::AttrType::TypeRef(ty)) if ty == "str" || ty == "strz" => "str",
+        Some(de::attr::AttrType::TypeRef(ty)) => ty,
+        None if attr.contents.is_none() && (attr.size.is_some() || attr.size_eos) => "bytes",
+        _ => return Err(unsupported()),
+    };
+    param_type(&de::param::Param {
+        id: field.clone(),
+        ty: ty.to_owned(),
+        en,
+        ..Default::default()
+    })
+    .map_err(|_| unsupported())
+}
+
+/// Returns the fields of `root` that the types nested in it refer to as `_root.<field>`, by KS
+/// id of the type. A type also gets the root values of the types it reads, which it passes on to
+/// them.
+///
+/// The references are looked up in the sources of the expressions, before they are parsed, so
+/// that the types reading a type know its root values when they are converted.
+pub fn root_values(root: &de::ty::Type) -> Result<HashMap<String, Vec<RootValue>>, Error> {
+    fn collect<'a>(ty: &'a de::ty::Type, types: &mut Vec<(&'a str, &'a de::ty::Type)>) {
+        for (id, nested) in &ty.types {
+            types.push((id, nested));
+            collect(nested, types);
+        }
+    }
+
+    /// Returns the fields referred to as `_root.<field>` in `source`.
+    fn references(source: &str, fields: &mut BTreeSet<String>) {
+        for (start, _) in source.match_indices("_root.") {
+            let rest = &source[start + "_root.".len()..];
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            fields.insert(rest[..end].to_owned());
+        }
+    }
+
+    /// Returns the ids of the types that `type_ref` refers to, without their arguments.
+    fn type_ids(ty: &Option<de::attr::AttrType>) -> Vec<&str> {
+        fn strip(type_ref: &str) -> &str {
+            type_ref.split('(').next().unwrap_or_default().trim()
+        }
+        match ty {
+            Some(de::attr::AttrType::TypeRef(type_ref)) => vec![strip(type_ref)],
+            Some(de::attr::AttrType::Switch { cases, .. }) => {
+                cases.iter().map(|(_, type_ref)| strip(type_ref)).collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    let mut types = Vec::new();
+    collect(root, &mut types);
+
+    let mut direct = HashMap::new();
+    let mut reads = HashMap::new();
+    for (id, ty) in &types {
+        let mut fields = BTreeSet::new();
+        let mut read = Vec::new();
+        if let Some(de::meta::EndianSpec::Switch { switch_on, .. }) =
+            ty.meta.as_ref().and_then(|meta| meta.endianness.as_ref())
+        {
+            references(&switch_on.0, &mut fields);
+        }
+        for attr in ty.seq.iter().chain(ty.instances.values()) {
+            let sources = [
+                attr.if_expr.as_deref(),
+                attr.repeat_until.as_ref().map(|e| e.0.as_str()),
+                attr.value.as_deref(),
+            ];
+            for source in sources.into_iter().flatten() {
+                references(source, &mut fields);
+            }
+            if let Some(de::attr::AttrType::TypeRef(type_ref)) = &attr.ty {
+                references(type_ref, &mut fields);
+            }
+            if let Some(de::attr::AttrType::Switch { switch_on, .. }) = &attr.ty {
+                references(&switch_on.0, &mut fields);
+            }
+            if let Some(de::data::IntegerValue::Variable(pos)) = &attr.pos {
+                references(pos, &mut fields);
+            }
+            match &attr.valid {
+                Some(de::attr::Valid::Eq(eq)) => references(&eq.0, &mut fields),
+                Some(de::attr::Valid::Checks(checks)) => {
+                    let values = [&checks.eq, &checks.min, &checks.max, &checks.expr];
+                    for value in values.into_iter().flatten() {
+                        references(&value.0, &mut fields);
+                    }
+                    for value in checks.any_of.iter().flatten() {
+                        references(&value.0, &mut fields);
+                    }
+                }
+                None => {}
+            }
+            read.extend(type_ids(&attr.ty));
+        }
+        direct.insert(*id, fields);
+        reads.insert(*id, read);
+    }
+
+    // The root values of the types read by a type are added to its own until none are added.
+    let mut values = direct;
+    loop {
+        let mut changed = false;
+        for (id, _) in &types {
+            let inherited = reads[id]
+                .iter()
+                .filter_map(|read| values.get(read))
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>();
+            let fields = values.get_mut(id).unwrap();
+            for field in inherited {
+                changed |= fields.insert(field);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    values
+        .into_iter()
+        .filter(|(_, fields)| !fields.is_empty())
+        .map(|(id, fields)| {
+            let fields = fields
+                .into_iter()
+                .map(|field| {
+                    let attr = root
+                        .seq
+                        .iter()
+                        .find(|attr| attr.id.as_deref() == Some(&field))
+                        .ok_or_else(|| Error::UnsupportedRootValue(field.clone()))?;
+                    Ok((field, root_value_type(attr)?))
+                })
+                .collect::<Result<_, Error>>()?;
+            Ok((id.to_owned(), fields))
+        })
+        .collect()
+}
+
+/// Returns the tokens of the arguments passing the root values `values` to a type, from the
+/// fields of the root type if `in_root`, and from the root values of the type reading it
+/// otherwise.
+pub fn root_arguments(values: &[RootValue], in_root: bool) -> Vec<TokenStream> {
+    values
+        .iter()
+        .map(|(field, ty)| {
+            let arg = match in_root {
+                true => Expr::Name(field.clone()),
+                false => Expr::Root(field.clone()),
+            };
+            match ty {
+                ExprType::Str | ExprType::Bytes => quote! { &#arg },
+                _ => quote! { #arg },
+            }
+        })
+        .collect()
+}
+
+/// Returns the tokens of the arguments `args` passed to the parameters of the type `ty`, whose
+/// types are `params`.
+///
+/// Integer literals get the type of their parameter, other numbers are converted to it with
+/// [`From`], so that only lossless conversions compile, and strings and byte arrays are passed by
+/// reference.
+pub fn arguments(ty: &str, params: &[ExprType], args: &[Expr]) -> Result<Vec<TokenStream>, Error> {
+    if params.len() != args.len() {
+        return Err(Error::WrongArgumentCount {
+            ty: ty.to_owned(),
+            expected: params.len(),
+            actual: args.len(),
+        });
+    }
+    Ok(params
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| match (param, arg) {
+            (ExprType::Int(_) | ExprType::Float(_), Expr::Int(_)) => arg.cast(param.ty()),
+            (ExprType::Int(_) | ExprType::Float(_), _) => {
+                quote! { ::std::convert::From::from(#arg) }
+            }
+            (ExprType::Str | ExprType::Bytes, _) => {
+                let arg = arg.operand();
+                quote! { &#arg }
+            }
+            _ => quote! { #arg },
+        })
+        .collect())
+}
+
+impl TryFrom<de::param::Param> for Parameter {
+    type Error = Error;
+
+    fn try_from(param: de::param::Param) -> Result<Self, Self::Error> {
+        let ty = param_type(&param)?;
+        Ok(Self {
+            id: ident(&param.id),
+            ks_id: param.id,
+            doc: (None, param.doc).into(),
+            ty,
+            source: Source::Declared,
+        })
+    }
+}
+
+impl From<RootValue> for Parameter {
+    fn from((field, ty): RootValue) -> Self {
+        Self {
+            id: ident(&format!("_root_{}", field)),
+            ks_id: format!("_root.{}", field),
+            doc: Doc::new(),
+            ty,
+            source: Source::Root(field),
+        }
+    }
+}
+
+impl Parameter {
+    /// Returns the hidden parameter `_endian`, the calculated endianness a type inherits.
+    pub fn endian() -> Self {
+        Self {
+            id: ident("_endian"),
+            ks_id: "_endian".to_owned(),
+            doc: Doc::new(),
+            ty: ExprType::Other(quote! { ::kaitai::Endian }),
+            source: Source::Endian,
+        }
+    }
+
+    /// Returns whether the parameter is stored in a field, i.e. if it isn't hidden.
+    pub fn is_stored(&self) -> bool {
+        matches!(self.source, Source::Declared)
+    }
+
+    /// Returns the argument passing the root value of the parameter from `root`, a reference to
+    /// the root type, or [`None`] if it isn't a root value.
+    pub fn root_argument(&self) -> Option<TokenStream> {
+        let field = match &self.source {
+            Source::Root(field) => ident(field),
+            _ => return None,
+        };
+        Some(match self.ty {
+            ExprType::Str | ExprType::Bytes => quote! { &root.#field },
+            _ => quote! { root.#field },
+        })
+    }
+
+    /// Returns the KS id of the parameter and its type, for the expressions referring to it.
+    pub fn expr_type(&self) -> (String, ExprType) {
+        (self.ks_id.clone(), self.ty.clone())
+    }
+
+    pub fn id(&self) -> &Ident {
+        &self.id
+    }
+
+    pub fn field_definition(&self) -> TokenStream {
+        let doc = &self.doc;
+        let id = &self.id;
+        let ty = self.ty.ty();
+        quote! {
+            #doc
+            pub #id: #ty
+        }
+    }
+
+    /// Returns the argument of the constructor of the type. Strings and byte arrays are borrowed
+    /// and copied into the struct.
+    pub fn argument(&self) -> TokenStream {
+        let id = &self.id;
+        let ty = match self.ty {
+            ExprType::Str => quote! { &str },
+            ExprType::Bytes => quote! { &[u8] },
+            _ => self.ty.ty(),
+        };
+        quote! { #id: #ty }
+    }
+
+    /// Returns the statement converting the argument to the type of the field, if it is borrowed.
+    pub fn assignment(&self) -> Option<TokenStream> {
+        let id = &self.id;
+        match self.ty {
+            ExprType::Str => Some(quote! { let #id = #id.to_owned(); }),
+            ExprType::Bytes => Some(quote! { let #id = #id.to_vec(); }),
+            _ => None,
+        }
+    }
+
+    /// Returns the call adding the parameter to a `DebugStruct`, in hexadecimal for byte arrays.
+    pub fn debug_field(&self) -> TokenStream {
+        let id = &self.id;
+        let name = id.to_string().trim_start_matches("r#").to_owned();
+        match self.ty {
+            ExprType::Bytes => quote! { .field(#name, &::kaitai::HexBytes(&self.#id)) },
+            _ => quote! { .field(#name, &self.#id) },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hir::expr::parse_type_ref;
+
+    fn param(yaml: &str) -> Result<Parameter, Error> {
+        serde_yaml::from_str::<de::param::Param>(yaml)
+            .unwrap()
+            .try_into()
+    }
+
+    #[test]
+    fn param_types() {
+        let types = [
+            "{id: p, type: bytes}",
+            "{id: p, type: s2}",
+            "{id: p, type: u1, enum: kind}",
+            "{id: p, type: f8}",
+        ]
+        .map(|yaml| param(yaml).unwrap().ty.ty().to_string());
+        assert_eq!(types, [":: std :: vec :: Vec < u8 >", "i16", "Kind", "f64"]);
+
+        assert_eq!(
+            param("{id: p, type: f4, enum: kind}").unwrap_err(),
+            Error::UnsupportedParamType {
+                param: "p".to_owned(),
+                ty: "f4".to_owned(),
+            }
+        );
+        assert!(param("{id: p, type: header}").is_err());
+    }
+
+    #[test]
+    fn inherited_root_values() {
+        let root = |yaml: &str| serde_yaml::from_str::<de::ty::Type>(yaml).unwrap();
+        let values = root_values(&root(
+            r#"
+seq:
+  - id: version
+    type: u1
+  - id: body
+    type: outer
+types:
+  outer:
+    seq:
+      - id: inner
+        type: inner
+  inner:
+    seq:
+      - id: extra
+        type: u1
+        if: _root.version > 1
+  unrelated: {}
+"#,
+        ))
+        .unwrap();
+        let fields = |id: &str| {
+            values[id]
+                .iter()
+                .map(|(field, ty)| (field.as_str(), ty.ty().to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fields("inner"), [("version", "u8".to_owned())]);
+        assert_eq!(fields("outer"), fields("inner"));
+        assert!(!values.contains_key("unrelated"));
+
+        let err = root_values(&root(
+            "{seq: [{id: body, type: inner}], types: {inner: {instances: {a: {value: _root.missing}}}}}",
+        ))
+        .unwrap_err();
+        assert_eq!(err, Error::UnsupportedRootValue("missing".to_owned()));
+    }
+
+    #[test]
+    fn inherited_calculated_endianness() {
+        let root = serde_yaml::from_str::<de::ty::Type>(
+            r#"
+meta:
+  endian: le
+types:
+  body:
+    meta:
+      endian:
+        switch-on: _root.order
+        cases: {1: le, 2: be}
+    types:
+      entry:
+        types:
+          inner: {seq: [{id: value, type: u2}]}
+          flags: {seq: [{id: first, type: b1}]}
+      fixed:
+        meta:
+          endian: be
+        types:
+          inner_fixed: {}
+  unrelated: {}
+"#,
+        )
+        .unwrap();
+        let mut types = runtime_endian(&root, &[]).into_iter().collect::<Vec<_>>();
+        types.sort();
+        assert_eq!(types, ["entry", "inner"]);
+    }
+
+    #[test]
+    fn argument_conversions() {
+        let params = [ExprType::Int("u16"), ExprType::Bytes, ExprType::Int("u32")];
+        let (_, args) = parse_type_ref("entry(len, magic, 4)").unwrap();
+        let args = arguments("entry", &params, &args).unwrap();
+        assert_eq!(
+            args.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [":: std :: convert :: From :: from (len)", "& magic", "4u32"]
+        );
+
+        let (_, args) = parse_type_ref("entry(1)").unwrap();
+        assert_eq!(
+            arguments("entry", &params, &args).unwrap_err(),
+            Error::WrongArgumentCount {
+                ty: "entry".to_owned(),
+                expected: 3,
+                actual: 1,
+            }
+        );
+    }
+}
@@ -1,45 +1,228 @@
 use crate::{
     de,
+    error::Error,
     hir::{
-        attr::{Attribute, Attributes},
+        attr::{self, Attributes, Context},
         doc::Doc,
         en::Enumeration,
-        meta::Endianness,
-        param::Parameter,
+        expr::ExprType,
+        flags::Flags,
+        instance::Instances,
+        meta::{self, Endian, EndianSwitch, Endianness},
+        param::{Parameter, RootValue},
     },
-    util::sc_to_ucc,
+    util::{type_ident, ucc_to_sc},
 };
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use proc_macro2::{Ident, Span};
+use proc_macro2::{Ident, Span, TokenStream, TokenTree};
 use quote::ToTokens;
 
 #[derive(Debug)]
 pub struct Type {
     id: Ident,
-    endianness: Endianness,
+    visibility: TokenStream,
+    endianness: Endian,
+    /// The calculated endianness of the type, if it isn't inherited.
+    endian_switch: Option<EndianSwitch>,
     doc: Doc,
     params: Vec<Parameter>,
     seq: Attributes,
+    /// The flags of the type if its `seq` is only made of `b1` fields, in which case `seq` is
+    /// empty.
+    flags: Option<Flags>,
     types: Vec<Type>,
-    instances: HashMap<String, Attribute>,
+    instances: Instances,
     enums: Vec<Enumeration>,
+    /// The root type, if the type refers to root values and can be parsed on its own with them.
+    standalone_root: Option<Ident>,
+    /// Whether the type is a root type with the generic default encoding `UTF-16` or `UTF`, which
+    /// looks for the byte order mark of the file when it is parsed.
+    detects_bom: bool,
+    /// Whether the type is generated as a newtype around its only field, see
+    /// [`InheritedMeta::newtypes`].
+    newtype: bool,
+    /// Whether the type derives `Hash`, see [`InheritedMeta::hash`].
+    hash: bool,
+    /// Whether the type holds floats, see [`Type::mark_floats`].
+    floats: bool,
+    /// Whether the type borrows from the slice it is parsed from, see [`InheritedMeta::borrowed`].
+    borrowed: bool,
 }
 
+/// Whether the byte range of each field is recorded (feature `spans`).
+const SPANS: bool = cfg!(feature = "spans");
+
 pub struct InheritedMeta {
     pub id: Option<(Ident, bool)>,
-    pub endianness: Option<Endianness>,
+    /// The visibility of the type, `pub` if it isn't set.
+    pub visibility: Option<syn::Visibility>,
+    pub endianness: Option<Endian>,
+    /// The order of the bits of bit-sized integers (KS: `meta/bit-endian`).
+    pub bit_endianness: Option<Endianness>,
+    /// The enums defined in the parent types, mapping their KS ids to the KS ids of their members.
+    pub enums: HashMap<String, Vec<String>>,
+    /// The default encoding of strings, set in the `meta` of a parent type.
+    pub encoding: Option<String>,
+    /// The types of the parameters of all the user types, by KS id, see
+    /// [`param_types`](crate::hir::param::param_types).
+    pub params: HashMap<String, Vec<ExprType>>,
+    /// The root type.
+    pub root: Ident,
+    /// The root values of all the user types, by KS id, see
+    /// [`root_values`](crate::hir::param::root_values).
+    pub root_values: HashMap<String, Vec<RootValue>>,
+    /// The root values of the type, or [`None`] if it is the root type.
+    pub own_root_values: Option<Vec<RootValue>>,
+    /// The KS ids of the user types inheriting a calculated endianness, see
+    /// [`runtime_endian`](crate::hir::param::runtime_endian).
+    pub runtime_endian: HashSet<String>,
+    /// Whether the types other than the root type whose `seq` is a single field, without
+    /// parameters or instances, are generated as newtypes (macro argument `newtypes`).
+    pub newtypes: bool,
+    /// Whether the types derive `Hash` (macro argument `hash`), except the types holding floats,
+    /// see [`Type::mark_floats`].
+    pub hash: bool,
+    /// Whether the byte arrays and strings of the types borrow from the slice they are parsed
+    /// from (macro argument `borrowed`). The types then have a lifetime `'a` and are parsed from a
+    /// `SliceStream` rather than implementing `KaitaiStruct`, see [`Type::check_borrowed`].
+    pub borrowed: bool,
+}
+
+/// Moves the enums defined inline on the attributes of `ty` to its enums, named after the type
+/// and the attribute, e.g. `header_kind` for the `kind` attribute of `header`.
+fn hoist_inline_enums(ty: &mut de::ty::Type, ks_id: &str) -> Result<(), Error> {
+    let seq = ty
+        .seq
+        .iter_mut()
+        .filter_map(|attr| Some((attr.id.clone()?, attr)));
+    let instances = ty.instances.iter_mut().map(|(id, attr)| (id.clone(), attr));
+    for (attr_id, attr) in seq.chain(instances) {
+        if !matches!(attr.en, Some(de::attr::AttrEnum::Inline(_))) {
+            continue;
+        }
+        let en_id = format!("{}_{}", ks_id, attr_id);
+        if let Some(de::attr::AttrEnum::Inline(en)) =
+            attr.en.replace(de::attr::AttrEnum::Named(en_id.clone()))
+        {
+            if ty.enums.insert(en_id.clone(), en).is_some() {
+                return Err(Error::InlineEnumClash {
+                    field: attr_id,
+                    en: en_id,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The ids of the types nested in a type, and of the types nested in them.
+#[derive(Default)]
+struct TypeTree(HashMap<String, TypeTree>);
+
+impl TypeTree {
+    fn new(ty: &de::ty::Type) -> Self {
+        Self(
+            ty.types
+                .iter()
+                .map(|(id, ty)| (id.clone(), Self::new(ty)))
+                .collect(),
+        )
+    }
+
+    /// Returns the tree of `root`, in which the root types of the imported files `imports` are
+    /// next to the types nested in `root`.
+    fn with_imports(root: &de::ty::Type, imports: &[de::ty::Type]) -> Self {
+        let mut tree = Self::new(root);
+        for import in imports {
+            if let Some(id) = import.meta.as_ref().and_then(|meta| meta.id.clone()) {
+                tree.0.insert(id, Self::new(import));
+            }
+        }
+        tree
+    }
+
+    /// Returns the tree of the type at `path`, a list of nested type ids.
+    fn get(&self, path: &[String]) -> Option<&Self> {
+        path.iter().try_fold(self, |tree, id| tree.0.get(id))
+    }
+}
+
+/// Replaces the references to types by path (e.g. `header::entry`) in the type tree of `root`
+/// with the ids of the types, which are all defined next to each other.
+///
+/// The first type of a path is looked up in the types nested in the type the reference is in,
+/// then in the types nested in its parents, and each following one in the types nested in the
+/// previous one. The root types of `imports`, the files imported by `root`, are looked up like
+/// the types nested in `root`: their types are generated by the `kaitai_source` of the imported
+/// file, and referred to by their ids too.
+pub fn resolve_type_paths(root: &mut de::ty::Type, imports: &[de::ty::Type]) -> Result<(), Error> {
+    let tree = TypeTree::with_imports(root, imports);
+    resolve_paths_in(root, &tree, &mut Vec::new())
+}
+
+fn resolve_paths_in(
+    ty: &mut de::ty::Type,
+    tree: &TypeTree,
+    scope: &mut Vec<String>,
+) -> Result<(), Error> {
+    let resolve = |type_ref: &mut String| {
+        // The arguments of the parameters of the type are kept as they are.
+        let (path, args) = match type_ref.find('(') {
+            Some(start) => (type_ref[..start].to_owned(), type_ref[start..].to_owned()),
+            None => (type_ref.clone(), String::new()),
+        };
+        if !path.contains("::") {
+            return Ok(());
+        }
+        let segments = path.split("::").map(str::trim).collect::<Vec<_>>();
+        let unknown = |segment: &str| Error::UnknownTypePath {
+            path: path.clone(),
+            segment: segment.to_owned(),
+        };
+        // The innermost type with a nested type named after the first segment.
+        let start = (0..=scope.len())
+            .rev()
+            .filter_map(|depth| tree.get(&scope[..depth]))
+            .find_map(|tree| tree.0.get(segments[0]))
+            .ok_or_else(|| unknown(segments[0]))?;
+        segments[1..].iter().try_fold(start, |tree, segment| {
+            tree.0.get(*segment).ok_or_else(|| unknown(segment))
+        })?;
+        *type_ref = format!("{}{}", segments[segments.len() - 1], args);
+        Ok(())
+    };
+
+    for attr in ty.seq.iter_mut().chain(ty.instances.values_mut()) {
+        match &mut attr.ty {
+            Some(de::attr::AttrType::TypeRef(type_ref)) => resolve(type_ref)?,
+            Some(de::attr::AttrType::Switch { cases, .. }) => {
+                for (_, type_ref) in cases {
+                    resolve(type_ref)?;
+                }
+            }
+            None => {}
+        }
+    }
+    for (id, nested) in &mut ty.types {
+        scope.push(id.clone());
+        resolve_paths_in(nested, tree, scope)?;
+        scope.pop();
+    }
+    Ok(())
 }
 
 impl TryFrom<(InheritedMeta, de::ty::Type)> for Type {
-    type Error = ();
+    type Error = Error;
 
-    fn try_from((inherited_meta, ty): (InheritedMeta, de::ty::Type)) -> Result<Self, Self::Error> {
-        let meta_id = ty.meta.as_ref().and_then(|m| {
-            m.id.as_ref()
-                .map(|id| Ident::new(&sc_to_ucc(&id), Span::call_site()))
-        });
+    fn try_from(
+        (inherited_meta, mut ty): (InheritedMeta, de::ty::Type),
+    ) -> Result<Self, Self::Error> {
+        let meta_id = ty
+            .meta
+            .as_ref()
+            .and_then(|m| m.id.as_ref().map(|id| type_ident(id)));
         let id = match inherited_meta.id {
             Some((id, overwrite)) => {
                 if overwrite {
@@ -53,82 +236,868 @@ impl TryFrom<(InheritedMeta, de::ty::Type)> for Type {
             None => meta_id.unwrap(),
         };
 
-        let endianness = ty
+        if let Some(version) = ty.meta.as_ref().and_then(|m| m.ks_version.as_ref()) {
+            meta::check_ks_version(&version.0)?;
+        }
+
+        hoist_inline_enums(&mut ty, &ucc_to_sc(id.to_string()))?;
+
+        // Each key of the `meta` of a type overrides only the same key of its parents.
+        let (endianness, endian_switch) = match ty.meta.as_mut().and_then(|m| m.endianness.take()) {
+            Some(de::meta::EndianSpec::Fixed(endianness)) => (Endian::Fixed(endianness), None),
+            Some(de::meta::EndianSpec::Switch { switch_on, cases }) => {
+                (Endian::Runtime, Some((switch_on, cases)))
+            }
+            None => (
+                inherited_meta.endianness.unwrap_or(Endian::Unspecified),
+                None,
+            ),
+        };
+        let bit_endianness = ty
             .meta
             .as_ref()
-            .and_then(|m| m.endianness)
-            .or(inherited_meta.endianness)
-            .expect("no endianness inherited");
+            .and_then(|m| m.bit_endianness)
+            .or(inherited_meta.bit_endianness);
         // TODO: All the meta doc clones.
         let doc = (ty.meta.as_ref().map(|meta| meta.doc.clone()), ty.doc).into();
-        let seq = (ty.meta.as_ref().map(|m| m.doc.clone()), ty.seq)
-            .try_into()
-            .expect("seq validation failed");
+        // Enums of a type shadow the enums of its parents with the same id.
+        let mut visible_enums = inherited_meta.enums;
+        visible_enums.extend(ty.enums.iter().map(|(id, en)| {
+            let members = en.0.values().map(|v| v.id.clone()).collect();
+            (id.clone(), members)
+        }));
+        let encoding = ty
+            .meta
+            .as_ref()
+            .and_then(|m| m.encoding.clone())
+            .or(inherited_meta.encoding);
+        let context = Context {
+            meta_doc: ty.meta.as_ref().map(|m| m.doc.clone()),
+            ty: &id,
+            enums: &visible_enums,
+            encoding: encoding.as_deref(),
+            bit_endianness: bit_endianness.unwrap_or(Endianness::Be),
+            params: &inherited_meta.params,
+            root_values: &inherited_meta.root_values,
+            in_root: inherited_meta.own_root_values.is_none(),
+            endianness,
+            runtime_endian: &inherited_meta.runtime_endian,
+            hash: inherited_meta.hash,
+            borrowed: inherited_meta.borrowed,
+        };
+        let endian_switch = endian_switch
+            .map(|(on, cases)| (id.to_string().as_str(), &visible_enums, on, cases).try_into())
+            .transpose()?;
+        let flags = if ty.instances.is_empty() {
+            Flags::from_seq(&ty.seq, bit_endianness.unwrap_or(Endianness::Be))
+        } else {
+            None
+        };
+        let seq: Attributes = match flags {
+            Some(_) => (&context, Vec::new()).try_into()?,
+            None => (&context, ty.seq).try_into()?,
+        };
+        let mut params = ty
+            .params
+            .into_iter()
+            .map(Parameter::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        // The types nested in a type with a calculated endianness get it from the type reading
+        // them rather than calculating it again.
+        let inherits_endian = matches!(endianness, Endian::Runtime)
+            && endian_switch.is_none()
+            && flags.is_none()
+            && inherited_meta.own_root_values.is_some();
+        let root_values = inherited_meta.own_root_values.unwrap_or_default();
+        // Only the root values are needed to parse the type on its own.
+        let standalone_root = (!root_values.is_empty() && params.is_empty() && !inherits_endian)
+            .then(|| inherited_meta.root.clone());
+        params.extend(root_values.into_iter().map(Parameter::from));
+        if inherits_endian {
+            params.push(Parameter::endian());
+        }
+        let newtype = inherited_meta.newtypes
+            && !context.in_root
+            && params.is_empty()
+            && ty.instances.is_empty()
+            && seq.newtype_field().is_some();
+        let mut names = seq.expr_types();
+        names.extend(params.iter().map(Parameter::expr_type));
+        let mut instances: Instances = (&context, names, ty.instances).try_into()?;
+        instances.schedule(&seq.conditions())?;
         let types = ty
             .types
             .into_iter()
             .map(|(id, ty)| {
                 let inherited_meta = InheritedMeta {
-                    id: Some((Ident::new(&sc_to_ucc(&id), Span::call_site()), false)),
+                    id: Some((type_ident(&id), false)),
+                    visibility: None,
                     endianness: Some(endianness),
+                    bit_endianness,
+                    enums: visible_enums.clone(),
+                    encoding: encoding.clone(),
+                    params: inherited_meta.params.clone(),
+                    root: inherited_meta.root.clone(),
+                    own_root_values: Some(
+                        inherited_meta
+                            .root_values
+                            .get(&id)
+                            .cloned()
+                            .unwrap_or_default(),
+                    ),
+                    root_values: inherited_meta.root_values.clone(),
+                    runtime_endian: inherited_meta.runtime_endian.clone(),
+                    newtypes: inherited_meta.newtypes,
+                    hash: inherited_meta.hash,
+                    borrowed: inherited_meta.borrowed,
                 };
-                Type::try_from((inherited_meta, ty)).expect("type validation failed")
+                Type::try_from((inherited_meta, ty))
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
         let enums = ty
             .enums
             .into_iter()
             .map(|(id, en)| (id.as_ref(), en).into())
             .collect();
 
+        let detects_bom = context.in_root && encoding.as_deref().is_some_and(meta::is_generic_utf);
+
+        let visibility = match inherited_meta.visibility {
+            Some(visibility) => visibility.into_token_stream(),
+            None => quote::quote! { pub },
+        };
+
         Ok(Self {
             id,
+            visibility,
             endianness,
+            endian_switch,
             doc,
-            // TODO
-            params: Default::default(),
+            params,
             seq,
+            flags,
             types,
-            // TODO
-            instances: Default::default(),
+            instances,
             enums,
+            standalone_root,
+            detects_bom,
+            newtype,
+            hash: inherited_meta.hash,
+            floats: false,
+            borrowed: inherited_meta.borrowed,
+        })
+    }
+}
+
+impl Type {
+    /// Returns the `to_bytes` method of the type (macro argument `to_bytes`), which writes its
+    /// `seq` back to bytes, or an error if the bytes can't be determined from the fields alone.
+    pub fn to_bytes_method(&self) -> Result<TokenStream, Error> {
+        if self.flags.is_some() {
+            return Err(Error::NotSerializable {
+                field: self.id.to_string(),
+                reason: "types made of bit flags aren't supported",
+            });
+        }
+        if let Some(instance) = self.instances.positional() {
+            return Err(Error::NotSerializable {
+                field: instance.to_owned(),
+                reason: "positional instances aren't supported",
+            });
+        }
+
+        let (size, writes) = self.seq.write(self.endianness)?;
+        let size = size as usize;
+        let id = &self.id;
+        let lifetime = self.lifetime();
+        Ok(quote::quote! {
+            impl #lifetime #id #lifetime {
+                /// Returns the bytes of the fields, in the order and with the endianness they are
+                /// read with.
+                pub fn to_bytes(&self) -> ::kaitai::error::Result<::std::vec::Vec<u8>> {
+                    let mut _bytes = ::std::vec::Vec::with_capacity(#size);
+                    #(#writes)*
+                    Ok(_bytes)
+                }
+            }
+        })
+    }
+}
+
+impl Type {
+    /// Returns the `LAYOUT` constant of the type (macro argument `layout`), listing the offset and
+    /// size of each field of its `seq`, or an error if they can vary.
+    pub fn layout_const(&self) -> Result<TokenStream, Error> {
+        if self.flags.is_some() {
+            return Err(Error::NotFixedLayout {
+                field: self.id.to_string(),
+                reason: "types made of bit flags aren't supported",
+            });
+        }
+
+        let layout = self
+            .seq
+            .layout()?
+            .into_iter()
+            .map(|(field, offset, size)| format!("0x{:04x} {} {}", offset, size, field))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let id = &self.id;
+        let lifetime = self.lifetime();
+        Ok(quote::quote! {
+            impl #lifetime #id #lifetime {
+                /// The layout of the fields, one per line with its offset in hexadecimal, its size
+                /// in bytes and its id, e.g. `0x0004 2 version`.
+                pub const LAYOUT: &'static str = #layout;
+            }
         })
     }
 }
 
+impl Type {
+    /// Returns an error if the type or a type nested in it can't be parsed in the borrowed mode
+    /// (macro argument `borrowed`), which only supports types parsed from the slice on their own.
+    pub fn check_borrowed(&self) -> Result<(), Error> {
+        let reason = if self.flags.is_some() {
+            Some("types made of bit flags aren't supported")
+        } else if !self.params.is_empty() {
+            Some("types with params or _root values aren't supported")
+        } else if self.newtype {
+            Some("newtypes aren't supported")
+        } else if self.instances.parent_io() {
+            Some("instances read from _parent._io aren't supported")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Err(Error::NotBorrowable {
+                field: self.id.to_string(),
+                reason,
+            });
+        }
+        self.seq.check_borrowed()?;
+        self.types.iter().try_for_each(Type::check_borrowed)
+    }
+
+    /// Returns the lifetime parameter of the type, `<'a>` if it borrows from the slice it is
+    /// parsed from.
+    fn lifetime(&self) -> Option<TokenStream> {
+        self.borrowed.then(|| quote::quote! { <'a> })
+    }
+}
+
+impl Type {
+    /// Marks the types, and the enums of their switches, that hold floats, either in their own
+    /// fields or in the types of their fields. Floats are neither `Eq`, `Ord` nor `Hash`, so these
+    /// types only derive `PartialEq` and `PartialOrd`.
+    ///
+    /// The types of imported files are generated on their own and aren't known here, so they are
+    /// assumed not to hold floats.
+    pub fn mark_floats(&mut self) {
+        let mut items = Vec::new();
+        self.field_types(&mut items);
+
+        // A type holds floats once one of the types of its fields is known to hold them.
+        let mut floats = HashSet::new();
+        loop {
+            let known = floats.len();
+            for (id, types) in &items {
+                if !floats.contains(id) && types.iter().any(|ty| holds_float(ty, &floats)) {
+                    floats.insert(id.clone());
+                }
+            }
+            if floats.len() == known {
+                break;
+            }
+        }
+        self.set_floats(&floats);
+    }
+
+    /// Collects the identifier of the type with the types of its fields, and the same for the
+    /// enums of its switches and the types nested in it.
+    fn field_types(&self, items: &mut Vec<(String, Vec<TokenStream>)>) {
+        let mut fields = self
+            .params
+            .iter()
+            .filter(|param| param.is_stored())
+            .map(Parameter::field_definition)
+            .collect::<Vec<_>>();
+        fields.extend(self.seq.field_definitions());
+        fields.extend(self.instances.field_definitions());
+        items.push((self.id.to_string(), fields));
+        items.extend(
+            self.seq
+                .switch_types()
+                .map(|(id, types)| (id.to_string(), types)),
+        );
+        for ty in &self.types {
+            ty.field_types(items);
+        }
+    }
+
+    fn set_floats(&mut self, floats: &HashSet<String>) {
+        self.floats = floats.contains(&self.id.to_string());
+        self.seq.mark_float_switches(floats);
+        for ty in &mut self.types {
+            ty.set_floats(floats);
+        }
+    }
+}
+
+/// Returns whether `tokens` refer to a float type or to one of the types of `floats`.
+fn holds_float(tokens: &TokenStream, floats: &HashSet<String>) -> bool {
+    tokens.clone().into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => {
+            let ident = ident.to_string();
+            ident == "f32" || ident == "f64" || floats.contains(&ident)
+        }
+        TokenTree::Group(group) => holds_float(&group.stream(), floats),
+        _ => false,
+    })
+}
+
 impl ToTokens for Type {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         let type_defs = self.types.iter().map(|ty| ty.into_token_stream());
         let enum_defs = self.enums.iter().map(|en| en.into_token_stream());
+        let attr_type_defs = self.seq.type_definitions();
         let doc = &self.doc;
         let id = &self.id;
-        let field_defs = self.seq.field_definitions();
-        let var_assignments = self.seq.variable_assignments(self.endianness);
-        let field_assignments = self.seq.field_assignments();
+        let lifetime = self.lifetime();
+        let visibility = &self.visibility;
+        let name = id.to_string();
 
-        tokens.extend(quote::quote! {
-            #(#type_defs)*
-            #(#enum_defs)*
+        if let Some(flags) = &self.flags {
+            let flags_def = flags.definition(id, visibility, doc);
+            tokens.extend(quote::quote! {
+                #(#type_defs)*
+                #(#enum_defs)*
+                #flags_def
+            });
+            return;
+        }
+
+        let stored_params = self.params.iter().filter(|param| param.is_stored());
+        let mut field_defs = stored_params
+            .clone()
+            .map(Parameter::field_definition)
+            .collect::<Vec<_>>();
+        field_defs.extend(self.seq.field_definitions());
+        field_defs.extend(self.instances.field_definitions());
+        let var_assignments = self
+            .seq
+            .variable_assignments(self.endianness, SPANS && !self.newtype, |index| {
+                self.instances.assignments_before(index, self.endianness)
+            })
+            .chain(self.instances.variable_assignments(self.endianness));
+        let mut field_assignments = stored_params
+            .clone()
+            .map(|param| param.id().clone())
+            .collect::<Vec<_>>();
+        field_assignments.extend(self.seq.field_assignments());
+        field_assignments.extend(self.instances.field_assignments().cloned());
+        let mut methods = self.seq.methods().collect::<Vec<_>>();
+        methods.extend(self.instances.methods());
+        let schema = self.seq.schema();
+        methods.push(quote::quote! {
+            /// Returns the fields of the `seq` of the type, in the order they are read in, without
+            /// parsing anything.
+            pub fn schema() -> &'static [::kaitai::FieldInfo] {
+                &[#(#schema),*]
+            }
+        });
+        let mut debug_fields = stored_params
+            .map(Parameter::debug_field)
+            .collect::<Vec<_>>();
+        debug_fields.extend(self.seq.debug_fields());
+        debug_fields.extend(self.instances.debug_fields());
+
+        let derives = attr::derives(self.floats, self.hash);
+
+        // A newtype is a tuple struct around the field of its only attribute.
+        let newtype = self
+            .newtype
+            .then(|| self.seq.newtype_field())
+            .flatten()
+            .map(|(field, field_doc, ty, value)| {
+                let definition = quote::quote! {
+                    #doc
+                    #[derive(#derives)]
+                    #visibility struct #id(#field_doc pub #ty);
+
+                    #[automatically_derived]
+                    impl ::std::fmt::Debug for #id {
+                        fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                            f.debug_tuple(#name).field(#value).finish()
+                        }
+                    }
+
+                    #[automatically_derived]
+                    impl ::std::ops::Deref for #id {
+                        type Target = #ty;
+
+                        fn deref(&self) -> &Self::Target {
+                            &self.0
+                        }
+                    }
+
+                    #[automatically_derived]
+                    impl ::std::convert::From<#ty> for #id {
+                        fn from(value: #ty) -> Self {
+                            Self(value)
+                        }
+                    }
+
+                    #[automatically_derived]
+                    impl ::std::convert::From<#id> for #ty {
+                        fn from(value: #id) -> Self {
+                            value.0
+                        }
+                    }
+                };
+                (field, definition)
+            });
+
+        let mut spans_def = None;
+        let mut spans_init = None;
+        if SPANS && newtype.is_none() {
+            let spans_id = quote::format_ident!("{}Spans", id);
+            spans_def = Some(self.seq.spans_definition(id, &spans_id));
+            spans_init = Some(quote::quote! {
+                #[allow(unused_mut)]
+                let mut _spans = ::kaitai::__private::Spans(#spans_id::default());
+            });
+            field_defs.push(quote::quote! { _spans: ::kaitai::__private::Spans<#spans_id> });
+            field_assignments.push(Ident::new("_spans", Span::call_site()));
+            debug_fields.push(quote::quote! { .field("_spans", &self._spans) });
+            methods.push(quote::quote! {
+                /// Returns the byte ranges the fields were read from.
+                pub fn spans(&self) -> &#spans_id {
+                    &self._spans
+                }
+            });
+        }
+
+        // The lifetime of a borrowed type is used by a marker field, whatever its fields are.
+        let input_init = self.borrowed.then(|| {
+            field_defs.push(quote::quote! { _input: ::std::marker::PhantomData<&'a [u8]> });
+            field_assignments.push(Ident::new("_input", Span::call_site()));
+            quote::quote! { let _input = ::std::marker::PhantomData; }
+        });
+
+        let param_assignments = self.params.iter().filter_map(Parameter::assignment);
+        let endian_switch = &self.endian_switch;
+        let detect_bom = self
+            .detects_bom
+            .then(|| quote::quote! { _ctx.detect_bom(buf)?; });
+        let value = match &newtype {
+            Some((field, _)) => quote::quote! { Self(#field) },
+            None => quote::quote! {
+                Self {
+                    #(#field_assignments),*
+                }
+            },
+        };
+        let body = quote::quote! {
+            #detect_bom
+            #(#param_assignments)*
+            #endian_switch
+            #spans_init
+            #input_init
+            #(#var_assignments);*;
+            Ok(#value)
+        };
+        // Types with parameters can only be parsed with their values, so they are parsed with a
+        // constructor of their own rather than the ones of `KaitaiStruct`.
+        if !self.params.is_empty() {
+            let args = self.params.iter().map(Parameter::argument);
+            methods.push(quote::quote! {
+                #[doc(hidden)]
+                pub fn new_with_params<P: ::kaitai::__private::ParentStreams + ?Sized>(_streams: &mut P, _ctx: &::kaitai::ParseContext, #(#args),*) -> ::kaitai::error::Result<Self> {
+                    // The streams are associated types, whose methods need the trait in scope.
+                    #[allow(unused_imports)]
+                    use ::kaitai::__private::KaitaiStream as _;
 
-            #doc
-            // TODO: Pass down attributes from struct
-            #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-            pub struct #id {
-                #(#field_defs),*
+                    let buf = ::kaitai::__private::ParentStreams::io(_streams);
+                    #body
+                }
+            });
+        }
+        if let Some(root) = &self.standalone_root {
+            let args = self.params.iter().filter_map(Parameter::root_argument);
+            methods.push(quote::quote! {
+                /// Parses the type on its own from `stream`, like
+                /// [`KaitaiStruct::from_reader`](::kaitai::KaitaiStruct::from_reader), with the
+                /// fields of the root type it refers to as `_root` taken from `root`.
+                pub fn from_reader_with_root<S: ::kaitai::__private::KaitaiStream + ?Sized>(stream: &mut S, root: &#root) -> ::kaitai::error::Result<Self> {
+                    Self::new_with_params(&mut ::kaitai::__private::SameStream(stream), &::kaitai::ParseContext::default(), #(#args),*)
+                }
+            });
+        }
+        // Borrowed types are parsed from the `SliceStream` their fields borrow from, which the
+        // constructors of `KaitaiStruct` can't tie their lifetime to.
+        if self.borrowed {
+            methods.push(quote::quote! {
+                #[doc(hidden)]
+                pub fn new_borrowed(buf: &mut ::kaitai::SliceStream<'a>, _ctx: &::kaitai::ParseContext) -> ::kaitai::error::Result<Self> {
+                    #[allow(unused_imports)]
+                    use ::kaitai::__private::KaitaiStream as _;
+
+                    #body
+                }
+            });
+        }
+        // Types with instances read from the stream of their parent are parsed with both streams,
+        // and otherwise only with their own.
+        let constructors = if !self.params.is_empty() || self.borrowed {
+            None
+        } else if self.instances.parent_io() {
+            Some(quote::quote! {
+                fn new<S: ::kaitai::__private::KaitaiStream + ?Sized>(buf: &mut S, _ctx: &::kaitai::ParseContext) -> ::kaitai::error::Result<Self> {
+                    Self::new_in(&mut ::kaitai::__private::SameStream(buf), _ctx)
+                }
+                fn new_in<P: ::kaitai::__private::ParentStreams + ?Sized>(_streams: &mut P, _ctx: &::kaitai::ParseContext) -> ::kaitai::error::Result<Self> {
+                    // The streams are associated types, whose methods need the trait in scope.
+                    #[allow(unused_imports)]
+                    use ::kaitai::__private::KaitaiStream as _;
+
+                    let buf = ::kaitai::__private::ParentStreams::io(_streams);
+                    #body
+                }
+            })
+        } else {
+            Some(quote::quote! {
+                fn new<S: ::kaitai::__private::KaitaiStream + ?Sized>(buf: &mut S, _ctx: &::kaitai::ParseContext) -> ::kaitai::error::Result<Self> {
+                    #body
+                }
+            })
+        };
+        let kaitai_struct = constructors.map(|constructors| {
+            quote::quote! {
+                #[automatically_derived]
+                impl ::kaitai::KaitaiStruct for #id {
+                    #constructors
+                    fn read<S: ::kaitai::__private::KaitaiStream + ?Sized>(&mut self, _: &mut S, _: &::kaitai::ParseContext) -> ::kaitai::error::Result<()> {
+                        todo!();
+                    }
+                }
             }
+        });
 
-            #[automatically_derived]
-            impl ::kaitai::KaitaiStruct for #id {
-                fn new<S: ::kaitai::__private::KaitaiStream>(buf: &mut S) -> ::kaitai::error::Result<Self> {
-                    #(#var_assignments);*;
-                    Ok(Self {
-                        #(#field_assignments),*
-                    })
+        let definition = match newtype {
+            Some((_, definition)) => definition,
+            None => quote::quote! {
+                #doc
+                // TODO: Pass down attributes from struct
+                #[derive(#derives)]
+                #visibility struct #id #lifetime {
+                    #(#field_defs),*
                 }
-                fn read<S: ::kaitai::__private::KaitaiStream>(&mut self, _: &mut S) -> ::kaitai::error::Result<()> {
-                    todo!();
+
+                // Byte arrays are rendered in hexadecimal rather than as lists of numbers.
+                #[automatically_derived]
+                impl #lifetime ::std::fmt::Debug for #id #lifetime {
+                    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                        f.debug_struct(#name)
+                            #(#debug_fields)*
+                            .finish()
+                    }
                 }
+            },
+        };
+
+        tokens.extend(quote::quote! {
+            #(#type_defs)*
+            #(#enum_defs)*
+            #(#attr_type_defs)*
+            #spans_def
+
+            #definition
+
+            impl #lifetime #id #lifetime {
+                #(#methods)*
             }
+
+            #kaitai_struct
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inherited_meta() -> InheritedMeta {
+        InheritedMeta {
+            id: None,
+            visibility: None,
+            endianness: None,
+            bit_endianness: None,
+            enums: HashMap::new(),
+            encoding: None,
+            params: HashMap::new(),
+            root: Ident::new("Root", Span::call_site()),
+            root_values: HashMap::new(),
+            own_root_values: None,
+            runtime_endian: HashSet::new(),
+            newtypes: false,
+            hash: false,
+            borrowed: false,
+        }
+    }
+
+    #[test]
+    fn ks_version_too_high() {
+        let ty = serde_yaml::from_str::<de::ty::Type>(
+            r#"
+meta:
+  id: future
+  endian: le
+  ks-version: 99.0
+seq:
+  - id: magic
+    type: u1
+"#,
+        )
+        .unwrap();
+        let err = Type::try_from((inherited_meta(), ty)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the format requires Kaitai Struct 99.0, but only {} is supported",
+                meta::KS_VERSION
+            )
+        );
+    }
+
+    #[test]
+    fn endianness_only_for_multi_byte_numbers() {
+        let ty = |len_type: &str| {
+            let ty = serde_yaml::from_str::<de::ty::Type>(&format!(
+                r#"
+meta:
+  id: bytes
+seq:
+  - id: len
+    type: {}
+  - id: body
+    size: len
+  - id: inner
+    type: inner
+types:
+  inner:
+    seq:
+      - id: flag
+        type: s1
+"#,
+                len_type
+            ))
+            .unwrap();
+            Type::try_from((inherited_meta(), ty))
+        };
+
+        assert!(ty("u1").is_ok());
+        assert_eq!(
+            ty("u2").unwrap_err(),
+            Error::MissingEndianness("u2".to_owned())
+        );
+    }
+
+    #[test]
+    fn mark_floats() {
+        let ty = serde_yaml::from_str::<de::ty::Type>(
+            r#"
+meta:
+  id: sample
+  endian: le
+seq:
+  - id: points
+    type: point
+    repeat: eos
+  - id: tag
+    type: tag
+  - id: reading
+    type:
+      switch-on: tag.id
+      cases:
+        1: f4
+        _: u4
+types:
+  point:
+    seq:
+      - id: x
+        type: f8
+  tag:
+    seq:
+      - id: id
+        type: u2
+"#,
+        )
+        .unwrap();
+        let mut ty = Type::try_from((
+            InheritedMeta {
+                hash: true,
+                ..inherited_meta()
+            },
+            ty,
+        ))
+        .unwrap();
+        ty.mark_floats();
+
+        // The root type holds floats through `point`, the tag holds no floats.
+        assert!(ty.floats);
+        let floats = ty.types.iter().map(|t| (t.id.to_string(), t.floats));
+        assert_eq!(
+            floats.collect::<HashMap<_, _>>(),
+            HashMap::from([("Point".to_owned(), true), ("Tag".to_owned(), false)])
+        );
+        let tokens = ty.into_token_stream().to_string();
+        assert!(tokens.contains(
+            &quote::quote! { #[derive(Debug, Clone, PartialEq, PartialOrd)] pub enum SampleReading }
+                .to_string()
+        ));
+        assert!(tokens.contains(
+            &quote::quote! { #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)] pub struct Tag }
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn check_borrowed() {
+        let ty = |seq: &str| {
+            let ty = serde_yaml::from_str::<de::ty::Type>(&format!(
+                r#"
+meta:
+  id: sample
+  endian: le
+seq:
+{}
+types:
+  entry:
+    params:
+      - id: len
+        type: u1
+    seq:
+      - id: body
+        size: len
+"#,
+                seq
+            ))
+            .unwrap();
+            let inherited_meta = InheritedMeta {
+                borrowed: true,
+                ..inherited_meta()
+            };
+            Type::try_from((inherited_meta, ty)).unwrap()
+        };
+
+        let switch = ty(r#"
+  - id: body
+    type:
+      switch-on: 1
+      cases:
+        1: u1"#);
+        assert_eq!(
+            switch.check_borrowed().unwrap_err(),
+            Error::NotBorrowable {
+                field: "body".to_owned(),
+                reason: "switches aren't supported",
+            }
+        );
+        // The nested types are checked too, even if they aren't read.
+        let sized = ty(r#"
+  - id: tag
+    size: 4"#);
+        assert_eq!(
+            sized.check_borrowed().unwrap_err(),
+            Error::NotBorrowable {
+                field: "Entry".to_owned(),
+                reason: "types with params or _root values aren't supported",
+            }
+        );
+        let tokens = sized.into_token_stream().to_string();
+        assert!(tokens.contains(&quote::quote! { pub tag: &'a [u8] }.to_string()));
+    }
+
+    #[test]
+    fn inline_enum_clash() {
+        let ty = serde_yaml::from_str::<de::ty::Type>(
+            r#"
+meta:
+  id: header
+  endian: le
+seq:
+  - id: kind
+    type: u1
+    enum:
+      1: a
+enums:
+  header_kind:
+    1: b
+"#,
+        )
+        .unwrap();
+
+        let err = Type::try_from((inherited_meta(), ty)).unwrap_err();
+        assert_eq!(
+            err,
+            Error::InlineEnumClash {
+                field: "kind".to_owned(),
+                en: "header_kind".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn type_paths() {
+        let ty = |type_ref: &str| {
+            serde_yaml::from_str::<de::ty::Type>(&format!(
+                r#"
+types:
+  outer:
+    types:
+      inner:
+        types:
+          leaf: {{}}
+  sibling:
+    seq:
+      - id: field
+        type: {}
+"#,
+                type_ref
+            ))
+            .unwrap()
+        };
+        let field_ty =
+            |mut ty: de::ty::Type| match ty.types.remove("sibling").unwrap().seq.remove(0).ty {
+                Some(de::attr::AttrType::TypeRef(type_ref)) => type_ref,
+                ty => panic!("unexpected type {:?}", ty),
+            };
+
+        let mut resolved = ty("outer::inner::leaf");
+        resolve_type_paths(&mut resolved, &[]).unwrap();
+        assert_eq!(field_ty(resolved), "leaf");
+        let mut unchanged = ty("leaf");
+        resolve_type_paths(&mut unchanged, &[]).unwrap();
+        assert_eq!(field_ty(unchanged), "leaf");
+
+        let imports = [serde_yaml::from_str("{meta: {id: common}, types: {point: {}}}").unwrap()];
+        let mut imported = ty("common::point");
+        resolve_type_paths(&mut imported, &imports).unwrap();
+        assert_eq!(field_ty(imported), "point");
+
+        for (type_ref, segment) in [
+            ("outer::missing::leaf", "missing"),
+            ("inner::leaf", "inner"),
+            ("outer::inner::leaf::more", "more"),
+        ] {
+            assert_eq!(
+                resolve_type_paths(&mut ty(type_ref), &[]).unwrap_err(),
+                Error::UnknownTypePath {
+                    path: type_ref.to_owned(),
+                    segment: segment.to_owned(),
+                }
+            );
+        }
+    }
+}
@@ -1,5 +1,5 @@
 //! Please see the main [kaitai](https://www.crates.io/crates/kaitai) crate.
-#![feature(proc_macro_span, register_tool)]
+#![feature(register_tool)]
 #![allow(dead_code)]
 #![deny(
     non_ascii_idents,
@@ -13,6 +13,7 @@
 )]
 #![register_tool(tarpaulin)]
 
+mod args;
 mod de;
 mod error;
 mod hir;
@@ -31,7 +32,10 @@ pub fn kaitai_source(
     args: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let ks_source_path = parse_macro_input!(args as syn::LitStr);
+    let args = match args::Args::try_from(parse_macro_input!(args as syn::AttributeArgs)) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
     let item_ast = parse_macro_input!(item as syn::Item);
 
     let struct_item = match item_ast {
@@ -51,18 +55,193 @@ pub fn kaitai_source(
     }
 
     // // Span::call_site() is a nightly feature.
-    let mut source_file_path = proc_macro::Span::call_site().source_file().path();
+    let mut source_file_path = proc_macro::Span::call_site().local_file().unwrap();
     source_file_path.pop();
-    let file_path = source_file_path.join(Path::new(&ks_source_path.value()));
-
-    let toml = std::fs::read_to_string(file_path).expect("error reading ksy file");
-    let de_type = serde_yaml::from_str::<de::ty::Type>(&toml).expect("invalid ks file");
+    // The imports of an inline ksy are relative to the current file.
+    let (mut de_type, dir) = match &args.source {
+        args::Source::Path(path) => {
+            let file_path = source_file_path.join(Path::new(&path.value()));
+            let toml = std::fs::read_to_string(&file_path).expect("error reading ksy file");
+            let de_type = serde_yaml::from_str::<de::ty::Type>(&toml).expect("invalid ks file");
+            (de_type, file_path.parent().unwrap().to_owned())
+        }
+        args::Source::Inline(ksy) => match serde_yaml::from_str::<de::ty::Type>(&ksy.value()) {
+            Ok(de_type) => (de_type, source_file_path),
+            Err(e) => {
+                return syn::Error::new_spanned(ksy, format!("invalid inline ksy: {}", e))
+                    .to_compile_error()
+                    .into()
+            }
+        },
+    };
+    let imports = match read_imports(&dir, &de_type) {
+        Ok(imports) => imports,
+        Err(e) => {
+            return syn::Error::new(proc_macro2::Span::call_site(), e)
+                .to_compile_error()
+                .into()
+        }
+    };
 
-    let inherited_meta = hir::ty::InheritedMeta {
-        id: Some((struct_item.ident, true)),
+    let root = args.root.unwrap_or(struct_item.ident);
+    // In a module the root type is `pub`, and the module and the `use` of the root type outside of
+    // it get the visibility instead.
+    let module = args
+        .module
+        .map(|name| name.unwrap_or_else(|| util::ident(&util::ucc_to_sc(root.to_string()))));
+    let visibility = match module {
+        Some(_) => None,
+        None => args.visibility.clone(),
+    };
+    let inherited_meta = |(params, root_values, runtime_endian)| hir::ty::InheritedMeta {
+        id: Some((root.clone(), true)),
+        visibility,
         endianness: None,
+        bit_endianness: None,
+        enums: Default::default(),
+        encoding: None,
+        params,
+        root: root.clone(),
+        root_values,
+        own_root_values: None,
+        runtime_endian,
+        newtypes: args.newtypes,
+        hash: args.hash,
+        borrowed: args.borrowed,
     };
 
-    let ty: hir::ty::Type = (inherited_meta, de_type).try_into().unwrap();
-    quote::ToTokens::into_token_stream(ty).into()
+    let mut ty: hir::ty::Type = match hir::ty::resolve_type_paths(&mut de_type, &imports)
+        .and_then(|()| {
+            let params = hir::param::param_types(&de_type, &imports)?;
+            let runtime_endian = hir::param::runtime_endian(&de_type, &imports);
+            Ok((params, hir::param::root_values(&de_type)?, runtime_endian))
+        })
+        .and_then(|params| (inherited_meta(params), de_type).try_into())
+    {
+        Ok(ty) => ty,
+        Err(e) => {
+            return syn::Error::new(proc_macro2::Span::call_site(), e)
+                .to_compile_error()
+                .into()
+        }
+    };
+    ty.mark_floats();
+    // The imported types are generated by their own `kaitai_source`, which may not borrow.
+    if args.borrowed {
+        let borrowable = match imports.is_empty() {
+            true => ty.check_borrowed(),
+            false => Err(error::Error::NotBorrowable {
+                field: root.to_string(),
+                reason: "imports aren't supported",
+            }),
+        };
+        if let Err(e) = borrowable {
+            return syn::Error::new(proc_macro2::Span::call_site(), e)
+                .to_compile_error()
+                .into();
+        }
+    }
+    let to_bytes = if args.to_bytes {
+        match ty.to_bytes_method() {
+            Ok(to_bytes) => Some(to_bytes),
+            Err(e) => {
+                return syn::Error::new(proc_macro2::Span::call_site(), e)
+                    .to_compile_error()
+                    .into()
+            }
+        }
+    } else {
+        None
+    };
+    let layout = if args.layout {
+        match ty.layout_const() {
+            Ok(layout) => Some(layout),
+            Err(e) => {
+                return syn::Error::new(proc_macro2::Span::call_site(), e)
+                    .to_compile_error()
+                    .into()
+            }
+        }
+    } else {
+        None
+    };
+    let mut tokens = quote::ToTokens::into_token_stream(ty);
+    tokens.extend(to_bytes);
+    tokens.extend(layout);
+    // Only the root type can be converted from bytes, the other types are parsed as part of it.
+    if args.borrowed {
+        tokens.extend(quote::quote! {
+            impl<'a> #root<'a> {
+                /// Parses the type from `data`, which its byte arrays and strings borrow from.
+                pub fn from_slice(data: &'a [u8]) -> ::kaitai::error::Result<Self> {
+                    Self::new_borrowed(&mut ::kaitai::SliceStream::new(data), &::kaitai::ParseContext::default())
+                }
+            }
+
+            #[automatically_derived]
+            impl<'a> ::std::convert::TryFrom<&'a [u8]> for #root<'a> {
+                type Error = ::kaitai::error::Error;
+
+                fn try_from(bytes: &'a [u8]) -> ::kaitai::error::Result<Self> {
+                    Self::from_slice(bytes)
+                }
+            }
+        });
+    } else {
+        tokens.extend(quote::quote! {
+            #[automatically_derived]
+            impl ::std::convert::TryFrom<&[u8]> for #root {
+                type Error = ::kaitai::error::Error;
+
+                fn try_from(bytes: &[u8]) -> ::kaitai::error::Result<Self> {
+                    <Self as ::kaitai::KaitaiStruct>::from_bytes(bytes)
+                }
+            }
+        });
+    }
+
+    if let Some(module) = module {
+        let visibility = match args.visibility {
+            Some(visibility) => quote::quote! { #visibility },
+            None => quote::quote! { pub },
+        };
+        let doc = format!(" The types of [`{}`].", root);
+        // The imported types are referred to by name, like in the scope of the struct.
+        let imports = (!imports.is_empty()).then(|| {
+            quote::quote! {
+                #[allow(unused_imports)]
+                use super::*;
+            }
+        });
+        tokens = quote::quote! {
+            #[doc = #doc]
+            #visibility mod #module {
+                #imports
+                #tokens
+            }
+
+            #visibility use #module::#root;
+        };
+    }
+    tokens.into()
+}
+
+/// Reads the files imported by `ty` (KS: `meta/imports`), relative to `dir`, the directory of the
+/// ksy file. Their types aren't generated here, only looked up: each imported file has its own
+/// `kaitai_source`, shared by all the files importing it.
+#[tarpaulin::skip]
+fn read_imports(dir: &Path, ty: &de::ty::Type) -> Result<Vec<de::ty::Type>, error::Error> {
+    let imports = ty.meta.as_ref().map_or(&[][..], |meta| &meta.imports[..]);
+    imports
+        .iter()
+        .map(|import| {
+            let invalid = |reason: String| error::Error::InvalidImport {
+                import: import.clone(),
+                reason,
+            };
+            let path = dir.join(format!("{}.ksy", import.trim_start_matches('/')));
+            let ksy = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+            serde_yaml::from_str(&ksy).map_err(|e| invalid(e.to_string()))
+        })
+        .collect()
 }
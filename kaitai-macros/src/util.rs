@@ -1,3 +1,33 @@
+use proc_macro2::{Ident, Span};
+
+/// The keywords that have to be escaped to be used as identifiers, in any edition.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Returns the Rust identifier for the KS identifier `id`.
+///
+/// Keywords are escaped as raw identifiers, e.g. a field named `type` becomes `r#type`. The
+/// keywords `self`, `Self`, `super` and `crate` can't be raw identifiers, so an underscore is
+/// appended to them instead, e.g. `self` becomes `self_`.
+pub fn ident(id: &str) -> Ident {
+    match id {
+        "self" | "Self" | "super" | "crate" => Ident::new(&format!("{}_", id), Span::call_site()),
+        _ if KEYWORDS.contains(&id) => Ident::new_raw(id, Span::call_site()),
+        _ => Ident::new(id, Span::call_site()),
+    }
+}
+
+/// Returns the Rust identifier of a type or variant named after the KS identifier `id`, i.e. `id`
+/// in upper camel case and escaped like [`ident`].
+pub fn type_ident(id: &str) -> Ident {
+    ident(&sc_to_ucc(id))
+}
+
 /// Converts a snake case string to an upper camel case string.
 pub fn sc_to_ucc<S: AsRef<str>>(string: S) -> String {
     let mut result = String::new();
@@ -15,6 +45,24 @@ pub fn sc_to_ucc<S: AsRef<str>>(string: S) -> String {
     result
 }
 
+/// Converts an upper camel case string to a snake case string.
+pub fn ucc_to_sc<S: AsRef<str>>(string: S) -> String {
+    let mut result = String::new();
+
+    for (i, c) in string.as_ref().chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                result.push('_');
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,4 +85,20 @@ mod tests {
             vec!["ExampleId", "Oneword", "NumAtEnd1", "NumAtEnd2", "A", ""]
         );
     }
+
+    #[test]
+    fn ucc_to_sc_test() {
+        let output = ["ExampleId", "Oneword", "NumAtEnd1", "A", ""].map(ucc_to_sc);
+        assert_eq!(output, ["example_id", "oneword", "num_at_end1", "a", ""]);
+    }
+
+    #[test]
+    fn ident_escaping() {
+        assert_eq!(ident("length").to_string(), "length");
+        assert_eq!(ident("type").to_string(), "r#type");
+        assert_eq!(ident("match").to_string(), "r#match");
+        assert_eq!(ident("self").to_string(), "self_");
+        assert_eq!(type_ident("self").to_string(), "Self_");
+        assert_eq!(type_ident("match").to_string(), "Match");
+    }
 }
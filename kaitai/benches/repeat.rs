@@ -0,0 +1,20 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("../tests/formats/repeat_expr.ksy")]
+struct RepeatExpr;
+
+fn repeat_expr_u4(c: &mut Criterion) {
+    const COUNT: u32 = 1_000_000;
+    let mut input = COUNT.to_le_bytes().to_vec();
+    for i in 0..COUNT {
+        input.extend(i.to_le_bytes());
+    }
+
+    c.bench_function("repeat_expr_u4", |b| {
+        b.iter(|| RepeatExpr::from_bytes(&input).unwrap())
+    });
+}
+
+criterion_group!(benches, repeat_expr_u4);
+criterion_main!(benches);
@@ -0,0 +1,130 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kaitai::{KaitaiStream, TerminatorFlags};
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+const LEN: usize = 1 << 20;
+
+fn input() -> Vec<u8> {
+    (0..LEN).map(|i| (i % 251) as u8 + 1).collect()
+}
+
+fn read_u4le(c: &mut Criterion) {
+    let mut stream = Cursor::new(input());
+    c.bench_function("read_u4le", |b| {
+        b.iter(|| {
+            stream.seek(SeekFrom::Start(0)).unwrap();
+            for _ in 0..LEN / 4 {
+                black_box(stream.read_u4le().unwrap());
+            }
+        })
+    });
+}
+
+fn read_u4le_array(c: &mut Criterion) {
+    let mut stream = Cursor::new(input());
+    c.bench_function("read_u4le_array", |b| {
+        b.iter(|| {
+            stream.seek(SeekFrom::Start(0)).unwrap();
+            black_box(stream.read_u4le_array(LEN / 4).unwrap());
+        })
+    });
+}
+
+/// A reader that hides the cursor it wraps, so that `read_bytes` takes the generic path.
+struct Opaque<R>(R);
+
+impl<R: Read> Read for Opaque<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for Opaque<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+fn read_bytes_small(c: &mut Criterion) {
+    let input = input();
+    c.bench_function("read_bytes_small", |b| {
+        b.iter(|| {
+            let mut stream = Cursor::new(&input[..]);
+            for _ in 0..LEN / 16 {
+                black_box(stream.read_bytes(16).unwrap());
+            }
+        })
+    });
+}
+
+fn read_bytes_small_generic(c: &mut Criterion) {
+    let input = input();
+    c.bench_function("read_bytes_small_generic", |b| {
+        b.iter(|| {
+            let mut stream = Opaque(Cursor::new(&input[..]));
+            for _ in 0..LEN / 16 {
+                black_box(stream.read_bytes(16).unwrap());
+            }
+        })
+    });
+}
+
+fn read_bytes_large(c: &mut Criterion) {
+    let input = input();
+    c.bench_function("read_bytes_large", |b| {
+        b.iter(|| {
+            let mut stream = Cursor::new(&input[..]);
+            black_box(stream.read_bytes(LEN).unwrap());
+        })
+    });
+}
+
+fn read_bytes_large_generic(c: &mut Criterion) {
+    let input = input();
+    c.bench_function("read_bytes_large_generic", |b| {
+        b.iter(|| {
+            let mut stream = Opaque(Cursor::new(&input[..]));
+            black_box(stream.read_bytes(LEN).unwrap());
+        })
+    });
+}
+
+fn read_bytes_term(c: &mut Criterion) {
+    let mut input = input();
+    input.push(0);
+    let mut stream = Cursor::new(input);
+    c.bench_function("read_bytes_term", |b| {
+        b.iter(|| {
+            stream.seek(SeekFrom::Start(0)).unwrap();
+            black_box(
+                stream
+                    .read_bytes_term('\0', TerminatorFlags::consume())
+                    .unwrap(),
+            );
+        })
+    });
+}
+
+fn read_bytes_full(c: &mut Criterion) {
+    let mut stream = Cursor::new(input());
+    c.bench_function("read_bytes_full", |b| {
+        b.iter(|| {
+            stream.seek(SeekFrom::Start(0)).unwrap();
+            black_box(stream.read_bytes_full().unwrap());
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    read_u4le,
+    read_u4le_array,
+    read_bytes_small,
+    read_bytes_small_generic,
+    read_bytes_large,
+    read_bytes_large_generic,
+    read_bytes_term,
+    read_bytes_full
+);
+criterion_main!(benches);
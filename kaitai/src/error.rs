@@ -11,13 +11,50 @@ pub enum Error {
     /// when the cursor reaches the end of the buffer before the terminator is reached. This should
     /// not necessarily be treated as an error but it should be differentiated from an
     /// [`IoError`](Error::IoError).
-    #[error("end of stream reached, but no terminator {0} found")]
-    EofBeforeTerminator(char),
+    ///
+    /// The message gives the position the read started at and the number of bytes read before the
+    /// end of the stream in hex, e.g. to find the truncated string in a hex dump.
+    #[error(
+        "end of stream reached, but no terminator {term:?} found in the {read:#x} bytes read from {start:#x}"
+    )]
+    EofBeforeTerminator {
+        /// The terminator that was looked for
+        term: char,
+        /// The position the read started at
+        start: u64,
+        /// The number of bytes read before the end of the stream
+        read: u64,
+    },
+
+    /// Returned by the `read_bytes_term_any` function in
+    /// [`KaitaiStream`](crate::runtime::KaitaiStream) when the end of the stream is reached before
+    /// any of the terminators, like [`EofBeforeTerminator`](Error::EofBeforeTerminator).
+    #[error(
+        "end of stream reached, but none of the terminators {terms:x?} found in the {read:#x} bytes read from {start:#x}"
+    )]
+    EofBeforeTerminators {
+        /// The terminators that were looked for
+        terms: Vec<u8>,
+        /// The position the read started at
+        start: u64,
+        /// The number of bytes read before the end of the stream
+        read: u64,
+    },
 
     /// Returned by the `ensure_fixed_contents` function in `KaitaiStream` when the contents of the
     /// file don't match the expected value.
-    #[error("unexpected fixed contents got {actual:?}, was expecting {expected:?}")]
+    ///
+    /// The message shows both values in hex, with the first mismatching byte marked:
+    /// ```text
+    /// unexpected fixed contents of magic, first mismatch at offset 1
+    /// expected: 89 50 4e 47
+    ///   actual: 89 51 4e 47
+    ///              ^^
+    /// ```
+    #[error("{}", contents_diff(field.as_deref(), actual, expected))]
     UnexpectedContents {
+        /// The id of the attribute, if the contents were checked by a generated type
+        field: Option<String>,
         /// The actual value read in
         actual: Vec<u8>,
         /// The expected value
@@ -28,7 +65,366 @@ pub enum Error {
     #[error("no matching enum variants found")]
     NoEnumMatch,
 
+    /// Returned when the value a switch is on doesn't match any of its cases and there is no
+    /// default (`_`) case.
+    #[error("no case of the switch of {field} matches {value}")]
+    NoSwitchMatch {
+        /// The id of the attribute
+        field: String,
+        /// The value the switch is on
+        value: String,
+    },
+
+    /// Returned when the value the endianness of a type is calculated from (KS:
+    /// `meta/endian/switch-on`) doesn't match any of its cases and there is no default (`_`) case.
+    #[error("the endianness of {ty} is undecided for {value}")]
+    UndecidedEndianness {
+        /// The name of the type
+        ty: String,
+        /// The value the endianness is calculated from
+        value: String,
+    },
+
+    /// Returned when a value doesn't equal the value required by the `valid` key of its
+    /// attribute.
+    #[error("{field} is not valid, expected {expected} but got {actual}")]
+    ValidationNotEqual {
+        /// The id of the attribute
+        field: String,
+        /// The expected value
+        expected: String,
+        /// The actual value read in
+        actual: String,
+    },
+
+    /// Returned when a value is less than the `min` bound of the `valid` key of its attribute.
+    #[error("{field} is not valid, {actual} is less than the minimum {min}")]
+    ValidationLessThan {
+        /// The id of the attribute
+        field: String,
+        /// The inclusive lower bound
+        min: String,
+        /// The actual value read in
+        actual: String,
+    },
+
+    /// Returned when a value is greater than the `max` bound of the `valid` key of its attribute.
+    #[error("{field} is not valid, {actual} is greater than the maximum {max}")]
+    ValidationGreaterThan {
+        /// The id of the attribute
+        field: String,
+        /// The inclusive upper bound
+        max: String,
+        /// The actual value read in
+        actual: String,
+    },
+
+    /// Returned when a value isn't one of the values listed in the `any-of` key of the `valid` key
+    /// of its attribute.
+    #[error("{field} is not valid, {actual} is not any of {allowed}")]
+    ValidationNotAnyOf {
+        /// The id of the attribute
+        field: String,
+        /// The allowed values, as they are written in the generated code
+        allowed: String,
+        /// The actual value read in
+        actual: String,
+    },
+
+    /// Returned when a value doesn't satisfy the `expr` of the `valid` key of its attribute, a
+    /// boolean expression referring to the value as `_`.
+    #[error("{field} is not valid, {actual} doesn't satisfy `{expr}`")]
+    ValidationExprFailed {
+        /// The id of the attribute
+        field: String,
+        /// The expression, as it is written in the format
+        expr: String,
+        /// The actual value read in
+        actual: String,
+    },
+
+    /// Returned when a length that has to be the number of bytes left in the stream (KS:
+    /// `valid: _io.size - _io.pos`) isn't, e.g. the total length of a truncated container.
+    #[error("{field} declares {declared} bytes, but {remaining} bytes are left in the stream")]
+    ValidationRemainingSize {
+        /// The id of the attribute
+        field: String,
+        /// The length read in
+        declared: String,
+        /// The number of bytes left in the stream after the attribute
+        remaining: u64,
+    },
+
+    /// Returned by the `read_vlq_base128_le` and `read_vlq_base128_be` functions in
+    /// [`KaitaiStream`](crate::runtime::KaitaiStream) when a variable-length integer doesn't fit
+    /// in 64 bits, e.g. because the continuation bits of corrupt data are all set.
+    #[error("variable-length integer read from {start:#x} doesn't fit in 64 bits")]
+    VlqOverflow {
+        /// The position the read started at
+        start: u64,
+    },
+
+    /// Returned by [`process_zlib`](crate::runtime::process_zlib) when the data isn't a valid
+    /// zlib stream, e.g. an attribute with `process: zlib` whose data is truncated.
+    #[error("invalid zlib data: {0}")]
+    InvalidZlib(String),
+
+    /// Returned when the `encoding` of a string isn't a known encoding label.
+    #[error("unknown encoding {0}")]
+    UnknownEncoding(String),
+
+    /// Returned when a string contains bytes that are invalid in its encoding and strict
+    /// [`Decoding`](crate::runtime::Decoding) is used.
+    #[error("invalid {encoding} string")]
+    InvalidString {
+        /// The encoding of the string
+        encoding: String,
+    },
+
+    /// Returned when the `to_i` method of the expression language is called on a string that
+    /// isn't an integer, or with a radix outside of 2 to 36.
+    #[error("{string} is not a valid integer in radix {radix}")]
+    InvalidInteger {
+        /// The string that was parsed
+        string: String,
+        /// The radix it was parsed in
+        radix: u32,
+    },
+
+    /// Returned when the `first`, `last`, `min` or `max` method of the expression language is
+    /// called on an empty array.
+    #[error("{0} of an empty array")]
+    EmptyArray(&'static str),
+
+    /// Returned when an array of the expression language is indexed with a negative index or one
+    /// past its end.
+    #[error("index {index} out of bounds of an array of {len} elements")]
+    IndexOutOfBounds {
+        /// The index.
+        index: i64,
+        /// The number of elements of the array.
+        len: usize,
+    },
+
+    /// Returned when an integer operation of the expression language overflows the type it is
+    /// computed in, e.g. a position computed from corrupt data, or divides by zero.
+    #[error("overflow in the {0} of an expression")]
+    ArithmeticOverflow(&'static str),
+
+    /// Returned by the generated `to_bytes` method when a byte array field doesn't have the size
+    /// it is read with.
+    #[error("{field} must be {expected} bytes long to be written, but is {actual} bytes long")]
+    SizeMismatch {
+        /// The id of the attribute
+        field: String,
+        /// The size the attribute is read with
+        expected: u64,
+        /// The actual size of the value
+        actual: u64,
+    },
+
+    /// Returned when an attribute would read more bytes than the
+    /// [allocation limit](crate::runtime::ParseContext::with_allocation_limit) of the parse
+    /// allows, or than are left in the stream, e.g. because its size was read from corrupt data,
+    /// or when its data would decompress (KS: `process: zlib`) to more bytes than the limit.
+    #[error("reading {requested} bytes exceeds the allocation limit of {limit} bytes")]
+    AllocationLimitExceeded {
+        /// The number of bytes the attribute would read, or the limit plus one for decompressed
+        /// data, whose size isn't known before it is decompressed
+        requested: u64,
+        /// The allocation limit, or the number of bytes left in the stream if it is lower
+        limit: u64,
+    },
+
+    /// Returned when user types are nested deeper than the
+    /// [recursion limit](crate::runtime::ParseContext::with_recursion_limit) of the parse allows,
+    /// e.g. a recursive type read from corrupt data, before the stack overflows.
+    #[error("user types nested deeper than the recursion limit of {0}")]
+    RecursionLimitExceeded(usize),
+
+    /// Returned when a boolean read with `-rust-bool: strict` is neither 0 nor 1.
+    #[error("invalid boolean {0}, expected 0 or 1")]
+    InvalidBool(u8),
+
+    /// Returned when bytes are left in the stream after the root type and the parse
+    /// [rejects trailing data](crate::runtime::ParseContext::with_reject_trailing).
+    #[error("{remaining} bytes left after the parsed type")]
+    TrailingData {
+        /// The number of bytes left in the stream
+        remaining: u64,
+    },
+
+    /// Returned with the `error-path` feature instead of the error of a field, with the path to
+    /// the field from the parsed type. The message is the path followed by the error, e.g.
+    /// `header.entries[3].name: failed to fill whole buffer`.
+    #[error("{path}: {source}")]
+    AtPath {
+        /// The ids of the fields and the indices of the elements of arrays leading to the field,
+        /// e.g. `header.entries[3].name`
+        path: String,
+        /// The error of the field
+        source: Box<Error>,
+    },
+
     /// A generic IO error.
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 }
+
+/// Renders the message of [`Error::UnexpectedContents`].
+fn contents_diff(field: Option<&str>, actual: &[u8], expected: &[u8]) -> String {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mismatch = actual
+        .iter()
+        .zip(expected)
+        .position(|(a, e)| a != e)
+        .unwrap_or_else(|| actual.len().min(expected.len()));
+    let field = match field {
+        Some(field) => format!(" of {}", field),
+        None => String::new(),
+    };
+
+    format!(
+        "unexpected fixed contents{}, first mismatch at offset {}\nexpected: {}\n  actual: {}\n{}^^",
+        field,
+        mismatch,
+        hex(expected),
+        hex(actual),
+        // Each byte takes 3 characters, after the 10 of the `expected: ` prefix.
+        " ".repeat(10 + 3 * mismatch),
+    )
+}
+
+impl Error {
+    /// Returns the kind of the underlying IO error, or `None` if the error didn't come from IO.
+    ///
+    /// This can be used to tell apart e.g. a truncated input ([`UnexpectedEof`]) from a file that
+    /// can't be read ([`PermissionDenied`]).
+    ///
+    /// [`UnexpectedEof`]: std::io::ErrorKind::UnexpectedEof
+    /// [`PermissionDenied`]: std::io::ErrorKind::PermissionDenied
+    pub fn io_kind(&self) -> Option<std::io::ErrorKind> {
+        match self.without_path() {
+            Error::IoError(e) => Some(e.kind()),
+            _ => None,
+        }
+    }
+
+    /// Returns the path to the field that failed, if the error has one, see [`Error::AtPath`].
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Error::AtPath { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the error of the field that failed, without the path of an [`Error::AtPath`].
+    pub fn without_path(&self) -> &Error {
+        match self {
+            Error::AtPath { source, .. } => source,
+            e => e,
+        }
+    }
+
+    /// Adds the id of a field to the start of the path of the error.
+    #[doc(hidden)]
+    pub fn in_field(self, field: &str) -> Self {
+        match self {
+            Error::AtPath { path, source } => {
+                let separator = if path.starts_with('[') { "" } else { "." };
+                Error::AtPath {
+                    path: format!("{}{}{}", field, separator, path),
+                    source,
+                }
+            }
+            e => Error::AtPath {
+                path: field.to_owned(),
+                source: Box::new(e),
+            },
+        }
+    }
+
+    /// Adds the index of an element to the start of the path of the error.
+    #[doc(hidden)]
+    pub fn in_element(self, index: usize) -> Self {
+        match self {
+            Error::AtPath { path, source } => Error::AtPath {
+                path: format!("[{}].{}", index, path),
+                source,
+            },
+            e => Error::AtPath {
+                path: format!("[{}]", index),
+                source: Box::new(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn io_kind() {
+        let err: Error = io::Error::new(io::ErrorKind::PermissionDenied, "no access").into();
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::PermissionDenied));
+        assert_eq!(err.to_string(), "no access");
+
+        let err: Error = io::Error::from(io::ErrorKind::UnexpectedEof).into();
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::UnexpectedEof));
+
+        assert_eq!(Error::NoEnumMatch.io_kind(), None);
+    }
+
+    #[test]
+    fn paths() {
+        let err = Error::from(io::Error::from(io::ErrorKind::UnexpectedEof))
+            .in_field("name")
+            .in_element(3)
+            .in_field("entries")
+            .in_field("header");
+        assert_eq!(err.path(), Some("header.entries[3].name"));
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::UnexpectedEof));
+        assert!(err.to_string().starts_with("header.entries[3].name: "));
+
+        let err = Error::NoEnumMatch.in_element(0).in_field("kinds");
+        assert_eq!(err.path(), Some("kinds[0]"));
+        assert!(matches!(err.without_path(), Error::NoEnumMatch));
+        assert_eq!(Error::NoEnumMatch.path(), None);
+    }
+
+    #[test]
+    fn contents_diff() {
+        let err = Error::UnexpectedContents {
+            field: Some("magic".to_owned()),
+            actual: vec![0x89, 0x51, 0x4e, 0x47],
+            expected: vec![0x89, 0x50, 0x4e, 0x47],
+        };
+        assert_eq!(
+            err.to_string(),
+            [
+                "unexpected fixed contents of magic, first mismatch at offset 1",
+                "expected: 89 50 4e 47",
+                "  actual: 89 51 4e 47",
+                "             ^^",
+            ]
+            .join("\n")
+        );
+
+        let err = Error::UnexpectedContents {
+            field: None,
+            actual: vec![0],
+            expected: vec![1],
+        };
+        assert!(err
+            .to_string()
+            .starts_with("unexpected fixed contents, first mismatch at offset 0\n"));
+    }
+}
@@ -19,7 +19,7 @@
 //! ```
 //! The Rust code to read a `basic_be` buffer would look something like this:
 //! ```
-//! # use kaitai::{kaitai_source, KaitaiStruct, error::Result};
+//! # use kaitai::prelude::*;
 //! #[kaitai_source("../tests/formats/basic_be.ksy")]
 //! struct BasicBigEndian;
 //!
@@ -38,7 +38,233 @@
 //! The filepath provided to [`kaitai_source`] is taken relative to the current file, similarly to how
 //! modules are found. However, the filepath provided to [`from_file`](KaitaiStruct::from_file) is taken relative to the root
 //! of the project, like [`std::fs::File::open`].
-#![feature(extend_one, seek_stream_len)]
+//!
+//! Small specs, e.g. in tests and examples, can be given inline instead of a path, with
+//! `#[kaitai_source(inline = "meta:\n  id: foo\n...")]`. Their imports are relative to the
+//! current file.
+//!
+//! The root type is named after the struct the macro is applied to and is `pub`. Both can be
+//! changed with arguments after the path, e.g.
+//! `#[kaitai_source("foo.ksy", root = "MyRoot", visibility = "pub(crate)")]`.
+//! The root type also implements `TryFrom<&[u8]>`, which is
+//! [`from_bytes`](KaitaiStruct::from_bytes).
+//!
+//! With the `to_bytes` argument, e.g. `#[kaitai_source("header.ksy", to_bytes)]`, the root type
+//! also has a `to_bytes` method, which writes its fields back to bytes in the order and with the
+//! endianness they are read with, e.g. to patch a header. It is only supported by types whose
+//! size doesn't depend on their values: the fields must be integers, floats, enums, fixed
+//! contents or byte arrays of a literal size, without `repeat` or `if`. Other types are a compile
+//! error, and byte arrays that were resized are an [`Error::SizeMismatch`](error::Error::SizeMismatch).
+//!
+//! With the `layout` argument, the root type has a `LAYOUT` constant listing the offset and size
+//! of each of its fields, e.g. to check a format against the definition of a hardware struct. The
+//! fields must have a fixed size, like the ones of `to_bytes`.
+//!
+//! Every generated type also has a `schema` function listing the name, Rust type and offset of
+//! each field of its `seq` as a [`FieldInfo`], without parsing anything. Unlike `LAYOUT`, it
+//! accepts fields of any size, and only the offsets after a field of a variable size are unknown.
+//!
+//! With the `module` argument, the generated types are placed in a module named after the root
+//! type in snake case, or given by `module = "name"`, so that the types of `types` and the enums
+//! don't clash with other items, e.g. `my_root::SubType`. The root type is also re-exported next
+//! to the module, which gets the visibility of the root type.
+//!
+//! With the `newtypes` argument, the types of `types` whose `seq` is a single field, without
+//! parameters or instances, are generated as newtypes, e.g. `pub struct Length(pub u32)`, which
+//! implement `Deref` to the field and `From` in both directions. The root type is never a newtype,
+//! and an enum field, which also stores its raw value, isn't made one. Since the field is `.0`,
+//! the expressions of other types can't refer to it by its id.
+//!
+//! With the `borrowed` argument, the byte arrays and strings of the generated types borrow from
+//! the slice they are parsed from rather than being copied, as `&'a [u8]` and `Cow<'a, str>`. The
+//! types then have a lifetime `'a` and don't implement [`KaitaiStruct`]: the root type is parsed
+//! with `from_slice(data)` or `TryFrom<&'a [u8]>`, from a [`SliceStream`], and only allocates the
+//! strings it has to transcode, e.g. from UTF-16. Parameters, bit flags, switches, processed
+//! types, instances of `_parent._io`, newtypes and imports aren't supported and are a compile
+//! error.
+//!
+//! The generated types derive `Clone`, `PartialEq`, `Eq`, `PartialOrd` and `Ord`, and also `Hash`
+//! with the `hash` argument, e.g. to use parsed records as the keys of a `HashMap`. Floats are
+//! neither `Eq`, `Ord` nor `Hash`, so the types holding an `f4` or `f8` field or a float value
+//! instance, directly or through the types of their fields, only derive `PartialEq` and
+//! `PartialOrd`. The types of imported files are assumed not to hold floats.
+//! Instances are computed when a type is parsed, so a clone has their values rather than
+//! computing them again. The [`Debug`](std::fmt::Debug) implementations render byte array fields
+//! in hexadecimal, see [`HexBytes`].
+//!
+//! The types of `types` are all generated next to each other, whatever their nesting, and are
+//! named after their id rather than their path. They can still be referenced by path, e.g.
+//! `header::entry` from a sibling of `header`: the first type of the path is looked up in the
+//! types nested in the type the reference is in and then in its parents, and a path to a type that
+//! doesn't exist is a compile error.
+//!
+//! The files imported in `meta/imports` (relative to the ksy file, with the `.ksy` extension left
+//! out) aren't generated by the files importing them: each one is generated once, by its own
+//! [`kaitai_source`], and the types referring to it, e.g. `type: common` or `type: common::point`,
+//! use the types in scope of the importing struct. Several formats of a crate can then import the
+//! same file without defining its types twice:
+//!
+//! ```ignore
+//! #[kaitai_source("formats/common.ksy")]
+//! struct Common;
+//!
+//! // Both import `common`.
+//! #[kaitai_source("formats/image.ksy")]
+//! struct Image;
+//! #[kaitai_source("formats/archive.ksy", module)]
+//! struct Archive;
+//! ```
+//!
+//! With `module`, the module of a file with imports has the items in scope of the struct in scope
+//! too.
+//!
+//! Types with parameters (KS: `params`) store them as fields, before the ones of their `seq`, and
+//! are read with their arguments, e.g. `type: entry(len_entry, magic)`. Parameters can be integers,
+//! floats, booleans, strings, byte arrays (KS: `bytes`) or enums (an integer type with `enum`).
+//! Integers passed to a parameter of a wider type are converted, and other mismatches are a
+//! compile error. The root type can't have parameters.
+//!
+//! Fields of the root type can be referred to as `_root.<field>` in the expressions of the types
+//! nested in it, e.g. `if: _root.version > 1`. They are passed down like parameters, by the types
+//! reading the ones referring to them, so they have to be read once (without `if` or `repeat`),
+//! before the attribute reading the type, into a type a parameter could have. A type using
+//! `_root` can't be parsed with [`KaitaiStruct`] on its own, as it would have no root: it has a
+//! `from_reader_with_root(stream, &root)` method instead, taking the values from `root`, and
+//! calling `from_reader` on it is a compile error.
+//!
+//! Ids that are Rust keywords are escaped: fields become raw identifiers (e.g. `type` is
+//! accessed as `r#type`), except for `self`, `super` and `crate`, which can't be raw
+//! identifiers and get an underscore appended instead (e.g. `self_`).
+//!
+//! Types whose `seq` is only made of up to 64 `b1` fields, e.g. a byte of named flags, store the
+//! bits that were read (rounded up to whole bytes) instead of a field for each flag. Each flag is
+//! then a method returning a `bool`, the first flag being the most significant bit (the least
+//! significant one with `bit-endian: le`), and `bits` returns the raw bits. These types don't
+//! record spans.
+//!
+//! Bit-sized integers (KS: `bN`) in other types are `bool`s for `b1` and the smallest unsigned
+//! integer holding their bits otherwise, e.g. a `u16` for `b12`. Consecutive ones share the bits
+//! left over in the last byte they were read from, and, as in Kaitai Struct, the first attribute
+//! after them that isn't bit-sized discards those bits and starts on the next byte. Each type
+//! reads its bits separately, so a nested type, sized or not, starts on the byte after the bits
+//! of its parent, and the bits left over at its end are discarded when it returns. With an
+//! `enum`, e.g. `type: b3` and `enum: mode`, they are converted like enum fields of integer types,
+//! and the bits that were read are returned by the `_raw` method, e.g. `mode_raw`.
+//!
+//! Attributes with `size-eos: true` read the rest of the stream of their type. In a type read
+//! from a substream (i.e. with a `size`), that is the rest of the substream, not of the file, so
+//! a byte array with `size-eos` after the parsed fields of a record captures its unparsed tail.
+//!
+//! Attributes with an `if` are stored in an [`Option`], which is [`None`] when the condition is
+//! false. With the `-rust-if-default: true` key, which is specific to this crate, they are
+//! instead stored as their type, with its default value (e.g. `0`) when the condition is false.
+//! The condition of a repeated attribute is checked once, before the repetitions, so the whole
+//! array is an `Option<Vec<T>>`.
+//!
+//! Repeated attributes are stored in a [`Vec`], except those whose `repeat-expr` is a literal
+//! count, which are stored in an array of that size, e.g. a `[u8; 4]` for `repeat-expr: 4`.
+//! Other `repeat-expr` arrays of multi-byte integers and floats are read with a single read of all
+//! their bytes, e.g. with [`read_u4le_array`](KaitaiStream::read_u4le_array) for `u4le`.
+//! The `repeat-until` of an array of user types can refer to the fields of the element that was
+//! just read, e.g. `_.kind == record_kind::end` to stop at a sentinel record, which is the last
+//! element of the array as in Kaitai Struct. Fields of user types can be compared in any
+//! expression, but not be the value of an instance, whose type couldn't be inferred.
+//!
+//! Attributes with the `-rust-guid: true` key, also specific to this crate, are 16 bytes read as
+//! a [`Guid`], whose first three groups are little endian as in Windows formats, and which is
+//! displayed in the canonical `{xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx}` form.
+//!
+//! Attributes with the `-rust-bool` key are a byte read as a `bool`: with `-rust-bool: nonzero`, 0
+//! is `false` and any other value `true`, and with `-rust-bool: strict` values other than 0 and 1
+//! are an [`InvalidBool`](Error::InvalidBool) error. `to_bytes` writes `true` back as 1.
+//!
+//! Instances (KS: `instances`) are computed when the type is parsed, right after its `seq`, and
+//! are returned by methods named after them, by value for numbers and booleans and by reference
+//! otherwise. Instances with an `if` return an [`Option`]. Positional instances (KS: `pos`) seek
+//! back to where they started, so the stream is left right after the `seq`. They can be of any
+//! type, e.g. a record of a user type at an offset read in the `seq`, which is parsed once with
+//! the type and kept for the instance method to return. They can be read from the stream of the
+//! parent type with `io: _parent._io`, e.g. at offsets into the parent of a type with a `size`;
+//! the stream of a root type is its own parent stream.
+//! Value instances the `if` of a `seq` attribute refers to, e.g. `if: has_extension`, are computed
+//! right before the first attribute referring to them instead, and can only refer to the
+//! attributes before it.
+//! The branches of a conditional value (`cond ? a : b`) are converted to a common type, e.g. a
+//! `u1` and an `s1` branch make an `i16` instance, and branches without one, e.g. a string and an
+//! integer, are a compile error. Integer arithmetic in expressions is checked: an operation that
+//! overflows its type, e.g. a `pos` computed from corrupt offsets, or a division by zero, is an
+//! [`Error::ArithmeticOverflow`](error::Error::ArithmeticOverflow) rather than a wrapped around
+//! value or a panic. The remainder of `%` is never negative, as in Kaitai Struct, e.g. `-7 % 4`
+//! is 1. Arrays and byte arrays can be indexed, e.g. `offsets[selector]`: an
+//! index past the end, or a negative one, which doesn't count from the end as in some target
+//! languages, is an [`Error::IndexOutOfBounds`](error::Error::IndexOutOfBounds). Strings can't
+//! be indexed, as in Kaitai Struct, `substring` returns their characters instead.
+//! In value instances and the `if` of instances, `+` concatenates strings, e.g.
+//! `value: name + "." + ext`, which makes a `String` instance. Strings compare with `==`, `!=` and
+//! the orderings, also against literals, e.g. `if: name < "m"`.
+//! The bitwise operators `&`, `|`, `^` and `~` and the shifts `<<` and `>>` work on integers,
+//! with the precedences of Kaitai Struct, so that `flags >> 4 & 0xf == 2` masks before it
+//! compares. A shift by a negative amount or by at least the width of its integer is an
+//! [`Error::ArithmeticOverflow`](error::Error::ArithmeticOverflow).
+//!
+//! Expressions can read the `size` and `pos` of the stream of the type, and whether it is at its
+//! `eof`, as `_io.size`, `_io.pos` and `_io.eof`, at the time they are evaluated. A length that
+//! has to be the number of bytes left after it, e.g. `valid: _io.size - _io.pos` on the total
+//! length of a container, fails with an
+//! [`Error::ValidationRemainingSize`](error::Error::ValidationRemainingSize) giving both lengths,
+//! which catches truncated files before anything is read from the rest of the stream.
+//!
+//! A switch (KS: `switch-on`) is an enum with a variant for each type of its cases. A value
+//! matching no case is parsed with the `_` case if there is one. Otherwise it is an
+//! [`Error::NoSwitchMatch`](error::Error::NoSwitchMatch), unless the switch has a `size`: then the
+//! bytes are kept in an `Unknown(Vec<u8>)` variant.
+//!
+//! An `enum` can also be defined inline on an attribute, as a map of values to members instead of
+//! the id of an enum. The enum is then named after the type and the attribute, e.g. `HeaderKind`
+//! for the `kind` attribute of `header`.
+//!
+//! Enum fields can be compared to the members of their enum in expressions, e.g.
+//! `if: file_type == file_type::png`, and a member that doesn't exist in the enums in scope is a
+//! compile error.
+//! Their integer value is `to_i`, e.g. `repeat-expr: shape.to_i`, and a value that doesn't fit in an
+//! `i64` is an [`Error::ArithmeticOverflow`](error::Error::ArithmeticOverflow).
+//!
+//! The `doc` of types and attributes becomes their doc comment, followed by a list of `References`
+//! with a line for each `doc-ref`, which is either a single reference or a list of them.
+//!
+//! Types without a `meta` inherit `endian`, `encoding` and `bit-endian` from their parent types,
+//! and the keys set in the `meta` of a type only override the same keys of its parents.
+//! `meta/endian` is only required by the formats reading multi-byte numbers: one made of
+//! single bytes, byte arrays and strings doesn't need it, and a `u2` read without it is a compile
+//! error.
+//!
+//! A root type whose `meta/encoding` is the generic `UTF-16` or `UTF` looks for a byte order mark
+//! at the start of the file once, and the strings using that default encoding, in the root type
+//! and the types nested in it, are decoded in the encoding of the mark, see
+//! [`ParseContext::bom_encoding`]. An `encoding` set on a string takes precedence over the mark.
+//! Without a mark, `UTF-16` is little endian and `UTF` is UTF-8.
+//!
+//! The `endian` of a type can be calculated when it is parsed, with a `switch-on` expression and
+//! `cases` mapping its values to `le` or `be`, e.g. from a byte order mark read by the root type
+//! and referred to as `_root.order`. A value matching no case is an
+//! [`UndecidedEndianness`](Error::UndecidedEndianness) error. The types nested in it inherit the
+//! calculated endianness from the type reading them instead of calculating it again, so they can't
+//! be parsed on their own, and `to_bytes` doesn't support their multi-byte numbers.
+//!
+//! # Features
+//!
+//! - `spans`: records the byte range each field was read from. Every generated type `Foo` then
+//!   gets a `spans` method returning a `FooSpans` struct, with a `(start, end)` pair of stream
+//!   positions for each field. The spans don't take part in comparisons and hashes, so two
+//!   values read from different offsets are still equal. Positions inside a type read from a substream
+//!   (i.e. with a `size`) are relative to the start of the substream.
+//! - `error-path`: returns the errors of the fields in an
+//!   [`Error::AtPath`](error::Error::AtPath), with the path to the field that failed from the
+//!   parsed type, made of the ids of the fields and the indices of the elements of arrays, e.g.
+//!   `header.entries[3].name: end of stream reached, ...`. The recoverable errors collected by
+//!   lenient parses and the errors of value instances don't have a path, and
+//!   [`Error::without_path`](error::Error::without_path) returns the error of the field.
+#![feature(extend_one, min_specialization, seek_stream_len)]
 #![deny(
     non_ascii_idents,
     missing_docs,
@@ -51,14 +277,57 @@
 )]
 
 pub mod error;
+pub use error::{Error, Result};
 
 #[doc(inline)]
 pub use kaitai_macros::kaitai_source;
 
+/// The version of Kaitai Struct whose features this crate implements.
+///
+/// Formats that require a newer version with `meta/ks-version` fail to compile, as they may use
+/// features the crate would silently ignore or get wrong.
+pub const KS_VERSION: &str = "0.9";
+
 mod runtime;
-pub use runtime::KaitaiStruct;
+pub use runtime::{
+    bytes_strip_right, bytes_terminate, bytes_to_str, fixed_point, process_xor_many,
+    process_xor_one, process_zlib, slice_strip_right, slice_terminate, slice_to_str, BitReader,
+    Decoding, Endian, FieldInfo, Guid, HashingStream, HexBytes, KaitaiStream, KaitaiStruct,
+    ParseContext, RotateStream, SliceStream, SubStream, TerminatorFlags, TrackingReader, XorStream,
+};
+
+/// Re-exports of the items needed by most users of this crate.
+///
+/// ```
+/// use kaitai::prelude::*;
+///
+/// #[kaitai_source("../tests/formats/basic_le.ksy")]
+/// struct BasicLittleEndian;
+///
+/// fn main() -> Result<()> {
+///     // `from_bytes` is in scope as the prelude includes `KaitaiStruct`.
+///     let basic = BasicLittleEndian::from_bytes(&[0; 14])?;
+///     assert_eq!(basic.tail, 0);
+/// #   Ok(())
+/// }
+/// ```
+pub mod prelude {
+    pub use crate::{
+        error::{Error, Result},
+        kaitai_source,
+        runtime::{Endian, KaitaiStream, KaitaiStruct, TerminatorFlags},
+    };
+}
 
 #[doc(hidden)]
 pub mod __private {
-    pub use crate::runtime::KaitaiStream;
+    pub use crate::runtime::{
+        methods::{KsBytes, KsEnum, KsNum, KsSeq, KsShift, KsStr},
+        path::{in_element, in_field},
+        process::{process_zlib_limited, XorKey},
+        repeat::try_from_fn,
+        spans::Spans,
+        substream::{ParentStreams, SameStream, SplitStreams},
+        KaitaiStream,
+    };
 }
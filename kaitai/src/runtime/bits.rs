@@ -0,0 +1,180 @@
+use crate::{error::Result, runtime::KaitaiStream};
+
+/// The state needed to read bit-sized integers (KS: `bN`) from a [`KaitaiStream`].
+///
+/// Bits are read from whole bytes pulled out of the stream, any bits that aren't used by a read
+/// are kept for the next one. Calling [`align_to_byte`](BitReader::align_to_byte) discards them.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct BitReader {
+    bits: u64,
+    bits_left: u32,
+}
+
+impl BitReader {
+    /// Creates a `BitReader` with no buffered bits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Discards any buffered bits so that the next read starts on a byte boundary.
+    pub fn align_to_byte(&mut self) {
+        self.bits = 0;
+        self.bits_left = 0;
+    }
+
+    /// Reads an `n` bit unsigned big endian integer, where `n` is at most 64.
+    ///
+    /// The most significant bit of each byte is read first.
+    pub fn read_bits_int_be<S: KaitaiStream + ?Sized>(
+        &mut self,
+        stream: &mut S,
+        n: u32,
+    ) -> Result<u64> {
+        assert!(n <= 64, "cannot read more than 64 bits at once");
+
+        // At most 7 buffered bits and 64 new bits, so the accumulator can't overflow.
+        let mut acc = u128::from(self.bits);
+        let mut available = self.bits_left;
+        while available < n {
+            acc = (acc << 8) | u128::from(stream.read_u1()?);
+            available += 8;
+        }
+
+        let extra = available - n;
+        self.bits = (acc & mask(extra)) as u64;
+        self.bits_left = extra;
+
+        Ok(((acc >> extra) & mask(n)) as u64)
+    }
+
+    /// Reads an `n` bit unsigned little endian integer, where `n` is at most 64.
+    ///
+    /// The least significant bit of each byte is read first.
+    pub fn read_bits_int_le<S: KaitaiStream + ?Sized>(
+        &mut self,
+        stream: &mut S,
+        n: u32,
+    ) -> Result<u64> {
+        assert!(n <= 64, "cannot read more than 64 bits at once");
+
+        let mut acc = u128::from(self.bits);
+        let mut available = self.bits_left;
+        while available < n {
+            acc |= u128::from(stream.read_u1()?) << available;
+            available += 8;
+        }
+
+        self.bits = (acc >> n) as u64;
+        self.bits_left = available - n;
+
+        Ok((acc & mask(n)) as u64)
+    }
+
+    /// Reads an `n` bit signed big endian integer, where `n` is at most 64.
+    ///
+    /// The value is interpreted as two's complement, i.e. bit `n - 1` is the sign bit. This is
+    /// the only interpretation used by Kaitai Struct; one's complement and sign-magnitude values
+    /// have to be decoded manually from [`read_bits_int_be`](BitReader::read_bits_int_be).
+    pub fn read_bits_int_signed_be<S: KaitaiStream + ?Sized>(
+        &mut self,
+        stream: &mut S,
+        n: u32,
+    ) -> Result<i64> {
+        self.read_bits_int_be(stream, n).map(|v| sign_extend(v, n))
+    }
+
+    /// Reads an `n` bit signed little endian integer, where `n` is at most 64.
+    ///
+    /// See [`read_bits_int_signed_be`](BitReader::read_bits_int_signed_be) for how the sign is
+    /// interpreted.
+    pub fn read_bits_int_signed_le<S: KaitaiStream + ?Sized>(
+        &mut self,
+        stream: &mut S,
+        n: u32,
+    ) -> Result<i64> {
+        self.read_bits_int_le(stream, n).map(|v| sign_extend(v, n))
+    }
+}
+
+/// Returns a mask of the `n` lowest bits.
+fn mask(n: u32) -> u128 {
+    (1 << n) - 1
+}
+
+/// Sign extends the two's complement `n` bit value `value` to 64 bits.
+fn sign_extend(value: u64, n: u32) -> i64 {
+    if n == 0 {
+        return 0;
+    }
+    // When n is 64 the shift is 0, so nothing can overflow.
+    let shift = 64 - n;
+    ((value << shift) as i64) >> shift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_bits_int_be() {
+        let mut buf = Cursor::new(vec![0b1010_1100, 0b0101_0011]);
+        let mut bits = BitReader::new();
+
+        assert_eq!(bits.read_bits_int_be(&mut buf, 3).unwrap(), 0b101);
+        assert_eq!(bits.read_bits_int_be(&mut buf, 1).unwrap(), 0b0);
+        assert_eq!(bits.read_bits_int_be(&mut buf, 8).unwrap(), 0b1100_0101);
+        assert_eq!(bits.read_bits_int_be(&mut buf, 4).unwrap(), 0b0011);
+    }
+
+    #[test]
+    fn read_bits_int_le() {
+        let mut buf = Cursor::new(vec![0b1010_1100, 0b0101_0011]);
+        let mut bits = BitReader::new();
+
+        assert_eq!(bits.read_bits_int_le(&mut buf, 3).unwrap(), 0b100);
+        assert_eq!(bits.read_bits_int_le(&mut buf, 1).unwrap(), 0b1);
+        assert_eq!(bits.read_bits_int_le(&mut buf, 8).unwrap(), 0b0011_1010);
+        assert_eq!(bits.read_bits_int_le(&mut buf, 4).unwrap(), 0b0101);
+    }
+
+    #[test]
+    fn read_bits_int_64() {
+        let mut buf = Cursor::new(vec![0xff; 9]);
+        let mut bits = BitReader::new();
+
+        assert_eq!(bits.read_bits_int_be(&mut buf, 4).unwrap(), 0xf);
+        assert_eq!(bits.read_bits_int_be(&mut buf, 64).unwrap(), u64::MAX);
+
+        buf.set_position(0);
+        bits.align_to_byte();
+        assert_eq!(bits.read_bits_int_signed_le(&mut buf, 64).unwrap(), -1);
+    }
+
+    #[test]
+    fn read_bits_int_signed() {
+        let mut buf = Cursor::new(vec![0b1111_1000]);
+        let mut bits = BitReader::new();
+
+        assert_eq!(bits.read_bits_int_signed_be(&mut buf, 4).unwrap(), -1);
+        assert_eq!(bits.read_bits_int_signed_be(&mut buf, 4).unwrap(), -8);
+
+        buf.set_position(0);
+        bits.align_to_byte();
+        assert_eq!(bits.read_bits_int_signed_le(&mut buf, 4).unwrap(), -8);
+        assert_eq!(bits.read_bits_int_signed_le(&mut buf, 4).unwrap(), -1);
+
+        let mut buf = Cursor::new(vec![0b0111_0000]);
+        assert_eq!(bits.read_bits_int_signed_be(&mut buf, 4).unwrap(), 7);
+    }
+
+    #[test]
+    fn align_to_byte() {
+        let mut buf = Cursor::new(vec![0b1010_1100, 0b0101_0011]);
+        let mut bits = BitReader::new();
+
+        assert_eq!(bits.read_bits_int_be(&mut buf, 3).unwrap(), 0b101);
+        bits.align_to_byte();
+        assert_eq!(bits.read_bits_int_be(&mut buf, 3).unwrap(), 0b010);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::{
+    error::{Error, Result},
+    runtime::KaitaiStream,
+};
+
+use std::cell::{Cell, RefCell};
+
+/// The default [recursion limit](ParseContext::with_recursion_limit).
+const DEFAULT_RECURSION_LIMIT: usize = 128;
+
+/// The settings of a parse, passed to
+/// [`KaitaiStruct::from_reader_with`](crate::runtime::KaitaiStruct::from_reader_with).
+///
+/// The other constructors of [`KaitaiStruct`](crate::runtime::KaitaiStruct) use the default
+/// settings.
+///
+/// ```
+/// # use kaitai::{Decoding, ParseContext};
+/// let ctx = ParseContext::new().with_decoding(Decoding::Lossy);
+/// assert_eq!(ctx.decoding(), Decoding::Lossy);
+/// ```
+#[derive(Debug)]
+pub struct ParseContext {
+    decoding: Decoding,
+    lenient: bool,
+    reject_trailing: bool,
+    allocation_limit: Option<u64>,
+    recursion_limit: usize,
+    /// The number of user types being parsed within the current one.
+    depth: Cell<usize>,
+    /// The recoverable errors collected in lenient mode.
+    errors: RefCell<Vec<Error>>,
+    /// The encoding of the byte order mark at the start of the file, once it was looked for.
+    bom_encoding: Cell<Option<Option<&'static str>>>,
+}
+
+impl Default for ParseContext {
+    fn default() -> Self {
+        Self {
+            decoding: Decoding::default(),
+            lenient: false,
+            reject_trailing: false,
+            allocation_limit: None,
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            depth: Cell::new(0),
+            errors: RefCell::default(),
+            bom_encoding: Cell::new(None),
+        }
+    }
+}
+
+impl ParseContext {
+    /// Creates a `ParseContext` with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how strings with invalid bytes are decoded.
+    pub fn with_decoding(mut self, decoding: Decoding) -> Self {
+        self.decoding = decoding;
+        self
+    }
+
+    /// Returns how strings with invalid bytes are decoded.
+    pub fn decoding(&self) -> Decoding {
+        self.decoding
+    }
+
+    /// Sets whether the parse is lenient, i.e. whether recoverable errors are collected rather
+    /// than returned. Lenient parses are off by default.
+    ///
+    /// The recoverable errors are failed `valid` checks, in which case the field keeps the value
+    /// that was read, and enum values without a matching variant, in which case the field gets
+    /// the variant with the smallest value and the value that was read is still returned by the
+    /// `_raw` accessor of the field. All the other errors, e.g. IO errors, still end the parse.
+    ///
+    /// ```
+    /// # use kaitai::{prelude::*, ParseContext};
+    /// # use std::io::Cursor;
+    /// #[kaitai_source("../../tests/formats/valid.ksy")]
+    /// struct Valid;
+    ///
+    /// let ctx = ParseContext::new().with_lenient(true);
+    /// let valid = Valid::from_reader_with(&mut Cursor::new([0x7e, 1, 0, 0]), &ctx)?;
+    /// assert_eq!(valid.magic, 0x7e);
+    ///
+    /// let errors = ctx.take_errors();
+    /// assert_eq!(errors[0].to_string(), "magic is not valid, expected 127 but got 126");
+    /// # Ok::<(), kaitai::Error>(())
+    /// ```
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Returns whether the parse is lenient.
+    pub fn lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Sets whether bytes left in the stream after the parsed type are a
+    /// [`TrailingData`](Error::TrailingData) error, e.g. to validate files that must be exactly
+    /// one record. Trailing data is accepted by default.
+    ///
+    /// Only the stream the type is parsed from is checked, by
+    /// [`from_reader_with`](crate::runtime::KaitaiStruct::from_reader_with): the bytes left in the
+    /// substreams of the nested types (KS: `size`) are still ignored.
+    ///
+    /// ```
+    /// # use kaitai::{prelude::*, Error, ParseContext};
+    /// # use std::io::Cursor;
+    /// #[kaitai_source("../../tests/formats/basic_le.ksy")]
+    /// struct Record;
+    ///
+    /// let ctx = ParseContext::new().with_reject_trailing(true);
+    /// let err = Record::from_reader_with(&mut Cursor::new([0; 16]), &ctx).unwrap_err();
+    /// assert!(matches!(err, Error::TrailingData { remaining: 2 }));
+    /// ```
+    pub fn with_reject_trailing(mut self, reject_trailing: bool) -> Self {
+        self.reject_trailing = reject_trailing;
+        self
+    }
+
+    /// Returns whether bytes left after the parsed type are an error.
+    pub fn reject_trailing(&self) -> bool {
+        self.reject_trailing
+    }
+
+    /// Sets the maximum number of bytes a single attribute can read into memory, so that a
+    /// corrupt or malicious size (KS: `size`) doesn't make the parse allocate gigabytes. There is
+    /// no limit by default.
+    ///
+    /// Attributes over the limit, or over the number of bytes left in the stream, are an
+    /// [`AllocationLimitExceeded`](Error::AllocationLimitExceeded) error before anything is
+    /// allocated. Attributes read to the end of the stream (KS: `size-eos`) are bounded by the
+    /// stream and aren't checked.
+    ///
+    /// ```
+    /// # use kaitai::{prelude::*, Error, ParseContext};
+    /// # use std::io::Cursor;
+    /// #[kaitai_source("../../tests/formats/length_prefixed.ksy")]
+    /// struct Message;
+    ///
+    /// let ctx = ParseContext::new().with_allocation_limit(1024);
+    /// let bytes = [0xff, 0xff, 0xff, 0xff, 1, 2, 3];
+    /// let err = Message::from_reader_with(&mut Cursor::new(bytes), &ctx).unwrap_err();
+    /// assert!(matches!(
+    ///     err.without_path(),
+    ///     Error::AllocationLimitExceeded { requested: 0xffff_ffff, .. }
+    /// ));
+    /// ```
+    pub fn with_allocation_limit(mut self, limit: u64) -> Self {
+        self.allocation_limit = Some(limit);
+        self
+    }
+
+    /// Returns the maximum number of bytes a single attribute can read, if there is a limit.
+    pub fn allocation_limit(&self) -> Option<u64> {
+        self.allocation_limit
+    }
+
+    /// Sets the maximum number of user types that can be nested in the parsed type, e.g. the depth
+    /// of a tree of recursive nodes, so that corrupt data doesn't overflow the stack. The limit is
+    /// 128 by default.
+    ///
+    /// Parsing a user type deeper than the limit is a
+    /// [`RecursionLimitExceeded`](Error::RecursionLimitExceeded) error.
+    pub fn with_recursion_limit(mut self, limit: usize) -> Self {
+        self.recursion_limit = limit;
+        self
+    }
+
+    /// Returns the maximum number of user types that can be nested in the parsed type.
+    pub fn recursion_limit(&self) -> usize {
+        self.recursion_limit
+    }
+
+    /// Parses a user type nested in the one being parsed, checking the recursion limit first.
+    #[doc(hidden)]
+    pub fn nested<T>(&self, parse: impl FnOnce() -> Result<T>) -> Result<T> {
+        let depth = self.depth.get();
+        if depth >= self.recursion_limit {
+            return Err(Error::RecursionLimitExceeded(self.recursion_limit));
+        }
+        self.depth.set(depth + 1);
+        let result = parse();
+        self.depth.set(depth);
+        result
+    }
+
+    /// Reads the bytes of an attribute with a size, checking the size against the allocation
+    /// limit and the rest of the stream first if there is a limit.
+    #[doc(hidden)]
+    pub fn read_bytes<S: KaitaiStream + ?Sized>(&self, buf: &mut S, count: u64) -> Result<Vec<u8>> {
+        if let Some(limit) = self.allocation_limit {
+            let left = buf.size()?.saturating_sub(buf.pos()?);
+            let limit = limit.min(left);
+            if count > limit {
+                return Err(Error::AllocationLimitExceeded {
+                    requested: count,
+                    limit,
+                });
+            }
+        }
+        buf.read_bytes(count as usize)
+    }
+
+    /// Returns the encoding given by the byte order mark at the start of the file, `UTF-8`,
+    /// `UTF-16LE` or `UTF-16BE`, if the parsed type looked for one and found it.
+    ///
+    /// The mark is looked for once per parse, by a root type whose `meta/encoding` is the generic
+    /// `UTF-16` or `UTF`, at the position it is parsed from. Each parse with the same context looks
+    /// for it again, so this is the mark of the last one. The strings of the types using that
+    /// default encoding are then decoded in the encoding of the mark, while the strings with an
+    /// `encoding` of their own keep it.
+    ///
+    /// ```
+    /// # use kaitai::{prelude::*, ParseContext};
+    /// # use std::io::Cursor;
+    /// #[kaitai_source(inline = "
+    /// meta:
+    ///   id: text
+    ///   endian: le
+    ///   encoding: UTF-16
+    /// seq:
+    ///   - id: bom
+    ///     size: 2
+    ///   - id: line
+    ///     type: str
+    ///     size-eos: true
+    /// ")]
+    /// struct Text;
+    ///
+    /// let ctx = ParseContext::new();
+    /// let text = Text::from_reader_with(&mut Cursor::new(b"\xfe\xff\0h\0i"), &ctx)?;
+    /// assert_eq!(text.line, "hi");
+    /// assert_eq!(ctx.bom_encoding(), Some("UTF-16BE"));
+    /// # Ok::<(), kaitai::Error>(())
+    /// ```
+    pub fn bom_encoding(&self) -> Option<&'static str> {
+        self.bom_encoding.get().flatten()
+    }
+
+    /// Looks for a byte order mark at the position of `buf`, unless it was already looked for
+    /// by the same parse, and leaves the position unchanged.
+    #[doc(hidden)]
+    pub fn detect_bom<S: KaitaiStream + ?Sized>(&self, buf: &mut S) -> Result<()> {
+        // The root type is parsed at a depth of 0, so a mark found at that depth comes from a
+        // previous parse with the same context and is looked for again.
+        if self.depth.get() > 0 && self.bom_encoding.get().is_some() {
+            return Ok(());
+        }
+        let pos = buf.pos()?;
+        let mut bom = Vec::with_capacity(3);
+        while bom.len() < 3 {
+            match buf.try_read_u1()? {
+                Some(byte) => bom.push(byte),
+                None => break,
+            }
+        }
+        std::io::Seek::seek(buf, std::io::SeekFrom::Start(pos))?;
+        let encoding = match bom.as_slice() {
+            [0xef, 0xbb, 0xbf] => Some("UTF-8"),
+            [0xff, 0xfe, ..] => Some("UTF-16LE"),
+            [0xfe, 0xff, ..] => Some("UTF-16BE"),
+            _ => None,
+        };
+        self.bom_encoding.set(Some(encoding));
+        Ok(())
+    }
+
+    /// Returns the encoding the strings with the generic default encoding `label`, `UTF-16` or
+    /// `UTF`, are decoded in: the encoding of the byte order mark if it is one of `label`, and
+    /// otherwise `label` itself for `UTF-16`, which is little endian, and `UTF-8` for `UTF`.
+    #[doc(hidden)]
+    pub fn resolve_encoding<'a>(&self, label: &'a str) -> &'a str {
+        let generic = label.eq_ignore_ascii_case("UTF");
+        match self.bom_encoding() {
+            Some(encoding) if generic || encoding != "UTF-8" => encoding,
+            _ if generic => "UTF-8",
+            _ => label,
+        }
+    }
+
+    /// Returns the recoverable errors collected so far by lenient parses, in the order they
+    /// happened, and clears them.
+    pub fn take_errors(&self) -> Vec<Error> {
+        self.errors.take()
+    }
+
+    /// Handles a recoverable error: collects it if the parse is lenient, and returns it
+    /// otherwise.
+    #[doc(hidden)]
+    pub fn recoverable(&self, error: Error) -> Result<()> {
+        if self.lenient {
+            self.errors.borrow_mut().push(error);
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+}
+
+impl Clone for ParseContext {
+    /// Returns a `ParseContext` with the same settings, without the collected errors.
+    fn clone(&self) -> Self {
+        Self {
+            decoding: self.decoding,
+            lenient: self.lenient,
+            reject_trailing: self.reject_trailing,
+            allocation_limit: self.allocation_limit,
+            recursion_limit: self.recursion_limit,
+            depth: Cell::new(0),
+            errors: RefCell::default(),
+            bom_encoding: Cell::new(None),
+        }
+    }
+}
+
+/// How bytes that are invalid in the encoding of a string (KS: `type: str` and `strz`) are
+/// handled.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Decoding {
+    /// Invalid bytes are an [`InvalidString`](crate::error::Error::InvalidString) error, so that
+    /// corrupted data isn't silently accepted.
+    #[default]
+    Strict,
+    /// Invalid bytes are replaced with U+FFFD REPLACEMENT CHARACTER.
+    Lossy,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recoverable() {
+        let ctx = ParseContext::new();
+        assert!(ctx.recoverable(Error::NoEnumMatch).is_err());
+        assert!(ctx.take_errors().is_empty());
+
+        let ctx = ParseContext::new().with_lenient(true);
+        assert!(ctx.recoverable(Error::NoEnumMatch).is_ok());
+        assert!(ctx.clone().take_errors().is_empty());
+        assert_eq!(ctx.take_errors().len(), 1);
+        assert!(ctx.take_errors().is_empty());
+    }
+
+    #[test]
+    fn nested() {
+        let ctx = ParseContext::new().with_recursion_limit(2);
+        let depth = ctx.nested(|| ctx.nested(|| Ok(ctx.depth.get()))).unwrap();
+        assert_eq!(depth, 2);
+        assert_eq!(ctx.depth.get(), 0);
+        assert!(matches!(
+            ctx.nested(|| ctx.nested(|| ctx.nested(|| Ok(())))),
+            Err(Error::RecursionLimitExceeded(2))
+        ));
+        assert_eq!(ctx.depth.get(), 0);
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        let detect = |bytes: &[u8]| {
+            let ctx = ParseContext::new();
+            let mut stream = std::io::Cursor::new(bytes);
+            ctx.detect_bom(&mut stream).unwrap();
+            assert_eq!(stream.position(), 0);
+            ctx
+        };
+        let ctx = detect(b"\xff\xfeh\0");
+        assert_eq!(ctx.bom_encoding(), Some("UTF-16LE"));
+        assert_eq!(ctx.resolve_encoding("utf-16"), "UTF-16LE");
+        // The mark is only looked for once per parse, not by the types nested in the root.
+        ctx.nested(|| ctx.detect_bom(&mut std::io::Cursor::new(b"\xfe\xff")))
+            .unwrap();
+        assert_eq!(ctx.bom_encoding(), Some("UTF-16LE"));
+        // The next parse looks for it again.
+        ctx.detect_bom(&mut std::io::Cursor::new(b"\xfe\xff"))
+            .unwrap();
+        assert_eq!(ctx.bom_encoding(), Some("UTF-16BE"));
+
+        let ctx = detect(b"\xef\xbb\xbfhi");
+        assert_eq!(ctx.resolve_encoding("UTF"), "UTF-8");
+        assert_eq!(ctx.resolve_encoding("UTF-16"), "UTF-16");
+
+        let ctx = detect(b"h");
+        assert_eq!(ctx.bom_encoding(), None);
+        assert_eq!(ctx.resolve_encoding("UTF"), "UTF-8");
+        assert_eq!(ctx.resolve_encoding("UTF-16"), "UTF-16");
+    }
+}
@@ -0,0 +1,44 @@
+use std::convert::TryFrom;
+
+/// Converts the raw integer of a fixed-point number with `fractional_bits` bits after the binary
+/// point to a float, e.g. 16 for the 16.16 numbers of TrueType tables.
+///
+/// Fixed-point numbers aren't a type of Kaitai Struct, so they are read as integers, e.g. a 16.16
+/// number as an `s4`, and converted from the generated types:
+///
+/// ```
+/// # use kaitai::{fixed_point, prelude::*};
+/// #[kaitai_source(inline = "
+/// meta:
+///   id: table
+///   endian: be
+/// seq:
+///   - id: version
+///     type: s4
+/// ")]
+/// struct Table;
+///
+/// let table = Table::from_bytes(&[0x00, 0x01, 0x80, 0x00])?;
+/// assert_eq!(fixed_point(table.version, 16), 1.5);
+/// # Ok::<(), kaitai::Error>(())
+/// ```
+pub fn fixed_point(raw: i32, fractional_bits: u32) -> f64 {
+    let exponent = i32::try_from(fractional_bits).unwrap_or(i32::MAX);
+    f64::from(raw) / 2f64.powi(exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(fixed_point(0x0001_0000, 16), 1.0);
+        assert_eq!(fixed_point(0x0003_4000, 16), 3.25);
+        assert_eq!(fixed_point(-0x0000_8000, 16), -0.5);
+        assert_eq!(fixed_point(i32::MIN, 16), -32768.0);
+        assert_eq!(fixed_point(0x40, 6), 1.0);
+        assert_eq!(fixed_point(5, 0), 5.0);
+        assert_eq!(fixed_point(1, u32::MAX), 0.0);
+    }
+}
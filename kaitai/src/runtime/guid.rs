@@ -0,0 +1,101 @@
+use crate::{error::Result, runtime::KaitaiStream};
+
+use std::fmt;
+
+/// A GUID (or UUID) in the mixed-endian layout of Windows formats, used by the generated types
+/// for the fields with the `-rust-guid: true` key, which is specific to this crate.
+///
+/// The bytes are kept as they are read, and the [`Display`](fmt::Display) implementation renders
+/// the canonical form, in which the first three groups are little endian:
+///
+/// ```
+/// # use kaitai::Guid;
+/// let guid = Guid([
+///     0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff,
+/// ]);
+/// assert_eq!(guid.to_string(), "{00112233-4455-6677-8899-aabbccddeeff}");
+/// ```
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    /// Reads the 16 bytes of a GUID from `stream`.
+    pub fn read<S: KaitaiStream + ?Sized>(stream: &mut S) -> Result<Self> {
+        let mut bytes = [0; 16];
+        stream.read_exact(&mut bytes)?;
+        Ok(Self(bytes))
+    }
+
+    /// Returns the first group, the little endian `u32` of the first 4 bytes.
+    pub fn data1(&self) -> u32 {
+        u32::from_le_bytes([self.0[0], self.0[1], self.0[2], self.0[3]])
+    }
+
+    /// Returns the second group, the little endian `u16` of bytes 4 and 5.
+    pub fn data2(&self) -> u16 {
+        u16::from_le_bytes([self.0[4], self.0[5]])
+    }
+
+    /// Returns the third group, the little endian `u16` of bytes 6 and 7.
+    pub fn data3(&self) -> u16 {
+        u16::from_le_bytes([self.0[6], self.0[7]])
+    }
+
+    /// Returns the last 8 bytes, which are in the order they are read in.
+    pub fn data4(&self) -> [u8; 8] {
+        let mut data4 = [0; 8];
+        data4.copy_from_slice(&self.0[8..]);
+        data4
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data4 = self.data4();
+        write!(
+            f,
+            "{{{:08x}-{:04x}-{:04x}-{:02x}{:02x}-",
+            self.data1(),
+            self.data2(),
+            self.data3(),
+            data4[0],
+            data4[1]
+        )?;
+        for byte in &data4[2..] {
+            write!(f, "{:02x}", byte)?;
+        }
+        f.write_str("}")
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn read() {
+        // The GUID of the ASF header object.
+        let bytes = [
+            0x30, 0x26, 0xb2, 0x75, 0x8e, 0x66, 0xcf, 0x11, 0xa6, 0xd9, 0x00, 0xaa, 0x00, 0x62,
+            0xce, 0x6c, 0x01,
+        ];
+        let mut stream = Cursor::new(bytes);
+        let guid = Guid::read(&mut stream).unwrap();
+        assert_eq!(guid.data1(), 0x75b2_2630);
+        assert_eq!(
+            format!("{:?}", guid),
+            "{75b22630-668e-11cf-a6d9-00aa0062ce6c}"
+        );
+        assert_eq!(guid.0, bytes[..16]);
+
+        assert!(Guid::read(&mut stream).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+
+/// A byte array whose [`Debug`](fmt::Debug) implementation renders the bytes in hexadecimal,
+/// used by the generated types for their byte array fields.
+///
+/// The bytes are rendered on a single line, e.g. `01 02 03`, or as a hex dump with the offset of
+/// each line with the alternate flag (`{:#?}`). Long arrays are truncated, followed by their
+/// length.
+///
+/// ```
+/// # use kaitai::HexBytes;
+/// assert_eq!(format!("{:?}", HexBytes(&[1, 2, 0xff])), "01 02 ff");
+/// assert_eq!(
+///     format!("{:?}", HexBytes(&[0; 100])),
+///     format!("{} ... (100 bytes)", ["00"; 32].join(" "))
+/// );
+/// assert_eq!(
+///     format!("{:#?}", HexBytes(&(0..20).collect::<Vec<_>>())),
+///     "00000000  00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n\
+///      00000010  10 11 12 13"
+/// );
+/// ```
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct HexBytes<'a>(pub &'a [u8]);
+
+impl HexBytes<'_> {
+    /// The number of bytes rendered on a single line.
+    const LINE_LIMIT: usize = 32;
+    /// The number of bytes rendered as a hex dump.
+    const DUMP_LIMIT: usize = 256;
+    /// The number of bytes on each line of a hex dump.
+    const DUMP_WIDTH: usize = 16;
+
+    fn write_line(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+        for (i, byte) in bytes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for HexBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = if f.alternate() {
+            Self::DUMP_LIMIT
+        } else {
+            Self::LINE_LIMIT
+        };
+        let shown = &self.0[..self.0.len().min(limit)];
+
+        if f.alternate() {
+            for (i, line) in shown.chunks(Self::DUMP_WIDTH).enumerate() {
+                if i > 0 {
+                    f.write_str("\n")?;
+                }
+                write!(f, "{:08x}  ", i * Self::DUMP_WIDTH)?;
+                Self::write_line(f, line)?;
+            }
+        } else {
+            Self::write_line(f, shown)?;
+        }
+
+        if shown.len() < self.0.len() {
+            let separator = if f.alternate() { "\n" } else { " " };
+            write!(f, "{}... ({} bytes)", separator, self.0.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        assert_eq!(format!("{:?}", HexBytes(&[])), "");
+        assert_eq!(format!("{:#?}", HexBytes(&[])), "");
+    }
+
+    #[test]
+    fn truncated_dump() {
+        let bytes = [0xab; 300];
+        let dump = format!("{:#?}", HexBytes(&bytes));
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 17);
+        assert!(lines[15].starts_with("000000f0  ab ab"));
+        assert_eq!(lines[16], "... (300 bytes)");
+    }
+}
@@ -1,6 +1,9 @@
 // The contents of this file are **heavily** inspired by https://github.com/kaitai-io/kaitai_struct_rust_runtime.
 // Although this file is not a copy-paste, without their work this would have been much harder.
-use crate::{error::Result, runtime::KaitaiStream};
+use crate::{
+    error::{Error, Result},
+    runtime::{substream::ParentStreams, KaitaiStream, ParseContext},
+};
 
 /// The trait that is implemented by the [kaitai_source](crate::kaitai_source) macro.
 pub trait KaitaiStruct
@@ -13,18 +16,150 @@ where
     /// of the project.
     fn from_file(path: &str) -> Result<Self> {
         let mut f = std::fs::File::open(path)?;
-        Self::new(&mut f)
+        Self::new(&mut f, &ParseContext::default())
     }
 
     /// Create an instance of a `KaitaiStruct` format from an array of bytes.
     fn from_bytes(bytes: &[u8]) -> Result<Self> {
         let mut b = std::io::Cursor::new(bytes);
-        Self::new(&mut b)
+        Self::new(&mut b, &ParseContext::default())
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from an owned buffer, e.g. the body of a
+    /// network response, which is consumed by the parse.
+    ///
+    /// The types implementing `KaitaiStruct` own their fields, so the result of
+    /// [`from_bytes`](KaitaiStruct::from_bytes) doesn't borrow the bytes either: this only saves
+    /// keeping a buffer alive to borrow it when it isn't needed after the parse. The types
+    /// generated with the `borrowed` argument borrow from the buffer instead.
+    ///
+    /// ```
+    /// # use kaitai::prelude::*;
+    /// #[kaitai_source("../../tests/formats/basic_le.ksy")]
+    /// struct Record;
+    ///
+    /// let record = Record::from_vec(vec![1; 14])?;
+    /// assert_eq!(record.header, 0x0101);
+    /// # Ok::<(), kaitai::Error>(())
+    /// ```
+    fn from_vec(data: Vec<u8>) -> Result<Self> {
+        let mut b = std::io::Cursor::new(data);
+        Self::new(&mut b, &ParseContext::default())
+    }
+
+    /// Create an instance of a `KaitaiStruct` format from a stream, starting at the current
+    /// position of the stream.
+    ///
+    /// The stream can be a trait object, so a `&mut dyn KaitaiStream` chosen at runtime works.
+    ///
+    /// Generated types never seek back to the start of the stream, so any type, including the
+    /// nested ones, can be parsed from the middle of a stream by seeking to its offset first.
+    /// The stream is left positioned right after the parsed type, and absolute positions (such as
+    /// the ones returned by [`KaitaiStream::pos`]) are still those of the whole stream.
+    ///
+    /// ```
+    /// # use kaitai::prelude::*;
+    /// # use std::io::{Cursor, Seek, SeekFrom};
+    /// #[kaitai_source("../../tests/formats/basic_le.ksy")]
+    /// struct Record;
+    ///
+    /// let mut stream = Cursor::new(vec![0; 64]);
+    /// stream.seek(SeekFrom::Start(16))?;
+    /// let record = Record::from_reader(&mut stream)?;
+    /// assert_eq!(stream.pos()?, 30);
+    /// # Ok::<(), kaitai::Error>(())
+    /// ```
+    fn from_reader<S: KaitaiStream + ?Sized>(stream: &mut S) -> Result<Self> {
+        Self::new(stream, &ParseContext::default())
+    }
+
+    /// Like [`from_reader`](KaitaiStruct::from_reader), with the settings of `ctx` instead of the
+    /// default ones. The settings apply to the nested types too, except
+    /// [`with_reject_trailing`](ParseContext::with_reject_trailing), which only checks `stream`.
+    ///
+    /// ```
+    /// # use kaitai::{prelude::*, Decoding, ParseContext};
+    /// #[kaitai_source("../../tests/formats/strings.ksy")]
+    /// struct Strings;
+    ///
+    /// let bytes = b"caf\xe9\0h\0i\0ab\0\0\x07";
+    /// assert!(Strings::from_bytes(bytes).is_err());
+    ///
+    /// let ctx = ParseContext::new().with_decoding(Decoding::Lossy);
+    /// let strings = Strings::from_reader_with(&mut std::io::Cursor::new(bytes), &ctx)?;
+    /// assert_eq!(strings.name, "caf\u{fffd}");
+    /// # Ok::<(), kaitai::Error>(())
+    /// ```
+    fn from_reader_with<S: KaitaiStream + ?Sized>(
+        stream: &mut S,
+        ctx: &ParseContext,
+    ) -> Result<Self> {
+        let parsed = Self::new(stream, ctx)?;
+        if ctx.reject_trailing() {
+            let remaining = stream.size()?.saturating_sub(stream.pos()?);
+            if remaining > 0 {
+                return Err(Error::TrailingData { remaining });
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Like [`from_reader`](KaitaiStruct::from_reader), also returning the number of bytes left
+    /// in the stream after the parsed type, e.g. to detect extra data after a file.
+    ///
+    /// ```
+    /// # use kaitai::prelude::*;
+    /// # use std::io::Cursor;
+    /// #[kaitai_source("../../tests/formats/basic_le.ksy")]
+    /// struct Record;
+    ///
+    /// let (_, trailing) = Record::from_reader_with_trailing(&mut Cursor::new([0; 20]))?;
+    /// assert_eq!(trailing, 6);
+    /// # Ok::<(), kaitai::Error>(())
+    /// ```
+    fn from_reader_with_trailing<S: KaitaiStream + ?Sized>(stream: &mut S) -> Result<(Self, u64)> {
+        let parsed = Self::new(stream, &ParseContext::default())?;
+        // A seek past the end leaves the position after it.
+        let trailing = stream.size()?.saturating_sub(stream.pos()?);
+        Ok((parsed, trailing))
+    }
+
+    /// Parses records of this type from `stream` until its end, e.g. a log file made of
+    /// concatenated records.
+    ///
+    /// A trailing partial record is an error rather than being silently ignored.
+    ///
+    /// ```
+    /// # use kaitai::prelude::*;
+    /// # use std::io::Cursor;
+    /// #[kaitai_source("../../tests/formats/basic_le.ksy")]
+    /// struct Record;
+    ///
+    /// let records = Record::parse_all(&mut Cursor::new([0; 28]))?;
+    /// assert_eq!(records.len(), 2);
+    ///
+    /// assert!(Record::parse_all(&mut Cursor::new([0; 20])).is_err());
+    /// # Ok::<(), kaitai::Error>(())
+    /// ```
+    fn parse_all<S: KaitaiStream + ?Sized>(stream: &mut S) -> Result<Vec<Self>> {
+        let ctx = ParseContext::default();
+        let mut records = Vec::new();
+        while !stream.is_eof()? {
+            records.push(Self::new(stream, &ctx)?);
+        }
+        Ok(records)
     }
 
     #[doc(hidden)]
-    fn new<S: KaitaiStream>(stream: &mut S) -> Result<Self>;
+    fn new<S: KaitaiStream + ?Sized>(stream: &mut S, ctx: &ParseContext) -> Result<Self>;
+
+    /// Parses the type from the stream of `streams`, which also has the stream of the parent type
+    /// for the instances read from it (KS: `io: _parent._io`).
+    #[doc(hidden)]
+    fn new_in<P: ParentStreams + ?Sized>(streams: &mut P, ctx: &ParseContext) -> Result<Self> {
+        Self::new(streams.io(), ctx)
+    }
 
     #[doc(hidden)]
-    fn read<S: KaitaiStream>(&mut self, stream: &mut S) -> Result<()>;
+    fn read<S: KaitaiStream + ?Sized>(&mut self, stream: &mut S, ctx: &ParseContext) -> Result<()>;
 }
@@ -0,0 +1,459 @@
+//! The built-in methods of the expression language, called by the generated code.
+//!
+//! The methods are split in traits by the type they are called on, so that e.g. `length` can be
+//! called on both strings and arrays. They are prefixed with `ks_` so that they never resolve to
+//! inherent methods with the same name, like [`slice::reverse`].
+
+use crate::{
+    error::{Error, Result},
+    runtime::{bytes_to_str, Decoding},
+};
+
+use std::convert::{TryFrom, TryInto};
+
+/// The methods of strings.
+pub trait KsStr {
+    /// Returns the number of characters of the string (KS: `length`).
+    fn ks_length(&self) -> usize;
+
+    /// Returns the string with its characters in reverse order (KS: `reverse`).
+    fn ks_reverse(&self) -> String;
+
+    /// Returns the characters from index `from` up to, but excluding, index `to` (KS:
+    /// `substring`).
+    ///
+    /// Indices past the end of the string are clamped to its length and a `from` after `to`
+    /// returns an empty string rather than failing, as in the other Kaitai Struct runtimes.
+    fn ks_substring(&self, from: i64, to: i64) -> String;
+
+    /// Parses the string as an integer in the given radix (KS: `to_i`). A radix outside of 2 to
+    /// 36, which can come from the data, is an [`Error::InvalidInteger`] too.
+    fn ks_to_i(&self, radix: u32) -> Result<i64>;
+}
+
+impl KsStr for str {
+    fn ks_length(&self) -> usize {
+        self.chars().count()
+    }
+
+    fn ks_reverse(&self) -> String {
+        self.chars().rev().collect()
+    }
+
+    fn ks_substring(&self, from: i64, to: i64) -> String {
+        let clamp = |i: i64| usize::try_from(i.max(0)).unwrap_or(usize::MAX);
+        let (from, to) = (clamp(from), clamp(to));
+        if from >= to {
+            return String::new();
+        }
+        self.chars().skip(from).take(to - from).collect()
+    }
+
+    fn ks_to_i(&self, radix: u32) -> Result<i64> {
+        let invalid = || Error::InvalidInteger {
+            string: self.to_owned(),
+            radix,
+        };
+        // `from_str_radix` panics on other radixes.
+        if !(2..=36).contains(&radix) {
+            return Err(invalid());
+        }
+        i64::from_str_radix(self, radix).map_err(|_| invalid())
+    }
+}
+
+/// The methods of arrays, including byte arrays.
+pub trait KsSeq<T> {
+    /// Returns the number of elements of the array (KS: `length` and `size`).
+    fn ks_length(&self) -> usize;
+
+    /// Returns the array with its elements in reverse order (KS: `reverse`).
+    fn ks_reverse(&self) -> Vec<T>;
+
+    /// Returns the first element of the array (KS: `first`).
+    fn ks_first(&self) -> Result<T>;
+
+    /// Returns the last element of the array (KS: `last`).
+    fn ks_last(&self) -> Result<T>;
+
+    /// Returns the element at `index` (KS: `array[index]`).
+    ///
+    /// A negative index is out of bounds like one past the end, it doesn't count from the end of
+    /// the array.
+    fn ks_at(&self, index: i64) -> Result<T>;
+
+    /// Returns the smallest element of the array (KS: `min`).
+    ///
+    /// Calling it on an array of elements that can't be ordered, e.g. user-defined types, fails
+    /// to compile.
+    fn ks_min(&self) -> Result<T>
+    where
+        T: Ord;
+
+    /// Returns the largest element of the array (KS: `max`).
+    ///
+    /// Calling it on an array of elements that can't be ordered, e.g. user-defined types, fails
+    /// to compile.
+    fn ks_max(&self) -> Result<T>
+    where
+        T: Ord;
+}
+
+impl<T: Clone> KsSeq<T> for [T] {
+    fn ks_length(&self) -> usize {
+        self.len()
+    }
+
+    fn ks_reverse(&self) -> Vec<T> {
+        self.iter().rev().cloned().collect()
+    }
+
+    fn ks_first(&self) -> Result<T> {
+        self.first().cloned().ok_or(Error::EmptyArray("first"))
+    }
+
+    fn ks_last(&self) -> Result<T> {
+        self.last().cloned().ok_or(Error::EmptyArray("last"))
+    }
+
+    fn ks_at(&self, index: i64) -> Result<T> {
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| self.get(i))
+            .cloned()
+            .ok_or(Error::IndexOutOfBounds {
+                index,
+                len: self.len(),
+            })
+    }
+
+    fn ks_min(&self) -> Result<T>
+    where
+        T: Ord,
+    {
+        self.iter().min().cloned().ok_or(Error::EmptyArray("min"))
+    }
+
+    fn ks_max(&self) -> Result<T>
+    where
+        T: Ord,
+    {
+        self.iter().max().cloned().ok_or(Error::EmptyArray("max"))
+    }
+}
+
+/// The methods of byte arrays.
+pub trait KsBytes {
+    /// Decodes the bytes as a string in `encoding` (KS: `to_s`), see [`bytes_to_str`].
+    fn ks_to_s(&self, encoding: &str, decoding: Decoding) -> Result<String>;
+}
+
+impl KsBytes for [u8] {
+    fn ks_to_s(&self, encoding: &str, decoding: Decoding) -> Result<String> {
+        bytes_to_str(self, encoding, decoding)
+    }
+}
+
+/// The methods of enums, implemented by the generated enums.
+pub trait KsEnum: Copy {
+    /// Returns the value of the variant.
+    fn ks_value(self) -> u64;
+
+    /// Returns the value of the variant as an integer (KS: `to_i`), or an
+    /// [`Error::ArithmeticOverflow`] if it doesn't fit in an `i64`. The radix is only used by
+    /// `to_i` on strings.
+    fn ks_to_i(&self, _radix: u32) -> Result<i64> {
+        i64::try_from(self.ks_value()).map_err(|_| Error::ArithmeticOverflow("conversion"))
+    }
+}
+
+/// The arithmetic of numbers, which is checked for integers.
+///
+/// An integer operation that overflows, e.g. the sum of an offset and a size read from corrupt
+/// data, or divides by zero returns an [`Error::ArithmeticOverflow`] rather than panicking or
+/// wrapping around.
+pub trait KsNum: Sized {
+    /// Returns `self + rhs` (KS: `+`).
+    fn ks_add(self, rhs: Self) -> Result<Self>;
+
+    /// Returns `self - rhs` (KS: `-`).
+    fn ks_sub(self, rhs: Self) -> Result<Self>;
+
+    /// Returns `self * rhs` (KS: `*`).
+    fn ks_mul(self, rhs: Self) -> Result<Self>;
+
+    /// Returns `self / rhs` (KS: `/`), rounded towards zero for integers.
+    fn ks_div(self, rhs: Self) -> Result<Self>;
+
+    /// Returns the remainder of `self / rhs` (KS: `%`), which is never negative, e.g. `-7 % 4` is
+    /// 1 as in Kaitai Struct.
+    fn ks_rem(self, rhs: Self) -> Result<Self>;
+
+    /// Returns `-self` (KS: unary `-`).
+    fn ks_neg(self) -> Result<Self>;
+}
+
+macro_rules! impl_ks_num {
+    ($($ty:ty),*) => {
+        $(
+            impl KsNum for $ty {
+                fn ks_add(self, rhs: Self) -> Result<Self> {
+                    self.checked_add(rhs).ok_or(Error::ArithmeticOverflow("addition"))
+                }
+
+                fn ks_sub(self, rhs: Self) -> Result<Self> {
+                    self.checked_sub(rhs).ok_or(Error::ArithmeticOverflow("subtraction"))
+                }
+
+                fn ks_mul(self, rhs: Self) -> Result<Self> {
+                    self.checked_mul(rhs).ok_or(Error::ArithmeticOverflow("multiplication"))
+                }
+
+                fn ks_div(self, rhs: Self) -> Result<Self> {
+                    self.checked_div(rhs).ok_or(Error::ArithmeticOverflow("division"))
+                }
+
+                fn ks_rem(self, rhs: Self) -> Result<Self> {
+                    self.checked_rem_euclid(rhs).ok_or(Error::ArithmeticOverflow("modulo"))
+                }
+
+                fn ks_neg(self) -> Result<Self> {
+                    self.checked_neg().ok_or(Error::ArithmeticOverflow("negation"))
+                }
+            }
+        )*
+    };
+}
+
+impl_ks_num!(u8, u16, u32, u64, usize, i8, i16, i32, i64, i128, isize);
+
+macro_rules! impl_ks_num_float {
+    ($($ty:ty),*) => {
+        $(
+            impl KsNum for $ty {
+                fn ks_add(self, rhs: Self) -> Result<Self> {
+                    Ok(self + rhs)
+                }
+
+                fn ks_sub(self, rhs: Self) -> Result<Self> {
+                    Ok(self - rhs)
+                }
+
+                fn ks_mul(self, rhs: Self) -> Result<Self> {
+                    Ok(self * rhs)
+                }
+
+                fn ks_div(self, rhs: Self) -> Result<Self> {
+                    Ok(self / rhs)
+                }
+
+                fn ks_rem(self, rhs: Self) -> Result<Self> {
+                    Ok(self.rem_euclid(rhs))
+                }
+
+                fn ks_neg(self) -> Result<Self> {
+                    Ok(-self)
+                }
+            }
+        )*
+    };
+}
+
+impl_ks_num_float!(f32, f64);
+
+/// The shifts of integers, which are checked.
+///
+/// A shift by a negative amount or by at least the width of the integer returns an
+/// [`Error::ArithmeticOverflow`] rather than panicking.
+pub trait KsShift: Sized {
+    /// Returns `self << rhs` (KS: `<<`).
+    fn ks_shl<R: TryInto<u32>>(self, rhs: R) -> Result<Self>;
+
+    /// Returns `self >> rhs` (KS: `>>`).
+    fn ks_shr<R: TryInto<u32>>(self, rhs: R) -> Result<Self>;
+}
+
+macro_rules! impl_ks_shift {
+    ($($ty:ty),*) => {
+        $(
+            impl KsShift for $ty {
+                fn ks_shl<R: TryInto<u32>>(self, rhs: R) -> Result<Self> {
+                    rhs.try_into()
+                        .ok()
+                        .and_then(|rhs| self.checked_shl(rhs))
+                        .ok_or(Error::ArithmeticOverflow("shift"))
+                }
+
+                fn ks_shr<R: TryInto<u32>>(self, rhs: R) -> Result<Self> {
+                    rhs.try_into()
+                        .ok()
+                        .and_then(|rhs| self.checked_shr(rhs))
+                        .ok_or(Error::ArithmeticOverflow("shift"))
+                }
+            }
+        )*
+    };
+}
+
+impl_ks_shift!(u8, u16, u32, u64, usize, i8, i16, i32, i64, i128, isize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn length() {
+        assert_eq!("café".ks_length(), 4);
+        assert_eq!([1, 2, 3].ks_length(), 3);
+        assert_eq!(b"bytes".ks_length(), 5);
+    }
+
+    #[test]
+    fn reverse() {
+        assert_eq!("café".ks_reverse(), "éfac");
+        assert_eq!([1, 2, 3].ks_reverse(), [3, 2, 1]);
+    }
+
+    #[test]
+    fn aggregates() {
+        let values = [3, 1, 4, 1, 5];
+        assert_eq!(values.ks_first().unwrap(), 3);
+        assert_eq!(values.ks_last().unwrap(), 5);
+        assert_eq!(values.ks_min().unwrap(), 1);
+        assert_eq!(values.ks_max().unwrap(), 5);
+
+        let empty: [u32; 0] = [];
+        for (result, method) in [
+            (empty.ks_first(), "first"),
+            (empty.ks_last(), "last"),
+            (empty.ks_min(), "min"),
+            (empty.ks_max(), "max"),
+        ] {
+            assert!(matches!(result, Err(Error::EmptyArray(m)) if m == method));
+        }
+    }
+
+    #[test]
+    fn at() {
+        let values = [3, 1, 4];
+        assert_eq!(values.ks_at(0).unwrap(), 3);
+        assert_eq!(b"ab".ks_at(1).unwrap(), b'b');
+        for index in [-1, 3, i64::MAX] {
+            assert!(matches!(
+                values.ks_at(index),
+                Err(Error::IndexOutOfBounds { index: i, len: 3 }) if i == index
+            ));
+        }
+    }
+
+    #[test]
+    fn substring() {
+        let s = String::from("kaitai");
+        assert_eq!(s.ks_substring(1, 4), "ait");
+        assert_eq!(s.ks_substring(0, 6), "kaitai");
+        assert_eq!(s.ks_substring(3, 100), "tai");
+        assert_eq!(s.ks_substring(-2, 2), "ka");
+        assert_eq!(s.ks_substring(4, 2), "");
+        assert_eq!("día".ks_substring(1, 2), "í");
+    }
+
+    #[test]
+    fn to_s() {
+        assert_eq!(b"hi".ks_to_s("UTF-8", Decoding::Strict).unwrap(), "hi");
+        assert!(b"\xff".ks_to_s("UTF-8", Decoding::Strict).is_err());
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        assert_eq!(200u8.ks_add(55).unwrap(), 255);
+        assert_eq!(2i8.ks_sub(3).unwrap(), -1);
+        assert_eq!(1.5f64.ks_mul(2.0).unwrap(), 3.0);
+        assert!(matches!(
+            u64::MAX.ks_add(1),
+            Err(Error::ArithmeticOverflow("addition"))
+        ));
+        assert!(matches!(
+            0u32.ks_sub(1),
+            Err(Error::ArithmeticOverflow("subtraction"))
+        ));
+        let err = (u64::MAX / 2).ks_mul(3).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "overflow in the multiplication of an expression"
+        );
+    }
+
+    #[test]
+    fn checked_division() {
+        assert_eq!(7u8.ks_div(2).unwrap(), 3);
+        assert_eq!((-7i32).ks_div(2).unwrap(), -3);
+        assert_eq!((-7i32).ks_rem(4).unwrap(), 1);
+        assert_eq!(7i32.ks_rem(-4).unwrap(), 3);
+        assert_eq!((-7.5f64).ks_rem(4.0).unwrap(), 0.5);
+        assert_eq!((-128i16).ks_neg().unwrap(), 128);
+        assert!(matches!(
+            1u32.ks_div(0),
+            Err(Error::ArithmeticOverflow("division"))
+        ));
+        assert!(matches!(
+            i8::MIN.ks_div(-1),
+            Err(Error::ArithmeticOverflow("division"))
+        ));
+        assert!(matches!(
+            5i64.ks_rem(0),
+            Err(Error::ArithmeticOverflow("modulo"))
+        ));
+        assert!(matches!(
+            i8::MIN.ks_neg(),
+            Err(Error::ArithmeticOverflow("negation"))
+        ));
+        assert!(matches!(
+            1u8.ks_neg(),
+            Err(Error::ArithmeticOverflow("negation"))
+        ));
+    }
+
+    #[test]
+    fn checked_shifts() {
+        assert_eq!(1u8.ks_shl(7).unwrap(), 128);
+        assert_eq!(0x80u32.ks_shr(4u8).unwrap(), 8);
+        assert_eq!((-16i32).ks_shr(2).unwrap(), -4);
+        for result in [1u8.ks_shl(8), 1u8.ks_shr(-1), 1u8.ks_shl(u64::MAX)] {
+            assert!(matches!(result, Err(Error::ArithmeticOverflow("shift"))));
+        }
+    }
+
+    #[test]
+    fn to_i() {
+        assert_eq!("42".ks_to_i(10).unwrap(), 42);
+        assert_eq!("-42".ks_to_i(10).unwrap(), -42);
+        assert_eq!("ff".ks_to_i(16).unwrap(), 255);
+        let err = "12a".ks_to_i(10).unwrap_err();
+        assert_eq!(err.to_string(), "12a is not a valid integer in radix 10");
+        for radix in [0, 1, 37] {
+            assert!(matches!(
+                "10".ks_to_i(radix),
+                Err(Error::InvalidInteger { radix: r, .. }) if r == radix
+            ));
+        }
+    }
+
+    #[test]
+    fn enum_to_i() {
+        #[derive(Copy, Clone)]
+        struct Value(u64);
+
+        impl KsEnum for Value {
+            fn ks_value(self) -> u64 {
+                self.0
+            }
+        }
+
+        assert_eq!(Value(3).ks_to_i(10).unwrap(), 3);
+        let err = Value(u64::MAX).ks_to_i(10).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "overflow in the conversion of an expression"
+        );
+    }
+}
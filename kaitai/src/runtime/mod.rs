@@ -1,7 +1,38 @@
 //! Module containing the traits implemented by the [`kaitai_source`](kaitai_macros::kaitai_source) macro.
 
+mod bits;
+mod context;
+mod fixed_point;
+mod guid;
+mod hex;
 mod kstruct;
+pub(crate) mod methods;
+pub(crate) mod path;
+pub(crate) mod process;
+pub(crate) mod repeat;
+mod schema;
+mod slice;
+pub(crate) mod spans;
 mod stream;
+mod strings;
+pub(crate) mod substream;
+mod tracking;
+mod transform;
 
+pub use bits::BitReader;
+pub use context::{Decoding, ParseContext};
+pub use fixed_point::fixed_point;
+pub use guid::Guid;
+pub use hex::HexBytes;
 pub use kstruct::KaitaiStruct;
-pub use stream::KaitaiStream;
+pub use process::{process_xor_many, process_xor_one, process_zlib};
+pub use schema::FieldInfo;
+pub use slice::SliceStream;
+pub use stream::{Endian, KaitaiStream, TerminatorFlags};
+pub use strings::{
+    bytes_strip_right, bytes_terminate, bytes_to_str, slice_strip_right, slice_terminate,
+    slice_to_str,
+};
+pub use substream::SubStream;
+pub use tracking::TrackingReader;
+pub use transform::{HashingStream, RotateStream, XorStream};
@@ -0,0 +1,15 @@
+//! The helpers of the generated code for the `error-path` feature.
+
+use crate::error::Result;
+
+/// Returns the value returned by `read`, or its error with `field` added to the start of its
+/// path.
+pub fn in_field<T>(field: &str, read: impl FnOnce() -> Result<T>) -> Result<T> {
+    read().map_err(|e| e.in_field(field))
+}
+
+/// Returns the value returned by `read`, or its error with the element `index` added to the
+/// start of its path.
+pub fn in_element<T>(index: usize, read: impl FnOnce() -> Result<T>) -> Result<T> {
+    read().map_err(|e| e.in_element(index))
+}
@@ -0,0 +1,136 @@
+//! The processing of raw bytes before they are parsed (KS: `process`).
+
+use crate::error::{Error, Result};
+
+use std::convert::TryFrom;
+
+use miniz_oxide::inflate::{self, TINFLStatus};
+
+/// Returns `data` XORed with the byte `key` (KS: `process: xor(key)` with an integer key).
+pub fn process_xor_one(data: &[u8], key: u8) -> Vec<u8> {
+    data.iter().map(|byte| byte ^ key).collect()
+}
+
+/// Returns `data` XORed with `key`, repeating the key as many times as needed (KS:
+/// `process: xor(key)` with a byte array key).
+///
+/// An empty key leaves the data unchanged.
+///
+/// ```
+/// assert_eq!(kaitai::process_xor_many(&[1, 2, 3], &[1, 0]), [0, 2, 2]);
+/// ```
+pub fn process_xor_many(data: &[u8], key: &[u8]) -> Vec<u8> {
+    if key.is_empty() {
+        return data.to_vec();
+    }
+    data.iter()
+        .zip(key.iter().cycle())
+        .map(|(byte, key)| byte ^ key)
+        .collect()
+}
+
+/// Returns `data` decompressed as a zlib stream (KS: `process: zlib`), e.g. the compressed chunks
+/// of PNG or PDF files.
+///
+/// Returns an [`InvalidZlib`](Error::InvalidZlib) error if `data` isn't a valid, complete zlib
+/// stream.
+///
+/// ```
+/// let compressed = [0x78, 0x9c, 0xcb, 0x4e, 0xcc, 0x2c, 0x49, 0xcc, 0x04, 0x00, 0x08, 0x98, 0x02, 0x74];
+/// assert_eq!(kaitai::process_zlib(&compressed)?, b"kaitai");
+/// # Ok::<(), kaitai::Error>(())
+/// ```
+pub fn process_zlib(data: &[u8]) -> Result<Vec<u8>> {
+    process_zlib_limited(data, None)
+}
+
+/// Like [`process_zlib`], with an [`AllocationLimitExceeded`](Error::AllocationLimitExceeded)
+/// error if the decompressed data is larger than `limit`, so that a small corrupt or malicious
+/// stream can't make the parse allocate gigabytes.
+pub fn process_zlib_limited(data: &[u8], limit: Option<u64>) -> Result<Vec<u8>> {
+    let max_size = limit.map_or(usize::MAX, |limit| {
+        usize::try_from(limit).unwrap_or(usize::MAX)
+    });
+    inflate::decompress_to_vec_zlib_with_limit(data, max_size).map_err(|e| match e.status {
+        TINFLStatus::HasMoreOutput => Error::AllocationLimitExceeded {
+            requested: e.output.len() as u64 + 1,
+            limit: e.output.len() as u64,
+        },
+        _ => Error::InvalidZlib(e.to_string()),
+    })
+}
+
+/// The keys `xor` can be called with, so that the generated code doesn't need to know whether
+/// the key expression is an integer or a byte array.
+pub trait XorKey {
+    /// Returns `data` XORed with the key.
+    fn xor_bytes(&self, data: &[u8]) -> Vec<u8>;
+}
+
+impl XorKey for u8 {
+    fn xor_bytes(&self, data: &[u8]) -> Vec<u8> {
+        process_xor_one(data, *self)
+    }
+}
+
+impl XorKey for [u8] {
+    fn xor_bytes(&self, data: &[u8]) -> Vec<u8> {
+        process_xor_many(data, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xor_one() {
+        assert_eq!(
+            process_xor_one(&[0x00, 0x0f, 0xff], 0xff),
+            [0xff, 0xf0, 0x00]
+        );
+        assert!(process_xor_one(&[], 0xff).is_empty());
+    }
+
+    #[test]
+    fn xor_many() {
+        let key = [0x01, 0x02, 0x03, 0x04];
+        assert_eq!(
+            process_xor_many(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06], &key),
+            [0, 0, 0, 0, 0x04, 0x04]
+        );
+        assert_eq!(process_xor_many(&[1, 2], &[]), [1, 2]);
+    }
+
+    #[test]
+    fn zlib() {
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&[7; 100], 6);
+        assert_eq!(process_zlib(&compressed).unwrap(), [7; 100]);
+        assert_eq!(
+            process_zlib_limited(&compressed, Some(100)).unwrap(),
+            [7; 100]
+        );
+        assert!(matches!(
+            process_zlib_limited(&compressed, Some(99)),
+            Err(Error::AllocationLimitExceeded { limit: 99, .. })
+        ));
+        // Truncated streams are invalid.
+        assert!(matches!(
+            process_zlib(&compressed[..compressed.len() - 1]),
+            Err(Error::InvalidZlib(_))
+        ));
+        assert!(matches!(
+            process_zlib(b"kaitai"),
+            Err(Error::InvalidZlib(_))
+        ));
+    }
+
+    #[test]
+    fn xor_key() {
+        assert_eq!(0xffu8.xor_bytes(&[0x0f]), [0xf0]);
+        assert_eq!(
+            [0x0f, 0xf0][..].xor_bytes(&[0xff, 0xff, 0xff]),
+            [0xf0, 0x0f, 0xf0]
+        );
+    }
+}
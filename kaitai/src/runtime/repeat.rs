@@ -0,0 +1,45 @@
+//! The helpers of the generated code for repeated attributes.
+
+use crate::error::{Error, Result};
+
+/// Returns the array of the `N` elements returned by `f` for each index, or the first error it
+/// returns, in which case it isn't called for the following indices.
+///
+/// This is [`std::array::from_fn`] for fallible reads, used to read the elements of an attribute
+/// with a literal `repeat-expr`, which is stored as an array.
+pub fn try_from_fn<T, const N: usize>(mut f: impl FnMut(usize) -> Result<T>) -> Result<[T; N]> {
+    let mut error: Option<Error> = None;
+    let elements: [Option<T>; N] = std::array::from_fn(|index| {
+        if error.is_some() {
+            return None;
+        }
+        f(index).map_err(|e| error = Some(e)).ok()
+    });
+    match error {
+        Some(e) => Err(e),
+        // Every element was read if there was no error.
+        None => Ok(elements.map(Option::unwrap)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_at_first_error() {
+        let squares = try_from_fn::<_, 4>(|i| Ok(i * i)).unwrap();
+        assert_eq!(squares, [0, 1, 4, 9]);
+
+        let mut calls = 0;
+        let result = try_from_fn::<u8, 4>(|i| {
+            calls += 1;
+            match i {
+                1 => Err(Error::NoEnumMatch),
+                _ => Ok(0),
+            }
+        });
+        assert!(matches!(result, Err(Error::NoEnumMatch)));
+        assert_eq!(calls, 2);
+    }
+}
@@ -0,0 +1,41 @@
+/// A field of the `seq` of a generated type, as listed by its `schema` function, which describes
+/// the type without parsing any data, e.g. to build a viewer over several formats:
+///
+/// ```
+/// # use kaitai::{kaitai_source, FieldInfo};
+/// #[kaitai_source(inline = "
+/// meta:
+///   id: header
+///   endian: le
+/// seq:
+///   - id: magic
+///     contents: [0x7f, 0x45, 0x4c, 0x46]
+///   - id: version
+///     type: u2
+///   - id: name
+///     type: strz
+///     encoding: ASCII
+///   - id: flags
+///     type: u1
+/// ")]
+/// struct Header;
+///
+/// let version = FieldInfo {
+///     name: "version",
+///     ty: "u16",
+///     offset: Some(4),
+/// };
+/// assert_eq!(Header::schema()[0], version);
+/// // The offsets of the fields after a field of a variable size aren't known.
+/// assert_eq!(Header::schema()[2].offset, None);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FieldInfo {
+    /// The id of the field in the ksy file.
+    pub name: &'static str,
+    /// The Rust type of the field in the generated struct, e.g. `u16` or `::std::vec::Vec<u8>`.
+    pub ty: &'static str,
+    /// The offset of the field from the start of the type, if it is the same in every parse,
+    /// i.e. if the fields before it have a fixed size.
+    pub offset: Option<u64>,
+}
@@ -0,0 +1,251 @@
+use crate::{
+    error::{Error, Result},
+    runtime::{Decoding, TerminatorFlags},
+};
+
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+/// A [`KaitaiStream`](crate::runtime::KaitaiStream) over a byte slice, whose reads can borrow from
+/// the slice rather than allocate.
+///
+/// The [`read_slice`](SliceStream::read_slice) methods return parts of the slice, tied to its
+/// lifetime rather than to the stream, and [`read_str`](SliceStream::read_str) only allocates
+/// when the string has to be transcoded, e.g. to read ASCII fields of a large buffer without an
+/// allocation per field:
+///
+/// ```
+/// # use kaitai::{Decoding, KaitaiStream, SliceStream};
+/// # use std::borrow::Cow;
+/// let data = b"\x04RIFFtail";
+/// let mut stream = SliceStream::new(data);
+/// let len = stream.read_u1()?;
+/// let tag = stream.read_str(len.into(), "ASCII", Decoding::Strict)?;
+/// assert!(matches!(tag, Cow::Borrowed("RIFF")));
+/// assert_eq!(stream.read_slice_full(), b"tail");
+/// # Ok::<(), kaitai::Error>(())
+/// ```
+///
+/// The types generated with the `borrowed` argument of [`kaitai_source`](crate::kaitai_source)
+/// are parsed from a `SliceStream`, which their byte arrays and strings borrow from. The other
+/// types own their fields, and are parsed from it like from any other stream.
+#[derive(Copy, Clone, Debug)]
+pub struct SliceStream<'a> {
+    data: &'a [u8],
+    pos: u64,
+}
+
+impl<'a> SliceStream<'a> {
+    /// Creates a stream over `data`, starting at its first byte.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Returns an independent stream over the same slice, at `pos`, e.g. to parse the regions of
+    /// an offset table in parallel. The slice isn't copied and the position of `self` is left
+    /// unchanged.
+    ///
+    /// Only streams over shared immutable data can be forked this way: over a memory-mapped file,
+    /// the `SliceStream` is created from the mapped slice.
+    pub fn clone_at(&self, pos: u64) -> Self {
+        Self {
+            data: self.data,
+            pos,
+        }
+    }
+
+    /// Returns the whole slice, whatever the position of the stream.
+    pub fn get_ref(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the bytes from the position to the end of the slice.
+    fn remaining(&self) -> &'a [u8] {
+        let start =
+            usize::try_from(self.pos).map_or(self.data.len(), |pos| pos.min(self.data.len()));
+        &self.data[start..]
+    }
+
+    /// Reads `count` bytes, borrowed from the slice.
+    ///
+    /// Returns an [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) error, without moving the
+    /// position, if there aren't enough bytes left.
+    pub fn read_slice(&mut self, count: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .remaining()
+            .get(..count)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        self.pos += count as u64;
+        Ok(bytes)
+    }
+
+    /// Reads the remaining bytes, borrowed from the slice.
+    pub fn read_slice_full(&mut self) -> &'a [u8] {
+        let bytes = self.remaining();
+        self.pos += bytes.len() as u64;
+        bytes
+    }
+
+    /// Reads the bytes up to the `term` byte, borrowed from the slice, with the same handling of
+    /// the terminator as [`read_bytes_term`](crate::runtime::KaitaiStream::read_bytes_term).
+    pub fn read_slice_term(&mut self, term: u8, flags: TerminatorFlags) -> Result<&'a [u8]> {
+        let remaining = self.remaining();
+        let end = remaining
+            .iter()
+            .position(|&b| b == term)
+            .ok_or(Error::EofBeforeTerminator {
+                term: term as char,
+                start: self.pos,
+                read: remaining.len() as u64,
+            })?;
+        let len = end + usize::from(flags.include);
+        self.pos += (end + usize::from(flags.consume)) as u64;
+        Ok(&remaining[..len])
+    }
+
+    /// Ensures that the next bytes are `expected`, like
+    /// [`KaitaiStream::ensure_fixed_contents`](crate::runtime::KaitaiStream::ensure_fixed_contents)
+    /// but comparing them in the slice rather than copying them first.
+    pub fn ensure_fixed_contents(&mut self, expected: &[u8]) -> Result<()> {
+        let actual = self.read_slice(expected.len())?;
+        if actual != expected {
+            return Err(Error::UnexpectedContents {
+                field: None,
+                actual: actual.to_vec(),
+                expected: expected.to_vec(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads a string of `count` bytes in the encoding with the label `encoding`, borrowed from
+    /// the slice unless it has to be transcoded, like [`slice_to_str`](crate::slice_to_str).
+    pub fn read_str(
+        &mut self,
+        count: usize,
+        encoding: &str,
+        decoding: Decoding,
+    ) -> Result<Cow<'a, str>> {
+        crate::slice_to_str(self.read_slice(count)?, encoding, decoding)
+    }
+}
+
+impl Read for SliceStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.remaining();
+        let len = buf.len().min(remaining.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for SliceStream<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => {
+                self.pos = pos;
+                return Ok(pos);
+            }
+            SeekFrom::End(offset) => (self.data.len() as u64, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        match base.checked_add_signed(offset) {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::runtime::KaitaiStream;
+
+    #[test]
+    fn borrowed_reads() {
+        let data = [1, 2, 3, 0, 4, 5];
+        let mut stream = SliceStream::new(&data);
+        assert_eq!(stream.read_slice(2).unwrap(), [1, 2]);
+        assert!(stream.read_slice(5).is_err());
+        assert_eq!(stream.pos().unwrap(), 2);
+
+        assert_eq!(
+            stream.read_slice_term(0, TerminatorFlags::new()).unwrap(),
+            [3]
+        );
+        assert_eq!(stream.read_u1().unwrap(), 0);
+        stream.seek(SeekFrom::Current(-2)).unwrap();
+        assert_eq!(
+            stream
+                .read_slice_term(0, TerminatorFlags::include())
+                .unwrap(),
+            [3, 0]
+        );
+        // The included terminator isn't consumed.
+        assert_eq!(stream.pos().unwrap(), 3);
+        stream.seek(SeekFrom::Current(-1)).unwrap();
+        assert_eq!(
+            stream.read_slice_term(0, TerminatorFlags::all()).unwrap(),
+            [3, 0]
+        );
+        assert_eq!(stream.read_slice_full(), [4, 5]);
+        assert!(stream.is_eof().unwrap());
+        assert!(stream.seek(SeekFrom::Current(-7)).is_err());
+    }
+
+    #[test]
+    fn fixed_contents() {
+        let mut stream = SliceStream::new(b"RIFFWAVE");
+        assert!(stream.ensure_fixed_contents(b"RIFF").is_ok());
+        assert!(matches!(
+            stream.ensure_fixed_contents(b"AVI "),
+            Err(Error::UnexpectedContents { actual, .. }) if actual == b"WAVE"
+        ));
+        assert!(stream.ensure_fixed_contents(b"!").is_err());
+    }
+
+    #[test]
+    fn parallel_forks() {
+        let data = [0, 0, 1, 0, 2, 0, 0, 3];
+        let stream = SliceStream::new(&data);
+        let (first, second) = std::thread::scope(|scope| {
+            let mut first = stream.clone_at(2);
+            let mut second = stream.clone_at(6);
+            let first = scope.spawn(move || first.read_u2le().unwrap());
+            let second = scope.spawn(move || second.read_u2be().unwrap());
+            (first.join().unwrap(), second.join().unwrap())
+        });
+        assert_eq!((first, second), (1, 3));
+
+        let mut fork = stream.clone_at(4);
+        assert_eq!(fork.read_u1().unwrap(), 2);
+        assert_eq!(fork.pos().unwrap(), 5);
+        assert_eq!(stream.clone_at(0).pos().unwrap(), 0);
+    }
+
+    #[test]
+    fn transcoded_str() {
+        let mut stream = SliceStream::new(&[0x68, 0, 0x69, 0, 0xff, 0xfe]);
+        assert!(matches!(
+            stream.read_str(4, "UTF-16LE", Decoding::Strict).unwrap(),
+            Cow::Owned(s) if s == "hi"
+        ));
+        let mut copy = stream;
+        assert!(copy.read_str(2, "UTF-8", Decoding::Strict).is_err());
+        assert_eq!(
+            stream.read_str(2, "UTF-8", Decoding::Lossy).unwrap(),
+            "\u{fffd}\u{fffd}"
+        );
+    }
+}
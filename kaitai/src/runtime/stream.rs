@@ -1,8 +1,14 @@
 // The contents of this file are **heavily** inspired by https://github.com/kaitai-io/kaitai_struct_rust_runtime.
 // Although this file is not a copy-paste, without their work this would have been much harder.
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    runtime::{bytes_strip_right, bytes_to_str, Decoding, SliceStream, SubStream},
+};
 
-use std::io::{Read, Seek, SeekFrom};
+use std::{
+    convert::TryFrom,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+};
 
 use byteorder::ReadBytesExt;
 
@@ -10,11 +16,19 @@ use byteorder::ReadBytesExt;
 /// them into Rust types.
 /// # Use
 /// ```
-/// # trait Example: std::io::Read + std::io::Seek {
-/// // s is the letter used by Kaitai Struct, [2, 4] are the numbers used by Kaitai Struct,
-/// // and [i32, i64] are the Rust types that the Kaitai Struct types (i.e. s2, s4) map to.
-/// generate_read_functions!(s; [2, 4] => [i32, i64]);
-/// # }
+/// use kaitai::KaitaiStream;
+///
+/// // Inside `KaitaiStream`, s is the letter used by Kaitai Struct, [2, 4] are the numbers used
+/// // by Kaitai Struct, and [i16, i32] are the Rust types that the Kaitai Struct types (i.e. s2,
+/// // s4) map to:
+/// //
+/// //     generate_read_functions!(s; [2, 4] => [i16, i32]);
+/// //
+/// // which generates e.g. `read_s2le` and `read_s4be`.
+/// let mut buf = std::io::Cursor::new([0xfe, 0xff, 0xff, 0xff, 0xff, 0xfd]);
+/// assert_eq!(buf.read_s2le()?, -2);
+/// assert_eq!(buf.read_s4be()?, -3);
+/// # Ok::<(), kaitai::error::Error>(())
 /// ```
 macro_rules! generate_read_functions {
     ($letter:ident; [$($size:literal),+$(,)?] => [$($rust_type:ty),+$(,)?]) => {
@@ -30,22 +44,79 @@ macro_rules! generate_read_functions {
             use ::byteorder::ReadBytesExt;
             self.[<read_ $rust_type>]::<::byteorder::BigEndian>().map_err(|e| e.into())
         }
+        #[doc = concat!(" Reads in a little endian ", stringify!($rust_type), " (KS: ", stringify!($letter), stringify!($size), "), or returns `None` if the stream is at its end.")]
+        ///
+        /// Reaching the end of the stream after the first byte is still an error.
+        fn [<try_read_ $letter $size le>](&mut self) -> $crate::error::Result<Option<$rust_type>> {
+            let mut bytes = [0; $size];
+            Ok(read_exact_or_eof(self, &mut bytes)?.then(|| $rust_type::from_le_bytes(bytes)))
+        }
+        #[doc = concat!(" Reads in a big endian ", stringify!($rust_type), " (KS: ", stringify!($letter), stringify!($size), "), or returns `None` if the stream is at its end.")]
+        ///
+        /// Reaching the end of the stream after the first byte is still an error.
+        fn [<try_read_ $letter $size be>](&mut self) -> $crate::error::Result<Option<$rust_type>> {
+            let mut bytes = [0; $size];
+            Ok(read_exact_or_eof(self, &mut bytes)?.then(|| $rust_type::from_be_bytes(bytes)))
+        }
+        #[doc = concat!(" Reads in `count` little endian ", stringify!($rust_type), "s (KS: ", stringify!($letter), stringify!($size), " with `repeat-expr`), decoded from a single read of their bytes.")]
+        fn [<read_ $letter $size le_array>](&mut self, count: usize) -> $crate::error::Result<Vec<$rust_type>> {
+            let bytes = read_array_bytes(self, count, $size)?;
+            Ok(bytes
+                .chunks_exact($size)
+                .map(|chunk| $rust_type::from_le_bytes(<[u8; $size]>::try_from(chunk).unwrap()))
+                .collect())
+        }
+        #[doc = concat!(" Reads in `count` big endian ", stringify!($rust_type), "s (KS: ", stringify!($letter), stringify!($size), " with `repeat-expr`), decoded from a single read of their bytes.")]
+        fn [<read_ $letter $size be_array>](&mut self, count: usize) -> $crate::error::Result<Vec<$rust_type>> {
+            let bytes = read_array_bytes(self, count, $size)?;
+            Ok(bytes
+                .chunks_exact($size)
+                .map(|chunk| $rust_type::from_be_bytes(<[u8; $size]>::try_from(chunk).unwrap()))
+                .collect())
+        }
         )*
     }
     };
 }
 
+/// The byte order of a multi-byte value (KS: `le` and `be`).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Endian {
+    /// Little endian.
+    Le,
+    /// Big endian.
+    Be,
+}
+
+/// Flags controlling how [`read_bytes_term`](KaitaiStream::read_bytes_term) treats the terminator.
+///
+/// The KS keys of a `terminator` map to the constructors as follows, `strz` types and
+/// terminators without either key using the defaults of Kaitai Struct:
+///
+/// | KS keys                               | Constructor                           |
+/// |---------------------------------------|---------------------------------------|
+/// | none                                  | [`strz`](TerminatorFlags::strz)       |
+/// | `include: true`                       | [`all`](TerminatorFlags::all)         |
+/// | `consume: false`                      | [`new`](TerminatorFlags::new)         |
+/// | `include: true` and `consume: false`  | [`include`](TerminatorFlags::include) |
+///
+/// Note that [`Default`] is [`new`](TerminatorFlags::new), which doesn't consume the terminator,
+/// unlike the defaults of Kaitai Struct.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
 pub struct TerminatorFlags {
+    /// Whether the terminator is included in the returned bytes (KS: `include`).
     pub include: bool,
+    /// Whether the terminator is consumed from the stream (KS: `consume`).
     pub consume: bool,
 }
 
 impl TerminatorFlags {
+    /// Neither includes nor consumes the terminator.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Includes the terminator in the returned bytes, but doesn't consume it.
     pub fn include() -> Self {
         Self {
             include: true,
@@ -53,6 +124,7 @@ impl TerminatorFlags {
         }
     }
 
+    /// Consumes the terminator, but doesn't include it in the returned bytes.
     pub fn consume() -> Self {
         Self {
             include: false,
@@ -60,15 +132,26 @@ impl TerminatorFlags {
         }
     }
 
+    /// Both includes and consumes the terminator.
     pub fn all() -> Self {
         Self {
             include: true,
             consume: true,
         }
     }
+
+    /// Consumes the terminator without including it, like the null terminator of a C string (KS:
+    /// `strz`). These are the defaults of Kaitai Struct, the same flags as
+    /// [`consume`](TerminatorFlags::consume).
+    pub fn strz() -> Self {
+        Self::consume()
+    }
 }
 
 /// Trait that adds useful functions to all structs that implement Read and Seek.
+///
+/// The trait is object safe, so streams can be passed around as `&mut dyn KaitaiStream` or
+/// `Box<dyn KaitaiStream>`.
 pub trait KaitaiStream: Read + Seek {
     // The trait doesn't require a close method as buffers are automatically closed on drop.
     // The trait doesn't require a seek method as it is already implemented by std::io::Seek.
@@ -81,9 +164,13 @@ pub trait KaitaiStream: Read + Seek {
         // self.seek(SeekFrom::Start(pos))?;
         // Ok(pos >= size)
         let mut buf = [0u8; 1];
-        let result = self.read(&mut buf).map(|n| n == 0);
-        self.seek(SeekFrom::Current(-1))?;
-        result.map_err(|e| e.into())
+        let eof = self.read(&mut buf)? == 0;
+        // Only step back if a byte was actually read, otherwise the position would move back
+        // from the end of the stream.
+        if !eof {
+            self.seek(SeekFrom::Current(-1))?;
+        }
+        Ok(eof)
     }
 
     /// Returns the position in the stream.
@@ -101,14 +188,35 @@ pub trait KaitaiStream: Read + Seek {
         self.stream_len().map_err(|e| e.into())
     }
 
-    /// Reads a number of bytes from the stream.
-    fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>> {
-        let mut buffer = vec![0; count];
+    /// Returns the number of bytes left from the position to the end of the stream, 0 if the
+    /// position is past the end.
+    fn remaining(&mut self) -> Result<u64> {
+        Ok(self.size()?.saturating_sub(self.pos()?))
+    }
 
-        match self.read_exact(&mut buffer) {
-            Ok(_) => Ok(buffer),
-            Err(e) => Err(e.into()),
+    /// Seeks forward to the next multiple of `alignment` from the start of the stream, e.g. to skip
+    /// the padding after a record aligned to 4 bytes, and returns the number of bytes skipped.
+    ///
+    /// The position is left unchanged if it is already aligned, or if `alignment` is 0 or 1. The
+    /// padding isn't read, so skipping past the end of the stream only fails on the next read.
+    fn align(&mut self, alignment: u64) -> Result<u64> {
+        if alignment <= 1 {
+            return Ok(0);
         }
+        let pos = self.pos()?;
+        let padding = (alignment - pos % alignment) % alignment;
+        if padding > 0 {
+            self.seek(SeekFrom::Start(pos + padding))?;
+        }
+        Ok(padding)
+    }
+
+    /// Reads a number of bytes from the stream.
+    ///
+    /// Over an in-memory [`Cursor`] of a byte slice, a `Vec<u8>` or a byte array, the bytes are
+    /// copied directly from the underlying slice.
+    fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>> {
+        self.read_vec(count)
     }
 
     /// Read the remaining bytes in the stream.
@@ -123,11 +231,39 @@ pub trait KaitaiStream: Read + Seek {
         }
     }
 
+    /// Appends the remaining bytes in the stream to `buf`, returning the number of bytes appended.
+    ///
+    /// Unlike [`read_bytes_full`](KaitaiStream::read_bytes_full) this doesn't allocate a new
+    /// buffer, so it can be used to assemble a payload that is split over multiple reads.
+    fn read_bytes_full_into(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        self.read_to_end(buf).map_err(|e| e.into())
+    }
+
+    /// Returns a [`SubStream`] of the next `size` bytes, e.g. to parse a type with a `size` from
+    /// them without copying them first, and moves the position past them.
+    ///
+    /// Reads from the substream move the position of `self` again, which
+    /// [`finish`](SubStream::finish) puts back right after the substream.
+    ///
+    /// Returns an [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) error, without moving the
+    /// position, if there aren't `size` bytes left.
+    fn read_substream(&mut self, size: u64) -> Result<SubStream<'_, Self>>
+    where
+        Self: Sized,
+    {
+        let sub = SubStream::new(self, size)?;
+        let end = sub.end();
+        sub.parent.seek(SeekFrom::Start(end))?;
+        Ok(sub)
+    }
+
     /// Read bytes up to a terminator.
     ///
     /// The Include flag determines whether the terminator is included in the return value. If the
     /// Consumed flag is set, the stream points to the character after the terminator, otherwise
-    /// it points to the terminator.
+    /// it points to the terminator, whether it is included or not, as in Kaitai Struct. A
+    /// terminator right at the position gives empty data, or only the terminator with the Include
+    /// flag.
     fn read_bytes_term(&mut self, term: char, flags: TerminatorFlags) -> Result<Vec<u8>> {
         let mut buffer = Vec::new();
 
@@ -136,7 +272,13 @@ pub trait KaitaiStream: Read + Seek {
             let bytes_read = self.read(&mut temp_buffer)?;
 
             if bytes_read == 0 {
-                return Err(Error::EofBeforeTerminator(term));
+                // The start is only needed for the error, so it isn't queried for every read.
+                let read = buffer.len() as u64;
+                return Err(Error::EofBeforeTerminator {
+                    term,
+                    start: self.pos()? - read,
+                    read,
+                });
             }
 
             if temp_buffer[0] as char == term {
@@ -144,7 +286,8 @@ pub trait KaitaiStream: Read + Seek {
                     // buffer.extend_from_slice(&temp_buffer);
                     // NOTE: NIGHTLY FEATURE
                     buffer.extend_one(temp_buffer[0]);
-                } else if !flags.consume {
+                }
+                if !flags.consume {
                     self.seek(SeekFrom::Current(-1))?;
                 }
                 return Ok(buffer);
@@ -156,6 +299,54 @@ pub trait KaitaiStream: Read + Seek {
         }
     }
 
+    /// Reads bytes up to the first byte that is one of `terms`, e.g. for fields ending with either
+    /// a newline or a NUL, and returns them with the terminator that matched.
+    ///
+    /// The flags apply to the matched terminator like in
+    /// [`read_bytes_term`](KaitaiStream::read_bytes_term). Reaching the end of the stream first
+    /// is an [`EofBeforeTerminators`](Error::EofBeforeTerminators) error.
+    fn read_bytes_term_any(
+        &mut self,
+        terms: &[u8],
+        flags: TerminatorFlags,
+    ) -> Result<(Vec<u8>, u8)> {
+        let mut buffer = Vec::new();
+
+        loop {
+            let byte = match self.try_read_u1()? {
+                Some(byte) => byte,
+                None => {
+                    let read = buffer.len() as u64;
+                    return Err(Error::EofBeforeTerminators {
+                        terms: terms.to_vec(),
+                        start: self.pos()? - read,
+                        read,
+                    });
+                }
+            };
+
+            if terms.contains(&byte) {
+                if flags.include {
+                    buffer.push(byte);
+                }
+                if !flags.consume {
+                    self.seek(SeekFrom::Current(-1))?;
+                }
+                return Ok((buffer, byte));
+            }
+            buffer.push(byte);
+        }
+    }
+
+    /// Reads a string of `size` bytes padded with `pad` bytes at the end, e.g. a NUL-padded
+    /// C-style name, and decodes it strictly in the encoding with the label `encoding`.
+    ///
+    /// The whole `size` is consumed, and the padding isn't part of the string.
+    fn read_str_fixed(&mut self, size: usize, encoding: &str, pad: u8) -> Result<String> {
+        let bytes = bytes_strip_right(self.read_bytes(size)?, pad);
+        bytes_to_str(&bytes, encoding, Decoding::Strict)
+    }
+
     /// Ensures that the contents of the buffer is equal to the expected value.
     fn ensure_fixed_contents(&mut self, expected: &[u8]) -> Result<()> {
         let mut buf = vec![0; expected.len()];
@@ -165,6 +356,7 @@ pub trait KaitaiStream: Read + Seek {
                     Ok(())
                 } else {
                     Err(Error::UnexpectedContents {
+                        field: None,
                         actual: buf,
                         expected: Vec::from(expected),
                     })
@@ -174,6 +366,55 @@ pub trait KaitaiStream: Read + Seek {
         }
     }
 
+    /// Checks whether the next bytes of the stream are equal to the expected value, without
+    /// consuming them.
+    ///
+    /// Unlike [`ensure_fixed_contents`](KaitaiStream::ensure_fixed_contents), a mismatch isn't an
+    /// error and the position of the stream is restored whether the contents match or not. The
+    /// end of the stream being reached before all the bytes are read counts as a mismatch.
+    fn check_fixed_contents(&mut self, expected: &[u8]) -> Result<bool> {
+        let pos = self.pos()?;
+        let mut buf = vec![0; expected.len()];
+        let result = match self.read_exact(&mut buf) {
+            Ok(_) => Ok(buf == expected),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
+        };
+        self.seek(SeekFrom::Start(pos))?;
+        result
+    }
+
+    /// Scans forward for the next occurrence of `needle`, e.g. to find the next magic signature
+    /// when carving records out of a damaged file, and returns its absolute offset.
+    ///
+    /// The stream is read in chunks and left at the start of the occurrence, which can be the
+    /// current position, or at the end of the stream if there is none. An empty `needle` is found
+    /// at the current position.
+    fn scan_for(&mut self, needle: &[u8]) -> Result<Option<u64>> {
+        let mut window_start = self.pos()?;
+        if needle.is_empty() {
+            return Ok(Some(window_start));
+        }
+        let mut window = Vec::new();
+        let mut chunk = [0u8; 4096];
+        loop {
+            let len = self.read(&mut chunk)?;
+            if len == 0 {
+                return Ok(None);
+            }
+            window.extend_from_slice(&chunk[..len]);
+            if let Some(i) = window.windows(needle.len()).position(|w| w == needle) {
+                let offset = window_start + i as u64;
+                self.seek(SeekFrom::Start(offset))?;
+                return Ok(Some(offset));
+            }
+            // Keep the bytes an occurrence spanning the next chunk could start with.
+            let scanned = window.len().saturating_sub(needle.len() - 1);
+            window.drain(..scanned);
+            window_start += scanned as u64;
+        }
+    }
+
     // generate_read_functions can't generate u1 => u8 and s1 => i8 as they don't have an endian
     // generic. Guess this works as additional documentation for how the macro works :)
 
@@ -187,13 +428,211 @@ pub trait KaitaiStream: Read + Seek {
         self.read_i8().map_err(|e| e.into())
     }
 
+    /// Reads a boolean from a byte, 0 being `false` and any other value `true`, as read by the
+    /// fields with the `-rust-bool: nonzero` key, which is specific to this crate.
+    fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.read_u1()? != 0)
+    }
+
+    /// Reads a boolean from a byte that must be 0 or 1, as read by the fields with the
+    /// `-rust-bool: strict` key. Other values are an [`InvalidBool`](Error::InvalidBool) error.
+    fn read_bool_strict(&mut self) -> Result<bool> {
+        match self.read_u1()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(Error::InvalidBool(byte)),
+        }
+    }
+
+    /// Reads in a u8 (KS: u1), or returns `None` if the stream is at its end, e.g. to tell the
+    /// end of a list of records apart from a truncated record.
+    fn try_read_u1(&mut self) -> Result<Option<u8>> {
+        let mut bytes = [0; 1];
+        Ok(read_exact_or_eof(self, &mut bytes)?.then(|| bytes[0]))
+    }
+
+    /// Reads in an i8 (KS: s1), or returns `None` if the stream is at its end.
+    fn try_read_s1(&mut self) -> Result<Option<i8>> {
+        Ok(self.try_read_u1()?.map(|b| b as i8))
+    }
+
+    /// Reads an unsigned variable-length integer in little endian base 128 (LEB128, KS:
+    /// `vlq_base128_le` in the format library), as in DWARF or protobuf: 7 bits per byte, least
+    /// significant group first, until a byte without its most significant bit set.
+    ///
+    /// Returns a [`VlqOverflow`](Error::VlqOverflow) error if the value doesn't fit in a `u64`.
+    fn read_vlq_base128_le(&mut self) -> Result<u64> {
+        let start = self.pos()?;
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u1()?;
+            let group = u64::from(byte & 0x7f);
+            // Groups of zeros past the 64 bits are allowed, they don't change the value.
+            if group != 0 && (shift >= 64 || (shift > 57 && group >> (64 - shift) != 0)) {
+                return Err(Error::VlqOverflow { start });
+            }
+            if shift < 64 {
+                value |= group << shift;
+            }
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Reads an unsigned variable-length integer in big endian base 128 (KS: `vlq_base128_be` in
+    /// the format library), as in MIDI: 7 bits per byte, most significant group first, until a
+    /// byte without its most significant bit set.
+    ///
+    /// Returns a [`VlqOverflow`](Error::VlqOverflow) error if the value doesn't fit in a `u64`.
+    fn read_vlq_base128_be(&mut self) -> Result<u64> {
+        let start = self.pos()?;
+        let mut value = 0u64;
+        loop {
+            let byte = self.read_u1()?;
+            if value >> 57 != 0 {
+                return Err(Error::VlqOverflow { start });
+            }
+            value = value << 7 | u64::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
     generate_read_functions!(u; [2, 4, 8] => [u16, u32, u64]);
     generate_read_functions!(s; [2, 4, 8] => [i16, i32, i64]);
     generate_read_functions!(f; [4, 8] => [f32, f64]);
+
+    // There are no integer equivalents as e.g. read_u8 would clash with ReadBytesExt::read_u8.
+
+    /// Reads in an f32 (KS: f4) with an endianness chosen at runtime.
+    fn read_f4(&mut self, endian: Endian) -> Result<f32> {
+        match endian {
+            Endian::Le => self.read_f4le(),
+            Endian::Be => self.read_f4be(),
+        }
+    }
+
+    /// Reads in an f64 (KS: f8) with an endianness chosen at runtime.
+    fn read_f8(&mut self, endian: Endian) -> Result<f64> {
+        match endian {
+            Endian::Le => self.read_f8le(),
+            Endian::Be => self.read_f8be(),
+        }
+    }
 }
 
 impl<T: Read + Seek> KaitaiStream for T {}
 
+/// Fills `buf` from `stream`, returning `false` if the stream is at its end before the first byte
+/// and an [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) error if it ends after it.
+fn read_exact_or_eof<S: Read + ?Sized>(stream: &mut S, buf: &mut [u8]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match stream.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(len) => filled += len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+/// Reads the bytes of `count` values of `size` bytes each. The count is checked against the bytes
+/// left in the stream first, so that a corrupt count is an
+/// [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) error rather than a huge allocation.
+fn read_array_bytes<S: KaitaiStream + ?Sized>(
+    stream: &mut S,
+    count: usize,
+    size: usize,
+) -> Result<Vec<u8>> {
+    let len = count
+        .checked_mul(size)
+        .filter(|&len| len as u64 <= stream.remaining().unwrap_or(u64::MAX))
+        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    stream.read_bytes(len)
+}
+
+/// The implementation of [`KaitaiStream::read_bytes`], specialized for the cursors whose bytes are
+/// in memory. The trait is private so that the specializations can't be relied on or extended.
+trait ReadBytes {
+    fn read_vec(&mut self, count: usize) -> Result<Vec<u8>>;
+}
+
+impl<T: Read + ?Sized> ReadBytes for T {
+    default fn read_vec(&mut self, count: usize) -> Result<Vec<u8>> {
+        let mut buffer = vec![0; count];
+
+        match self.read_exact(&mut buffer) {
+            Ok(_) => Ok(buffer),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Reads `count` bytes from `cursor` by copying them from its slice, without zeroing a buffer
+/// first. The position is left unchanged if there aren't enough bytes.
+fn read_cursor_bytes<B: AsRef<[u8]>>(cursor: &mut Cursor<B>, count: usize) -> Result<Vec<u8>> {
+    let data = cursor.get_ref().as_ref();
+    let start = usize::try_from(cursor.position()).map_or(data.len(), |pos| pos.min(data.len()));
+    let bytes = start
+        .checked_add(count)
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?
+        .to_vec();
+    // `start` is clamped to the end of the data, so advance from the real position instead: an
+    // empty read past the end mustn't move the cursor backwards.
+    cursor.set_position(cursor.position() + count as u64);
+    Ok(bytes)
+}
+
+impl ReadBytes for Cursor<&[u8]> {
+    fn read_vec(&mut self, count: usize) -> Result<Vec<u8>> {
+        read_cursor_bytes(self, count)
+    }
+}
+
+impl ReadBytes for Cursor<&Vec<u8>> {
+    fn read_vec(&mut self, count: usize) -> Result<Vec<u8>> {
+        read_cursor_bytes(self, count)
+    }
+}
+
+impl ReadBytes for Cursor<&mut Vec<u8>> {
+    fn read_vec(&mut self, count: usize) -> Result<Vec<u8>> {
+        read_cursor_bytes(self, count)
+    }
+}
+
+impl ReadBytes for Cursor<Vec<u8>> {
+    fn read_vec(&mut self, count: usize) -> Result<Vec<u8>> {
+        read_cursor_bytes(self, count)
+    }
+}
+
+impl<const N: usize> ReadBytes for Cursor<&[u8; N]> {
+    fn read_vec(&mut self, count: usize) -> Result<Vec<u8>> {
+        read_cursor_bytes(self, count)
+    }
+}
+
+impl<const N: usize> ReadBytes for Cursor<[u8; N]> {
+    fn read_vec(&mut self, count: usize) -> Result<Vec<u8>> {
+        read_cursor_bytes(self, count)
+    }
+}
+
+impl ReadBytes for SliceStream<'_> {
+    fn read_vec(&mut self, count: usize) -> Result<Vec<u8>> {
+        Ok(self.read_slice(count)?.to_vec())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,6 +673,94 @@ mod tests {
         assert_eq!(buf.size().unwrap(), 10)
     }
 
+    #[test]
+    fn read_bools() {
+        let mut buf = Cursor::new([0, 1, 255, 0, 1, 255]);
+        assert!(!buf.read_bool().unwrap());
+        assert!(buf.read_bool().unwrap());
+        assert!(buf.read_bool().unwrap());
+
+        assert!(!buf.read_bool_strict().unwrap());
+        assert!(buf.read_bool_strict().unwrap());
+        assert!(matches!(
+            buf.read_bool_strict(),
+            Err(Error::InvalidBool(255))
+        ));
+    }
+
+    #[test]
+    fn read_arrays() {
+        let mut buf = new_buf();
+        assert_eq!(buf.read_u2le_array(2).unwrap(), [0x0100, 0x0302]);
+        assert_eq!(buf.read_s2be_array(1).unwrap(), [0x0405]);
+        assert!(buf.read_u4le_array(0).unwrap().is_empty());
+        // A count larger than the rest of the stream fails before anything is read.
+        assert!(buf.read_u8be_array(usize::MAX).is_err());
+        assert!(buf.read_f4le_array(2).is_err());
+        assert_eq!(buf.pos().unwrap(), 6);
+        assert_eq!(buf.read_u4be_array(1).unwrap(), [0x06070809]);
+    }
+
+    #[test]
+    fn read_vlq() {
+        let mut buf = Cursor::new(vec![0x7f, 0xe5, 0x8e, 0x26, 0x81, 0x00, 0x00]);
+        assert_eq!(buf.read_vlq_base128_le().unwrap(), 0x7f);
+        assert_eq!(buf.read_vlq_base128_le().unwrap(), 624_485);
+        buf.seek(SeekFrom::Start(4)).unwrap();
+        assert_eq!(buf.read_vlq_base128_be().unwrap(), 0x80);
+        assert_eq!(buf.read_vlq_base128_be().unwrap(), 0);
+        // The end of the stream before the last byte is an IO error.
+        let mut buf = Cursor::new(vec![0x81]);
+        assert!(buf.read_vlq_base128_le().unwrap_err().io_kind().is_some());
+
+        let mut max = vec![0xff; 9];
+        max.push(0x01);
+        assert_eq!(Cursor::new(&max).read_vlq_base128_le().unwrap(), u64::MAX);
+        max[9] = 0x02;
+        assert!(matches!(
+            Cursor::new(&max).read_vlq_base128_le(),
+            Err(Error::VlqOverflow { start: 0 })
+        ));
+        let mut max = vec![0x81];
+        max.extend([0xff; 8]);
+        max.push(0x7f);
+        assert_eq!(Cursor::new(&max).read_vlq_base128_be().unwrap(), u64::MAX);
+        max[0] = 0x82;
+        assert!(matches!(
+            Cursor::new(&max).read_vlq_base128_be(),
+            Err(Error::VlqOverflow { start: 0 })
+        ));
+    }
+
+    #[test]
+    fn remaining() {
+        let mut buf = new_buf();
+        buf.seek(SeekFrom::Start(4)).unwrap();
+        assert_eq!(buf.remaining().unwrap(), 6);
+        buf.seek(SeekFrom::Start(12)).unwrap();
+        assert_eq!(buf.remaining().unwrap(), 0);
+    }
+
+    #[test]
+    fn align() {
+        let mut buf = new_buf();
+        buf.seek(SeekFrom::Start(5)).unwrap();
+        assert_eq!(buf.align(8).unwrap(), 3);
+        assert_eq!(buf.pos().unwrap(), 8);
+        // Aligned positions and alignments of 0 or 1 don't move.
+        assert_eq!(buf.align(8).unwrap(), 0);
+        assert_eq!(buf.align(4).unwrap(), 0);
+        buf.read_u1().unwrap();
+        assert_eq!(buf.align(1).unwrap(), 0);
+        assert_eq!(buf.align(0).unwrap(), 0);
+        assert_eq!(buf.pos().unwrap(), 9);
+        assert_eq!(buf.align(2).unwrap(), 1);
+        assert!(buf.is_eof().unwrap());
+        // Padding past the end is skipped too.
+        assert_eq!(buf.align(16).unwrap(), 6);
+        assert!(buf.read_u1().is_err());
+    }
+
     #[test]
     fn read_bytes() {
         let mut buf = new_buf();
@@ -242,6 +769,41 @@ mod tests {
         assert_eq!(vec![2, 3, 4], buf.read_bytes(3).unwrap());
     }
 
+    #[test]
+    fn read_bytes_cursors() {
+        let data = [0, 1, 2, 3, 4];
+        let mut buf = Cursor::new(&data[..]);
+        assert_eq!(buf.read_bytes(2).unwrap(), [0, 1]);
+        assert!(buf.read_bytes(4).is_err());
+        assert_eq!(buf.pos().unwrap(), 2);
+        assert_eq!(buf.read_bytes(3).unwrap(), [2, 3, 4]);
+        assert!(buf.read_bytes(0).unwrap().is_empty());
+
+        let mut buf = Cursor::new(data);
+        buf.set_position(10);
+        assert!(buf.read_bytes(1).is_err());
+        assert!(buf.read_bytes(0).unwrap().is_empty());
+
+        // The generic path, through a reader that isn't a cursor.
+        let mut buf = (&data[..]).take(3);
+        assert_eq!(ReadBytes::read_vec(&mut buf, 3).unwrap(), [0, 1, 2]);
+        assert!(ReadBytes::read_vec(&mut buf, 1).is_err());
+    }
+
+    #[test]
+    fn read_empty_bytes_past_end() {
+        let data = [0, 1, 2, 3, 4];
+        let mut buf = Cursor::new(&data[..]);
+        buf.set_position(10);
+        assert!(buf.read_bytes(0).unwrap().is_empty());
+        assert_eq!(buf.pos().unwrap(), 10);
+
+        let mut buf = Cursor::new(data.to_vec());
+        buf.set_position(u64::MAX);
+        assert!(buf.read_bytes(0).unwrap().is_empty());
+        assert_eq!(buf.position(), u64::MAX);
+    }
+
     #[test]
     fn read_bytes_full() {
         let mut buf = new_buf();
@@ -252,6 +814,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_bytes_full_into() {
+        let mut result = vec![7, 8];
+
+        let mut buf = new_buf();
+        buf.seek(SeekFrom::Start(7)).unwrap();
+        assert_eq!(buf.read_bytes_full_into(&mut result).unwrap(), 3);
+        assert_eq!(buf.read_bytes_full_into(&mut result).unwrap(), 0);
+
+        let mut buf = Cursor::new(vec![10]);
+        assert_eq!(buf.read_bytes_full_into(&mut result).unwrap(), 1);
+
+        assert_eq!(result, [7, 8, 7, 8, 9, 10]);
+    }
+
+    #[test]
+    fn terminator_flags() {
+        let strz = TerminatorFlags::strz();
+        assert!(!strz.include);
+        assert!(strz.consume);
+        assert_eq!(strz, TerminatorFlags::consume());
+        assert_ne!(strz, TerminatorFlags::default());
+    }
+
     #[test]
     fn read_bytes_term() {
         let mut buf: Cursor<Vec<u8>> = Cursor::new(vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
@@ -276,9 +862,76 @@ mod tests {
             buf.read_bytes_term('\u{8}', TerminatorFlags::include())
                 .unwrap()
         );
-        assert!(buf
-            .read_bytes_term('\u{15}', TerminatorFlags::new())
-            .is_err());
+        // The included terminator isn't consumed.
+        assert_eq!(buf.position(), 8);
+        assert!(matches!(
+            buf.read_bytes_term('\u{15}', TerminatorFlags::new()),
+            Err(Error::EofBeforeTerminator {
+                term: '\u{15}',
+                start: 8,
+                read: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn read_bytes_term_immediate() {
+        // The terminator is the first byte, so the data is empty.
+        for (flags, expected, pos) in [
+            (TerminatorFlags::new(), &[][..], 1),
+            (TerminatorFlags::consume(), &[][..], 2),
+            (TerminatorFlags::include(), &[0][..], 1),
+            (TerminatorFlags::all(), &[0][..], 2),
+        ] {
+            let mut buf = Cursor::new(vec![7, 0, 1]);
+            buf.set_position(1);
+            assert_eq!(buf.read_bytes_term('\0', flags).unwrap(), expected);
+            assert_eq!(buf.position(), pos, "{:?}", flags);
+        }
+    }
+
+    #[test]
+    fn read_bytes_term_any() {
+        let mut buf = Cursor::new(b"key=value\nrest\0tail".to_vec());
+        // The second terminator comes first.
+        let (key, term) = buf
+            .read_bytes_term_any(b"\n=", TerminatorFlags::consume())
+            .unwrap();
+        assert_eq!((key.as_slice(), term), (&b"key"[..], b'='));
+
+        let (value, term) = buf
+            .read_bytes_term_any(b"\0\n", TerminatorFlags::include())
+            .unwrap();
+        assert_eq!((value.as_slice(), term), (&b"value\n"[..], b'\n'));
+        assert_eq!(buf.pos().unwrap(), 9);
+        buf.seek(SeekFrom::Current(1)).unwrap();
+
+        let (rest, term) = buf
+            .read_bytes_term_any(b"\0\n", TerminatorFlags::new())
+            .unwrap();
+        assert_eq!((rest.as_slice(), term), (&b"rest"[..], b'\0'));
+        // Without `consume` the terminator is left in the stream.
+        assert_eq!(buf.pos().unwrap(), 14);
+
+        buf.seek(SeekFrom::Current(1)).unwrap();
+        assert!(matches!(
+            buf.read_bytes_term_any(b"\n", TerminatorFlags::new()),
+            Err(Error::EofBeforeTerminators {
+                start: 15,
+                read: 4,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn read_str_fixed() {
+        let mut buf = Cursor::new(b"EXPLORER\0\0\0\0\0\0\0\0README\0\0\x01".to_vec());
+        assert_eq!(buf.read_str_fixed(8, "ASCII", 0).unwrap(), "EXPLORER");
+        assert_eq!(buf.read_str_fixed(8, "ASCII", 0).unwrap(), "");
+        assert_eq!(buf.read_str_fixed(8, "ASCII", 0).unwrap(), "README");
+        assert_eq!(buf.pos().unwrap(), 24);
+        assert!(buf.read_str_fixed(8, "ASCII", 0).is_err());
     }
 
     #[test]
@@ -292,6 +945,71 @@ mod tests {
         assert!(buf.ensure_fixed_contents(&[8, 9, 10]).is_err());
     }
 
+    #[test]
+    fn check_fixed_contents() {
+        let mut buf = new_buf();
+        buf.seek(SeekFrom::Start(2)).unwrap();
+
+        assert!(buf.check_fixed_contents(&[2, 3, 4]).unwrap());
+        assert_eq!(buf.pos().unwrap(), 2);
+        assert!(!buf.check_fixed_contents(&[2, 4]).unwrap());
+        assert_eq!(buf.pos().unwrap(), 2);
+
+        buf.seek(SeekFrom::End(-1)).unwrap();
+        let pos = buf.pos().unwrap();
+        assert!(!buf.check_fixed_contents(&[9, 10]).unwrap());
+        assert_eq!(buf.pos().unwrap(), pos);
+    }
+
+    #[test]
+    fn scan_for() {
+        let mut buf = new_buf();
+        buf.seek(SeekFrom::Start(1)).unwrap();
+        assert_eq!(buf.scan_for(&[4, 5, 6]).unwrap(), Some(4));
+        assert_eq!(buf.pos().unwrap(), 4);
+        // An occurrence at the current position is found again.
+        assert_eq!(buf.scan_for(&[4]).unwrap(), Some(4));
+        assert_eq!(buf.scan_for(&[]).unwrap(), Some(4));
+
+        // Occurrences spanning chunks are found too.
+        let mut data = vec![0; 5000];
+        data[4094..4098].copy_from_slice(b"RIFF");
+        let mut buf = Cursor::new(data);
+        assert_eq!(buf.scan_for(b"RIFF").unwrap(), Some(4094));
+        assert_eq!(buf.read_bytes(4).unwrap(), b"RIFF");
+    }
+
+    #[test]
+    fn scan_for_absent() {
+        let mut buf = new_buf();
+        assert_eq!(buf.scan_for(&[4, 6]).unwrap(), None);
+        assert!(buf.is_eof().unwrap());
+        assert_eq!(buf.scan_for(&[9]).unwrap(), None);
+    }
+
+    #[test]
+    fn try_read_integers() {
+        let mut buf = Cursor::new(vec![0x01, 0x02, 0x03, 0x04, 0xff]);
+        assert_eq!(buf.try_read_u4le().unwrap(), Some(0x0403_0201));
+        assert_eq!(buf.try_read_s1().unwrap(), Some(-1));
+        assert_eq!(buf.try_read_u4le().unwrap(), None);
+        assert_eq!(buf.try_read_u1().unwrap(), None);
+        buf.set_position(2);
+        assert_eq!(buf.try_read_u2be().unwrap(), Some(0x0304));
+    }
+
+    #[test]
+    fn try_read_integers_mid_integer_eof() {
+        let mut buf = Cursor::new(vec![0x01, 0x02, 0x03]);
+        let err = buf.try_read_u4le().unwrap_err();
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::UnexpectedEof));
+
+        buf.set_position(1);
+        assert!(buf.try_read_s8be().is_err());
+        buf.set_position(1);
+        assert_eq!(buf.try_read_u2le().unwrap(), Some(0x0302));
+    }
+
     macro_rules! test_read_integer {
         ($name:ident, $value:expr) => {
             #[test]
@@ -343,4 +1061,26 @@ mod tests {
         let mut buf = Cursor::new(vec![63, 208, 0, 0, 0, 0, 0, 0]);
         assert!((buf.read_f8be().unwrap() - 0.25).abs() < f64::EPSILON);
     }
+
+    #[test]
+    fn read_f4() {
+        let mut buf = Cursor::new(vec![0, 0, 128, 62]);
+        assert_eq!(buf.read_f4(Endian::Le).unwrap(), 0.25);
+        buf.set_position(0);
+        assert_eq!(
+            buf.read_f4(Endian::Be).unwrap(),
+            f32::from_bits(0x0000_803e)
+        );
+    }
+
+    #[test]
+    fn read_f8() {
+        let mut buf = Cursor::new(vec![0, 0, 0, 0, 0, 0, 208, 63]);
+        assert_eq!(buf.read_f8(Endian::Le).unwrap(), 0.25);
+        buf.set_position(0);
+        assert_eq!(
+            buf.read_f8(Endian::Be).unwrap(),
+            f64::from_bits(0x0000_0000_0000_d03f)
+        );
+    }
 }
@@ -0,0 +1,114 @@
+use crate::{
+    error::{Error, Result},
+    runtime::Decoding,
+};
+
+use std::borrow::Cow;
+
+/// Decodes `bytes` as a string in the encoding with the label `encoding` (KS: `encoding`), e.g.
+/// `UTF-8` or `UTF-16LE`.
+///
+/// The labels are the ones of the [Encoding Standard](https://encoding.spec.whatwg.org/#names-and-labels).
+/// A byte order mark is decoded like any other character rather than being used to detect the
+/// encoding.
+///
+/// ```
+/// # use kaitai::{bytes_to_str, Decoding};
+/// assert_eq!(bytes_to_str(b"caf\xc3\xa9", "UTF-8", Decoding::Strict)?, "café");
+/// assert!(bytes_to_str(b"caf\xe9", "UTF-8", Decoding::Strict).is_err());
+/// assert_eq!(bytes_to_str(b"caf\xe9", "UTF-8", Decoding::Lossy)?, "caf\u{fffd}");
+/// # Ok::<(), kaitai::Error>(())
+/// ```
+pub fn bytes_to_str(bytes: &[u8], encoding: &str, decoding: Decoding) -> Result<String> {
+    slice_to_str(bytes, encoding, decoding).map(Cow::into_owned)
+}
+
+/// Decodes `bytes` like [`bytes_to_str`], borrowing the string from `bytes` if they are already
+/// valid UTF-8, e.g. UTF-8 and ASCII strings. It is only allocated if it has to be transcoded or
+/// invalid bytes have to be replaced.
+pub fn slice_to_str<'a>(
+    bytes: &'a [u8],
+    encoding: &str,
+    decoding: Decoding,
+) -> Result<Cow<'a, str>> {
+    let encoding_rs = encoding_rs::Encoding::for_label(encoding.as_bytes())
+        .ok_or_else(|| Error::UnknownEncoding(encoding.to_owned()))?;
+
+    match decoding {
+        Decoding::Strict => encoding_rs
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .ok_or_else(|| Error::InvalidString {
+                encoding: encoding.to_owned(),
+            }),
+        Decoding::Lossy => Ok(encoding_rs.decode_without_bom_handling(bytes).0),
+    }
+}
+
+/// Removes the `pad` bytes at the end of `bytes` (KS: `pad-right`), e.g. the NULs after a C-style
+/// string in a fixed-size field.
+pub fn bytes_strip_right(mut bytes: Vec<u8>, pad: u8) -> Vec<u8> {
+    let len = bytes.iter().rposition(|&b| b != pad).map_or(0, |i| i + 1);
+    bytes.truncate(len);
+    bytes
+}
+
+/// Removes the `pad` bytes at the end of `bytes` like [`bytes_strip_right`], for borrowed bytes.
+pub fn slice_strip_right(bytes: &[u8], pad: u8) -> &[u8] {
+    let len = bytes.iter().rposition(|&b| b != pad).map_or(0, |i| i + 1);
+    &bytes[..len]
+}
+
+/// Truncates `bytes` at the first `term` byte, keeping the terminator if `include` is set.
+///
+/// This is used for a `terminator` inside a field with a `size`.
+pub fn bytes_terminate(mut bytes: Vec<u8>, term: u8, include: bool) -> Vec<u8> {
+    if let Some(i) = bytes.iter().position(|&b| b == term) {
+        bytes.truncate(if include { i + 1 } else { i });
+    }
+    bytes
+}
+
+/// Truncates `bytes` at the first `term` byte like [`bytes_terminate`], for borrowed bytes.
+pub fn slice_terminate(bytes: &[u8], term: u8, include: bool) -> &[u8] {
+    match bytes.iter().position(|&b| b == term) {
+        Some(i) => &bytes[..if include { i + 1 } else { i }],
+        None => bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_utf16() {
+        let bytes = [0x68, 0, 0x69, 0];
+        assert_eq!(
+            bytes_to_str(&bytes, "UTF-16LE", Decoding::Strict).unwrap(),
+            "hi"
+        );
+    }
+
+    #[test]
+    fn unknown_encoding() {
+        let err = bytes_to_str(b"", "UTF-9", Decoding::Lossy).unwrap_err();
+        assert_eq!(err.to_string(), "unknown encoding UTF-9");
+    }
+
+    #[test]
+    fn strip_right() {
+        assert_eq!(bytes_strip_right(vec![1, 0, 2, 0, 0], 0), [1, 0, 2]);
+        assert_eq!(bytes_strip_right(vec![0x20; 3], 0x20), []);
+        assert_eq!(bytes_strip_right(vec![1, 2], 0), [1, 2]);
+        assert_eq!(slice_strip_right(&[1, 0, 2, 0, 0], 0), [1, 0, 2]);
+    }
+
+    #[test]
+    fn terminate() {
+        assert_eq!(bytes_terminate(vec![1, 0, 2, 0], 0, false), [1]);
+        assert_eq!(bytes_terminate(vec![1, 0, 2, 0], 0, true), [1, 0]);
+        assert_eq!(bytes_terminate(vec![1, 2], 0, false), [1, 2]);
+        assert_eq!(slice_terminate(&[1, 0, 2, 0], 0, true), [1, 0]);
+        assert_eq!(slice_terminate(&[1, 2], 0, false), [1, 2]);
+    }
+}
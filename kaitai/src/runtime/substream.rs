@@ -0,0 +1,236 @@
+use crate::{error::Result, runtime::KaitaiStream};
+
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+/// A view of a part of a parent [`KaitaiStream`], used to parse a type with a `size` or
+/// `size-eos` key.
+///
+/// Positions are relative to the start of the substream and reads stop at its end, so e.g.
+/// `repeat: eos` inside the type stops at the end of the substream rather than the end of the
+/// parent. The parent is seeked to the right position before every read, it doesn't have to be
+/// left untouched while the `SubStream` is alive.
+///
+/// After parsing, [`finish`](SubStream::finish) positions the parent right after the substream,
+/// whether the whole substream was read or not.
+#[derive(Debug)]
+pub struct SubStream<'a, S: KaitaiStream + ?Sized> {
+    pub(crate) parent: &'a mut S,
+    /// The position of the start of the substream in the parent.
+    start: u64,
+    len: u64,
+    /// The position in the substream.
+    pos: u64,
+}
+
+impl<'a, S: KaitaiStream + ?Sized> SubStream<'a, S> {
+    /// Creates a substream of the next `len` bytes of `parent`.
+    ///
+    /// Returns an [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) error if the parent doesn't
+    /// have `len` bytes left, which includes a `len` that overflows the position of the parent.
+    pub fn new(parent: &'a mut S, len: u64) -> Result<Self> {
+        let start = parent.pos()?;
+        match start.checked_add(len) {
+            Some(end) if end <= parent.size()? => {}
+            _ => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        }
+        Ok(Self {
+            parent,
+            start,
+            len,
+            pos: 0,
+        })
+    }
+
+    /// Creates a substream of the remaining bytes of `parent` (KS: `size-eos`), which is empty
+    /// if the parent is past its end.
+    pub fn to_end(parent: &'a mut S) -> Result<Self> {
+        let start = parent.pos()?;
+        let len = parent.size()?.saturating_sub(start);
+        Ok(Self {
+            parent,
+            start,
+            len,
+            pos: 0,
+        })
+    }
+
+    /// Returns the length of the substream.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether the substream is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the position of the end of the substream in the parent.
+    pub fn end(&self) -> u64 {
+        // Can't overflow, the end is at most the size of the parent, or the start if it is past
+        // the end of the parent.
+        self.start + self.len
+    }
+
+    /// Seeks the parent to the end of the substream.
+    pub fn finish(self) -> Result<()> {
+        self.parent.seek(SeekFrom::Start(self.end()))?;
+        Ok(())
+    }
+}
+
+impl<S: KaitaiStream + ?Sized> Read for SubStream<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        let max = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        if max == 0 {
+            return Ok(0);
+        }
+
+        self.parent.seek(SeekFrom::Start(self.start + self.pos))?;
+        let n = self.parent.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<S: KaitaiStream + ?Sized> Seek for SubStream<'_, S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.pos = offset;
+                return Ok(offset);
+            }
+            SeekFrom::End(offset) => (self.len, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        match base.checked_add_signed(offset) {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// The stream a type is parsed from and the stream of its parent type, which the instances of the
+/// type with `io: _parent._io` are read from.
+#[doc(hidden)]
+pub trait ParentStreams {
+    type Io: KaitaiStream + ?Sized;
+    type Parent: KaitaiStream + ?Sized;
+
+    fn io(&mut self) -> &mut Self::Io;
+
+    fn parent(&mut self) -> &mut Self::Parent;
+}
+
+/// A type parsed from a substream of its parent stream, i.e. with a `size`.
+impl<S: KaitaiStream + ?Sized> ParentStreams for SubStream<'_, S> {
+    type Io = Self;
+    type Parent = S;
+
+    fn io(&mut self) -> &mut Self {
+        self
+    }
+
+    fn parent(&mut self) -> &mut S {
+        self.parent
+    }
+}
+
+/// A type parsed from the stream of its parent, or a root type, whose parent stream is its own.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct SameStream<'a, S: ?Sized>(pub &'a mut S);
+
+impl<S: KaitaiStream + ?Sized> ParentStreams for SameStream<'_, S> {
+    type Io = S;
+    type Parent = S;
+
+    fn io(&mut self) -> &mut S {
+        self.0
+    }
+
+    fn parent(&mut self) -> &mut S {
+        self.0
+    }
+}
+
+/// A type parsed from a stream unrelated to the stream of its parent, e.g. processed bytes.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct SplitStreams<'a, I: ?Sized, P: ?Sized>(pub &'a mut I, pub &'a mut P);
+
+impl<I: KaitaiStream + ?Sized, P: KaitaiStream + ?Sized> ParentStreams for SplitStreams<'_, I, P> {
+    type Io = I;
+    type Parent = P;
+
+    fn io(&mut self) -> &mut I {
+        self.0
+    }
+
+    fn parent(&mut self) -> &mut P {
+        self.1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn bounded_reads() {
+        let mut buf = Cursor::new(vec![0, 1, 2, 3, 4, 5]);
+        buf.set_position(1);
+
+        let mut sub = SubStream::new(&mut buf, 3).unwrap();
+        assert_eq!(sub.size().unwrap(), 3);
+        assert_eq!(sub.read_u1().unwrap(), 1);
+        assert_eq!(sub.pos().unwrap(), 1);
+        assert_eq!(sub.read_bytes_full().unwrap(), [2, 3]);
+        assert!(sub.is_eof().unwrap());
+        assert!(sub.read_u1().is_err());
+
+        sub.seek(SeekFrom::Start(1)).unwrap();
+        assert_eq!(sub.read_u1().unwrap(), 2);
+        assert!(sub.seek(SeekFrom::Current(-3)).is_err());
+
+        sub.finish().unwrap();
+        assert_eq!(buf.position(), 4);
+    }
+
+    #[test]
+    fn to_end() {
+        let mut buf = Cursor::new(vec![0, 1, 2, 3]);
+        buf.set_position(2);
+
+        let mut sub = SubStream::to_end(&mut buf).unwrap();
+        assert_eq!(sub.len(), 2);
+        assert_eq!(sub.read_u2le().unwrap(), 0x0302);
+
+        // E.g. after an instance seeked past the end.
+        buf.set_position(6);
+        assert!(SubStream::to_end(&mut buf).unwrap().is_empty());
+    }
+
+    #[test]
+    fn too_long() {
+        let mut buf = Cursor::new(vec![0, 1, 2, 3]);
+        let err = SubStream::new(&mut buf, 5).unwrap_err();
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::UnexpectedEof));
+
+        buf.set_position(1);
+        let err = SubStream::new(&mut buf, u64::MAX).unwrap_err();
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::UnexpectedEof));
+    }
+}
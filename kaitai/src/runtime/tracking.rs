@@ -0,0 +1,181 @@
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+/// A wrapper that makes a [`Read`]-only source usable as a
+/// [`KaitaiStream`](crate::runtime::KaitaiStream), e.g. in-memory data that is being decompressed
+/// or decrypted on the fly.
+///
+/// The position is tracked by the wrapper and the last bytes read are buffered, so that seeking
+/// back at most [`MAX_SEEK_BACK`](TrackingReader::MAX_SEEK_BACK) bytes from the furthest
+/// position read works. This covers the small backward seeks done by the runtime, e.g. in
+/// `is_eof` and `read_bytes_term`. Seeking further back returns an
+/// [`Unsupported`](io::ErrorKind::Unsupported) error.
+///
+/// Seeking forwards reads and buffers the skipped bytes. Seeking relative to the end, which is
+/// also done to get the size of the stream, reads and buffers the whole rest of the source.
+#[derive(Debug)]
+pub struct TrackingReader<R> {
+    inner: R,
+    /// The bytes read from `inner` that are kept, the first one being at position `buf_start`.
+    buf: Vec<u8>,
+    buf_start: u64,
+    pos: u64,
+}
+
+/// The number of bytes read from the source at once.
+const CHUNK_SIZE: usize = 4096;
+
+impl<R: Read> TrackingReader<R> {
+    /// The maximum number of bytes a seek is guaranteed to be able to go back.
+    pub const MAX_SEEK_BACK: u64 = 4096;
+
+    /// Creates a `TrackingReader` starting at position 0 of `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            buf_start: 0,
+            pos: 0,
+        }
+    }
+
+    /// Returns the wrapped source, dropping the buffered bytes.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns the position after the last byte read from the source.
+    fn buf_end(&self) -> u64 {
+        self.buf_start + self.buf.len() as u64
+    }
+
+    /// Reads from the source until `target` is buffered or the end of the source is reached.
+    fn fill_to(&mut self, target: u64) -> io::Result<()> {
+        let mut chunk = [0; CHUNK_SIZE];
+        while self.buf_end() < target {
+            let len = usize::try_from(target - self.buf_end())
+                .map_or(CHUNK_SIZE, |len| len.min(CHUNK_SIZE));
+            match self.inner.read(&mut chunk[..len]) {
+                Ok(0) => break,
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the buffered bytes that are too far behind the position to be seeked back to.
+    fn trim(&mut self) {
+        // Only trimming once twice the needed bytes are buffered avoids moving the buffer on
+        // every read.
+        if self.pos > self.buf_start + 2 * Self::MAX_SEEK_BACK {
+            let keep_from = self.pos - Self::MAX_SEEK_BACK;
+            self.buf.drain(..(keep_from - self.buf_start) as usize);
+            self.buf_start = keep_from;
+        }
+    }
+}
+
+impl<R: Read> Read for TrackingReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.buf_end() {
+            self.fill_to(self.pos + out.len() as u64)?;
+        }
+        if self.pos >= self.buf_end() {
+            return Ok(0);
+        }
+
+        let start = (self.pos - self.buf_start) as usize;
+        let n = out.len().min(self.buf.len() - start);
+        out[..n].copy_from_slice(&self.buf[start..start + n]);
+        self.pos += n as u64;
+        self.trim();
+        Ok(n)
+    }
+}
+
+impl<R: Read> Seek for TrackingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                self.fill_to(u64::MAX)?;
+                self.buf_end().checked_add_signed(offset)
+            }
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        if target < self.buf_start {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "cannot seek back to bytes that are no longer buffered",
+            ));
+        }
+        self.fill_to(target)?;
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{KaitaiStream, TerminatorFlags};
+
+    /// A source that can only be read.
+    struct ReadOnly<'a>(&'a [u8]);
+
+    impl Read for ReadOnly<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn read_bytes_term() {
+        let mut stream = TrackingReader::new(ReadOnly(b"abc\0def\0"));
+
+        let flags = TerminatorFlags::new();
+        assert_eq!(stream.read_bytes_term('\0', flags).unwrap(), b"abc");
+        assert_eq!(stream.pos().unwrap(), 3);
+        assert_eq!(stream.read_u1().unwrap(), 0);
+
+        let flags = TerminatorFlags::consume();
+        assert_eq!(stream.read_bytes_term('\0', flags).unwrap(), b"def");
+        assert!(stream.is_eof().unwrap());
+        assert_eq!(stream.size().unwrap(), 8);
+    }
+
+    #[test]
+    fn seek_back_limit() {
+        let data = vec![7; 3 * CHUNK_SIZE];
+        let mut stream = TrackingReader::new(ReadOnly(&data));
+
+        stream.read_bytes(3 * CHUNK_SIZE).unwrap();
+        let max = TrackingReader::<ReadOnly<'_>>::MAX_SEEK_BACK as i64;
+        assert!(stream.seek(SeekFrom::Current(-max)).is_ok());
+        let err = stream.seek(SeekFrom::Current(-1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn seek_forwards() {
+        let mut stream = TrackingReader::new(ReadOnly(&[0, 1, 2, 3, 4]));
+
+        stream.seek(SeekFrom::Start(3)).unwrap();
+        assert_eq!(stream.read_u1().unwrap(), 3);
+        stream.seek(SeekFrom::Start(1)).unwrap();
+        assert_eq!(stream.read_u2be().unwrap(), 0x0102);
+        stream.seek(SeekFrom::End(-1)).unwrap();
+        assert_eq!(stream.read_bytes_full().unwrap(), [4]);
+    }
+}
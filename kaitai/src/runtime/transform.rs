@@ -0,0 +1,287 @@
+use crate::{error::Result, runtime::KaitaiStream};
+
+use std::{
+    hash::Hasher,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+/// A view of a [`KaitaiStream`] whose bytes are XORed with a key as they are read, the on the fly
+/// equivalent of [`process_xor_many`](crate::process_xor_many).
+///
+/// Unlike processing the bytes with `process_xor_many`, this doesn't need the processed region
+/// to be in memory, so a type can be parsed directly from a large processed region:
+///
+/// ```
+/// # use kaitai::{prelude::*, XorStream};
+/// # use std::io::Cursor;
+/// #[kaitai_source("../../tests/formats/basic_le.ksy")]
+/// struct Record;
+///
+/// let key = [0xaa, 0x55];
+/// let bytes = kaitai::process_xor_many(&[1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0], &key);
+/// let mut stream = XorStream::new(Cursor::new(bytes), key.to_vec())?;
+/// let record = Record::from_reader(&mut stream)?;
+/// assert_eq!((record.header, record.body, record.tail), (1, 2, 3));
+/// # Ok::<(), kaitai::Error>(())
+/// ```
+///
+/// The key is aligned with the position of the stream when the `XorStream` is created: the byte
+/// at that position is XORed with the first byte of the key. Positions are those of the wrapped
+/// stream, and seeking keeps the key aligned.
+#[derive(Debug)]
+pub struct XorStream<S> {
+    inner: S,
+    key: Vec<u8>,
+    /// The position the key starts at.
+    start: u64,
+    pos: u64,
+}
+
+impl<S: KaitaiStream> XorStream<S> {
+    /// Creates a view of `inner` XORed with `key`, starting at the current position of `inner`.
+    /// An empty key leaves the bytes unchanged.
+    pub fn new(mut inner: S, key: Vec<u8>) -> Result<Self> {
+        let start = inner.pos()?;
+        Ok(Self {
+            inner,
+            key,
+            start,
+            pos: start,
+        })
+    }
+
+    /// Returns the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: KaitaiStream> Read for XorStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if !self.key.is_empty() {
+            let key_len = self.key.len() as i128;
+            let offset = (i128::from(self.pos) - i128::from(self.start)).rem_euclid(key_len);
+            for (byte, key) in buf[..len]
+                .iter_mut()
+                .zip(self.key.iter().cycle().skip(offset as usize))
+            {
+                *byte ^= key;
+            }
+        }
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<S: KaitaiStream> Seek for XorStream<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+/// A view of a [`KaitaiStream`] whose bytes are rotated left by a number of bits as they are read
+/// (KS: `process: rol(n)`). Rotating right by `n` bits (KS: `ror(n)`) is rotating left by
+/// `8 - n` bits.
+///
+/// ```
+/// # use kaitai::{KaitaiStream, RotateStream};
+/// # use std::io::Cursor;
+/// let mut stream = RotateStream::new(Cursor::new([0x81, 0x10]), 1);
+/// assert_eq!(stream.read_u2be()?, 0x0320);
+/// # Ok::<(), kaitai::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct RotateStream<S> {
+    inner: S,
+    amount: u32,
+}
+
+impl<S: KaitaiStream> RotateStream<S> {
+    /// Creates a view of `inner` with its bytes rotated left by `amount` bits.
+    pub fn new(inner: S, amount: u32) -> Self {
+        Self {
+            inner,
+            amount: amount % 8,
+        }
+    }
+
+    /// Returns the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: KaitaiStream> Read for RotateStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        for byte in &mut buf[..len] {
+            *byte = byte.rotate_left(self.amount);
+        }
+        Ok(len)
+    }
+}
+
+impl<S: KaitaiStream> Seek for RotateStream<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// A view of a [`KaitaiStream`] that feeds the bytes read through it into a [`Hasher`], e.g. to
+/// compare a checksum stored in a format against the one of the bytes it covers.
+///
+/// The bytes are hashed once each, in the order of the stream, from the position of the stream
+/// when the `HashingStream` is created. Bytes that are read again after seeking back aren't hashed
+/// again, so peeking (e.g. [`is_eof`](KaitaiStream::is_eof)) doesn't change the hash, and bytes
+/// read after seeking past unread bytes aren't hashed at all.
+///
+/// ```
+/// # use kaitai::{prelude::*, HashingStream};
+/// # use std::{collections::hash_map::DefaultHasher, hash::Hasher, io::Cursor};
+/// #[kaitai_source("../../tests/formats/basic_le.ksy")]
+/// struct Record;
+///
+/// let bytes = [1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0];
+/// let mut stream = HashingStream::new(Cursor::new(bytes), DefaultHasher::new())?;
+/// Record::from_reader(&mut stream)?;
+///
+/// let mut expected = DefaultHasher::new();
+/// expected.write(&bytes);
+/// assert_eq!(stream.finalize(), expected.finish());
+/// # Ok::<(), kaitai::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct HashingStream<S, H> {
+    inner: S,
+    hasher: H,
+    /// The position the bytes are hashed up to.
+    hashed_to: u64,
+    pos: u64,
+}
+
+impl<S: KaitaiStream, H: Hasher> HashingStream<S, H> {
+    /// Creates a view of `inner` hashing the bytes read with `hasher`, starting at the current
+    /// position of `inner`.
+    pub fn new(mut inner: S, hasher: H) -> Result<Self> {
+        let pos = inner.pos()?;
+        Ok(Self {
+            inner,
+            hasher,
+            hashed_to: pos,
+            pos,
+        })
+    }
+
+    /// Returns the hash of the bytes hashed so far.
+    pub fn finalize(&self) -> u64 {
+        self.hasher.finish()
+    }
+
+    /// Returns the wrapped stream and the hasher.
+    pub fn into_inner(self) -> (S, H) {
+        (self.inner, self.hasher)
+    }
+}
+
+impl<S: KaitaiStream, H: Hasher> Read for HashingStream<S, H> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        let end = self.pos + len as u64;
+        // Only the bytes right after the hashed ones are hashed, so that each is hashed once.
+        if (self.pos..end).contains(&self.hashed_to) {
+            let new = (self.hashed_to - self.pos) as usize;
+            self.hasher.write(&buf[new..len]);
+            self.hashed_to = end;
+        }
+        self.pos = end;
+        Ok(len)
+    }
+}
+
+impl<S: KaitaiStream, H: Hasher> Seek for HashingStream<S, H> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn xor_key_offset_across_reads() {
+        let key = vec![1, 2, 3];
+        let data = crate::process_xor_many(&[10, 20, 30, 40, 50, 60, 70], &key);
+        let mut stream = XorStream::new(Cursor::new(data), key).unwrap();
+
+        assert_eq!(stream.read_bytes(2).unwrap(), [10, 20]);
+        assert_eq!(stream.read_bytes(4).unwrap(), [30, 40, 50, 60]);
+        stream.seek(SeekFrom::Start(1)).unwrap();
+        assert_eq!(stream.read_bytes_full().unwrap(), [20, 30, 40, 50, 60, 70]);
+    }
+
+    #[test]
+    fn xor_starts_at_current_position() {
+        let mut inner = Cursor::new([0xff, 0x0f, 0xf0]);
+        inner.seek(SeekFrom::Start(1)).unwrap();
+        let mut stream = XorStream::new(inner, vec![0xff]).unwrap();
+        assert_eq!(stream.read_bytes(2).unwrap(), [0xf0, 0x0f]);
+
+        // Before the start the key is still aligned with it.
+        let mut stream = XorStream::new(stream.into_inner(), vec![0xff, 0x00]).unwrap();
+        stream.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(stream.read_bytes(3).unwrap(), [0xff, 0xf0, 0xf0]);
+    }
+
+    /// The 64-bit FNV-1a hash, whose digests are the same on every platform.
+    struct Fnv(u64);
+
+    impl Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x100_0000_01b3);
+            }
+        }
+    }
+
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+    #[test]
+    fn hash_read_range() {
+        let mut inner = Cursor::new(b"--hello--".to_vec());
+        inner.seek(SeekFrom::Start(2)).unwrap();
+        let mut stream = HashingStream::new(inner, Fnv(FNV_OFFSET)).unwrap();
+
+        assert_eq!(stream.read_bytes(2).unwrap(), b"he");
+        // Peeking and re-reading don't hash the bytes twice.
+        assert!(!stream.is_eof().unwrap());
+        stream.seek(SeekFrom::Start(3)).unwrap();
+        assert_eq!(stream.read_bytes(4).unwrap(), b"ello");
+        assert_eq!(stream.finalize(), 0xa430_d846_80aa_bd0b);
+
+        // Bytes after a gap aren't hashed.
+        stream.seek(SeekFrom::Current(1)).unwrap();
+        stream.read_bytes(1).unwrap();
+        assert_eq!(stream.finalize(), 0xa430_d846_80aa_bd0b);
+    }
+
+    #[test]
+    fn rotate() {
+        let bytes = [0b1000_0001, 0b0000_0011];
+        let mut stream = RotateStream::new(Cursor::new(bytes), 3);
+        assert_eq!(stream.read_bytes(2).unwrap(), [0b0000_1100, 0b0001_1000]);
+
+        // Rotating right by 3 bits undoes it.
+        let mut stream = RotateStream::new(Cursor::new([0b0000_1100, 0b0001_1000]), 8 - 3);
+        assert_eq!(stream.read_bytes(2).unwrap(), bytes);
+    }
+}
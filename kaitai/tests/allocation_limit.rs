@@ -0,0 +1,58 @@
+use kaitai::{kaitai_source, Error, KaitaiStruct, ParseContext};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/length_prefixed.ksy")]
+struct Message;
+
+#[test]
+fn within_limit() {
+    let ctx = ParseContext::new().with_allocation_limit(3);
+    let bytes = [3, 0, 0, 0, 1, 2, 3, 2, b'h', b'i'];
+    let message = Message::from_reader_with(&mut Cursor::new(bytes), &ctx).unwrap();
+    assert_eq!(message.data, [1, 2, 3]);
+    assert_eq!(message.name, "hi");
+}
+
+#[test]
+fn absurd_length() {
+    let ctx = ParseContext::new().with_allocation_limit(1 << 20);
+    let mut bytes = vec![0xff; 4];
+    bytes.resize(1 << 21, 0);
+    let err = Message::from_reader_with(&mut Cursor::new(bytes), &ctx).unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::AllocationLimitExceeded {
+            requested: 0xffff_ffff,
+            limit: 0x10_0000,
+        }
+    ));
+    assert_eq!(
+        err.without_path().to_string(),
+        "reading 4294967295 bytes exceeds the allocation limit of 1048576 bytes"
+    );
+}
+
+#[test]
+fn limited_by_stream() {
+    // The limit is higher than the size of the string, but the stream ends before it.
+    let ctx = ParseContext::new().with_allocation_limit(1024);
+    let bytes = [1, 0, 0, 0, 0xaa, 200, b'a', b'b'];
+    let err = Message::from_reader_with(&mut Cursor::new(bytes), &ctx).unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::AllocationLimitExceeded {
+            requested: 200,
+            limit: 2,
+        }
+    ));
+}
+
+#[test]
+fn no_limit() {
+    let ctx = ParseContext::new();
+    assert_eq!(ctx.allocation_limit(), None);
+    let bytes = [1, 0, 0, 0, 0xaa, 200, b'a', b'b'];
+    let err = Message::from_reader_with(&mut Cursor::new(bytes), &ctx).unwrap_err();
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+}
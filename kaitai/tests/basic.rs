@@ -30,3 +30,38 @@ fn other_attributes_untouched() {
     let file = BasicLittleEndian::from_file("tests/files/example.basic").unwrap();
     println!("{:#?}", file);
 }
+
+#[test]
+fn try_from_bytes() {
+    use std::convert::TryFrom;
+
+    let bytes = std::fs::read("tests/files/example.basic").unwrap();
+    let file = BasicLittleEndian::try_from(&bytes[..]).unwrap();
+    assert_eq!(file.header, 0x4b_50);
+
+    let err = BasicLittleEndian::try_from(&bytes[..4]).unwrap_err();
+    assert!(matches!(err.without_path(), kaitai::Error::IoError(_)));
+}
+
+// Without multi-byte numbers, the format doesn't need `meta/endian`.
+#[kaitai_source("formats/byte_only.ksy")]
+struct ByteOnly;
+
+#[test]
+fn without_endianness() {
+    let file = ByteOnly::from_bytes(&[2, b'o', b'k', 0xaa, 0xbb, 0xff]).unwrap();
+    assert_eq!(file.tag, "ok");
+    assert_eq!(file.payload, [0xaa, 0xbb]);
+    assert_eq!(file.trailer.checksum, -1);
+}
+
+fn receive(data: Vec<u8>) -> kaitai::Result<BasicLittleEndian> {
+    BasicLittleEndian::from_vec(data)
+}
+
+#[test]
+fn from_vec() {
+    let file = receive(vec![1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0]).unwrap();
+    assert_eq!((file.header, file.body, file.tail), (1, 2, 3));
+    assert!(receive(vec![0; 13]).is_err());
+}
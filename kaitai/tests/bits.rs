@@ -0,0 +1,73 @@
+use kaitai::{kaitai_source, KaitaiStruct, ParseContext};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/bits_mixed.ksy")]
+struct PacketHeader;
+
+#[test]
+fn byte_reads_after_bits_start_on_next_byte() {
+    let header =
+        PacketHeader::from_bytes(&[0b1011_0110, 0x2a, 0xab, 0b1101_0011, 0x12, 0x34]).unwrap();
+    assert_eq!(header.version, 0b101);
+    assert!(header.has_payload);
+    // The 4 bits left in the first byte are discarded.
+    assert_eq!(header.length, 0x2a);
+    assert_eq!(header.channel, 0xabd_u16);
+    assert_eq!(header.priority, 0b00);
+    assert_eq!(header.tail, 0x1234);
+    // Positional bits are read from the byte at the position.
+    assert_eq!(header.low_bits(), 0b1_0110);
+}
+
+#[kaitai_source("formats/bits_enum.ksy")]
+struct BitsEnum;
+
+#[test]
+fn bits_enums() {
+    let e = BitsEnum::from_bytes(&[0b1011_0010, 0b00_010_001]).unwrap();
+    assert_eq!(e.mode, Mode::Exec);
+    assert_eq!(e.mode_raw(), 0b101);
+    assert!(e.enabled);
+    assert_eq!(e.last_mode, Mode::Write);
+    // With `bit-endian: le` the first bits are the least significant ones.
+    assert_eq!(e.le_modes.first, Mode::Read);
+    assert_eq!(e.le_modes.second, Mode::Write);
+
+    // Bits that aren't a member of the enum are an error, or keep their raw value in lenient
+    // parses.
+    let bytes = [0b1110_0000, 0];
+    assert!(BitsEnum::from_bytes(&bytes).is_err());
+    let ctx = ParseContext::new().with_lenient(true);
+    let e = BitsEnum::from_reader_with(&mut Cursor::new(bytes), &ctx).unwrap();
+    assert_eq!(e.mode_raw(), 0b111);
+    assert_eq!(ctx.take_errors().len(), 1);
+}
+
+#[kaitai_source("formats/bits_nested.ksy")]
+struct BitsNested;
+
+#[test]
+fn bits_reset_at_type_boundaries() {
+    let bytes = [
+        0b1010_0000,
+        0x2a,
+        0b1100_1111,
+        0x7f,
+        0b0110_1111,
+        0b1001_0000,
+        0xff,
+        0x55,
+    ];
+    let nested = BitsNested::from_bytes(&bytes).unwrap();
+    assert_eq!(nested.flags, 0b101);
+    // The nested type starts on the byte after the bits of its parent, and the bits left over at
+    // its end aren't read by its parent.
+    assert_eq!(nested.header.first, 0x2a);
+    assert_eq!(nested.header.low, 0b1100);
+    assert_eq!(nested.tail, 0x7f);
+    assert_eq!(nested.more, 0b0110);
+    // The same goes for a type read from a substream.
+    assert_eq!(nested.body.nibble, 0b1001);
+    assert_eq!(nested.last, 0x55);
+}
@@ -0,0 +1,26 @@
+use kaitai::{kaitai_source, Error, KaitaiStruct};
+
+#[kaitai_source("formats/bools.ksy")]
+struct Bools;
+
+#[test]
+fn nonzero_bytes() {
+    let bools = Bools::from_bytes(&[0, 0]).unwrap();
+    assert!(!bools.enabled);
+    assert!(!bools.visible);
+    assert_eq!(bools.extra, None);
+
+    assert!(Bools::from_bytes(&[1, 0]).unwrap().enabled);
+    assert!(Bools::from_bytes(&[255, 0]).unwrap().enabled);
+}
+
+#[test]
+fn strict_bytes() {
+    let bools = Bools::from_bytes(&[0, 1, 7]).unwrap();
+    assert!(bools.visible);
+    // Booleans can be used as conditions.
+    assert_eq!(bools.extra, Some(7));
+
+    let err = Bools::from_bytes(&[0, 255]).unwrap_err();
+    assert!(matches!(err.without_path(), Error::InvalidBool(255)));
+}
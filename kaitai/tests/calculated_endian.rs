@@ -0,0 +1,34 @@
+use kaitai::{kaitai_source, Error, KaitaiStruct};
+
+#[kaitai_source("formats/calculated_endian.ksy")]
+struct CalculatedEndian;
+
+#[test]
+fn little_endian() {
+    let parsed =
+        CalculatedEndian::from_bytes(&[1, 0x34, 0x12, 2, 2, 0, 5, 0, 1, 3, 0, 0, 4]).unwrap();
+    assert_eq!(parsed.body.magic, 0x1234);
+    // The nested types without an endianness of their own inherit the calculated one.
+    assert_eq!(parsed.body.entry.values, [2, 5]);
+    assert_eq!(parsed.body.sized.values, [3]);
+    assert_eq!(parsed.body.big.value, 4);
+}
+
+#[test]
+fn big_endian() {
+    let parsed =
+        CalculatedEndian::from_bytes(&[2, 0x12, 0x34, 2, 0, 2, 0, 5, 1, 0, 3, 0, 4]).unwrap();
+    assert_eq!(parsed.body.magic, 0x1234);
+    assert_eq!(parsed.body.entry.values, [2, 5]);
+    assert_eq!(parsed.body.sized.values, [3]);
+    assert_eq!(parsed.body.big.value, 4);
+}
+
+#[test]
+fn undecided() {
+    let err = CalculatedEndian::from_bytes(&[3, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::UndecidedEndianness { ty, value } if ty == "Body" && value == "3"
+    ));
+}
@@ -0,0 +1,33 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/instances.ksy")]
+struct Instances;
+
+#[kaitai_source("formats/switch.ksy")]
+struct Switch;
+
+#[test]
+fn clone_instances() {
+    let bytes = vec![2, 6, 0x10, 0x00, b'o', b'k', 0x34, 0x12];
+    let instances = Instances::from_bytes(&bytes).unwrap();
+    // The instances are computed when the type is parsed, so the copy has their values without a
+    // stream to read them from.
+    let copy = instances.clone();
+    drop(bytes);
+    assert_eq!(copy.extension(), Some(0x1234));
+    assert_eq!(copy.tag().map(Vec::as_slice), Some(&b"ok"[..]));
+    assert_eq!(copy.double_length(), instances.double_length());
+    assert!(copy == instances);
+}
+
+#[test]
+fn clone_sub_structure() {
+    let body = {
+        let s = Switch::from_bytes(&[1, 9]).unwrap();
+        s.body.clone()
+    };
+    assert!(matches!(
+        body,
+        SwitchBody::CatBody(CatBody { lives: 9, .. })
+    ));
+}
@@ -0,0 +1,75 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/conditional.ksy")]
+struct Conditional;
+
+#[kaitai_source("formats/conditional_repeat.ksy")]
+struct ConditionalRepeat;
+
+#[kaitai_source("formats/conditional_instance.ksy")]
+struct ConditionalInstance;
+
+#[test]
+fn absent_fields() {
+    let c = Conditional::from_bytes(&[1, 9]).unwrap();
+    assert_eq!(c.extra, None);
+    // `-rust-if-default` fields are zero when absent.
+    assert_eq!(c.flags, 0);
+    assert_eq!(c.kind, None);
+    assert_eq!(c.kind_raw(), None);
+    assert_eq!(c.tail, 9);
+}
+
+#[test]
+fn present_fields() {
+    let c = Conditional::from_bytes(&[3, 0x34, 0x12, 0x01, 0x00, 2, 9]).unwrap();
+    assert_eq!(c.extra, Some(0x1234));
+    assert_eq!(c.flags, 1);
+    assert_eq!(c.kind, Some(Animal::Dog));
+    assert_eq!(c.kind_raw(), Some(2));
+    assert_eq!(c.tail, 9);
+}
+
+#[test]
+fn absent_repeated_fields() {
+    let c = ConditionalRepeat::from_bytes(&[0]).unwrap();
+    assert_eq!(c.count, 0);
+    assert!(c.records.is_none());
+    assert_eq!(c.kinds, None);
+    assert_eq!(c.kinds_raw(), None);
+    assert_eq!(c.tail, None);
+}
+
+#[test]
+fn present_repeated_fields() {
+    let c = ConditionalRepeat::from_bytes(&[2, 0, 1, 7, 0, 2, 8, 1, 2, 5, 6]).unwrap();
+    let records = c.records.as_ref().unwrap();
+    assert_eq!(
+        records.iter().map(|r| (r.id, r.value)).collect::<Vec<_>>(),
+        [(1, 7), (2, 8)]
+    );
+    assert_eq!(c.kinds, Some(vec![Kind::Small, Kind::Large]));
+    assert_eq!(c.kinds_raw().map(|raw| raw.to_vec()), Some(vec![1, 2]));
+    assert_eq!(c.tail, Some(vec![5, 6]));
+
+    // A single record has no tail, even though bytes remain.
+    let c = ConditionalRepeat::from_bytes(&[1, 0, 3, 9, 2, 4]).unwrap();
+    assert_eq!(c.records.unwrap().len(), 1);
+    assert_eq!(c.tail, None);
+}
+
+#[test]
+fn conditions_on_instances() {
+    let c = ConditionalInstance::from_bytes(&[1, 1]).unwrap();
+    assert!(!c.has_extension());
+    assert_eq!((c.extension, c.checksum), (None, None));
+
+    // `has_checksum` refers to `has_extension`, which is computed before it.
+    let c = ConditionalInstance::from_bytes(&[2, 0, 0x34, 0x12]).unwrap();
+    assert_eq!((c.extension, c.checksum), (Some(0x1234), None));
+
+    let c = ConditionalInstance::from_bytes(&[2, 1, 0x34, 0x12, 0xff]).unwrap();
+    assert!(c.has_checksum());
+    assert_eq!(c.checksum, Some(0xff));
+    assert_eq!(c.total(), 3);
+}
@@ -0,0 +1,20 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/bytes.ksy")]
+struct Bytes;
+
+#[test]
+fn byte_arrays_in_hex() {
+    let bytes = Bytes::from_bytes(&[0x7f, 0x01, 0x02, 0x03]).unwrap();
+    let debug = format!("{:?}", bytes);
+    assert!(debug.starts_with("Bytes { magic: 127, data: 01 02 03, tail: "));
+}
+
+#[test]
+fn long_byte_arrays_truncated() {
+    let mut input = vec![0x7f, 0x01, 0x02, 0x03];
+    input.extend_from_slice(&[0xee; 1000]);
+    let bytes = Bytes::from_bytes(&input).unwrap();
+    let debug = format!("{:?}", bytes);
+    assert!(debug.contains("ee ee ... (1000 bytes)"));
+}
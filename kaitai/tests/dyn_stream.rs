@@ -0,0 +1,24 @@
+use kaitai::{kaitai_source, KaitaiStream, KaitaiStruct};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/basic_le.ksy")]
+struct BasicLittleEndian;
+
+fn parse(stream: &mut dyn KaitaiStream) -> kaitai::Result<BasicLittleEndian> {
+    BasicLittleEndian::from_reader(stream)
+}
+
+#[test]
+fn boxed_stream() {
+    let mut input = vec![0x50, 0x4b];
+    input.extend(&[0x03, 0x04, 0x14, 0x00, 0x02, 0x00, 0x00, 0x00]);
+    input.extend(&[0x02, 0x5d, 0x5e, 0x49]);
+
+    let mut stream: Box<dyn KaitaiStream> = Box::new(Cursor::new(input));
+    let file = parse(&mut stream).unwrap();
+
+    assert_eq!(file.header, 0x4b_50);
+    assert_eq!(file.body, 0x02_00_14_04_03);
+    assert_eq!(file.tail, 0x49_5e_5d_02);
+}
@@ -10,3 +10,98 @@ fn main() {
     assert_eq!(e.protocol2, IpProtocol::Tcp);
     assert_eq!(e.protocol3, IpProtocol::Udp);
 }
+
+#[test]
+fn raw_values() {
+    let e = EnumsStruct::from_bytes(&[1, 6, 17]).unwrap();
+    assert_eq!(e.protocol, IpProtocol::Icmp);
+    assert_eq!(e.protocol_raw(), 1);
+    assert_eq!(e.protocol2_raw(), 6);
+    assert_eq!(e.protocol3_raw(), 17);
+    assert_eq!(e.protocol3_raw(), e.protocol3 as u8);
+}
+
+// The types are in a module so that they don't clash with the ones of `enums.ksy`.
+#[kaitai_source("formats/enums_doc.ksy", module)]
+struct EnumsDoc;
+
+#[test]
+fn documented_variants() {
+    let e = EnumsDoc::from_bytes(&[6, 6, 1]).unwrap();
+    assert_eq!(e.protocol, enums_doc::IpProtocol::Tcp);
+    assert_eq!(
+        e.others,
+        [enums_doc::IpProtocol::Tcp, enums_doc::IpProtocol::Icmp]
+    );
+    assert_eq!(e.others_raw(), [6, 1]);
+}
+
+#[kaitai_source("formats/enum_u64.ksy")]
+struct EnumU64;
+
+#[test]
+fn values_above_i64_max() {
+    let e =
+        EnumU64::from_bytes(&[0xff; 8].iter().chain(&[1]).copied().collect::<Vec<_>>()).unwrap();
+    assert_eq!(e.marker, Marker::Full);
+    assert_eq!(e.marker_raw(), u64::MAX);
+    assert_eq!(e.level, Level::Low);
+
+    let mut bytes = 0x8000_0000_0000_0000u64.to_le_bytes().to_vec();
+    bytes.push(127);
+    let e = EnumU64::from_bytes(&bytes).unwrap();
+    assert_eq!(e.marker, Marker::High);
+    assert_eq!(e.level, Level::Max);
+}
+
+#[test]
+fn negative_values_have_no_variant() {
+    let mut bytes = 0u64.to_le_bytes().to_vec();
+    bytes.push(0xff);
+    assert!(matches!(
+        EnumU64::from_bytes(&bytes)
+            .as_ref()
+            .map_err(kaitai::Error::without_path),
+        Err(kaitai::Error::NoEnumMatch)
+    ));
+}
+
+#[test]
+fn try_from_integers() {
+    use std::convert::TryFrom;
+
+    assert_eq!(Marker::try_from(u64::MAX).unwrap(), Marker::Full);
+    assert!(Marker::try_from(1u64).is_err());
+    assert_eq!(Level::try_from(127i64).unwrap(), Level::Max);
+    assert!(Level::try_from(-1i64).is_err());
+}
+
+#[kaitai_source("formats/enum_inline.ksy")]
+struct Packet;
+
+#[test]
+fn inline_enums() {
+    let packet = Packet::from_bytes(&[2, 5, 1]).unwrap();
+    // The enums are named after the type and the field, so fields of different types don't clash.
+    assert_eq!(packet.kind, PacketKind::Ack);
+    assert_eq!(packet.kind_raw(), 2);
+    assert_eq!(packet.next[0].kind, HeaderKind::Reset);
+    assert_eq!(packet.next[1].kind, HeaderKind::Data);
+}
+
+#[kaitai_source("formats/enum_compare.ksy")]
+struct EnumCompare;
+
+#[test]
+fn compare_to_members() {
+    let e = EnumCompare::from_bytes(&[1, 0x20, 0, 5]).unwrap();
+    assert_eq!(e.file_type, FileType::Png);
+    assert_eq!((e.width, e.pages), (Some(0x20), None));
+    assert_eq!(e.body, EnumCompareBody::U1(5));
+    assert!(e.is_image());
+
+    let e = EnumCompare::from_bytes(&[3, 4, 0x34, 0x12]).unwrap();
+    assert_eq!((e.width, e.pages), (None, Some(4)));
+    assert_eq!(e.body, EnumCompareBody::U2(0x1234));
+    assert!(!e.is_image());
+}
@@ -0,0 +1,24 @@
+#![cfg(feature = "error-path")]
+
+use kaitai::{kaitai_source, Error, KaitaiStruct};
+
+#[kaitai_source("formats/error_path.ksy")]
+struct ErrorPath;
+
+#[test]
+fn nested_field_path() {
+    let bytes = [4, 1, b'a', 1, b'b', 1, b'c', 5, b'd'];
+    let err = ErrorPath::from_bytes(&bytes).unwrap_err();
+    assert_eq!(err.path(), Some("header.entries[3].name"));
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+    assert!(err.to_string().starts_with("header.entries[3].name: "));
+
+    let err = ErrorPath::from_bytes(&[2, 1, b'a']).unwrap_err();
+    assert_eq!(err.path(), Some("header.entries[1].len"));
+    assert!(matches!(err.without_path(), Error::IoError(_)));
+
+    assert_eq!(
+        ErrorPath::from_bytes(&[1, 1, b'a']).unwrap().header.entries[0].name,
+        "a"
+    );
+}
@@ -29,3 +29,16 @@ fn fixed_err() {
 
     assert!(Fixed::from_bytes(&input).is_err());
 }
+
+#[test]
+fn fixed_err_message() {
+    let mut input: Vec<u8> = Vec::new();
+    input.extend(b"test string");
+    input.extend(&[0xab, 0xad, 0xbb, 0xbe]);
+
+    let err = Fixed::from_bytes(&input).unwrap_err();
+    let message = err.without_path().to_string();
+    assert!(message
+        .starts_with("unexpected fixed contents of byte_contents, first mismatch at offset 2\n"));
+    assert!(message.contains("expected: ab ad ba be\n  actual: ab ad bb be"));
+}
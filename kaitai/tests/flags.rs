@@ -0,0 +1,37 @@
+use kaitai::{kaitai_source, KaitaiStruct, ParseContext};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/flags.ksy")]
+struct Flags;
+
+#[test]
+fn flag_accessors() {
+    let flags = Flags::from_bytes(&[0b1010_1000, 0x34, 0x12]).unwrap();
+    assert!(flags.header.compressed());
+    assert!(!flags.header.encrypted());
+    assert!(flags.header.signed());
+    assert!(!flags.header.reserved());
+    assert!(flags.header.has_checksum());
+    assert_eq!(flags.header.bits(), 0b1010_1000);
+    assert_eq!(flags.length, 0x1234);
+}
+
+#[test]
+fn unused_bits() {
+    // The bits after the last flag are read but don't belong to any flag.
+    let flags = Flags::from_bytes(&[0b0000_0111, 0, 0]).unwrap();
+    assert!(!flags.header.compressed());
+    assert!(!flags.header.has_checksum());
+    assert_eq!(flags.header.bits(), 0b0000_0111);
+}
+
+#[test]
+fn reread_flags() {
+    let mut header = Flags::from_bytes(&[0b1010_1000, 0, 0]).unwrap().header;
+    let mut stream = Cursor::new([0b0100_0000]);
+    header.read(&mut stream, &ParseContext::default()).unwrap();
+    assert!(!header.compressed());
+    assert!(header.encrypted());
+    assert_eq!(stream.position(), 1);
+}
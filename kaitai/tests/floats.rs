@@ -0,0 +1,26 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+use std::collections::HashSet;
+
+#[kaitai_source("formats/floats.ksy", hash)]
+struct Floats;
+
+#[test]
+fn float_fields() {
+    let mut bytes = vec![1];
+    bytes.extend(0.25f32.to_le_bytes());
+    bytes.extend(f64::NAN.to_le_bytes());
+    bytes.extend([7, 0]);
+    bytes.extend(1.5f32.to_le_bytes());
+
+    let file = Floats::from_bytes(&bytes).unwrap();
+    assert_eq!(file.points[0].x, 0.25);
+    assert!(file.points[0].y.is_nan());
+    assert_eq!(file.reading, FloatsReading::F4(1.5));
+    assert_eq!(file.scale, 0.5);
+
+    // The types holding floats are only `PartialEq`, the others are still `Eq` and `Hash`.
+    assert_ne!(file.points[0], file.points[0].clone());
+    let tags = HashSet::from([file.tag.clone(), file.tag]);
+    assert_eq!(tags.len(), 1);
+}
@@ -0,0 +1,32 @@
+use kaitai::{kaitai_source, Guid, KaitaiStruct};
+
+#[kaitai_source("formats/guid.ksy", to_bytes)]
+struct ObjectHeader;
+
+const BYTES: [u8; 24] = [
+    0x30, 0x26, 0xb2, 0x75, 0x8e, 0x66, 0xcf, 0x11, 0xa6, 0xd9, 0x00, 0xaa, 0x00, 0x62, 0xce, 0x6c,
+    0x1e, 0, 0, 0, 0, 0, 0, 0,
+];
+
+#[test]
+fn canonical_guid() {
+    let header = ObjectHeader::from_bytes(&BYTES).unwrap();
+    assert_eq!(
+        header.object_id.to_string(),
+        "{75b22630-668e-11cf-a6d9-00aa0062ce6c}"
+    );
+    // The raw bytes are kept as they were read.
+    assert_eq!(header.object_id.0, BYTES[..16]);
+    assert_eq!(header.size, 0x1e);
+    assert_eq!(header.to_bytes().unwrap(), BYTES);
+}
+
+#[test]
+fn debug_renders_canonical_guid() {
+    let header = ObjectHeader::from_bytes(&BYTES).unwrap();
+    assert!(format!("{:?}", header).contains("object_id: {75b22630-668e-11cf-a6d9-00aa0062ce6c}"));
+    assert_eq!(
+        Guid::default().to_string(),
+        "{00000000-0000-0000-0000-000000000000}"
+    );
+}
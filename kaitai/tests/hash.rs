@@ -0,0 +1,20 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+use std::collections::HashSet;
+
+#[kaitai_source("formats/records.ksy", hash)]
+struct Records;
+
+#[test]
+fn deduplicated_records() {
+    let first = Records::from_bytes(&[1, 3, b'a', b'b', b'c', 2, 7, 0, 0, 0]).unwrap();
+    let second = Records::from_bytes(&[1, 3, b'a', b'b', b'c', 2, 8, 0, 0, 0]).unwrap();
+
+    let unique = first
+        .records
+        .into_iter()
+        .chain(second.records)
+        .collect::<HashSet<_>>();
+    assert_eq!(unique.len(), 3);
+    assert!(unique.iter().any(|r| r.body == RecordBody::U4(8)));
+}
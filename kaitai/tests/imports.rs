@@ -0,0 +1,30 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+// The imported file is generated once, and its types are shared by the files importing it.
+#[kaitai_source("formats/common.ksy")]
+struct Common;
+
+#[kaitai_source("formats/import_shape.ksy")]
+struct ImportShape;
+
+mod paths {
+    use super::*;
+
+    #[kaitai_source("formats/import_path.ksy", module)]
+    struct ImportPath;
+}
+
+#[test]
+fn shared_imported_types() {
+    let shape = ImportShape::from_bytes(&[1, 2, 3]).unwrap();
+    let header: &Common = &shape.header;
+    assert_eq!(header.version, 1);
+    assert_eq!((shape.origin.x, shape.origin.y), (2, 3));
+
+    let path = paths::ImportPath::from_bytes(&[2, 1, 2, 3, 4]).unwrap();
+    let points: &[Point] = &path.points;
+    assert_eq!((points[1].x, points[1].y), (3, 4));
+    // The shared types are parsed the same way whichever file they are read from.
+    let point = Point::from_bytes(&[1, 2]).unwrap();
+    assert_eq!((point.x, point.y), (points[0].x, points[0].y));
+}
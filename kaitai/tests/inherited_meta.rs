@@ -0,0 +1,40 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/inherited_meta.ksy")]
+struct InheritedMeta;
+
+const BYTES: &[u8] = &[
+    0x12,
+    0x34, // child.value
+    0x12,
+    0x34,
+    0x56,
+    0x78,
+    b'h',
+    b'i',
+    0b0000_0001, // child.grandchild
+    0x34,
+    0x12,
+    b'y',
+    b'o', // override
+];
+
+#[test]
+fn grandchild_inherits_meta() {
+    let meta = InheritedMeta::from_bytes(BYTES).unwrap();
+    assert_eq!(meta.child.value, 0x1234);
+
+    let grandchild = &meta.child.grandchild;
+    assert_eq!(grandchild.value, 0x1234_5678);
+    assert_eq!(grandchild.name, "hi");
+    // The first flag is the least significant bit, as the root is `bit-endian: le`.
+    assert!(grandchild.flags.first());
+    assert!(!grandchild.flags.second());
+}
+
+#[test]
+fn child_meta_overrides_only_its_keys() {
+    let meta = InheritedMeta::from_bytes(BYTES).unwrap();
+    assert_eq!(meta.r#override.value, 0x1234);
+    assert_eq!(meta.r#override.name, "yo");
+}
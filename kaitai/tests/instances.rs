@@ -0,0 +1,115 @@
+use kaitai::{error::Error, kaitai_source, KaitaiStruct};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/instances.ksy")]
+struct Instances;
+
+#[kaitai_source("formats/chunk_offset.ksy")]
+struct ChunkOffset;
+
+#[kaitai_source("formats/record_ref.ksy")]
+struct RecordRef;
+
+#[kaitai_source("formats/division.ksy")]
+struct Division;
+
+#[test]
+fn value_instances() {
+    let instances = Instances::from_bytes(&[1, 0, 0x10, 0x00]).unwrap();
+    assert_eq!(instances.double_length(), 0x20);
+    assert!(!instances.has_extension());
+}
+
+#[test]
+fn instance_with_false_condition() {
+    // The extension offset is out of bounds, but the extension isn't read with version 1.
+    let instances = Instances::from_bytes(&[1, 0xff, 0x10, 0x00]).unwrap();
+    assert_eq!(instances.extension(), None);
+    assert_eq!(instances.tag(), None);
+}
+
+#[test]
+fn instance_with_true_condition() {
+    let instances = Instances::from_bytes(&[2, 6, 0x10, 0x00, b'o', b'k', 0x34, 0x12]).unwrap();
+    assert_eq!(instances.extension(), Some(0x1234));
+    assert_eq!(instances.tag().map(Vec::as_slice), Some(&b"ok"[..]));
+}
+
+#[test]
+fn ternary_instances() {
+    // The branches are a `u2` and a `u1`, so the value is a `u16`.
+    let instances = Instances::from_bytes(&[1, 0, 0x10, 0x01]).unwrap();
+    let effective_length: u16 = instances.effective_length();
+    assert_eq!(effective_length, 1);
+    assert!(instances.is_short());
+
+    let instances = Instances::from_bytes(&[2, 6, 0x10, 0x01, b'o', b'k', 0x34, 0x12]).unwrap();
+    assert_eq!(instances.effective_length(), 0x0110);
+    assert!(!instances.is_short());
+}
+
+#[test]
+fn positional_instances_restore_position() {
+    let mut stream = Cursor::new([2, 6, 0x10, 0x00, b'o', b'k', 0x34, 0x12]);
+    Instances::from_reader(&mut stream).unwrap();
+    assert_eq!(stream.position(), 4);
+}
+
+#[test]
+fn overflowing_position() {
+    let mut bytes = [0; 17];
+    bytes[0] = 8;
+    bytes[8] = 8;
+    bytes[16] = 0x2a;
+    assert_eq!(ChunkOffset::from_bytes(&bytes).unwrap().chunk(), 0x2a);
+
+    // The position would wrap around to 7 instead of failing.
+    bytes[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let err = ChunkOffset::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err, Error::ArithmeticOverflow("addition")));
+}
+
+#[test]
+fn overflowing_value() {
+    let err = Instances::from_bytes(&[1, 0, 0xff, 0xff]).unwrap_err();
+    assert!(matches!(err, Error::ArithmeticOverflow("multiplication")));
+}
+
+#[test]
+fn checked_division() {
+    let division = Division::from_bytes(&[-7i8 as u8, 2]).unwrap();
+    assert_eq!(division.quotient(), -3);
+    // The modulo is never negative, as in Kaitai Struct.
+    assert_eq!(division.modulo(), 1);
+    assert_eq!(division.negated(), -2);
+
+    for (bytes, operation) in [
+        ([1, 0], "division"),
+        ([i8::MIN as u8, -1i8 as u8], "division"),
+        ([1, i8::MIN as u8], "negation"),
+    ] {
+        let err = Division::from_bytes(&bytes).unwrap_err();
+        assert!(matches!(err, Error::ArithmeticOverflow(op) if op == operation));
+    }
+}
+
+#[test]
+fn user_type_at_offset() {
+    let bytes = [
+        3, 8, 0xee, 7, 0, b'o', b'n', b'e', 1, 0, b'a', b'b', b'c', 2, 0, b'x', b'y', b'z',
+    ];
+    let mut stream = Cursor::new(bytes);
+    let record_ref = RecordRef::from_reader(&mut stream).unwrap();
+    assert_eq!(stream.position(), 3);
+    assert_eq!(record_ref.tail, 0xee);
+
+    // The records are parsed once, when the type is parsed, and kept.
+    let record = record_ref.record();
+    assert_eq!((record.id, record.name.value.as_str()), (7, "one"));
+    let records = record_ref.records();
+    assert_eq!((records[0].id, records[1].id), (1, 2));
+    assert_eq!(records[1].name.value, "xyz");
+    // Sized instances are parsed from a substream at the position.
+    assert_eq!(record_ref.named().value, "one");
+}
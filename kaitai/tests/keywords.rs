@@ -0,0 +1,19 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/keywords.ksy")]
+struct Keywords;
+
+#[test]
+fn keyword_fields() {
+    let k = Keywords::from_bytes(&[2, 3, 4, 0x34, 0x12]).unwrap();
+    assert_eq!(k.r#type, Match::Struct);
+    assert_eq!(k.self_, 3);
+    assert_eq!(k.r#while, 4);
+    assert_eq!(k.r#loop, KeywordsLoop::U2(0x1234));
+
+    let err = Keywords::from_bytes(&[1, 3, 2, 0]).unwrap_err();
+    assert_eq!(
+        err.without_path().to_string(),
+        "while is not valid, 2 is less than the minimum 3"
+    );
+}
@@ -0,0 +1,20 @@
+use kaitai::kaitai_source;
+
+#[kaitai_source("formats/header.ksy", layout)]
+struct Header;
+
+#[test]
+fn fixed_layout() {
+    assert_eq!(
+        Header::LAYOUT,
+        [
+            "0x0000 4 magic",
+            "0x0004 2 version",
+            "0x0006 1 kind",
+            "0x0007 4 offset",
+            "0x000b 3 reserved",
+            "0x000e 4 entries",
+        ]
+        .join("\n")
+    );
+}
@@ -0,0 +1,54 @@
+use kaitai::{kaitai_source, Error, KaitaiStruct, ParseContext};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/valid.ksy")]
+struct Valid;
+
+#[kaitai_source("formats/enums.ksy")]
+struct EnumsStruct;
+
+fn lenient() -> ParseContext {
+    ParseContext::new().with_lenient(true)
+}
+
+#[test]
+fn validation_collected() {
+    let ctx = lenient();
+    let v = Valid::from_reader_with(&mut Cursor::new([0x7e, 9, 0, 0]), &ctx).unwrap();
+    assert_eq!(v.magic, 0x7e);
+    assert_eq!(v.version, 9);
+
+    let errors = ctx.take_errors();
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(&errors[0], Error::ValidationNotEqual { field, .. } if field == "magic"));
+    assert!(matches!(&errors[1], Error::ValidationGreaterThan { field, .. } if field == "version"));
+}
+
+#[test]
+fn unknown_enum_collected() {
+    let ctx = lenient();
+    let e = EnumsStruct::from_reader_with(&mut Cursor::new([1, 99, 42]), &ctx).unwrap();
+    assert_eq!(e.protocol2, IpProtocol::Icmp);
+    assert_eq!(e.protocol2_raw(), 99);
+    assert_eq!(e.protocol3_raw(), 42);
+
+    let errors = ctx.take_errors();
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|e| matches!(e, Error::NoEnumMatch)));
+}
+
+#[test]
+fn fatal_errors_abort() {
+    let ctx = lenient();
+    let err = Valid::from_reader_with(&mut Cursor::new([0x7e, 1]), &ctx).unwrap_err();
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+    // The failed check before the end of the stream was still collected.
+    assert_eq!(ctx.take_errors().len(), 1);
+}
+
+#[test]
+fn strict_by_default() {
+    assert!(Valid::from_bytes(&[0x7e, 1, 0, 0]).is_err());
+    assert!(EnumsStruct::from_bytes(&[1, 99, 17]).is_err());
+}
@@ -0,0 +1,32 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+mod inner {
+    use super::*;
+
+    #[kaitai_source("formats/basic_le.ksy", root = "Renamed", visibility = "pub(crate)")]
+    struct Basic;
+}
+
+#[test]
+fn renamed_root() {
+    let basic = inner::Renamed::from_bytes(&[0; 14]).unwrap();
+    assert_eq!(basic.tail, 0);
+}
+
+#[kaitai_source(inline = "
+meta:
+  id: point
+  endian: be
+seq:
+  - id: x
+    type: u2
+  - id: y
+    type: u2
+")]
+struct Point;
+
+#[test]
+fn inline_ksy() {
+    let point = Point::from_bytes(&[0, 1, 0x12, 0x34]).unwrap();
+    assert_eq!((point.x, point.y), (1, 0x1234));
+}
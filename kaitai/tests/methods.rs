@@ -0,0 +1,68 @@
+use kaitai::{kaitai_source, Error, KaitaiStruct};
+
+#[kaitai_source("formats/methods.ksy")]
+struct Methods;
+
+#[kaitai_source("formats/array_methods.ksy")]
+struct ArrayMethods;
+
+#[kaitai_source("formats/indexing.ksy")]
+struct Indexing;
+
+#[test]
+fn string_methods() {
+    let m = Methods::from_bytes(b"ab\0endless\x0010\x07xyok").unwrap();
+    assert_eq!(m.names, ["ab", "endless"]);
+    assert_eq!(m.number, "10");
+    assert_eq!(m.body, MethodsBody::U1(7));
+    assert_eq!(m.chunks, [b"xy", b"ok"]);
+}
+
+#[test]
+fn empty_string_length() {
+    let m = Methods::from_bytes(b"\0ff\x34\x12ok").unwrap();
+    assert_eq!(m.names, [""]);
+    assert_eq!(m.body, MethodsBody::U2(0x1234));
+}
+
+#[test]
+fn invalid_integer() {
+    let err = Methods::from_bytes(b"\0zz\0\0ok").unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::InvalidInteger { radix: 16, .. }
+    ));
+}
+
+#[test]
+fn array_methods() {
+    let a = ArrayMethods::from_bytes(&[3, 0, 1, 0, 2, 0, 3, 0, 2, 0, 9, 0, 3, 0]).unwrap();
+    assert_eq!(a.values, [3, 1, 2]);
+    assert_eq!(a.rest, [9, 3]);
+
+    // `largest` isn't the maximum.
+    assert!(ArrayMethods::from_bytes(&[3, 0, 1, 0, 2, 0, 2, 0, 2, 0, 3, 0]).is_err());
+    // `between` is greater than the last value.
+    assert!(ArrayMethods::from_bytes(&[3, 0, 1, 0, 2, 0, 3, 0, 3, 0, 3, 0]).is_err());
+}
+
+#[test]
+fn indexing() {
+    let i = Indexing::from_bytes(&[3, 1, 5, 2, 1, b'M', b'Z', 7]).unwrap();
+    assert_eq!(i.selected, 5);
+    assert_eq!(i.magic_head, b'Z');
+    assert_eq!(i.extra, Some(7));
+
+    let i = Indexing::from_bytes(&[3, 1, 5, 2, 0, b'M', b'Z']).unwrap();
+    assert_eq!(i.selected, 1);
+    assert_eq!(i.extra, None);
+}
+
+#[test]
+fn index_out_of_bounds() {
+    let err = Indexing::from_bytes(&[2, 1, 5, 2, b'M', b'Z', 7]).unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::IndexOutOfBounds { index: 2, len: 2 }
+    ));
+}
@@ -0,0 +1,30 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/user_type_be.ksy", module)]
+struct UserType;
+
+mod inner {
+    use super::*;
+
+    #[kaitai_source("formats/switch.ksy", module = "formats", visibility = "pub(crate)")]
+    struct Switch;
+}
+
+#[test]
+fn nested_types_in_module() {
+    let file = UserType::from_file("tests/files/example.png").unwrap();
+    let header: &user_type::Header = &file.header;
+    assert_eq!(header.seq_1, 0x89504E47);
+    // The root type is also in the module.
+    let _: &user_type::UserType = &file;
+}
+
+#[test]
+fn named_module() {
+    let s = inner::Switch::from_bytes(&[1, 9]).unwrap();
+    assert_eq!(s.kind, inner::formats::Animal::Cat);
+    assert!(matches!(
+        s.body,
+        inner::formats::SwitchBody::CatBody(inner::formats::CatBody { lives: 9, .. })
+    ));
+}
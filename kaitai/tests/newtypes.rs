@@ -0,0 +1,24 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/newtypes.ksy", newtypes)]
+struct Newtypes;
+
+#[test]
+fn single_field_types() {
+    let file = Newtypes::from_bytes(&[1, 0, 0, 0, 0xca, 0xfe, 2, 0, 0, 0, 3, 0, 0, 0]).unwrap();
+    assert_eq!(file.length.0, 1);
+    assert_eq!(*file.length, 1);
+    assert_eq!(file.length.pow(2), 1);
+    assert_eq!(file.tag.0, [0xca, 0xfe]);
+    assert_eq!(file.tag.len(), 2);
+
+    let lengths = file
+        .lengths
+        .iter()
+        .cloned()
+        .map(u32::from)
+        .collect::<Vec<_>>();
+    assert_eq!(lengths, [2, 3]);
+    assert_eq!(Length::from(4), Length(4));
+    assert_eq!(format!("{:?}", file.tag), "Tag(ca fe)");
+}
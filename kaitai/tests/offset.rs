@@ -0,0 +1,49 @@
+use kaitai::{kaitai_source, KaitaiStream, KaitaiStruct};
+
+use std::io::{Seek, SeekFrom};
+
+#[kaitai_source("formats/user_type_be.ksy")]
+struct UserType;
+
+#[test]
+fn nested_type_from_offset() {
+    let mut file = std::fs::File::open("tests/files/example.png").unwrap();
+    file.seek(SeekFrom::Start(22)).unwrap();
+
+    let tail = Header::from_reader(&mut file).unwrap();
+    assert_eq!(tail.seq_1, 0x12c0802);
+    assert_eq!(tail.seq_2, 0xf61f192200);
+    assert_eq!(file.pos().unwrap(), 34);
+
+    assert_eq!(
+        tail,
+        UserType::from_file("tests/files/example.png").unwrap().tail
+    );
+}
+
+#[kaitai_source("formats/parent_io.ksy", module)]
+struct OffsetTable;
+
+#[test]
+fn parent_relative_instances() {
+    let bytes = [2, 9, 2, 11, 1, 1, 2, !11, !1, 0xaa, 0xbb, 0xcc];
+    let mut stream = std::io::Cursor::new(bytes);
+    let table = OffsetTable::from_reader(&mut stream).unwrap();
+
+    // The entries are read from substreams, their bodies from the stream of the table.
+    assert_eq!(table.entries[0].body(), &[0xaa, 0xbb]);
+    assert_eq!(table.entries[1].body(), &[0xcc]);
+    assert_eq!(table.entries[0].first(), 9);
+    assert_eq!(table.entries[1].first(), 11);
+
+    // Without a size, the stream of the parent is the stream of the entry.
+    assert_eq!(table.last.body(), &[9, 2]);
+    assert_eq!(table.last.first(), 2);
+
+    // The processed entry is read from its processed bytes.
+    assert_eq!(table.masked.body(), &[0xcc]);
+    assert_eq!(table.masked.first(), 11);
+
+    // The positions of both streams are restored.
+    assert_eq!(stream.pos().unwrap(), 9);
+}
@@ -0,0 +1,21 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/params.ksy")]
+struct Params;
+
+#[test]
+fn byte_array_and_enum_params() {
+    let params = Params::from_bytes(&[0xaa, 0x55, 2, 3, 1, 2, 3]).unwrap();
+    let body = &params.body;
+    assert_eq!(body.key, [0xaa, 0x55]);
+    assert_eq!(body.kind, Kind::Keyed);
+    assert_eq!(body.len_data, 3);
+    assert_eq!(body.base, 0x10);
+    assert_eq!(body.data, [1, 2, 3]);
+    assert_eq!(body.first_key(), 0xaa);
+    assert!(body.is_keyed());
+    assert_eq!(body.double_base(), 0x20);
+
+    // The parameter of the size is passed before the body is read.
+    assert!(Params::from_bytes(&[0xaa, 0x55, 1, 3, 1, 2]).is_err());
+}
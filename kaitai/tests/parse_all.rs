@@ -0,0 +1,42 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/basic_le.ksy")]
+struct Record;
+
+fn record(header: u16, body: i64, tail: u32) -> Vec<u8> {
+    [
+        &header.to_le_bytes()[..],
+        &body.to_le_bytes(),
+        &tail.to_le_bytes(),
+    ]
+    .concat()
+}
+
+#[test]
+fn three_records() {
+    let bytes = [record(1, -1, 10), record(2, -2, 20), record(3, -3, 30)].concat();
+    let records = Record::parse_all(&mut Cursor::new(bytes)).unwrap();
+
+    assert_eq!(records.len(), 3);
+    for (i, record) in records.iter().enumerate() {
+        let n = i as u16 + 1;
+        assert_eq!(record.header, n);
+        assert_eq!(record.body, -i64::from(n));
+        assert_eq!(record.tail, u32::from(n) * 10);
+    }
+}
+
+#[test]
+fn empty_stream() {
+    let records = Record::parse_all(&mut Cursor::new([])).unwrap();
+    assert!(records.is_empty());
+}
+
+#[test]
+fn trailing_partial_record() {
+    let mut bytes = [record(1, -1, 10), record(2, -2, 20)].concat();
+    bytes.extend_from_slice(&[0; 5]);
+    assert!(Record::parse_all(&mut Cursor::new(bytes)).is_err());
+}
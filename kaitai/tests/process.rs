@@ -0,0 +1,98 @@
+use kaitai::{error::Error, kaitai_source, KaitaiStruct, ParseContext};
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+
+#[kaitai_source("formats/process.ksy")]
+struct Process;
+
+const BYTES: &[u8] = &[
+    0x01, 0x02, 0x03, 0x04, // key
+    0x6a, 0x63, 0x6a, 0x70, 0x60, 0x6b, // blob
+    0xf0, 0x0f, // masked
+    0x35, 0x10, // body
+];
+
+#[test]
+fn xor_with_key_field() {
+    let process = Process::from_bytes(BYTES).unwrap();
+    assert_eq!(process.key, [0x01, 0x02, 0x03, 0x04]);
+    // The key is repeated past its length.
+    assert_eq!(process.blob, b"kaitai");
+}
+
+#[test]
+fn xor_with_byte() {
+    let process = Process::from_bytes(BYTES).unwrap();
+    assert_eq!(process.masked, [0x0f, 0xf0]);
+}
+
+#[test]
+fn type_parsed_from_processed_bytes() {
+    let process = Process::from_bytes(BYTES).unwrap();
+    assert_eq!(process.body.value, 0x1234);
+}
+
+#[kaitai_source("formats/basic_be.ksy")]
+struct Record;
+
+#[test]
+fn parse_through_xor_stream() {
+    let key = [0x01, 0x02, 0x03, 0x04];
+    let bytes = [
+        0x12, 0x34, // header
+        0, 0, 0, 0, 0, 0, 0x56, 0x78, // body
+        0x9a, 0xbc, 0xde, 0xf0, // tail
+    ];
+    let mut stream = kaitai::XorStream::new(
+        std::io::Cursor::new(kaitai::process_xor_many(&bytes, &key)),
+        key.to_vec(),
+    )
+    .unwrap();
+    let record = Record::from_reader(&mut stream).unwrap();
+    assert_eq!(record.header, 0x1234);
+    assert_eq!(record.body, 0x5678);
+    assert_eq!(record.tail, 0x9abc_def0);
+}
+
+#[kaitai_source("formats/zlib.ksy")]
+struct Zlib;
+
+/// Returns a `Zlib` with `body` and `note` compressed.
+fn zlib_bytes(body: &[u8], note: &[u8]) -> Vec<u8> {
+    let body = compress_to_vec_zlib(body, 6);
+    let note = compress_to_vec_zlib(note, 6);
+    let mut bytes = (body.len() as u16).to_le_bytes().to_vec();
+    bytes.extend(&body);
+    bytes.push(note.len() as u8);
+    bytes.extend(&note);
+    bytes.push(0x2a);
+    bytes
+}
+
+#[test]
+fn type_parsed_from_decompressed_bytes() {
+    let bytes = zlib_bytes(b"\x02\x34\x12\x78\x56zlib\0", &[b'n'; 64]);
+    let zlib = Zlib::from_bytes(&bytes).unwrap();
+    assert_eq!(zlib.body.values, [0x1234, 0x5678]);
+    assert_eq!(zlib.body.name, "zlib");
+    assert_eq!(zlib.note, [b'n'; 64]);
+    // Only the compressed bytes are read from the stream.
+    assert_eq!(zlib.tail, 0x2a);
+}
+
+#[test]
+fn invalid_zlib_data() {
+    let mut bytes = zlib_bytes(b"\x00\0", b"");
+    bytes[3] ^= 0xff;
+    let err = Zlib::from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err.without_path(), Error::InvalidZlib(_)));
+
+    // The decompressed bytes are bounded by the allocation limit.
+    let bytes = zlib_bytes(b"\x00\0", &[0; 200]);
+    let ctx = ParseContext::new().with_allocation_limit(100);
+    let err = Zlib::from_reader_with(&mut std::io::Cursor::new(bytes), &ctx).unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::AllocationLimitExceeded { limit: 100, .. }
+    ));
+}
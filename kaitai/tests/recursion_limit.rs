@@ -0,0 +1,44 @@
+use kaitai::{kaitai_source, Error, KaitaiStruct, ParseContext};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/tree.ksy")]
+struct Tree;
+
+/// Returns a tree of `depth` nodes, each having the next one as its only child.
+fn chain(depth: usize) -> Vec<u8> {
+    let mut bytes = [7, 1].repeat(depth - 1);
+    bytes.extend([7, 0]);
+    bytes
+}
+
+#[test]
+fn within_limit() {
+    let tree = Tree::from_bytes(&[1, 2, 2, 0, 3, 1, 4, 0]).unwrap();
+    assert_eq!(tree.root.value, 1);
+    assert_eq!(tree.root.children[1].children[0].value, 4);
+
+    let ctx = ParseContext::new().with_recursion_limit(4);
+    let tree = Tree::from_reader_with(&mut Cursor::new(chain(4)), &ctx).unwrap();
+    assert_eq!(tree.root.children[0].children[0].children[0].value, 7);
+}
+
+#[test]
+fn reduced_limit() {
+    let ctx = ParseContext::new().with_recursion_limit(4);
+    let err = Tree::from_reader_with(&mut Cursor::new(chain(5)), &ctx).unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::RecursionLimitExceeded(4)
+    ));
+}
+
+#[test]
+fn default_limit() {
+    assert_eq!(ParseContext::default().recursion_limit(), 128);
+    assert!(Tree::from_bytes(&chain(128)).is_ok());
+    assert!(matches!(
+        Tree::from_bytes(&chain(129)).unwrap_err().without_path(),
+        Error::RecursionLimitExceeded(128)
+    ));
+}
@@ -16,16 +16,22 @@ fn repeat_eos() {
             header: 0x100,
             body: 0x5040302,
             tail: 0x706,
+            #[cfg(feature = "spans")]
+            _spans: Default::default(),
         },
         Temp {
             header: 0x908,
             body: 0xd0c0b0a,
             tail: 0xf0e,
+            #[cfg(feature = "spans")]
+            _spans: Default::default(),
         },
         Temp {
             header: 0x1110,
             body: 0x15141312,
             tail: 0x1716,
+            #[cfg(feature = "spans")]
+            _spans: Default::default(),
         },
     ];
 
@@ -34,3 +40,74 @@ fn repeat_eos() {
     assert_eq!(result.pre_repeat, 0xadde);
     assert_eq!(result.main, expected);
 }
+
+#[kaitai_source("formats/repeat_expr.ksy")]
+struct RepeatExpr;
+
+#[test]
+fn repeat_expr() {
+    let r = RepeatExpr::from_bytes(&[2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]).unwrap();
+    assert_eq!(r.values, [1, 2]);
+}
+
+#[test]
+fn repeat_expr_eof() {
+    // The last element is cut short.
+    let err = RepeatExpr::from_bytes(&[2, 0, 0, 0, 1, 0, 0, 0, 2, 0]).unwrap_err();
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+
+    // A count far larger than the stream fails without allocating for it.
+    let err = RepeatExpr::from_bytes(&[0xff, 0xff, 0xff, 0xff, 1, 0, 0, 0]).unwrap_err();
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+}
+
+#[test]
+fn repeat_expr_bulk() {
+    // The elements of primitive arrays are all read at once.
+    let mut input = 1000u32.to_le_bytes().to_vec();
+    for i in 0..1000u32 {
+        input.extend(i.wrapping_mul(0x0001_0001).to_le_bytes());
+    }
+    let r = RepeatExpr::from_bytes(&input).unwrap();
+    assert_eq!(r.values.len(), 1000);
+    assert!(r
+        .values
+        .iter()
+        .zip(0u32..)
+        .all(|(&v, i)| v == i.wrapping_mul(0x0001_0001)));
+
+    input.pop();
+    let err = RepeatExpr::from_bytes(&input).unwrap_err();
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+}
+
+#[kaitai_source("formats/repeat_fixed.ksy")]
+struct RepeatFixed;
+
+#[test]
+fn repeat_expr_literal() {
+    let r = RepeatFixed::from_bytes(&[1, 2, 3, 4, 5, 0, 6, 0, 2, 1]).unwrap();
+    let values: [u8; 4] = r.values;
+    let words: [u16; 2] = r.words;
+    let kinds: [Kind; 2] = r.kinds;
+    assert_eq!(values, [1, 2, 3, 4]);
+    assert_eq!(words, [5, 6]);
+    assert_eq!(kinds, [Kind::Large, Kind::Small]);
+    assert_eq!(r.kinds_raw(), [2, 1]);
+
+    let err = RepeatFixed::from_bytes(&[1, 2, 3, 4, 5, 0, 6]).unwrap_err();
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+}
+
+#[kaitai_source("formats/repeat_enum.ksy")]
+struct RepeatEnum;
+
+#[test]
+fn repeat_expr_enum() {
+    let file = RepeatEnum::from_bytes(&[3, 1, 0, 2, 0, 3, 0]).unwrap();
+    assert_eq!(file.shape, Shape::Triangle);
+    assert_eq!(file.corners, [1, 2, 3]);
+
+    let file = RepeatEnum::from_bytes(&[0]).unwrap();
+    assert!(file.corners.is_empty());
+}
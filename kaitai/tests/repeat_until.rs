@@ -0,0 +1,52 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/repeat_until.ksy")]
+struct RepeatUntil;
+
+#[test]
+fn repeat_until_value() {
+    let r = RepeatUntil::from_bytes(&[5, 6, 0, 1, 0x34, 0x12]).unwrap();
+    assert_eq!(r.items, [5, 6, 0]);
+    assert_eq!(r.tail, 1);
+    assert_eq!(r.pairs, [0x1234]);
+}
+
+#[test]
+fn repeat_until_index() {
+    let r = RepeatUntil::from_bytes(&[1, 2, 3, 4, 0]).unwrap();
+    assert_eq!(r.items, [1, 2, 3, 4]);
+    assert_eq!(r.tail, 0);
+    assert!(r.pairs.is_empty());
+}
+
+#[test]
+fn repeat_until_eof() {
+    let err = RepeatUntil::from_bytes(&[1, 2]).unwrap_err();
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+}
+
+#[kaitai_source("formats/repeat_until_sentinel.ksy")]
+struct RepeatUntilSentinel;
+
+#[test]
+fn repeat_until_sentinel_element() {
+    let r = RepeatUntilSentinel::from_bytes(&[1, 2, b'h', b'i', 2, 1, 9, 0, 1, 7, 0xff]).unwrap();
+    let kinds = r
+        .records
+        .iter()
+        .map(|record| record.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [RecordKind::Text, RecordKind::Image, RecordKind::End]
+    );
+    // The sentinel is part of the array, as in Kaitai Struct.
+    assert_eq!(r.records[2].data, [7]);
+    assert_eq!(r.tail, 0xff);
+
+    let err = RepeatUntilSentinel::from_bytes(&[1, 0, 2, 0]).unwrap_err();
+    assert_eq!(
+        err.without_path().io_kind(),
+        Some(std::io::ErrorKind::UnexpectedEof)
+    );
+}
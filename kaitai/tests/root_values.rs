@@ -0,0 +1,32 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/root_values.ksy")]
+struct RootValues;
+
+#[test]
+fn root_fields_in_nested_types() {
+    let root = RootValues::from_bytes(&[b'R', b'V', 2, 1, 0x34, 0x12, 3, 4]).unwrap();
+    assert_eq!(root.header.extra, Some(0x1234));
+    // The nested types get the fields of the root through the types reading them.
+    assert_eq!(root.records[0].entry.scaled(), 6);
+    assert_eq!(root.records[1].entry.scaled(), 8);
+    assert!(root.records[1].entry.tagged());
+
+    let root = RootValues::from_bytes(&[b'X', b'X', 1, 1, 3, 4]).unwrap();
+    assert_eq!(root.header.extra, None);
+    assert!(!root.records[0].entry.tagged());
+}
+
+#[test]
+fn standalone_with_root() {
+    let root = RootValues::from_bytes(&[b'R', b'V', 2, 1, 0x34, 0x12, 3, 4]).unwrap();
+    // The types referring to `_root` are parsed on their own with the root the values come from.
+    let header = Header::from_reader_with_root(&mut Cursor::new([5, 0x78, 0x56]), &root).unwrap();
+    assert_eq!((header.flags, header.extra), (5, Some(0x5678)));
+    let record = Record::from_reader_with_root(&mut Cursor::new([10]), &root).unwrap();
+    assert_eq!(record.entry.scaled(), 20);
+    let entry = Entry::from_reader_with_root(&mut Cursor::new([7]), &root).unwrap();
+    assert!(entry.tagged());
+}
@@ -0,0 +1,55 @@
+use kaitai::{kaitai_source, FieldInfo};
+
+#[kaitai_source("formats/header.ksy")]
+struct Header;
+
+#[kaitai_source("formats/length_prefixed.ksy")]
+struct LengthPrefixed;
+
+#[test]
+fn fixed_offsets() {
+    let fields = Header::schema()
+        .iter()
+        .map(|field| (field.name, field.ty, field.offset))
+        .collect::<Vec<_>>();
+    // Fixed contents aren't stored, but move the offsets of the fields after them.
+    assert_eq!(
+        fields,
+        [
+            ("version", "u16", Some(4)),
+            ("kind", "Kind", Some(6)),
+            ("offset", "i32", Some(7)),
+            ("reserved", "::std::vec::Vec<u8>", Some(11)),
+            ("entries", "u32", Some(14)),
+        ]
+    );
+}
+
+#[test]
+fn variable_offsets() {
+    assert_eq!(
+        LengthPrefixed::schema(),
+        [
+            FieldInfo {
+                name: "len_data",
+                ty: "u32",
+                offset: Some(0),
+            },
+            FieldInfo {
+                name: "data",
+                ty: "::std::vec::Vec<u8>",
+                offset: Some(4),
+            },
+            FieldInfo {
+                name: "len_name",
+                ty: "u8",
+                offset: None,
+            },
+            FieldInfo {
+                name: "name",
+                ty: "::std::string::String",
+                offset: None,
+            },
+        ]
+    );
+}
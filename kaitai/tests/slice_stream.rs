@@ -0,0 +1,113 @@
+use kaitai::{kaitai_source, Decoding, KaitaiStream, SliceStream, TerminatorFlags};
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    borrow::Cow,
+    cell::Cell,
+    convert::TryFrom,
+};
+
+#[kaitai_source("formats/borrowed.ksy", borrowed)]
+struct Borrowed;
+
+// Strings that have to be transcoded can't be borrowed.
+#[kaitai_source(
+    inline = "
+meta:
+  id: title
+seq:
+  - id: title
+    type: str
+    size: 4
+    encoding: UTF-16LE
+",
+    borrowed
+)]
+struct Title;
+
+/// An allocator counting the allocations of each thread, so that tests running in parallel don't
+/// count each other's.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the result of `f` and the number of allocations it made.
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let value = f();
+    (value, ALLOCATIONS.with(Cell::get) - before)
+}
+
+const DATA: &[u8] = b"\x04\x00RIFFname\x00payload";
+
+static BORROWED: &[u8] = b"BR\x04RIFFname\x00ab    \x07\x00bodypayload";
+
+#[test]
+fn borrowed_parse_doesnt_allocate() {
+    let (borrowed, count) = allocations(|| Borrowed::from_slice(BORROWED).unwrap());
+    assert_eq!(count, 0);
+    assert!(matches!(borrowed.tag, Cow::Borrowed("RIFF")));
+    assert!(matches!(borrowed.name, Cow::Borrowed("name")));
+    assert!(matches!(borrowed.label, Cow::Borrowed("ab")));
+    assert_eq!(borrowed.chunk.kind, 7);
+    assert_eq!(borrowed.chunk.body, b"body");
+    assert_eq!(borrowed.payload, b"payload");
+    assert_eq!(borrowed.payload_len(), 7);
+    // The fields point into the input rather than to copies.
+    assert!(BORROWED.as_ptr_range().contains(&borrowed.payload.as_ptr()));
+}
+
+#[test]
+fn borrowed_transcoded_str() {
+    let title = Title::try_from(&b"h\x00i\x00"[..]).unwrap();
+    assert!(matches!(title.title, Cow::Owned(ref title) if title == "hi"));
+}
+
+#[test]
+fn slice_reads_dont_allocate() {
+    let mut stream = SliceStream::new(DATA);
+    let ((tag, name, payload), count) = allocations(|| {
+        let len = stream.read_u2le().unwrap();
+        let tag = stream
+            .read_str(len.into(), "ASCII", Decoding::Strict)
+            .unwrap();
+        let name = stream
+            .read_slice_term(0, TerminatorFlags::consume())
+            .unwrap();
+        (tag, name, stream.read_slice_full())
+    });
+    assert_eq!(count, 0);
+    assert!(matches!(tag, Cow::Borrowed("RIFF")));
+    assert_eq!(name, b"name");
+    assert_eq!(payload, b"payload");
+}
+
+#[test]
+fn owned_reads_allocate() {
+    let mut stream = SliceStream::new(DATA);
+    stream.read_u2le().unwrap();
+    let (tag, count) = allocations(|| stream.read_bytes(4).unwrap());
+    assert_eq!(tag, b"RIFF");
+    assert_eq!(count, 1);
+
+    // Strings that have to be transcoded are owned.
+    let (name, count) = allocations(|| stream.read_str(4, "UTF-16LE", Decoding::Strict).unwrap());
+    assert!(matches!(name, Cow::Owned(_)));
+    assert!(count > 0);
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "spans")]
+
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/fixed.ksy")]
+struct Fixed;
+
+#[kaitai_source("formats/strings.ksy")]
+struct Strings;
+
+#[test]
+fn field_spans() {
+    let mut input = b"test string".to_vec();
+    input.extend(&[0xab, 0xad, 0xba, 0xbe, 0x12, 0x34]);
+
+    let fixed = Fixed::from_bytes(&input).unwrap();
+    assert_eq!(fixed.spans().variable, (15, 17));
+}
+
+#[test]
+fn variable_length_spans() {
+    let strings = Strings::from_bytes(b"abc\0h\0i\0ab\0\0\x07").unwrap();
+    let spans = strings.spans();
+    // The terminator of `name` is consumed, so it is part of its span.
+    assert_eq!(spans.name, (0, 4));
+    assert_eq!(spans.code, (4, 8));
+    assert_eq!(spans.padded, (8, 12));
+    assert_eq!(spans.tail, (12, 13));
+}
@@ -0,0 +1,139 @@
+use kaitai::{kaitai_source, Decoding, Error, KaitaiStruct, ParseContext};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/strings.ksy")]
+struct Strings;
+
+fn parse(bytes: &[u8], decoding: Decoding) -> kaitai::Result<Strings> {
+    let ctx = ParseContext::new().with_decoding(decoding);
+    Strings::from_reader_with(&mut Cursor::new(bytes), &ctx)
+}
+
+#[test]
+fn valid_strings() {
+    let s = Strings::from_bytes(b"caf\xc3\xa9\0h\0i\0ab\0\0\x07").unwrap();
+    assert_eq!(s.name, "café");
+    assert_eq!(s.code, "hi");
+    assert_eq!(s.padded, "ab");
+    assert_eq!(s.tail, 7);
+}
+
+#[test]
+fn invalid_utf8_strict() {
+    let bytes = b"caf\xe9\0h\0i\0ab\0\0\x07";
+    let err = parse(bytes, Decoding::Strict).unwrap_err();
+    assert!(
+        matches!(err.without_path(), Error::InvalidString { ref encoding } if encoding == "UTF-8")
+    );
+    assert_eq!(err.without_path().to_string(), "invalid UTF-8 string");
+
+    // Strict decoding is the default.
+    assert!(Strings::from_bytes(bytes).is_err());
+}
+
+#[test]
+fn invalid_utf8_lossy() {
+    let s = parse(b"caf\xe9\0h\0i\0a\xff\0\0\x07", Decoding::Lossy).unwrap();
+    assert_eq!(s.name, "caf\u{fffd}");
+    assert_eq!(s.padded, "a\u{fffd}");
+    assert_eq!(s.tail, 7);
+}
+
+#[test]
+fn missing_terminator() {
+    let err = Strings::from_bytes(b"abc").unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::EofBeforeTerminator {
+            term: '\0',
+            start: 0,
+            read: 3
+        }
+    ));
+
+    let err = Strings::from_bytes(&[b'x'; 20]).unwrap_err();
+    assert_eq!(
+        err.without_path().to_string(),
+        "end of stream reached, but no terminator '\\0' found in the 0x14 bytes read from 0x0"
+    );
+}
+
+#[kaitai_source("formats/padded_str.ksy")]
+struct PaddedStr;
+
+#[test]
+fn padded_strings() {
+    let s = PaddedStr::from_bytes(b"CMD\0\0\0\0\0ab\0c  \x07").unwrap();
+    assert_eq!(s.name, "CMD");
+    // The padding is stripped before the string is truncated at its terminator.
+    assert_eq!(s.label, "ab");
+    assert_eq!(s.tail, 7);
+
+    // Only the padding at the end is stripped.
+    let s = PaddedStr::from_bytes(b"A\0B\0\0\0\0\0 lbl  \x07").unwrap();
+    assert_eq!(s.name, "A\0B");
+    assert_eq!(s.label, " lbl");
+}
+
+#[kaitai_source("formats/utf16_bom.ksy")]
+struct Utf16Bom;
+
+#[test]
+fn byte_order_mark() {
+    let little = b"\xff\xfeh\0i\0o\0k\0x\0";
+    let parsed = Utf16Bom::from_bytes(little).unwrap();
+    assert_eq!(
+        (parsed.title.as_str(), parsed.entry.name.as_str()),
+        ("hi", "x")
+    );
+
+    // The types nested in the root get the encoding of the mark too, the strings with an
+    // encoding of their own keep it.
+    let big = b"\xfe\xff\0h\0io\0k\0\0x";
+    let ctx = ParseContext::new();
+    let parsed = Utf16Bom::from_reader_with(&mut Cursor::new(big), &ctx).unwrap();
+    assert_eq!(ctx.bom_encoding(), Some("UTF-16BE"));
+    assert_eq!(parsed.title, "hi");
+    assert_eq!(parsed.label, "ok");
+    assert_eq!(parsed.entry.name, "x");
+    assert_eq!(parsed.bom, [0xfe, 0xff]);
+}
+
+#[test]
+fn byte_order_mark_per_parse() {
+    // A context reused for another file doesn't keep the mark of the previous one.
+    let ctx = ParseContext::new();
+    let little = b"\xff\xfeh\0i\0o\0k\0x\0";
+    let parsed = Utf16Bom::from_reader_with(&mut Cursor::new(little), &ctx).unwrap();
+    assert_eq!(ctx.bom_encoding(), Some("UTF-16LE"));
+    assert_eq!(parsed.title, "hi");
+
+    let big = b"\xfe\xff\0h\0io\0k\0\0x";
+    let parsed = Utf16Bom::from_reader_with(&mut Cursor::new(big), &ctx).unwrap();
+    assert_eq!(ctx.bom_encoding(), Some("UTF-16BE"));
+    assert_eq!(
+        (parsed.title.as_str(), parsed.entry.name.as_str()),
+        ("hi", "x")
+    );
+}
+
+#[kaitai_source("formats/str_ops.ksy")]
+struct StrOps;
+
+#[test]
+fn string_operators() {
+    let ops = StrOps::from_bytes(b"data\0bin\x02\x07").unwrap();
+    assert_eq!(ops.file_name, "data.bin");
+    assert!(!ops.is_archive);
+    assert_eq!(ops.version, Some(2));
+    assert_eq!(ops.sorted, Some(7));
+
+    let ops = StrOps::from_bytes(b"zip\0zip").unwrap();
+    assert_eq!(ops.file_name, "zip.zip");
+    assert_eq!((ops.version, ops.sorted), (None, None));
+
+    let ops = StrOps::from_bytes(b"data\0zip\x01").unwrap();
+    assert!(ops.is_archive);
+    assert_eq!(ops.sorted, Some(1));
+}
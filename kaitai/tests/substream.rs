@@ -0,0 +1,81 @@
+use kaitai::{kaitai_source, KaitaiStream, KaitaiStruct};
+
+#[kaitai_source("formats/substream.ksy")]
+struct Substream;
+
+#[test]
+fn size_eos_repeat() {
+    let s = Substream::from_bytes(&[1, 10, 2, 20, 3, 30, 4, 40, 5, 50]).unwrap();
+
+    let record = |tag, value| Record {
+        tag,
+        value,
+        #[cfg(feature = "spans")]
+        _spans: Default::default(),
+    };
+    assert_eq!(s.first.records, [record(1, 10), record(2, 20)]);
+    assert_eq!(
+        s.rest.records,
+        [record(3, 30), record(4, 40), record(5, 50)]
+    );
+}
+
+#[test]
+fn size_eos_empty() {
+    let s = Substream::from_bytes(&[1, 10, 2, 20]).unwrap();
+    assert!(s.rest.records.is_empty());
+}
+
+#[test]
+fn size_too_long() {
+    let err = Substream::from_bytes(&[1, 10]).unwrap_err();
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+}
+
+#[kaitai_source("formats/substream_len.ksy")]
+struct SubstreamLen;
+
+#[test]
+fn size_overflowing_position() {
+    let mut bytes = vec![0xaa];
+    bytes.extend(u64::MAX.to_le_bytes());
+    bytes.push(1);
+    let err = SubstreamLen::from_bytes(&bytes).unwrap_err();
+    assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+
+    bytes[1..9].copy_from_slice(&1u64.to_le_bytes());
+    assert_eq!(SubstreamLen::from_bytes(&bytes).unwrap().body.value, 1);
+}
+
+#[kaitai_source("formats/substream_tail.ksy")]
+struct SubstreamTail;
+
+#[test]
+fn size_eos_bytes_in_substream() {
+    let s = SubstreamTail::from_bytes(&[3, 1, 0xaa, 0xbb, 1, 2, 9]).unwrap();
+    assert_eq!(s.entries[0].body.kind, 1);
+    // The tail stops at the end of the entry, not of the file.
+    assert_eq!(s.entries[0].body.tail, [0xaa, 0xbb]);
+    assert_eq!(s.entries[1].body.kind, 2);
+    assert!(s.entries[1].body.tail.is_empty());
+    assert_eq!(s.trailer, 9);
+}
+
+#[test]
+fn read_substream() {
+    let mut stream = std::io::Cursor::new([1, 10, 2, 20, 3, 30]);
+    stream.set_position(2);
+    let mut sub = stream.read_substream(2).unwrap();
+    let record = Record::from_reader(&mut sub).unwrap();
+    assert_eq!((record.tag, record.value), (2, 20));
+    assert!(sub.is_eof().unwrap());
+    sub.finish().unwrap();
+    assert_eq!(stream.pos().unwrap(), 4);
+
+    // The parent is past the substream even if it isn't read.
+    stream.read_substream(1).unwrap();
+    assert_eq!(stream.pos().unwrap(), 5);
+    assert!(stream.read_substream(2).is_err());
+    assert!(stream.read_substream(u64::MAX).is_err());
+    assert_eq!(stream.pos().unwrap(), 5);
+}
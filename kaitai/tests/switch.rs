@@ -0,0 +1,68 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/switch.ksy")]
+struct Switch;
+
+#[test]
+fn switch_enum() {
+    let s = Switch::from_bytes(&[1, 9]).unwrap();
+    assert_eq!(s.kind, Animal::Cat);
+    assert_eq!(
+        s.body,
+        SwitchBody::CatBody(CatBody {
+            lives: 9,
+            #[cfg(feature = "spans")]
+            _spans: Default::default(),
+        })
+    );
+
+    let s = Switch::from_bytes(&[2, 0x34, 0x12]).unwrap();
+    assert_eq!(s.kind, Animal::Dog);
+    assert_eq!(
+        s.body,
+        SwitchBody::DogBody(DogBody {
+            bones: 0x1234,
+            #[cfg(feature = "spans")]
+            _spans: Default::default(),
+        })
+    );
+}
+
+#[test]
+fn switch_default() {
+    let s = Switch::from_bytes(&[3, 0]).unwrap();
+    assert_eq!(s.kind, Animal::Fish);
+    assert_eq!(
+        s.body,
+        SwitchBody::OtherBody(OtherBody {
+            legs: 0,
+            #[cfg(feature = "spans")]
+            _spans: Default::default(),
+        })
+    );
+}
+
+// The types are in a module so that they don't clash with the ones of `switch.ksy`.
+#[kaitai_source("formats/switch_sized.ksy", module)]
+struct SwitchSized;
+
+use switch_sized::{SwitchSizedBody, SwitchSizedCode};
+
+#[test]
+fn switch_sized_match() {
+    let s = SwitchSized::from_bytes(&[1, 1, 9, 7]).unwrap();
+    assert!(matches!(
+        s.body,
+        SwitchSizedBody::CatBody(switch_sized::CatBody { lives: 9, .. })
+    ));
+    assert_eq!(s.code, SwitchSizedCode::U1(7));
+}
+
+#[test]
+fn switch_sized_unknown() {
+    // Without a default case, the bytes of a value matching no case are kept.
+    let s = SwitchSized::from_bytes(&[5, 3, 1, 2, 3, 0x34, 0x12]).unwrap();
+    assert_eq!(s.body, SwitchSizedBody::Unknown(vec![1, 2, 3]));
+    // The default case of a built-in type is parsed like the other cases.
+    assert_eq!(s.code, SwitchSizedCode::U2(0x1234));
+}
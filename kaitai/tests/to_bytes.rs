@@ -0,0 +1,62 @@
+use kaitai::{kaitai_source, Error, KaitaiStruct, ParseContext};
+
+use std::convert::TryFrom;
+
+#[kaitai_source("formats/header.ksy", to_bytes)]
+struct Header;
+
+#[kaitai_source("formats/basic_le.ksy", to_bytes)]
+struct Basic;
+
+const BYTES: [u8; 18] = [
+    0x7f, b'H', b'D', b'R', 0, 3, 1, 0xff, 0xff, 0xff, 0xfe, 9, 8, 7, 0, 0, 0, 5,
+];
+
+#[test]
+fn round_trip() {
+    let header = Header::try_from(&BYTES[..]).unwrap();
+    assert_eq!(header.offset, -2);
+    assert_eq!(header.to_bytes().unwrap(), BYTES);
+
+    let basic = Basic::from_bytes(&[1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0]).unwrap();
+    assert_eq!(
+        basic.to_bytes().unwrap(),
+        [1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0]
+    );
+}
+
+#[test]
+fn patched_fields() {
+    let mut header = Header::try_from(&BYTES[..]).unwrap();
+    header.version = 0x0102;
+    header.kind = Kind::Audio;
+    header.entries = 6;
+
+    let bytes = header.to_bytes().unwrap();
+    assert_eq!(&bytes[4..7], [1, 2, 2]);
+    assert_eq!(bytes[17], 6);
+    let patched = Header::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        (patched.version, patched.kind, patched.entries),
+        (0x0102, Kind::Audio, 6)
+    );
+}
+
+#[test]
+fn unknown_enum_value_kept() {
+    let mut bytes = BYTES;
+    bytes[6] = 42;
+    let ctx = ParseContext::new().with_lenient(true);
+    let header = Header::from_reader_with(&mut std::io::Cursor::new(bytes), &ctx).unwrap();
+    assert_eq!(header.to_bytes().unwrap(), bytes);
+}
+
+#[test]
+fn size_mismatch() {
+    let mut header = Header::try_from(&BYTES[..]).unwrap();
+    header.reserved.push(0);
+    assert!(matches!(
+        header.to_bytes().unwrap_err(),
+        Error::SizeMismatch { field, expected: 3, actual: 4 } if field == "reserved"
+    ));
+}
@@ -0,0 +1,47 @@
+use kaitai::{kaitai_source, Error, KaitaiStream, KaitaiStruct, ParseContext};
+
+use std::io::{Cursor, Seek, SeekFrom};
+
+#[kaitai_source("formats/basic_le.ksy")]
+struct Record;
+
+#[test]
+fn no_trailing_bytes() {
+    let (record, trailing) = Record::from_reader_with_trailing(&mut Cursor::new([1; 14])).unwrap();
+    assert_eq!(record.header, 0x0101);
+    assert_eq!(trailing, 0);
+}
+
+#[test]
+fn trailing_bytes() {
+    // Only the bytes after the record count, not the ones before it.
+    let mut bytes = vec![0xff; 2];
+    bytes.extend_from_slice(&[0; 14]);
+    bytes.extend_from_slice(b"extra");
+    let mut stream = Cursor::new(bytes);
+    stream.seek(SeekFrom::Start(2)).unwrap();
+
+    let (_, trailing) = Record::from_reader_with_trailing(&mut stream).unwrap();
+    assert_eq!(trailing, 5);
+    // The stream is left right after the record.
+    assert_eq!(stream.pos().unwrap(), 16);
+    assert_eq!(stream.read_bytes_full().unwrap(), b"extra");
+}
+
+#[test]
+fn truncated_record() {
+    assert!(Record::from_reader_with_trailing(&mut Cursor::new([0; 13])).is_err());
+}
+
+#[test]
+fn rejected_trailing_bytes() {
+    let mut bytes = vec![0; 14];
+    bytes.extend_from_slice(b"extra");
+    let lenient = Record::from_reader_with(&mut Cursor::new(&bytes), &ParseContext::new());
+    assert!(lenient.is_ok());
+
+    let ctx = ParseContext::new().with_reject_trailing(true);
+    let err = Record::from_reader_with(&mut Cursor::new(&bytes), &ctx).unwrap_err();
+    assert!(matches!(err, Error::TrailingData { remaining: 5 }));
+    assert!(Record::from_reader_with(&mut Cursor::new(&bytes[..14]), &ctx).is_ok());
+}
@@ -0,0 +1,17 @@
+use kaitai::{kaitai_source, KaitaiStruct};
+
+#[kaitai_source("formats/type_path.ksy")]
+struct Archive;
+
+#[test]
+fn types_referenced_by_path() {
+    let archive = Archive::from_bytes(&[2, 0x10, 0, 1, 0x20, 0, 2, 3]).unwrap();
+    assert_eq!(archive.header.count, 2);
+    let entries = &archive.directory.entries;
+    assert_eq!(entries.len(), 2);
+    assert_eq!((entries[0].offset, entries[0].label.code), (0x10, 1));
+    assert_eq!((entries[1].offset, entries[1].label.code), (0x20, 2));
+    // The leaf of a two-level path is the type nested in `entry`.
+    let last: &Label = &archive.directory.last_label;
+    assert_eq!(last.code, 3);
+}
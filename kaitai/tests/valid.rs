@@ -0,0 +1,130 @@
+use kaitai::{kaitai_source, Error, KaitaiStruct, ParseContext};
+
+use std::io::Cursor;
+
+#[kaitai_source("formats/valid.ksy")]
+struct Valid;
+
+#[kaitai_source("formats/valid_any_of.ksy")]
+struct ValidAnyOf;
+
+#[kaitai_source("formats/valid_expr.ksy")]
+struct ValidExpr;
+
+#[kaitai_source("formats/valid_remaining.ksy")]
+struct ValidRemaining;
+
+#[test]
+fn valid_eq() {
+    assert!(Valid::from_bytes(&[0x7f, 1, 0, 0]).is_ok());
+
+    let err = Valid::from_bytes(&[0x7e, 1, 0, 0]).unwrap_err();
+    assert_eq!(
+        err.without_path().to_string(),
+        "magic is not valid, expected 127 but got 126"
+    );
+}
+
+#[test]
+fn valid_bounds_inclusive() {
+    assert_eq!(Valid::from_bytes(&[0x7f, 1, 0, 0]).unwrap().version, 1);
+    assert_eq!(Valid::from_bytes(&[0x7f, 3, 0, 0]).unwrap().version, 3);
+
+    let offset = |bytes: [u8; 2]| Valid::from_bytes(&[0x7f, 1, bytes[0], bytes[1]]);
+    assert_eq!(offset((-4i16).to_le_bytes()).unwrap().offset, -4);
+    assert_eq!(offset(4i16.to_le_bytes()).unwrap().offset, 4);
+}
+
+#[test]
+fn valid_bounds_err() {
+    let err = Valid::from_bytes(&[0x7f, 0, 0, 0]).unwrap_err();
+    assert_eq!(
+        err.without_path().to_string(),
+        "version is not valid, 0 is less than the minimum 1"
+    );
+
+    let err = Valid::from_bytes(&[0x7f, 4, 0, 0]).unwrap_err();
+    assert_eq!(
+        err.without_path().to_string(),
+        "version is not valid, 4 is greater than the maximum 3"
+    );
+
+    let err = Valid::from_bytes(&[0x7f, 1, 0xfb, 0xff]).unwrap_err();
+    assert_eq!(
+        err.without_path().to_string(),
+        "offset is not valid, -5 is less than the minimum -4"
+    );
+}
+
+#[test]
+fn valid_any_of_enum_members() {
+    assert_eq!(ValidAnyOf::from_bytes(&[1, 1]).unwrap().kind, Animal::Cat);
+    assert_eq!(
+        ValidAnyOf::from_bytes(&[2, 0x10]).unwrap().kind,
+        Animal::Dog
+    );
+
+    let err = ValidAnyOf::from_bytes(&[3, 1]).unwrap_err();
+    assert_eq!(
+        err.without_path().to_string(),
+        "kind is not valid, Fish is not any of Animal::Cat, Animal::Dog"
+    );
+}
+
+#[test]
+fn valid_any_of_integers() {
+    let err = ValidAnyOf::from_bytes(&[1, 3]).unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::ValidationNotAnyOf { field, actual, .. } if field == "version" && actual == "3"
+    ));
+
+    // The check is recoverable like the other checks.
+    let ctx = ParseContext::new().with_lenient(true);
+    let v = ValidAnyOf::from_reader_with(&mut Cursor::new([3, 3]), &ctx).unwrap();
+    assert_eq!((v.kind, v.version), (Animal::Fish, 3));
+    assert_eq!(ctx.take_errors().len(), 2);
+}
+
+#[test]
+fn valid_expr() {
+    let valid = ValidExpr::from_bytes(&[12, 0, 0, 0, 3]).unwrap();
+    assert_eq!(valid.len_aligned, 12);
+
+    let err = ValidExpr::from_bytes(&[13, 0, 0, 0, 3]).unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::ValidationExprFailed { ref field, ref actual, .. }
+            if field == "len_aligned" && actual == "13"
+    ));
+    assert_eq!(
+        err.without_path().to_string(),
+        "len_aligned is not valid, 13 doesn't satisfy `_ % 4 == 0`"
+    );
+
+    // The expression is checked along with the other checks.
+    let err = ValidExpr::from_bytes(&[4, 0, 0, 0, 2]).unwrap_err();
+    assert_eq!(
+        err.without_path().to_string(),
+        "version is not valid, 2 doesn't satisfy `_ != 2`"
+    );
+    assert!(ValidExpr::from_bytes(&[4, 0, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn valid_remaining_size() {
+    let valid = ValidRemaining::from_bytes(&[1, 3, 0, 0, 0, b'a', b'b', b'c']).unwrap();
+    assert_eq!(valid.body, b"abc");
+
+    // The stored length disagrees with the bytes left after it, e.g. in a truncated file.
+    let err = ValidRemaining::from_bytes(&[1, 5, 0, 0, 0, b'a', b'b', b'c']).unwrap_err();
+    assert!(matches!(
+        err.without_path(),
+        Error::ValidationRemainingSize { ref field, ref declared, remaining: 3 }
+            if field == "len_body" && declared == "5"
+    ));
+    assert_eq!(
+        err.without_path().to_string(),
+        "len_body declares 5 bytes, but 3 bytes are left in the stream"
+    );
+}